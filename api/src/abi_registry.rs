@@ -0,0 +1,370 @@
+use chrono::{DateTime, Utc};
+use indexnode_core::ContractAbi;
+use serde::Deserialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::db;
+use crate::security::{InputValidator, Sanitizer};
+
+/// Largest ABI document accepted, in bytes of serialized JSON.
+const MAX_ABI_BYTES: usize = 512 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AbiRegistryError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("An ABI with this name or contract is already registered")]
+    Conflict,
+    #[error("ABI not found")]
+    NotFound,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// An ABI to register, or to replace a registered one with.
+///
+/// It is stored for `contract_address` on `chain`, under `name`, or both.
+#[derive(Debug, Deserialize)]
+pub struct AbiDefinition {
+    pub name: Option<String>,
+    pub chain: Option<String>,
+    pub contract_address: Option<String>,
+    /// A JSON ABI array, or a compiler artifact with an `abi` field.
+    pub abi: serde_json::Value,
+}
+
+/// A registered ABI.
+#[derive(Debug, Clone)]
+pub struct StoredAbi {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub chain: Option<String>,
+    pub contract_address: Option<String>,
+    pub abi: ContractAbi,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl StoredAbi {
+    fn from_row(row: &PgRow) -> Result<Self, AbiRegistryError> {
+        Ok(Self {
+            id: row.get("id"),
+            name: row.get("name"),
+            chain: row.get("chain"),
+            contract_address: row.get("contract_address"),
+            abi: parse_stored(row)?,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+}
+
+impl AbiDefinition {
+    /// Validates the definition and normalizes its name, chain and address.
+    fn validate(self) -> Result<ValidDefinition, AbiRegistryError> {
+        let invalid = |e: anyhow::Error| AbiRegistryError::Invalid(e.to_string());
+
+        let name = self.name.map(|n| n.trim().to_string());
+        if let Some(name) = &name {
+            InputValidator::validate_string_length(name, 1, 64, "name").map_err(invalid)?;
+            if !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            {
+                return Err(AbiRegistryError::Invalid(
+                    "name may only contain letters, digits, '_', '-' and '.'".to_string(),
+                ));
+            }
+        }
+
+        let target = match (self.chain, self.contract_address) {
+            (Some(chain), Some(address)) => {
+                InputValidator::validate_string_length(&chain, 1, 64, "chain").map_err(invalid)?;
+                InputValidator::validate_ethereum_address(&address).map_err(invalid)?;
+                Some((Sanitizer::sanitize_text(&chain), address.to_lowercase()))
+            }
+            (None, None) => None,
+            _ => {
+                return Err(AbiRegistryError::Invalid(
+                    "chain and contract_address must be given together".to_string(),
+                ))
+            }
+        };
+        if name.is_none() && target.is_none() {
+            return Err(AbiRegistryError::Invalid(
+                "An ABI needs a name, a chain and contract_address, or both".to_string(),
+            ));
+        }
+
+        if self.abi.to_string().len() > MAX_ABI_BYTES {
+            return Err(AbiRegistryError::Invalid(format!(
+                "An ABI may be at most {} KiB",
+                MAX_ABI_BYTES / 1024
+            )));
+        }
+        let abi = ContractAbi::parse(&self.abi).map_err(invalid)?;
+
+        let (chain, contract_address) = target.unzip();
+        Ok(ValidDefinition {
+            name,
+            chain,
+            contract_address,
+            abi,
+        })
+    }
+}
+
+struct ValidDefinition {
+    name: Option<String>,
+    chain: Option<String>,
+    contract_address: Option<String>,
+    abi: ContractAbi,
+}
+
+fn map_write_error(e: sqlx::Error) -> AbiRegistryError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => AbiRegistryError::Conflict,
+        _ => AbiRegistryError::Database(e),
+    }
+}
+
+const COLUMNS: &str = "id, name, chain, contract_address, abi, created_at, updated_at";
+
+/// Registers an ABI owned by `user_id`.
+pub async fn create(
+    pool: &PgPool,
+    user_id: Uuid,
+    definition: AbiDefinition,
+) -> Result<StoredAbi, AbiRegistryError> {
+    let def = definition.validate()?;
+    let row = sqlx::query(&format!(
+        "INSERT INTO contract_abis (user_id, name, chain, contract_address, abi)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING {}",
+        COLUMNS
+    ))
+    .bind(user_id)
+    .bind(&def.name)
+    .bind(&def.chain)
+    .bind(&def.contract_address)
+    .bind(def.abi.json())
+    .fetch_one(pool)
+    .await
+    .map_err(map_write_error)?;
+    let stored = StoredAbi::from_row(&row)?;
+
+    db::audit_log(
+        pool,
+        Some(user_id),
+        "create_abi",
+        "contract_abi",
+        Some(&stored.id.to_string()),
+        Some(serde_json::json!({
+            "name": stored.name,
+            "chain": stored.chain,
+            "contract_address": stored.contract_address,
+        })),
+    )
+    .await;
+    Ok(stored)
+}
+
+/// Lists the ABIs owned by `user_id`, newest first.
+pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<StoredAbi>, AbiRegistryError> {
+    sqlx::query(&format!(
+        "SELECT {} FROM contract_abis WHERE user_id = $1 ORDER BY created_at DESC",
+        COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(StoredAbi::from_row)
+    .collect()
+}
+
+/// Fetches one ABI owned by `user_id`.
+pub async fn get(pool: &PgPool, user_id: Uuid, id: Uuid) -> Result<StoredAbi, AbiRegistryError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM contract_abis WHERE id = $1 AND user_id = $2",
+        COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AbiRegistryError::NotFound)?;
+    StoredAbi::from_row(&row)
+}
+
+/// Replaces an ABI owned by `user_id`. Jobs created earlier keep the
+/// definitions they were created with.
+pub async fn update(
+    pool: &PgPool,
+    user_id: Uuid,
+    id: Uuid,
+    definition: AbiDefinition,
+) -> Result<StoredAbi, AbiRegistryError> {
+    let def = definition.validate()?;
+    let row = sqlx::query(&format!(
+        "UPDATE contract_abis
+         SET name = $3, chain = $4, contract_address = $5, abi = $6, updated_at = NOW()
+         WHERE id = $1 AND user_id = $2
+         RETURNING {}",
+        COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .bind(&def.name)
+    .bind(&def.chain)
+    .bind(&def.contract_address)
+    .bind(def.abi.json())
+    .fetch_optional(pool)
+    .await
+    .map_err(map_write_error)?
+    .ok_or(AbiRegistryError::NotFound)?;
+    let stored = StoredAbi::from_row(&row)?;
+
+    db::audit_log(
+        pool,
+        Some(user_id),
+        "update_abi",
+        "contract_abi",
+        Some(&id.to_string()),
+        None,
+    )
+    .await;
+    Ok(stored)
+}
+
+/// Deletes an ABI owned by `user_id`.
+pub async fn delete(pool: &PgPool, user_id: Uuid, id: Uuid) -> Result<(), AbiRegistryError> {
+    let result = sqlx::query("DELETE FROM contract_abis WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AbiRegistryError::NotFound);
+    }
+
+    db::audit_log(
+        pool,
+        Some(user_id),
+        "delete_abi",
+        "contract_abi",
+        Some(&id.to_string()),
+        None,
+    )
+    .await;
+    Ok(())
+}
+
+/// The ABI registered as `name`, which must exist.
+pub async fn find_by_name(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+) -> Result<ContractAbi, AbiRegistryError> {
+    let row = sqlx::query("SELECT abi FROM contract_abis WHERE user_id = $1 AND name = $2")
+        .bind(user_id)
+        .bind(name.trim())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AbiRegistryError::Invalid(format!("No ABI is registered as '{}'", name)))?;
+    parse_stored(&row)
+}
+
+/// The ABIs registered for any of `addresses` on `chain`, keyed by
+/// lowercased address.
+pub async fn find_for_contracts(
+    pool: &PgPool,
+    user_id: Uuid,
+    chain: &str,
+    addresses: &[String],
+) -> Result<HashMap<String, ContractAbi>, AbiRegistryError> {
+    let addresses: Vec<String> = addresses.iter().map(|a| a.to_lowercase()).collect();
+    sqlx::query(
+        "SELECT contract_address, abi FROM contract_abis
+         WHERE user_id = $1 AND chain = $2 AND contract_address = ANY($3)",
+    )
+    .bind(user_id)
+    .bind(chain)
+    .bind(&addresses)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(|row| Ok((row.get("contract_address"), parse_stored(row)?)))
+    .collect()
+}
+
+/// The ABI a new job on `contract_address` resolves names against: the one
+/// registered as `name` if given, otherwise the one registered for the
+/// contract, if any.
+pub async fn find_for_contract(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: Option<&str>,
+    chain: &str,
+    contract_address: &str,
+) -> Result<Option<ContractAbi>, AbiRegistryError> {
+    match name {
+        Some(name) => find_by_name(pool, user_id, name).await.map(Some),
+        None => Ok(
+            find_for_contracts(pool, user_id, chain, &[contract_address.to_string()])
+                .await?
+                .into_values()
+                .next(),
+        ),
+    }
+}
+
+fn parse_stored(row: &PgRow) -> Result<ContractAbi, AbiRegistryError> {
+    ContractAbi::parse(&row.get::<serde_json::Value, _>("abi"))
+        .map_err(|e| AbiRegistryError::Invalid(format!("Stored ABI is invalid: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn definition(name: Option<&str>, chain: Option<&str>, address: Option<&str>) -> AbiDefinition {
+        AbiDefinition {
+            name: name.map(str::to_string),
+            chain: chain.map(str::to_string),
+            contract_address: address.map(str::to_string),
+            abi: json!([{"type": "function", "name": "totalSupply", "inputs": [],
+                         "outputs": [{"name": "", "type": "uint256"}],
+                         "stateMutability": "view"}]),
+        }
+    }
+
+    #[test]
+    fn test_validate_normalizes_definitions() {
+        let address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+        let def = definition(Some(" usdc "), Some("ethereum"), Some(address))
+            .validate()
+            .unwrap();
+        assert_eq!(def.name.as_deref(), Some("usdc"));
+        assert_eq!(def.chain.as_deref(), Some("ethereum"));
+        assert_eq!(def.contract_address, Some(address.to_lowercase()));
+        assert_eq!(def.abi.functions().count(), 1);
+
+        let invalid =
+            |def: AbiDefinition| matches!(def.validate(), Err(AbiRegistryError::Invalid(_)));
+        assert!(invalid(definition(None, None, None)));
+        assert!(invalid(definition(Some("usdc v2"), None, None)));
+        assert!(invalid(definition(None, Some("ethereum"), None)));
+        assert!(invalid(definition(None, Some("ethereum"), Some("0x1234"))));
+        let mut empty = definition(Some("empty"), None, None);
+        empty.abi = json!([]);
+        assert!(invalid(empty));
+        let mut large = definition(Some("large"), None, None);
+        large.abi = json!([{"type": "event", "name": "A".repeat(MAX_ABI_BYTES), "inputs": []}]);
+        assert!(invalid(large));
+    }
+}
@@ -1,7 +1,7 @@
 pub mod schema;
 pub mod types;
 
-use indexnode_core::{CreditManager, MarketplaceClient};
+use indexnode_core::{ChainRegistry, CreditManager, MarketplaceClient};
 pub use schema::AppSchema;
 use sqlx::PgPool;
 
@@ -9,6 +9,7 @@ pub fn build_schema(
     pool: PgPool,
     credit_manager: CreditManager,
     marketplace: MarketplaceClient,
+    chains: ChainRegistry,
 ) -> AppSchema {
-    schema::build_schema(pool, credit_manager, marketplace)
+    schema::build_schema(pool, credit_manager, marketplace, chains)
 }
@@ -1,17 +1,29 @@
 use super::types::*;
+use crate::abi_registry::{self, AbiDefinition, AbiRegistryError, StoredAbi};
 use crate::auth::UserRole;
 use crate::db;
+use crate::proofs::{self, ProofError};
 use crate::security::{InputValidator, Sanitizer};
 use anyhow::Context as AnyhowContext;
 use async_graphql::*;
+use ethers::abi::StateMutability;
 use ethers::types::{Address, U256};
 use indexnode_core::{
-    BlockchainIndexParams, CreditManager, JobConfig, JobParams, JobType, MarketplaceClient,
+    merge_function_definitions, BlockchainIndexParams, ChainRegistry, CreditManager, EventDecoder,
+    EventFilter, FactoryConfig, JobConfig, JobParams, JobType, MarketplaceClient, MerkleMode,
+    Projection, StateSnapshotParams, TopicFilter, TransactionIndexParams, ViewCall, ANY_ADDRESS,
 };
 use sqlx::PgPool;
+use std::collections::HashMap;
 use tokio_stream::{Stream, StreamExt};
 use uuid::Uuid;
 
+/// Most blocks one transaction indexing job may scan; every block is fetched.
+const MAX_TRANSACTION_SCAN_BLOCKS: i64 = 100_000;
+
+/// Most values (calls × blocks) one state snapshot job may read.
+const MAX_SNAPSHOT_VALUES: usize = 10_000;
+
 pub struct Query;
 
 #[Object]
@@ -65,10 +77,14 @@ impl Query {
             .map_err(|e| Error::new(format!("Invalid contract address: {}", e)))?;
 
         let rows = sqlx::query(
-            "SELECT id, contract_address, event_name, block_number, transaction_hash, event_data, content_hash, ipfs_cid
-             FROM blockchain_events
-             WHERE contract_address = $1
-             ORDER BY block_number DESC
+            "SELECT be.id, be.contract_address, be.event_name, be.block_number, be.block_hash, be.block_timestamp, be.transaction_hash, be.log_index, be.event_data, be.content_hash, be.hash_version, be.ipfs_cid,
+                    be.token_standard, m.name AS token_name, m.symbol AS token_symbol, m.decimals AS token_decimals,
+                    be.token_amount_raw::TEXT AS token_amount_raw, be.token_amount::TEXT AS token_amount,
+                    be.token_id::TEXT AS token_id, be.token_uri, be.token_metadata
+             FROM blockchain_events be
+             LEFT JOIN token_metadata m ON m.chain = be.chain AND m.contract_address = be.contract_address
+             WHERE be.contract_address = $1 AND NOT be.orphaned
+             ORDER BY be.block_number DESC, be.log_index DESC
              LIMIT $2"
         )
         .bind(&contract_address)
@@ -85,10 +101,422 @@ impl Query {
                 contract_address: r.get("contract_address"),
                 event_name: r.get("event_name"),
                 block_number: r.get("block_number"),
+                block_hash: r.get("block_hash"),
+                block_timestamp: r
+                    .get::<Option<chrono::DateTime<chrono::Utc>>, _>("block_timestamp")
+                    .map(|t| t.to_rfc3339()),
                 transaction_hash: r.get("transaction_hash"),
+                log_index: r.get("log_index"),
                 event_data: r.get("event_data"),
                 content_hash: r.get("content_hash"),
+                hash_version: r.get::<i16, _>("hash_version").into(),
                 ipfs_cid: r.get("ipfs_cid"),
+                token: r
+                    .get::<Option<String>, _>("token_standard")
+                    .map(|standard| TokenInfo {
+                        standard,
+                        name: r.get("token_name"),
+                        symbol: r.get("token_symbol"),
+                        decimals: r.get::<Option<i16>, _>("token_decimals").map(i32::from),
+                        amount_raw: r.get("token_amount_raw"),
+                        amount: r.get("token_amount"),
+                        token_id: r.get("token_id"),
+                        token_uri: r.get("token_uri"),
+                        metadata: r.get("token_metadata"),
+                    }),
+            })
+            .collect())
+    }
+
+    /// Lists the contracts a factory job has discovered, in creation order.
+    /// Only the owning user may access them.
+    async fn child_contracts(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+    ) -> async_graphql::Result<Vec<ChildContract>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let job_id = Uuid::parse_str(&job_id)
+            .map_err(|e| Error::new(format!("Invalid job ID format: {}", e)))?;
+
+        let rows = sqlx::query(
+            "SELECT c.child_address, c.creation_block, c.creation_transaction_hash, c.content_hash, c.merkle_root
+             FROM job_child_contracts c
+             JOIN jobs j ON j.id = c.job_id
+             WHERE c.job_id = $1 AND j.user_id = $2 AND NOT c.orphaned
+             ORDER BY c.creation_block, c.creation_log_index",
+        )
+        .bind(job_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch child contracts")?;
+
+        use sqlx::Row;
+        Ok(rows
+            .into_iter()
+            .map(|r| ChildContract {
+                address: r.get("child_address"),
+                creation_block: r.get("creation_block"),
+                creation_transaction_hash: r.get("creation_transaction_hash"),
+                content_hash: r.get("content_hash"),
+                merkle_root: r.get("merkle_root"),
+            })
+            .collect())
+    }
+
+    /// Lists the transactions indexed by a transaction indexing job, in block
+    /// order. Only the owning user may access them.
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> async_graphql::Result<Vec<IndexedTransaction>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let job_id = Uuid::parse_str(&job_id)
+            .map_err(|e| Error::new(format!("Invalid job ID format: {}", e)))?;
+        let limit = limit.unwrap_or(100).clamp(1, 1000);
+        let offset = offset.unwrap_or(0).max(0);
+
+        let rows = sqlx::query(
+            "SELECT t.transaction_hash, t.block_number, t.block_timestamp, t.from_address, t.to_address,
+                    t.value::TEXT AS value, t.function_name, t.decoded_call, t.status, t.gas_used,
+                    t.contract_address, t.trace, t.content_hash, t.merkle_root
+             FROM indexed_transactions t
+             JOIN jobs j ON j.id = t.job_id
+             WHERE t.job_id = $1 AND j.user_id = $2 AND NOT t.orphaned
+             ORDER BY t.block_number, t.transaction_index
+             LIMIT $3 OFFSET $4",
+        )
+        .bind(job_id)
+        .bind(user_id)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch transactions")?;
+
+        use sqlx::Row;
+        Ok(rows
+            .into_iter()
+            .map(|r| IndexedTransaction {
+                transaction_hash: r.get("transaction_hash"),
+                block_number: r.get("block_number"),
+                block_timestamp: r
+                    .get::<chrono::DateTime<chrono::Utc>, _>("block_timestamp")
+                    .to_rfc3339(),
+                from_address: r.get("from_address"),
+                to_address: r.get("to_address"),
+                value: r.get("value"),
+                function_name: r.get("function_name"),
+                decoded_call: r.get("decoded_call"),
+                status: r.get("status"),
+                gas_used: r.get("gas_used"),
+                contract_address: r.get("contract_address"),
+                trace: r.get("trace"),
+                content_hash: r.get("content_hash"),
+                merkle_root: r.get("merkle_root"),
+            })
+            .collect())
+    }
+
+    /// Lists the values read by a state snapshot job, by block and call. Only
+    /// the owning user may access them.
+    async fn state_snapshot(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+    ) -> async_graphql::Result<Vec<SnapshotValue>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let job_id = Uuid::parse_str(&job_id)
+            .map_err(|e| Error::new(format!("Invalid job ID format: {}", e)))?;
+
+        let rows = sqlx::query(
+            "SELECT s.block_number, s.block_hash, s.call_index, s.contract_address,
+                    s.function_signature, s.args, s.return_data, s.result, s.decode_error,
+                    s.content_hash, s.merkle_root
+             FROM state_snapshots s
+             JOIN jobs j ON j.id = s.job_id
+             WHERE s.job_id = $1 AND j.user_id = $2 AND NOT s.orphaned
+             ORDER BY s.block_number, s.call_index",
+        )
+        .bind(job_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch state snapshot")?;
+
+        use sqlx::Row;
+        Ok(rows
+            .into_iter()
+            .map(|r| SnapshotValue {
+                block_number: r.get("block_number"),
+                block_hash: r.get("block_hash"),
+                call_index: r.get("call_index"),
+                contract_address: r.get("contract_address"),
+                function_signature: r.get("function_signature"),
+                args: r.get("args"),
+                return_data: r.get("return_data"),
+                result: r.get("result"),
+                decode_error: r.get("decode_error"),
+                content_hash: r.get("content_hash"),
+                merkle_root: r.get("merkle_root"),
+            })
+            .collect())
+    }
+
+    /// Returns the Merkle inclusion proof for an indexed event or a content
+    /// hash; exactly one must be given. Null when no tree of the
+    /// authenticated user's jobs contains it.
+    async fn inclusion_proof(
+        &self,
+        ctx: &Context<'_>,
+        event_id: Option<String>,
+        content_hash: Option<String>,
+    ) -> async_graphql::Result<Option<InclusionProof>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+
+        let result = match (event_id, content_hash) {
+            (Some(id), None) => {
+                let id = Uuid::parse_str(&id)
+                    .map_err(|e| Error::new(format!("Invalid event ID format: {}", e)))?;
+                proofs::for_event(pool, id, *user_id).await
+            }
+            (None, Some(hash)) => proofs::for_content_hash(pool, &hash, *user_id).await,
+            _ => return Err(Error::new("Pass exactly one of event_id and content_hash")),
+        };
+        match result {
+            Ok(proof) => Ok(Some(inclusion_proof(proof))),
+            Err(ProofError::NotFound) => Ok(None),
+            Err(e) => {
+                tracing::error!("Inclusion proof error: {}", e);
+                Err(Error::new("Failed to build inclusion proof"))
+            }
+        }
+    }
+
+    /// Fetches the backfill shards of one of the authenticated user's
+    /// blockchain jobs, in block order.
+    async fn job_shards(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+    ) -> async_graphql::Result<Vec<JobShard>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let job_id = Uuid::parse_str(&job_id)
+            .map_err(|e| Error::new(format!("Invalid job ID format: {}", e)))?;
+
+        let rows = sqlx::query(
+            "SELECT s.shard_index, s.from_block, s.to_block, s.status, s.events_found,
+                    s.error, s.started_at, s.completed_at
+             FROM job_shards s
+             JOIN jobs j ON j.id = s.job_id
+             WHERE s.job_id = $1 AND j.user_id = $2
+             ORDER BY s.shard_index",
+        )
+        .bind(job_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch job shards")?;
+
+        use sqlx::Row;
+        Ok(rows
+            .into_iter()
+            .map(|r| JobShard {
+                shard_index: r.get("shard_index"),
+                from_block: r.get("from_block"),
+                to_block: r.get("to_block"),
+                status: r.get("status"),
+                events_found: r.get("events_found"),
+                error: r.get("error"),
+                started_at: r
+                    .get::<Option<chrono::DateTime<chrono::Utc>>, _>("started_at")
+                    .map(|t| t.to_rfc3339()),
+                completed_at: r
+                    .get::<Option<chrono::DateTime<chrono::Utc>>, _>("completed_at")
+                    .map(|t| t.to_rfc3339()),
+            })
+            .collect())
+    }
+
+    /// Fetches balances from a job's `balances` projection, largest first.
+    /// Without `holder`, this lists the contract's top holders.
+    async fn token_balances(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+        contract_address: String,
+        holder: Option<String>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> async_graphql::Result<Vec<TokenBalance>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let limit = limit.unwrap_or(100).clamp(1, 1000);
+        let offset = offset.unwrap_or(0).max(0);
+        InputValidator::validate_ethereum_address(&contract_address)
+            .map_err(|e| Error::new(format!("Invalid contract address: {}", e)))?;
+        if let Some(holder) = &holder {
+            InputValidator::validate_ethereum_address(holder)
+                .map_err(|e| Error::new(format!("Invalid holder address: {}", e)))?;
+        }
+        let job_id = require_projection(pool, user_id, &job_id, Projection::Balances).await?;
+
+        let rows = sqlx::query(
+            "SELECT b.contract_address, b.holder, b.balance::TEXT AS balance, m.standard, m.decimals
+             FROM token_balances b
+             JOIN jobs j ON j.id = b.job_id
+             LEFT JOIN token_metadata m
+                 ON m.chain = j.config->'params'->>'chain' AND m.contract_address = b.contract_address
+             WHERE b.job_id = $1 AND b.contract_address = $2 AND ($3::TEXT IS NULL OR b.holder = $3)
+             ORDER BY b.balance DESC, b.holder
+             LIMIT $4 OFFSET $5",
+        )
+        .bind(job_id)
+        .bind(contract_address.to_lowercase())
+        .bind(holder.map(|h| h.to_lowercase()))
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch token balances")?;
+
+        use sqlx::Row;
+        Ok(rows
+            .into_iter()
+            .map(|r| TokenBalance {
+                contract_address: r.get("contract_address"),
+                holder: r.get("holder"),
+                balance: r.get("balance"),
+                standard: r.get("standard"),
+                decimals: r.get::<Option<i16>, _>("decimals").map(i32::from),
+            })
+            .collect())
+    }
+
+    /// Counts the holders with a positive balance in a job's `balances`
+    /// projection.
+    async fn token_holder_count(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+        contract_address: String,
+    ) -> async_graphql::Result<i64> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        InputValidator::validate_ethereum_address(&contract_address)
+            .map_err(|e| Error::new(format!("Invalid contract address: {}", e)))?;
+        let job_id = require_projection(pool, user_id, &job_id, Projection::Balances).await?;
+
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM token_balances
+             WHERE job_id = $1 AND contract_address = $2 AND balance > 0",
+        )
+        .bind(job_id)
+        .bind(contract_address.to_lowercase())
+        .fetch_one(pool)
+        .await
+        .context("Failed to count token holders")?;
+        Ok(count)
+    }
+
+    /// Fetches the blocks that changed a token's supply from a job's `supply`
+    /// projection, in block order, with the running total supply.
+    async fn token_supply(
+        &self,
+        ctx: &Context<'_>,
+        job_id: String,
+        contract_address: String,
+        from_block: Option<i64>,
+        to_block: Option<i64>,
+        limit: Option<i32>,
+    ) -> async_graphql::Result<Vec<SupplyPoint>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let limit = limit.unwrap_or(1000).clamp(1, 10_000);
+        InputValidator::validate_ethereum_address(&contract_address)
+            .map_err(|e| Error::new(format!("Invalid contract address: {}", e)))?;
+        let job_id = require_projection(pool, user_id, &job_id, Projection::Supply).await?;
+
+        // The running total covers every block, so it is computed before the
+        // range is applied.
+        let rows = sqlx::query(
+            "SELECT block_number, minted::TEXT AS minted, burned::TEXT AS burned,
+                    total_supply::TEXT AS total_supply
+             FROM (
+                 SELECT block_number, minted, burned,
+                        SUM(minted - burned) OVER (ORDER BY block_number) AS total_supply
+                 FROM token_supply_changes
+                 WHERE job_id = $1 AND contract_address = $2
+             ) s
+             WHERE block_number >= $3 AND block_number <= $4
+             ORDER BY block_number
+             LIMIT $5",
+        )
+        .bind(job_id)
+        .bind(contract_address.to_lowercase())
+        .bind(from_block.unwrap_or(0))
+        .bind(to_block.unwrap_or(i64::MAX))
+        .bind(limit as i64)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch token supply")?;
+
+        use sqlx::Row;
+        Ok(rows
+            .into_iter()
+            .map(|r| SupplyPoint {
+                block_number: r.get("block_number"),
+                minted: r.get("minted"),
+                burned: r.get("burned"),
+                total_supply: r.get("total_supply"),
             })
             .collect())
     }
@@ -167,6 +595,24 @@ impl Query {
                     config["params"]["contract_address"]
                         .as_str()
                         .map(|s| s.to_string())
+                } else if job_type == "transaction_index" {
+                    config["params"]["addresses"].as_array().map(|addresses| {
+                        addresses
+                            .iter()
+                            .filter_map(|a| a.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                } else if job_type == "state_snapshot" {
+                    config["params"]["calls"].as_array().map(|calls| {
+                        let mut contracts: Vec<&str> = calls
+                            .iter()
+                            .filter_map(|c| c["contract"].as_str())
+                            .collect();
+                        contracts.sort_unstable();
+                        contracts.dedup();
+                        contracts.join(", ")
+                    })
                 } else {
                     config["params"]["url"].as_str().map(|s| s.to_string())
                 };
@@ -234,6 +680,38 @@ impl Query {
         }))
     }
 
+    /// Lists the authenticated user's registered ABIs, newest first.
+    async fn abis(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<RegisteredAbi>> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+
+        let abis = abi_registry::list(pool, user_id).await.map_err(abi_error)?;
+        Ok(abis.into_iter().map(registered_abi).collect())
+    }
+
+    /// Fetches one of the authenticated user's registered ABIs.
+    async fn abi(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<RegisteredAbi> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let id = Uuid::parse_str(&id)
+            .map_err(|e| Error::new(format!("Invalid ABI ID format: {}", e)))?;
+
+        let stored = abi_registry::get(pool, user_id, id)
+            .await
+            .map_err(abi_error)?;
+        Ok(registered_abi(stored))
+    }
+
     /// Fetches AI-powered extractions for a specific blockchain event.
     async fn ai_extractions(
         &self,
@@ -300,6 +778,27 @@ impl Query {
         })
     }
 
+    /// Lists the chains blockchain indexing jobs can target.
+    async fn supported_chains(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<SupportedChain>> {
+        let chains = ctx
+            .data::<ChainRegistry>()
+            .map_err(|_| Error::new("Chain registry not available"))?;
+
+        Ok(chains
+            .iter()
+            .map(|c| SupportedChain {
+                name: c.name.clone(),
+                chain_id: c.chain_id as i64,
+                confirmations: c.confirmations as i64,
+                block_time_ms: c.block_time_ms as i64,
+                max_log_range: c.max_log_range as i64,
+            })
+            .collect())
+    }
+
     /// Fetches global system health and queue metrics. Requires admin role.
     async fn system_metrics(&self, ctx: &Context<'_>) -> async_graphql::Result<SystemMetrics> {
         // Admin-only endpoint.
@@ -462,23 +961,151 @@ impl Mutation {
             .ok_or_else(|| Error::new("Unauthorized"))?;
 
         // Input validation — done before opening the transaction.
-        InputValidator::validate_ethereum_address(&input.contract_address)
-            .map_err(|e| Error::new(format!("Security validation failed: {}", e)))?;
+        let contract_addresses = input.contract_addresses.unwrap_or_default();
+        if contract_addresses.len() > 100 {
+            return Err(Error::new(
+                "At most 100 contract addresses may be specified",
+            ));
+        }
+        for address in std::iter::once(&input.contract_address).chain(&contract_addresses) {
+            if address != ANY_ADDRESS {
+                InputValidator::validate_ethereum_address(address)
+                    .map_err(|e| Error::new(format!("Security validation failed: {}", e)))?;
+            }
+        }
         InputValidator::validate_string_length(&input.chain, 1, 64, "chain")
             .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
         InputValidator::validate_numeric_range(input.from_block, 0_i64, i64::MAX, "from_block")
             .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+        if let Some(shard_blocks) = input.shard_blocks {
+            InputValidator::validate_numeric_range(
+                shard_blocks,
+                1_000_i64,
+                10_000_000_i64,
+                "shard_blocks",
+            )
+            .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+        }
+        if let Some(mmr_commit_blocks) = input.mmr_commit_blocks {
+            InputValidator::validate_numeric_range(
+                mmr_commit_blocks,
+                1_i64,
+                1_000_000_i64,
+                "mmr_commit_blocks",
+            )
+            .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+        }
 
-        const SUPPORTED_CHAINS: &[&str] = &["ethereum", "polygon"];
+        let chains = ctx
+            .data::<ChainRegistry>()
+            .map_err(|_| Error::new("Chain registry not available"))?;
         let sanitized_chain = Sanitizer::sanitize_text(&input.chain);
-        if !SUPPORTED_CHAINS.contains(&sanitized_chain.as_str()) {
+        if !chains.contains(&sanitized_chain) {
             return Err(Error::new(format!(
                 "Unsupported chain '{}'. Supported chains: {}",
                 sanitized_chain,
-                SUPPORTED_CHAINS.join(", ")
+                chains.names().join(", ")
             )));
         }
 
+        // Event names resolve against the named ABI, or the one registered
+        // for the contract.
+        let registered_abi = if input.abi_name.is_some() || input.contract_address != ANY_ADDRESS {
+            abi_registry::find_for_contract(
+                pool,
+                user_id,
+                input.abi_name.as_deref(),
+                &sanitized_chain,
+                &input.contract_address,
+            )
+            .await
+            .map_err(abi_error)?
+        } else {
+            None
+        };
+        let resolve_event = |event: String| match &registered_abi {
+            Some(abi) => abi
+                .resolve_event(&event)
+                .map_err(|e| Error::new(format!("Invalid event definition: {}", e))),
+            None => Ok(event),
+        };
+        let events = input
+            .events
+            .into_iter()
+            .map(resolve_event)
+            .collect::<async_graphql::Result<Vec<_>>>()?;
+
+        let live = input.live.unwrap_or(false);
+        if live && input.to_block.is_some() {
+            return Err(Error::new("to_block cannot be set on a live job"));
+        }
+
+        let factory = input
+            .factory
+            .map(|f| {
+                Ok::<_, Error>(FactoryConfig {
+                    creation_event: resolve_event(f.creation_event)?,
+                    child_address_param: f.child_address_param,
+                    child_events: f.child_events,
+                })
+            })
+            .transpose()?;
+        if let Some(factory) = &factory {
+            factory
+                .validate()
+                .map_err(|e| Error::new(format!("Invalid factory: {:#}", e)))?;
+            if input.contract_address == ANY_ADDRESS {
+                return Err(Error::new("A factory job needs a factory contract address"));
+            }
+        } else if events.is_empty() {
+            return Err(Error::new("At least one event must be specified"));
+        }
+        for event in &events {
+            EventDecoder::parse(event)
+                .map_err(|e| Error::new(format!("Invalid event definition: {}", e)))?;
+        }
+
+        let topic_filters: Vec<TopicFilter> = input
+            .topic_filters
+            .unwrap_or_default()
+            .into_iter()
+            .map(|f| TopicFilter {
+                param: f.param,
+                values: f.values,
+            })
+            .collect();
+        if topic_filters.iter().any(|f| f.values.len() > 100) {
+            return Err(Error::new(
+                "At most 100 values may be given per topic filter",
+            ));
+        }
+        let event_filter = EventFilter::new(&sanitized_chain, Vec::new(), events.clone(), 0, 0);
+        if !events.is_empty() {
+            event_filter
+                .validate_events()
+                .map_err(|e| Error::new(format!("Invalid events: {:#}", e)))?;
+        }
+        // Resolves each filter against every event, rejecting unknown or
+        // non-indexed parameters and values of the wrong type.
+        event_filter
+            .with_topic_filters(&topic_filters)
+            .map_err(|e| Error::new(format!("Invalid topic filter: {:#}", e)))?;
+
+        let mut projections = Vec::new();
+        for name in input.projections.unwrap_or_default() {
+            let projection = Projection::parse(&name).ok_or_else(|| {
+                Error::new(format!(
+                    "Unknown projection '{}'. Supported projections: balances, supply",
+                    name
+                ))
+            })?;
+            if !projections.contains(&projection) {
+                projections.push(projection);
+            }
+        }
+
+        let merkle_mode = merkle_mode(input.merkle_mode.as_deref())?;
+
         let enable_ai = input.enable_ai_extraction.unwrap_or(false);
         let extraction_schema = if let Some(schema_str) = &input.extraction_schema {
             let parsed: serde_json::Value = serde_json::from_str(schema_str)
@@ -499,16 +1126,25 @@ impl Mutation {
 
         let config = JobConfig {
             job_type: JobType::BlockchainIndex,
-            params: JobParams::BlockchainIndex(BlockchainIndexParams {
+            params: JobParams::BlockchainIndex(Box::new(BlockchainIndexParams {
                 chain: sanitized_chain,
                 contract_address: input.contract_address,
-                events: input.events,
+                contract_addresses,
+                events,
+                topic_filters,
                 from_block: input.from_block as u64,
                 to_block: input.to_block.map(|b| b as u64),
                 enable_ai,
                 extraction_schema,
                 ai_token_budget,
-            }),
+                live,
+                factory,
+                resolve_token_uris: input.resolve_token_uris.unwrap_or(false),
+                shard_blocks: input.shard_blocks.map(|b| b as u64),
+                projections,
+                merkle_mode,
+                mmr_commit_blocks: input.mmr_commit_blocks.map(|b| b as u64),
+            })),
         };
         let config_json = serde_json::to_value(&config)
             .map_err(|e| Error::new(format!("Config error: {}", e)))?;
@@ -564,6 +1200,370 @@ impl Mutation {
         })
     }
 
+    /// Creates a job indexing the transactions sent from or to given addresses,
+    /// with receipts, decoded calldata and optional call traces.
+    async fn create_transaction_index_job(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateTransactionIndexJobInput,
+    ) -> async_graphql::Result<Job> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+
+        // Input validation — done before opening the transaction.
+        if input.addresses.is_empty() || input.addresses.len() > 100 {
+            return Err(Error::new("Between 1 and 100 addresses must be specified"));
+        }
+        for address in &input.addresses {
+            InputValidator::validate_ethereum_address(address)
+                .map_err(|e| Error::new(format!("Security validation failed: {}", e)))?;
+        }
+        InputValidator::validate_string_length(&input.chain, 1, 64, "chain")
+            .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+        InputValidator::validate_numeric_range(input.from_block, 0_i64, i64::MAX, "from_block")
+            .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+        let max_to_block = input
+            .from_block
+            .saturating_add(MAX_TRANSACTION_SCAN_BLOCKS - 1);
+        InputValidator::validate_numeric_range(
+            input.to_block,
+            input.from_block,
+            max_to_block,
+            "to_block",
+        )
+        .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+
+        let chains = ctx
+            .data::<ChainRegistry>()
+            .map_err(|_| Error::new("Chain registry not available"))?;
+        let sanitized_chain = Sanitizer::sanitize_text(&input.chain);
+        if !chains.contains(&sanitized_chain) {
+            return Err(Error::new(format!(
+                "Unsupported chain '{}'. Supported chains: {}",
+                sanitized_chain,
+                chains.names().join(", ")
+            )));
+        }
+
+        // ABIs registered for the addresses, and the named one, decode
+        // calldata alongside the definitions given.
+        let named = match &input.abi_name {
+            Some(name) => Some(
+                abi_registry::find_by_name(pool, user_id, name)
+                    .await
+                    .map_err(abi_error)?,
+            ),
+            None => None,
+        };
+        let mut registered: Vec<_> =
+            abi_registry::find_for_contracts(pool, user_id, &sanitized_chain, &input.addresses)
+                .await
+                .map_err(abi_error)?
+                .into_iter()
+                .collect();
+        registered.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let abi = merge_function_definitions(
+            input.abi.unwrap_or_default(),
+            named.iter().chain(registered.iter().map(|(_, abi)| abi)),
+        )
+        .map_err(|e| Error::new(format!("Invalid ABI: {:#}", e)))?;
+        let merkle_mode = merkle_mode(input.merkle_mode.as_deref())?;
+
+        let config = JobConfig {
+            job_type: JobType::TransactionIndex,
+            params: JobParams::TransactionIndex(TransactionIndexParams {
+                chain: sanitized_chain,
+                addresses: input.addresses,
+                abi,
+                from_block: input.from_block as u64,
+                to_block: input.to_block as u64,
+                include_traces: input.include_traces.unwrap_or(false),
+                merkle_mode,
+            }),
+        };
+        let config_json = serde_json::to_value(&config)
+            .map_err(|e| Error::new(format!("Config error: {}", e)))?;
+
+        let job_id = Uuid::new_v4();
+
+        // Atomically decrement credits and insert the job in one transaction.
+        let mut tx = pool.begin().await.context("Failed to begin transaction")?;
+
+        let rows_affected = sqlx::query(
+            "UPDATE user_credits SET credit_balance = credit_balance - 50
+             WHERE user_id = $1 AND credit_balance >= 50",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to decrement credits")?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(Error::new(
+                "Insufficient credits. Please top up your INC balance.",
+            ));
+        }
+
+        sqlx::query("INSERT INTO jobs (id, user_id, status, config) VALUES ($1, $2, 'queued', $3)")
+            .bind(job_id)
+            .bind(user_id)
+            .bind(config_json)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create job")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        db::audit_log(
+            pool,
+            Some(user_id),
+            "create_transaction_index_job",
+            "job",
+            Some(&job_id.to_string()),
+            config
+                .params
+                .as_transaction_index()
+                .map(|p| serde_json::json!({"addresses": &p.addresses})),
+        )
+        .await;
+
+        Ok(Job {
+            id: job_id.to_string(),
+            status: "queued".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Creates a job reading contract state through view calls at one or more
+    /// historical blocks.
+    async fn create_state_snapshot_job(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateStateSnapshotJobInput,
+    ) -> async_graphql::Result<Job> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+
+        // Input validation — done before opening the transaction.
+        if input.calls.is_empty() || input.calls.len() > 500 {
+            return Err(Error::new("Between 1 and 500 calls must be specified"));
+        }
+        if input.blocks.is_empty() || input.blocks.len() > 100 {
+            return Err(Error::new("Between 1 and 100 blocks must be specified"));
+        }
+        if input.calls.len() * input.blocks.len() > MAX_SNAPSHOT_VALUES {
+            return Err(Error::new(format!(
+                "A snapshot may read at most {} values (calls × blocks)",
+                MAX_SNAPSHOT_VALUES
+            )));
+        }
+        for block in &input.blocks {
+            InputValidator::validate_numeric_range(*block, 0_i64, i64::MAX, "blocks")
+                .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+        }
+        for call in &input.calls {
+            InputValidator::validate_ethereum_address(&call.contract)
+                .map_err(|e| Error::new(format!("Security validation failed: {}", e)))?;
+        }
+        InputValidator::validate_string_length(&input.chain, 1, 64, "chain")
+            .map_err(|e| Error::new(format!("Validation failed: {}", e)))?;
+
+        let chains = ctx
+            .data::<ChainRegistry>()
+            .map_err(|_| Error::new("Chain registry not available"))?;
+        let sanitized_chain = Sanitizer::sanitize_text(&input.chain);
+        if !chains.contains(&sanitized_chain) {
+            return Err(Error::new(format!(
+                "Unsupported chain '{}'. Supported chains: {}",
+                sanitized_chain,
+                chains.names().join(", ")
+            )));
+        }
+
+        // Function names resolve against the call's named ABI, or the one
+        // registered for its contract.
+        let contracts: Vec<String> = input.calls.iter().map(|c| c.contract.clone()).collect();
+        let registered =
+            abi_registry::find_for_contracts(pool, user_id, &sanitized_chain, &contracts)
+                .await
+                .map_err(abi_error)?;
+        let mut named = HashMap::new();
+        let mut calls = Vec::with_capacity(input.calls.len());
+        for (i, call) in input.calls.into_iter().enumerate() {
+            let abi = match &call.abi_name {
+                Some(name) => {
+                    if !named.contains_key(name) {
+                        let abi = abi_registry::find_by_name(pool, user_id, name)
+                            .await
+                            .map_err(abi_error)?;
+                        named.insert(name.clone(), abi);
+                    }
+                    named.get(name)
+                }
+                None => registered.get(&call.contract.to_lowercase()),
+            };
+            let function = match abi {
+                Some(abi) => abi
+                    .resolve_function(&call.function)
+                    .map_err(|e| Error::new(format!("Invalid call {}: {}", i, e)))?,
+                None => call.function,
+            };
+            calls.push(ViewCall {
+                contract: call.contract,
+                function,
+                args: call.args.unwrap_or_default(),
+            });
+        }
+        indexnode_core::prepare_calls(&calls).map_err(|e| Error::new(format!("{:#}", e)))?;
+        let merkle_mode = merkle_mode(input.merkle_mode.as_deref())?;
+
+        let config = JobConfig {
+            job_type: JobType::StateSnapshot,
+            params: JobParams::StateSnapshot(StateSnapshotParams {
+                chain: sanitized_chain,
+                calls,
+                blocks: input.blocks.iter().map(|b| *b as u64).collect(),
+                merkle_mode,
+            }),
+        };
+        let config_json = serde_json::to_value(&config)
+            .map_err(|e| Error::new(format!("Config error: {}", e)))?;
+
+        let job_id = Uuid::new_v4();
+
+        // Atomically decrement credits and insert the job in one transaction.
+        let mut tx = pool.begin().await.context("Failed to begin transaction")?;
+
+        let rows_affected = sqlx::query(
+            "UPDATE user_credits SET credit_balance = credit_balance - 50
+             WHERE user_id = $1 AND credit_balance >= 50",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to decrement credits")?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(Error::new(
+                "Insufficient credits. Please top up your INC balance.",
+            ));
+        }
+
+        sqlx::query("INSERT INTO jobs (id, user_id, status, config) VALUES ($1, $2, 'queued', $3)")
+            .bind(job_id)
+            .bind(user_id)
+            .bind(config_json)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create job")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        db::audit_log(
+            pool,
+            Some(user_id),
+            "create_state_snapshot_job",
+            "job",
+            Some(&job_id.to_string()),
+            config
+                .params
+                .as_state_snapshot()
+                .map(|p| serde_json::json!({"calls": p.calls.len(), "blocks": &p.blocks})),
+        )
+        .await;
+
+        Ok(Job {
+            id: job_id.to_string(),
+            status: "queued".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Pauses a live-tail job. Its block cursor is kept so it can be resumed.
+    async fn pause_job(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Job> {
+        transition_job_status(ctx, &id, "live", "paused", "pause_job").await
+    }
+
+    /// Resumes a paused live-tail job from its last processed block.
+    async fn resume_job(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Job> {
+        transition_job_status(ctx, &id, "paused", "live", "resume_job").await
+    }
+
+    /// Registers a contract ABI for a chain and contract address, under a
+    /// reusable name, or both.
+    async fn create_abi(
+        &self,
+        ctx: &Context<'_>,
+        input: AbiInput,
+    ) -> async_graphql::Result<RegisteredAbi> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+
+        let stored = abi_registry::create(pool, user_id, abi_definition(input)?)
+            .await
+            .map_err(abi_error)?;
+        Ok(registered_abi(stored))
+    }
+
+    /// Replaces a registered ABI. Existing jobs keep the definitions they
+    /// were created with.
+    async fn update_abi(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        input: AbiInput,
+    ) -> async_graphql::Result<RegisteredAbi> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let id = Uuid::parse_str(&id)
+            .map_err(|e| Error::new(format!("Invalid ABI ID format: {}", e)))?;
+
+        let stored = abi_registry::update(pool, user_id, id, abi_definition(input)?)
+            .await
+            .map_err(abi_error)?;
+        Ok(registered_abi(stored))
+    }
+
+    /// Deletes a registered ABI.
+    async fn delete_abi(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<bool> {
+        let pool = ctx
+            .data::<PgPool>()
+            .map_err(|_| Error::new("Failed to get database pool"))?;
+        let user_id = ctx
+            .data_opt::<Uuid>()
+            .cloned()
+            .ok_or_else(|| Error::new("Unauthorized"))?;
+        let id = Uuid::parse_str(&id)
+            .map_err(|e| Error::new(format!("Invalid ABI ID format: {}", e)))?;
+
+        abi_registry::delete(pool, user_id, id)
+            .await
+            .map_err(abi_error)?;
+        Ok(true)
+    }
+
     /// Registers or updates the Ethereum wallet address for the authenticated user.
     /// This address is used for on-chain credit spending when indexing jobs run.
     async fn register_wallet(
@@ -845,6 +1845,200 @@ impl Mutation {
     }
 }
 
+/// Moves one of the caller's jobs from status `from` to `to`, failing if the
+/// job does not exist, belongs to another user, or is not currently in `from`.
+async fn transition_job_status(
+    ctx: &Context<'_>,
+    id: &str,
+    from: &str,
+    to: &str,
+    action: &str,
+) -> async_graphql::Result<Job> {
+    let pool = ctx
+        .data::<PgPool>()
+        .map_err(|_| Error::new("Failed to get database pool"))?;
+    let user_id = ctx
+        .data_opt::<Uuid>()
+        .cloned()
+        .ok_or_else(|| Error::new("Unauthorized"))?;
+    let job_id =
+        Uuid::parse_str(id).map_err(|e| Error::new(format!("Invalid job ID format: {}", e)))?;
+
+    let created_at = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+        "UPDATE jobs SET status = $1 WHERE id = $2 AND user_id = $3 AND status = $4
+         RETURNING created_at",
+    )
+    .bind(to)
+    .bind(job_id)
+    .bind(user_id)
+    .bind(from)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to update job status")?
+    .ok_or_else(|| Error::new(format!("No {} job found with this ID", from)))?;
+
+    db::audit_log(
+        pool,
+        Some(user_id),
+        action,
+        "job",
+        Some(&job_id.to_string()),
+        None,
+    )
+    .await;
+
+    Ok(Job {
+        id: job_id.to_string(),
+        status: to.to_string(),
+        created_at: created_at.to_rfc3339(),
+    })
+}
+
+/// Resolves one of the caller's jobs, failing unless it maintains `projection`.
+async fn require_projection(
+    pool: &PgPool,
+    user_id: Uuid,
+    job_id: &str,
+    projection: Projection,
+) -> async_graphql::Result<Uuid> {
+    let job_id =
+        Uuid::parse_str(job_id).map_err(|e| Error::new(format!("Invalid job ID format: {}", e)))?;
+    let config = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT config FROM jobs WHERE id = $1 AND user_id = $2",
+    )
+    .bind(job_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch job")?
+    .ok_or_else(|| Error::new("Job not found"))?;
+    let config: JobConfig =
+        serde_json::from_value(config).map_err(|e| Error::new(format!("Config error: {}", e)))?;
+    let maintained = config
+        .params
+        .as_blockchain_index()
+        .is_some_and(|p| p.projections.contains(&projection));
+    if !maintained {
+        return Err(Error::new(format!(
+            "Job does not maintain the '{}' projection",
+            projection.as_str()
+        )));
+    }
+    Ok(job_id)
+}
+
+/// Parses a job's `merkle_mode` input, defaulting to the SHA-256 tree.
+fn merkle_mode(name: Option<&str>) -> async_graphql::Result<MerkleMode> {
+    name.map_or(Ok(MerkleMode::default()), |name| {
+        MerkleMode::parse(name).ok_or_else(|| {
+            Error::new(format!(
+                "Unknown merkle_mode '{}'. Supported modes: sha256, keccak256",
+                name
+            ))
+        })
+    })
+}
+
+fn abi_definition(input: AbiInput) -> async_graphql::Result<AbiDefinition> {
+    let abi = serde_json::from_str(&input.abi)
+        .map_err(|e| Error::new(format!("Invalid ABI JSON: {}", e)))?;
+    Ok(AbiDefinition {
+        name: input.name,
+        chain: input.chain,
+        contract_address: input.contract_address,
+        abi,
+    })
+}
+
+fn abi_error(e: AbiRegistryError) -> Error {
+    match e {
+        AbiRegistryError::Database(e) => {
+            tracing::error!("ABI registry error: {:?}", e);
+            Error::new("Failed to access the ABI registry")
+        }
+        e => Error::new(e.to_string()),
+    }
+}
+
+fn inclusion_proof(proof: proofs::InclusionProof) -> InclusionProof {
+    InclusionProof {
+        leaf: proof.leaf,
+        leaf_index: proof.leaf_index as i32,
+        leaf_count: proof.leaf_count as i32,
+        proof: proof.proof,
+        root: proof.root,
+        tree_version: proof.tree_version.into(),
+        chain: proof.chain,
+        transaction_hash: proof.transaction_hash,
+        block_number: proof.block_number,
+        committed_at: proof.committed_at,
+        registry_chain_id: proof.registry_chain_id,
+        registry_address: proof.registry_address,
+        reorg_affected: proof.reorg_affected,
+    }
+}
+
+fn registered_abi(stored: StoredAbi) -> RegisteredAbi {
+    let params = |params: &[ethers::abi::Param]| {
+        params
+            .iter()
+            .map(|p| AbiParam {
+                name: p.name.clone(),
+                kind: p.kind.to_string(),
+                indexed: None,
+            })
+            .collect()
+    };
+    let events = stored
+        .abi
+        .events()
+        .map(|e| AbiEvent {
+            name: e.name.clone(),
+            signature: indexnode_core::abi::event_signature(e),
+            topic0: format!("{:?}", e.signature()),
+            anonymous: e.anonymous,
+            inputs: e
+                .inputs
+                .iter()
+                .map(|p| AbiParam {
+                    name: p.name.clone(),
+                    kind: p.kind.to_string(),
+                    indexed: Some(p.indexed),
+                })
+                .collect(),
+        })
+        .collect();
+    let functions = stored
+        .abi
+        .functions()
+        .map(|f| AbiFunction {
+            name: f.name.clone(),
+            signature: f.signature(),
+            selector: format!("0x{}", hex::encode(f.short_signature())),
+            state_mutability: match f.state_mutability {
+                StateMutability::Pure => "pure",
+                StateMutability::View => "view",
+                StateMutability::NonPayable => "nonpayable",
+                StateMutability::Payable => "payable",
+            }
+            .to_string(),
+            inputs: params(&f.inputs),
+            outputs: params(&f.outputs),
+        })
+        .collect();
+    RegisteredAbi {
+        id: stored.id.to_string(),
+        name: stored.name,
+        chain: stored.chain,
+        contract_address: stored.contract_address,
+        abi: stored.abi.json().clone(),
+        events,
+        functions,
+        created_at: stored.created_at.to_rfc3339(),
+        updated_at: stored.updated_at.to_rfc3339(),
+    }
+}
+
 pub struct Subscription;
 
 #[Subscription]
@@ -886,10 +2080,28 @@ impl Subscription {
                 contract_address: v["contract_address"].as_str()?.to_string(),
                 event_name: v["event_name"].as_str()?.to_string(),
                 block_number: v["block_number"].as_i64()?,
+                block_hash: v["block_hash"].as_str().map(|s| s.to_string()),
+                block_timestamp: v["block_timestamp"].as_str().map(|s| s.to_string()),
                 transaction_hash: v["transaction_hash"].as_str()?.to_string(),
+                log_index: v["log_index"].as_i64(),
                 event_data: v["event_data"].clone(),
                 content_hash: v["content_hash"].as_str()?.to_string(),
+                hash_version: v["hash_version"].as_i64().unwrap_or(1) as i32,
                 ipfs_cid: v["ipfs_cid"].as_str().map(|s| s.to_string()),
+                token: v["token"]["standard"].as_str().map(|standard| {
+                    let text = |key: &str| v["token"][key].as_str().map(|s| s.to_string());
+                    TokenInfo {
+                        standard: standard.to_string(),
+                        name: text("name"),
+                        symbol: text("symbol"),
+                        decimals: v["token"]["decimals"].as_i64().map(|d| d as i32),
+                        amount_raw: text("amount_raw"),
+                        amount: text("amount"),
+                        token_id: text("token_id"),
+                        token_uri: text("token_uri"),
+                        metadata: Some(v["token"]["metadata"].clone()).filter(|m| !m.is_null()),
+                    }
+                }),
             })
         }))
     }
@@ -897,16 +2109,19 @@ impl Subscription {
 
 pub type AppSchema = Schema<Query, Mutation, Subscription>;
 
-/// Builds the GraphQL schema with the provided database pool and credit manager.
+/// Builds the GraphQL schema with the provided database pool, credit manager,
+/// marketplace client and chain registry.
 pub fn build_schema(
     pool: PgPool,
     credit_manager: CreditManager,
     marketplace: MarketplaceClient,
+    chains: ChainRegistry,
 ) -> AppSchema {
     Schema::build(Query, Mutation, Subscription)
         .data(pool)
         .data(credit_manager)
         .data(marketplace)
+        .data(chains)
         .finish()
 }
 
@@ -23,14 +23,49 @@ pub struct BlockchainEvent {
     pub event_name: String,
     /// The block number where the event was emitted.
     pub block_number: i64,
+    /// The hash of the block containing the event.
+    pub block_hash: Option<String>,
+    /// RFC3339 formatted timestamp of the block containing the event.
+    pub block_timestamp: Option<String>,
     /// The hash of the transaction that emitted the event.
     pub transaction_hash: String,
+    /// The position of the log within its block.
+    pub log_index: Option<i64>,
     /// JSON data containing event parameters.
     pub event_data: serde_json::Value,
     /// A cryptographic hash of the event content for integrity verification.
     pub content_hash: String,
+    /// How `content_hash` was computed: 2 for the SHA-256 of the event's
+    /// RFC 8785 canonical JSON, 1 for events indexed before canonical hashing.
+    pub hash_version: i32,
     /// The IPFS Content Identifier (CID) where the event data is stored.
     pub ipfs_cid: Option<String>,
+    /// Token context, for transfers emitted by ERC-20, ERC-721 and ERC-1155
+    /// contracts. Not part of the content hash.
+    pub token: Option<TokenInfo>,
+}
+
+/// The token moved by a transfer event.
+#[derive(SimpleObject, Serialize, Deserialize)]
+pub struct TokenInfo {
+    /// The token standard: "erc20", "erc721" or "erc1155".
+    pub standard: String,
+    /// The token's name, if the contract exposes one.
+    pub name: Option<String>,
+    /// The token's symbol, if the contract exposes one.
+    pub symbol: Option<String>,
+    /// The token's decimals (ERC-20 only).
+    pub decimals: Option<i32>,
+    /// The amount transferred in base units, as a decimal string.
+    pub amount_raw: Option<String>,
+    /// The amount scaled by the token's decimals, as a decimal string.
+    pub amount: Option<String>,
+    /// The NFT transferred, as a decimal string.
+    pub token_id: Option<String>,
+    /// The NFT's metadata URI, when the job resolves token URIs.
+    pub token_uri: Option<String>,
+    /// The JSON document behind `token_uri`, if it could be fetched.
+    pub metadata: Option<serde_json::Value>,
 }
 
 /// Metadata for content stored on IPFS.
@@ -53,10 +88,17 @@ pub struct IpfsContentMetadata {
 pub struct CreateBlockchainJobInput {
     /// The blockchain network (e.g., "ethereum").
     pub chain: String,
-    /// The address of the smart contract to index.
+    /// The address of the smart contract to index, or "*" to index matching
+    /// events from every contract.
     pub contract_address: String,
-    /// List of event signatures to monitor.
+    /// Further contracts indexed with the same events and filters (at most 100).
+    pub contract_addresses: Option<Vec<String>>,
+    /// Events to monitor. Each entry is a bare signature (`Transfer(address,address,uint256)`),
+    /// a human-readable declaration (`event Transfer(address indexed from, ...)`) or a JSON
+    /// ABI event fragment. Declarations with parameter names yield named decoded fields.
     pub events: Vec<String>,
+    /// Restrict indexed event parameters to given values. All filters must match.
+    pub topic_filters: Option<Vec<TopicFilterInput>>,
     /// The starting block number for indexing.
     pub from_block: i64,
     /// Optional ending block number for indexing.
@@ -69,6 +111,263 @@ pub struct CreateBlockchainJobInput {
     /// Maximum total tokens (input + output) the AI extractor may use across
     /// all events in this job. Defaults to 100,000. Capped at 1,000,000.
     pub ai_token_budget: Option<i32>,
+    /// Keep indexing new blocks as the chain advances instead of completing
+    /// after the backfill. Cannot be combined with to_block.
+    pub live: Option<bool>,
+    /// Treat contract_address as a factory and also index every contract it
+    /// creates. `events` may then be empty.
+    pub factory: Option<FactoryInput>,
+    /// Fetch the metadata document (`ipfs://`, `data:` or http(s)) behind the
+    /// token URI of each transferred NFT.
+    pub resolve_token_uris: Option<bool>,
+    /// Name of a registered ABI to look up event names in. Without it, the
+    /// ABI registered for contract_address is used, if there is one.
+    pub abi_name: Option<String>,
+    /// Blocks per backfill shard (1,000 to 10,000,000). Shards are fetched
+    /// concurrently and merged in block order. Defaults to 100,000.
+    pub shard_blocks: Option<i64>,
+    /// Token projections to maintain from the job's ERC-20 and ERC-721
+    /// transfers: "balances" (current balances and holders) and "supply"
+    /// (mints and burns per block).
+    pub projections: Option<Vec<String>>,
+    /// The tree the job's Merkle commitment is built in: "sha256" (the
+    /// default) or "keccak256", a sorted-pair tree whose proofs verify with
+    /// OpenZeppelin's `MerkleProof.verify` on-chain.
+    pub merkle_mode: Option<String>,
+    /// Blocks between on-chain commitments of a live job's Merkle Mountain
+    /// Range root (1 to 1,000,000). Defaults to 100.
+    pub mmr_commit_blocks: Option<i64>,
+}
+
+/// Restricts an indexed event parameter to a set of values.
+#[derive(InputObject)]
+pub struct TopicFilterInput {
+    /// Name of a parameter declared `indexed` in every event of the job (e.g. "to").
+    pub param: String,
+    /// Accepted values, any of which matches (at most 100). Addresses and integers
+    /// are given as text; string and bytes parameters match on their keccak hash.
+    pub values: Vec<String>,
+}
+
+/// Factory settings for a blockchain indexing job.
+#[derive(InputObject)]
+pub struct FactoryInput {
+    /// The factory's creation event, e.g. `event PoolCreated(address indexed token0,
+    /// address indexed token1, uint24 indexed fee, int24 tickSpacing, address pool)`.
+    pub creation_event: String,
+    /// The creation event parameter holding the new contract's address (e.g. "pool"),
+    /// or `argN` for unnamed parameters.
+    pub child_address_param: String,
+    /// Events to index on every created contract, in the same forms as `events`.
+    pub child_events: Vec<String>,
+}
+
+/// A contract discovered from a factory's creation event.
+#[derive(SimpleObject)]
+pub struct ChildContract {
+    /// The created contract's address.
+    pub address: String,
+    /// The block containing the creation event; indexing starts here.
+    pub creation_block: i64,
+    /// The transaction that emitted the creation event.
+    pub creation_transaction_hash: String,
+    /// Hash of this discovery as committed in the job's Merkle tree.
+    pub content_hash: String,
+    /// The committed Merkle root covering this discovery, once committed.
+    pub merkle_root: Option<String>,
+}
+
+/// Input for creating a transaction indexing job.
+#[derive(InputObject)]
+pub struct CreateTransactionIndexJobInput {
+    /// The blockchain network (e.g., "ethereum").
+    pub chain: String,
+    /// Transactions sent from or to any of these addresses are indexed (at most 100).
+    pub addresses: Vec<String>,
+    /// Function definitions used to decode calldata. Each entry is a JSON ABI, a JSON
+    /// ABI function fragment, a human-readable declaration
+    /// (`function transfer(address to, uint256 amount)`) or a bare signature.
+    /// ABIs registered for the addresses are added automatically.
+    pub abi: Option<Vec<String>>,
+    /// Name of a registered ABI to also decode calldata with.
+    pub abi_name: Option<String>,
+    /// The first block to scan.
+    pub from_block: i64,
+    /// The last block to scan. Every block is read, so a job spans at most
+    /// 100,000 blocks.
+    pub to_block: i64,
+    /// Attach a call trace with internal calls to each transaction, on nodes that
+    /// support `debug_traceTransaction`.
+    pub include_traces: Option<bool>,
+    /// The tree the job's Merkle commitment is built in: "sha256" (the
+    /// default) or "keccak256", a sorted-pair tree whose proofs verify with
+    /// OpenZeppelin's `MerkleProof.verify` on-chain.
+    pub merkle_mode: Option<String>,
+}
+
+/// A block-range shard of a blockchain job's backfill.
+#[derive(SimpleObject)]
+pub struct JobShard {
+    /// Position of the shard; shards cover the job's range in block order.
+    pub shard_index: i32,
+    pub from_block: i64,
+    pub to_block: i64,
+    /// "pending", "running", "completed", "failed" or "cancelled".
+    pub status: String,
+    /// Events the shard fetched, once completed.
+    pub events_found: Option<i64>,
+    /// Why the shard failed, if it did.
+    pub error: Option<String>,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+}
+
+/// A leaf's Merkle inclusion proof, verifiable with
+/// `verify_versioned_proof(leaf, proof, root, leafIndex, leafCount, treeVersion)`.
+#[derive(SimpleObject)]
+pub struct InclusionProof {
+    /// The content hash proven.
+    pub leaf: String,
+    /// The leaf's position in the job's tree.
+    pub leaf_index: i32,
+    pub leaf_count: i32,
+    /// Sibling hashes from the leaf up to the root.
+    pub proof: Vec<String>,
+    pub root: String,
+    /// The tree format: 1 (legacy), 2 (SHA-256) or 3 (keccak256 sorted pairs).
+    pub tree_version: i32,
+    /// The on-chain commitment of the root, once made.
+    pub chain: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub block_number: Option<i64>,
+    pub committed_at: Option<String>,
+    /// Chain ID and address of the registry the root was committed to.
+    pub registry_chain_id: Option<i64>,
+    pub registry_address: Option<String>,
+    /// True when a reorg orphaned data covered by the committed root.
+    pub reorg_affected: bool,
+}
+
+/// A holder's balance in a job's `balances` projection.
+#[derive(SimpleObject)]
+pub struct TokenBalance {
+    pub contract_address: String,
+    pub holder: String,
+    /// Base units for ERC-20 tokens, tokens held for ERC-721, as a decimal
+    /// string. Counted from the job's first block.
+    pub balance: String,
+    /// The token standard: "erc20" or "erc721".
+    pub standard: Option<String>,
+    /// The token's decimals (ERC-20 only).
+    pub decimals: Option<i32>,
+}
+
+/// Tokens minted and burned in one block, from a job's `supply` projection.
+#[derive(SimpleObject)]
+pub struct SupplyPoint {
+    pub block_number: i64,
+    /// Tokens minted in the block, as a decimal string.
+    pub minted: String,
+    /// Tokens burned in the block, as a decimal string.
+    pub burned: String,
+    /// Everything minted minus everything burned up to and including the
+    /// block, counted from the job's first block.
+    pub total_supply: String,
+}
+
+/// A transaction indexed by a transaction indexing job.
+#[derive(SimpleObject)]
+pub struct IndexedTransaction {
+    /// The transaction hash.
+    pub transaction_hash: String,
+    /// The block number containing the transaction.
+    pub block_number: i64,
+    /// RFC3339 formatted timestamp of the block containing the transaction.
+    pub block_timestamp: String,
+    /// The sender.
+    pub from_address: String,
+    /// The recipient; null for contract creations.
+    pub to_address: Option<String>,
+    /// Value transferred in wei, as a decimal string.
+    pub value: String,
+    /// The called function's name, if the job's ABI defines it.
+    pub function_name: Option<String>,
+    /// The decoded call: function, signature, selector and named params.
+    pub decoded_call: Option<serde_json::Value>,
+    /// Whether the transaction succeeded; null for pre-Byzantium receipts.
+    pub status: Option<bool>,
+    /// Gas used, from the receipt.
+    pub gas_used: Option<i64>,
+    /// The contract created by the transaction, if any.
+    pub contract_address: Option<String>,
+    /// The call trace including internal calls, if traces were requested.
+    pub trace: Option<serde_json::Value>,
+    /// Hash of the transaction record as committed in the job's Merkle tree.
+    pub content_hash: String,
+    /// The committed Merkle root covering this transaction, once committed.
+    pub merkle_root: Option<String>,
+}
+
+/// A view function call made by a state snapshot job.
+#[derive(InputObject)]
+pub struct ViewCallInput {
+    /// The contract to call.
+    pub contract: String,
+    /// The function, as a JSON ABI fragment or a human-readable declaration
+    /// (`function balanceOf(address owner) view returns (uint256)`). Return
+    /// types are needed to decode the result. With a registered ABI, the
+    /// function's name (`balanceOf`) is enough.
+    pub function: String,
+    /// Arguments in declaration order, e.g. `0x…` for addresses and decimal
+    /// strings for integers.
+    pub args: Option<Vec<String>>,
+    /// Name of a registered ABI to look the function up in. Without it, the
+    /// ABI registered for the contract is used, if there is one.
+    pub abi_name: Option<String>,
+}
+
+/// Input for creating a contract state snapshot job.
+#[derive(InputObject)]
+pub struct CreateStateSnapshotJobInput {
+    /// The blockchain network (e.g., "ethereum").
+    pub chain: String,
+    /// The view calls to run at each block (at most 500).
+    pub calls: Vec<ViewCallInput>,
+    /// The block heights to read state at (at most 100). Historical blocks
+    /// require an archive node.
+    pub blocks: Vec<i64>,
+    /// The tree the job's Merkle commitment is built in: "sha256" (the
+    /// default) or "keccak256", a sorted-pair tree whose proofs verify with
+    /// OpenZeppelin's `MerkleProof.verify` on-chain.
+    pub merkle_mode: Option<String>,
+}
+
+/// The result of one view call at one block of a state snapshot job.
+#[derive(SimpleObject)]
+pub struct SnapshotValue {
+    /// The block the call was run at.
+    pub block_number: i64,
+    /// The hash of that block; every call of the block was pinned to it.
+    pub block_hash: String,
+    /// Position of the call in the job's calls.
+    pub call_index: i32,
+    /// The called contract.
+    pub contract_address: String,
+    /// The canonical signature of the called function.
+    pub function_signature: String,
+    /// The call arguments.
+    pub args: serde_json::Value,
+    /// The raw return data.
+    pub return_data: String,
+    /// Return values keyed by output name; null if no return types were given
+    /// or decoding failed.
+    pub result: Option<serde_json::Value>,
+    /// Why the return data could not be decoded, if it could not.
+    pub decode_error: Option<String>,
+    /// Hash of the value as committed in the job's Merkle tree.
+    pub content_hash: String,
+    /// The committed Merkle root covering this value, once committed.
+    pub merkle_root: Option<String>,
 }
 
 /// Represents an AI-powered extraction from a blockchain event.
@@ -108,6 +407,21 @@ pub struct SystemMetrics {
     pub queue_depth: i64,
 }
 
+/// A chain that blockchain indexing jobs can target.
+#[derive(SimpleObject)]
+pub struct SupportedChain {
+    /// Name to pass as `chain` when creating a job (e.g., "arbitrum").
+    pub name: String,
+    /// EIP-155 chain ID.
+    pub chain_id: i64,
+    /// Blocks behind the head before events are indexed and committed.
+    pub confirmations: i64,
+    /// Average block time in milliseconds.
+    pub block_time_ms: i64,
+    /// Largest block range requested in a single eth_getLogs call.
+    pub max_log_range: i64,
+}
+
 /// A listing in the data marketplace.
 #[derive(SimpleObject)]
 pub struct MarketplaceListing {
@@ -137,10 +451,11 @@ pub struct MarketplacePurchase {
 #[derive(SimpleObject)]
 pub struct UserJob {
     pub id: String,
-    /// "blockchain_index" or "http_crawl"
+    /// "blockchain_index", "transaction_index" or "http_crawl"
     pub job_type: String,
     pub status: String,
-    /// Contract address for blockchain jobs, URL for crawl jobs.
+    /// Contract address for blockchain jobs, the comma-separated addresses for
+    /// transaction jobs, URL for crawl jobs.
     pub target: Option<String>,
     /// Chain name for blockchain jobs (e.g. "ethereum").
     pub chain: Option<String>,
@@ -166,3 +481,75 @@ pub struct CreateListingInput {
     pub ipfs_cid: String,
     pub price_credits: i64,
 }
+
+/// A contract ABI in the authenticated user's ABI registry.
+#[derive(SimpleObject)]
+pub struct RegisteredAbi {
+    pub id: String,
+    /// The name jobs reference the ABI by, if it has one.
+    pub name: Option<String>,
+    /// The chain of the contract the ABI is registered for.
+    pub chain: Option<String>,
+    /// The contract the ABI is registered for. Jobs on this contract resolve
+    /// names against the ABI without naming it.
+    pub contract_address: Option<String>,
+    /// The ABI as a JSON array.
+    pub abi: serde_json::Value,
+    /// The events the ABI defines, in name order.
+    pub events: Vec<AbiEvent>,
+    /// The functions the ABI defines, in name order.
+    pub functions: Vec<AbiFunction>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// An event defined by a registered ABI.
+#[derive(SimpleObject)]
+pub struct AbiEvent {
+    pub name: String,
+    /// The canonical signature, e.g. `Transfer(address,address,uint256)`.
+    pub signature: String,
+    /// The keccak hash of the signature, matched against a log's first topic.
+    pub topic0: String,
+    pub anonymous: bool,
+    pub inputs: Vec<AbiParam>,
+}
+
+/// A function defined by a registered ABI.
+#[derive(SimpleObject)]
+pub struct AbiFunction {
+    pub name: String,
+    /// The canonical signature, e.g. `balanceOf(address)`.
+    pub signature: String,
+    /// The 4-byte selector that starts the function's calldata.
+    pub selector: String,
+    /// "pure", "view", "nonpayable" or "payable".
+    pub state_mutability: String,
+    pub inputs: Vec<AbiParam>,
+    pub outputs: Vec<AbiParam>,
+}
+
+/// A parameter of an ABI event or function.
+#[derive(SimpleObject)]
+pub struct AbiParam {
+    pub name: String,
+    /// The Solidity type, e.g. "uint256".
+    #[graphql(name = "type")]
+    pub kind: String,
+    /// Whether an event parameter is indexed; null for function parameters.
+    pub indexed: Option<bool>,
+}
+
+/// Input for registering or replacing an ABI. Give a name, a chain and
+/// contract address, or both.
+#[derive(InputObject)]
+pub struct AbiInput {
+    /// A reusable name (letters, digits, `_`, `-` and `.`).
+    pub name: Option<String>,
+    /// The chain of the contract the ABI belongs to.
+    pub chain: Option<String>,
+    /// The contract the ABI belongs to.
+    pub contract_address: Option<String>,
+    /// The JSON ABI array, or a compiler artifact with an `abi` field, as a string.
+    pub abi: String,
+}
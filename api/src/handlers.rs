@@ -1,17 +1,20 @@
 use crate::{
+    abi_registry::{self, AbiDefinition, AbiRegistryError, StoredAbi},
     auth, db,
+    mmr::{self, MmrConsistency, MmrInclusion, MmrRoot},
     models::User,
+    proofs::{self, InclusionMultiproof, InclusionProof, ProofError},
     routes::AppState,
     security::{InputValidator, SecurityConfig},
 };
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
     http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
 use chrono::Utc;
-use indexnode_core::{HttpCrawlParams, Job, JobConfig, JobParams, JobQueue, JobStatus, JobType};
+use indexnode_core::{to_canonical_cbor, HttpCrawlParams, JobConfig, JobParams, JobType};
 use rand_core::{OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -271,9 +274,9 @@ pub async fn create_job(
             })?;
             JobParams::HttpCrawl(p)
         }
-        JobType::BlockchainIndex => {
-            // Blockchain index jobs must be created through the GraphQL mutation
-            // which performs address and event validation.
+        JobType::BlockchainIndex | JobType::TransactionIndex | JobType::StateSnapshot => {
+            // Blockchain jobs must be created through the GraphQL mutations
+            // which perform address, event and ABI validation.
             return Err(StatusCode::BAD_REQUEST);
         }
     };
@@ -384,6 +387,14 @@ pub struct VerifyHashResponse {
     pub block_number: Option<i64>,
     pub transaction_hash: Option<String>,
     pub committed_at: Option<String>,
+    /// True when a chain reorganization orphaned events covered by the
+    /// committed root, so the commitment no longer matches canonical data.
+    pub reorg_affected: bool,
+    /// The content hash version of the matched event, when the hash is an
+    /// event's rather than a committed root.
+    pub hash_version: Option<i16>,
+    /// The Merkle tree format of the matched commitment.
+    pub tree_version: Option<i16>,
 }
 
 // ── Me ────────────────────────────────────────────────────────────────────────
@@ -435,7 +446,7 @@ pub async fn verify_hash(
 
     // Pass 1: direct match against committed Merkle roots.
     let direct = sqlx::query(
-        "SELECT transaction_hash, block_number, committed_at
+        "SELECT transaction_hash, block_number, committed_at, reorg_affected, tree_version
          FROM timestamp_commits
          WHERE content_hash = $1
          LIMIT 1",
@@ -455,12 +466,16 @@ pub async fn verify_hash(
             block_number: Some(r.get("block_number")),
             transaction_hash: Some(r.get("transaction_hash")),
             committed_at: Some(committed_at.to_rfc3339()),
+            reorg_affected: r.get("reorg_affected"),
+            hash_version: None,
+            tree_version: Some(r.get("tree_version")),
         }));
     }
 
     // Pass 2: resolve via event → batch Merkle root → timestamp_commit.
     let via_event = sqlx::query(
-        "SELECT tc.transaction_hash, tc.block_number, tc.committed_at
+        "SELECT tc.transaction_hash, tc.block_number, tc.committed_at,
+                tc.reorg_affected OR be.orphaned AS reorg_affected, be.hash_version, tc.tree_version
          FROM blockchain_events be
          JOIN timestamp_commits tc ON tc.content_hash = be.merkle_root
          WHERE be.content_hash = $1
@@ -482,6 +497,9 @@ pub async fn verify_hash(
                 block_number: Some(r.get("block_number")),
                 transaction_hash: Some(r.get("transaction_hash")),
                 committed_at: Some(committed_at.to_rfc3339()),
+                reorg_affected: r.get("reorg_affected"),
+                hash_version: Some(r.get("hash_version")),
+                tree_version: Some(r.get("tree_version")),
             }))
         }
         None => Ok(Json(VerifyHashResponse {
@@ -489,10 +507,261 @@ pub async fn verify_hash(
             block_number: None,
             transaction_hash: None,
             committed_at: None,
+            reorg_affected: false,
+            hash_version: None,
+            tree_version: None,
         })),
     }
 }
 
+fn proof_error_status(e: ProofError) -> StatusCode {
+    match e {
+        ProofError::NotFound => StatusCode::NOT_FOUND,
+        ProofError::NotCommitted => StatusCode::CONFLICT,
+        ProofError::InvalidRange => StatusCode::BAD_REQUEST,
+        e => {
+            tracing::error!("Inclusion proof error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Returns the Merkle inclusion proof for an event of one of the caller's jobs.
+pub async fn event_proof(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<InclusionProof>, StatusCode> {
+    let proof = proofs::for_event(&state.pool, id, user_id)
+        .await
+        .map_err(proof_error_status)?;
+    Ok(Json(proof))
+}
+
+/// Returns the Merkle inclusion proof for a content hash in a tree of one of
+/// the caller's jobs.
+pub async fn hash_proof(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(content_hash): Path<String>,
+) -> Result<Json<InclusionProof>, StatusCode> {
+    let proof = proofs::for_content_hash(&state.pool, &content_hash, user_id)
+        .await
+        .map_err(proof_error_status)?;
+    Ok(Json(proof))
+}
+
+/// Most content hashes one multiproof request may cover.
+const MAX_MULTIPROOF_LEAVES: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct MultiproofRequest {
+    pub root: String,
+    pub content_hashes: Vec<String>,
+}
+
+/// Returns one multiproof for a set of content hashes in the tree with the
+/// given root, which must be one of the caller's jobs' trees.
+pub async fn multiproof(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(req): Json<MultiproofRequest>,
+) -> Result<Json<InclusionMultiproof>, StatusCode> {
+    if req.content_hashes.is_empty() || req.content_hashes.len() > MAX_MULTIPROOF_LEAVES {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let proof = proofs::multiproof(&state.pool, &req.root, &req.content_hashes, user_id)
+        .await
+        .map_err(proof_error_status)?;
+    Ok(Json(proof))
+}
+
+#[derive(Deserialize)]
+pub struct BundleQuery {
+    /// `json` (the default) or `cbor`.
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct JobBundlesQuery {
+    /// `json` (the default) or `cbor`.
+    pub format: Option<String>,
+    /// Page size; every committed event when absent.
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// One multiproof bundle per tree instead of a bundle per event.
+    #[serde(default)]
+    pub multiproof: bool,
+}
+
+/// Encodes `value` as a downloadable JSON or deterministic CBOR file named
+/// `name` with the format's extension.
+fn bundle_download<T: Serialize>(
+    value: &T,
+    format: Option<&str>,
+    name: &str,
+) -> Result<axum::response::Response, StatusCode> {
+    let (content_type, body) = match format.unwrap_or("json") {
+        "json" => ("application/json", serde_json::to_vec_pretty(value).ok()),
+        "cbor" => (
+            "application/cbor",
+            serde_json::to_value(value)
+                .ok()
+                .map(|v| to_canonical_cbor(&v)),
+        ),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    let body = body.ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let extension = content_type.trim_start_matches("application/");
+    let disposition = format!("attachment; filename=\"{}.{}\"", name, extension);
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Exports the proof bundle for an event of one of the caller's jobs.
+pub async fn event_bundle(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<BundleQuery>,
+) -> Result<axum::response::Response, StatusCode> {
+    let bundle = proofs::bundle_for_event(&state.pool, id, user_id)
+        .await
+        .map_err(proof_error_status)?;
+    bundle_download(
+        &bundle,
+        query.format.as_deref(),
+        &format!("event-{}.proof", id),
+    )
+}
+
+/// Exports the proof bundle for a crawled link of one of the caller's jobs.
+pub async fn crawl_result_bundle(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<BundleQuery>,
+) -> Result<axum::response::Response, StatusCode> {
+    let bundle = proofs::bundle_for_crawl_result(&state.pool, id, user_id)
+        .await
+        .map_err(proof_error_status)?;
+    bundle_download(
+        &bundle,
+        query.format.as_deref(),
+        &format!("crawl-result-{}.proof", id),
+    )
+}
+
+/// Exports the proof bundles of a job's committed events, optionally one
+/// page at a time.
+pub async fn job_bundles(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<JobBundlesQuery>,
+) -> Result<axum::response::Response, StatusCode> {
+    if query.limit.is_some_and(|limit| limit < 0) || query.offset.is_some_and(|offset| offset < 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (limit, offset) = (query.limit, query.offset.unwrap_or(0));
+    let name = format!("job-{}.proofs", id);
+    let (mut response, skipped) = if query.multiproof {
+        let exported = proofs::multiproof_bundles_for_job(&state.pool, id, user_id, limit, offset)
+            .await
+            .map_err(proof_error_status)?;
+        (
+            bundle_download(&exported.bundles, query.format.as_deref(), &name)?,
+            exported.skipped,
+        )
+    } else {
+        let exported = proofs::bundles_for_job(&state.pool, id, user_id, limit, offset)
+            .await
+            .map_err(proof_error_status)?;
+        (
+            bundle_download(&exported.bundles, query.format.as_deref(), &name)?,
+            exported.skipped,
+        )
+    };
+    for event in &skipped {
+        tracing::warn!(
+            "Job {} proof export skipped event {}: {}",
+            id,
+            event.event_id,
+            event.reason
+        );
+    }
+    response
+        .headers_mut()
+        .insert(SKIPPED_EVENTS_HEADER, skipped.len().into());
+    Ok(response)
+}
+
+/// The number of events a job's proof export left out.
+const SKIPPED_EVENTS_HEADER: &str = "x-skipped-events";
+
+#[derive(Deserialize)]
+pub struct MmrProofQuery {
+    /// The committed root to prove against; the latest when absent.
+    pub root: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MmrConsistencyQuery {
+    pub from: String,
+    pub to: String,
+}
+
+/// Lists the committed Merkle Mountain Range roots of a live job.
+pub async fn job_mmr_roots(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<MmrRoot>>, StatusCode> {
+    let roots = mmr::roots(&state.pool, id, user_id)
+        .await
+        .map_err(proof_error_status)?;
+    Ok(Json(roots))
+}
+
+/// Returns the inclusion proof of a content hash in a job's MMR against one
+/// of its committed roots.
+pub async fn job_mmr_proof(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path((id, content_hash)): Path<(Uuid, String)>,
+    Query(query): Query<MmrProofQuery>,
+) -> Result<Json<MmrInclusion>, StatusCode> {
+    let proof = mmr::inclusion(
+        &state.pool,
+        id,
+        user_id,
+        &content_hash,
+        query.root.as_deref(),
+    )
+    .await
+    .map_err(proof_error_status)?;
+    Ok(Json(proof))
+}
+
+/// Returns the consistency proof between two committed roots of a job's MMR.
+pub async fn job_mmr_consistency(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<MmrConsistencyQuery>,
+) -> Result<Json<MmrConsistency>, StatusCode> {
+    let proof = mmr::consistency(&state.pool, id, user_id, &query.from, &query.to)
+        .await
+        .map_err(proof_error_status)?;
+    Ok(Json(proof))
+}
+
 // ── API Keys ──────────────────────────────────────────────────────────────────
 
 #[derive(Deserialize)]
@@ -783,6 +1052,110 @@ pub async fn delete_webhook(
     }
 }
 
+// ── ABI registry ──────────────────────────────────────────────────────────────
+
+#[derive(Serialize)]
+pub struct AbiItem {
+    pub id: String,
+    pub name: Option<String>,
+    pub chain: Option<String>,
+    pub contract_address: Option<String>,
+    pub abi: serde_json::Value,
+    pub event_count: usize,
+    pub function_count: usize,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<StoredAbi> for AbiItem {
+    fn from(stored: StoredAbi) -> Self {
+        Self {
+            id: stored.id.to_string(),
+            event_count: stored.abi.events().count(),
+            function_count: stored.abi.functions().count(),
+            abi: stored.abi.json().clone(),
+            name: stored.name,
+            chain: stored.chain,
+            contract_address: stored.contract_address,
+            created_at: stored.created_at.to_rfc3339(),
+            updated_at: stored.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+fn abi_error_status(e: AbiRegistryError) -> StatusCode {
+    match e {
+        AbiRegistryError::Invalid(reason) => {
+            tracing::warn!("Invalid ABI: {}", reason);
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        AbiRegistryError::Conflict => StatusCode::CONFLICT,
+        AbiRegistryError::NotFound => StatusCode::NOT_FOUND,
+        AbiRegistryError::Database(e) => {
+            tracing::error!("ABI registry error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Registers an ABI for a contract, under a reusable name, or both.
+pub async fn create_abi(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(req): Json<AbiDefinition>,
+) -> Result<(StatusCode, Json<AbiItem>), StatusCode> {
+    let stored = abi_registry::create(&state.pool, user_id, req)
+        .await
+        .map_err(abi_error_status)?;
+    Ok((StatusCode::CREATED, Json(stored.into())))
+}
+
+/// Lists the authenticated user's ABIs.
+pub async fn list_abis(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<AbiItem>>, StatusCode> {
+    let abis = abi_registry::list(&state.pool, user_id)
+        .await
+        .map_err(abi_error_status)?;
+    Ok(Json(abis.into_iter().map(AbiItem::from).collect()))
+}
+
+pub async fn get_abi(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<AbiItem>, StatusCode> {
+    let stored = abi_registry::get(&state.pool, user_id, id)
+        .await
+        .map_err(abi_error_status)?;
+    Ok(Json(stored.into()))
+}
+
+/// Replaces an ABI. Existing jobs keep the definitions they were created with.
+pub async fn update_abi(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AbiDefinition>,
+) -> Result<Json<AbiItem>, StatusCode> {
+    let stored = abi_registry::update(&state.pool, user_id, id, req)
+        .await
+        .map_err(abi_error_status)?;
+    Ok(Json(stored.into()))
+}
+
+pub async fn delete_abi(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(id): Path<Uuid>,
+) -> StatusCode {
+    match abi_registry::delete(&state.pool, user_id, id).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) => abi_error_status(e),
+    }
+}
+
 // ── Password Reset ────────────────────────────────────────────────────────────
 
 #[derive(Deserialize)]
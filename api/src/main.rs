@@ -5,18 +5,24 @@ use axum::response::{Html, IntoResponse};
 use axum::Extension;
 use axum::{serve, Router as AxumRouter};
 use chrono::Utc;
-use ethers::types::Address;
+use ethers::types::{Address, H256, U256};
 use indexnode_core::{
-    compute_merkle_root, hash_content, AIExtractor, BlockchainClient, Coordinator, Crawler,
-    CreditManager, DistributedQueue, EventFilter, IpfsStorage, Job, JobConfig, JobParams, JobQueue,
-    JobStatus, MarketplaceClient, TimestampClient, Worker as DistributedWorker,
-    WorkerConfig as DistributedWorkerConfig,
+    fetch_token_uri, hash_event_document, is_transfer_event, plan_shards, prepare_calls,
+    resolve_token_uri, scan_transactions, take_snapshot, token_transfer, AIExtractor,
+    BlockchainClient, BlockchainEvent, BlockchainIndexParams, ChainConfig, ChainProvider,
+    ChainRegistry, ContentStore, Coordinator, CrawlResult, Crawler, CreditManager, CreditSpender,
+    DistributedQueue, EventFilter, HashCommitter, IndexedTransaction, IpfsStorage, Job, JobConfig,
+    JobParams, JobQueue, JobStatus, MarketplaceClient, SnapshotValue, TimestampClient,
+    TokenMetadata, TokenStandard, TokenTransfer, Worker as DistributedWorker,
+    WorkerConfig as DistributedWorkerConfig, DEFAULT_MMR_COMMIT_BLOCKS, DEFAULT_SHARD_BLOCKS,
+    EVENT_HASH_VERSION, MERKLE_TREE_VERSION,
 };
 use sqlx::postgres::PgPoolOptions;
 use std::collections::HashMap;
 use std::env;
 use std::net::SocketAddr;
 use std::time::Duration;
+use tokio_stream::StreamExt;
 use tower_http::cors::{AllowHeaders, AllowMethods, CorsLayer};
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
@@ -24,13 +30,16 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 use zeroize::Zeroizing;
 
+mod abi_registry;
 mod auth;
 mod db;
 mod graphql;
 mod handlers;
 mod metrics;
 mod middleware;
+mod mmr;
 mod models;
+mod proofs;
 mod routes;
 mod security;
 
@@ -65,6 +74,9 @@ async fn main() -> Result<()> {
     tracing::info!("Migrations complete");
 
     let rpc_url = env::var("ETHEREUM_RPC_URL").context("ETHEREUM_RPC_URL must be set")?;
+
+    let chain_registry = ChainRegistry::from_env()?;
+    tracing::info!("Indexable chains: {}", chain_registry.names().join(", "));
     let credit_contract_addr = env::var("CREDIT_CONTRACT_ADDRESS")
         .context("CREDIT_CONTRACT_ADDRESS must be set")?
         .parse::<Address>()
@@ -126,6 +138,7 @@ async fn main() -> Result<()> {
 
     let worker_pool = pool.clone();
     let worker_shutdown_rx = shutdown_rx.clone();
+    let worker_chains = chain_registry.clone();
 
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_current_thread()
@@ -140,6 +153,7 @@ async fn main() -> Result<()> {
                         .run_until(async {
                             if let Err(e) = run_worker(
                                 worker_pool,
+                                worker_chains,
                                 credit_manager_worker,
                                 timestamp_client_worker,
                                 ai_extractor_worker,
@@ -159,7 +173,7 @@ async fn main() -> Result<()> {
         }
     });
 
-    let schema = graphql::build_schema(pool.clone(), credit_manager, marketplace);
+    let schema = graphql::build_schema(pool.clone(), credit_manager, marketplace, chain_registry);
 
     let allowed_origin =
         env::var("ALLOWED_ORIGIN").unwrap_or_else(|_| "http://localhost:3000".to_string());
@@ -333,19 +347,40 @@ async fn get_queue_depth(pool: &sqlx::PgPool) -> Result<i64> {
     Ok(count)
 }
 
+/// Connected chain providers keyed by chain name.
+type ChainClients = HashMap<String, Box<dyn ChainProvider>>;
+
+/// Per-chain permits bounding how many backfill shards fetch at once.
+type ShardBudgets = HashMap<String, tokio::sync::Semaphore>;
+
+fn shard_budgets(chains: &ChainRegistry) -> ShardBudgets {
+    chains
+        .iter()
+        .map(|chain| {
+            (
+                chain.name.clone(),
+                tokio::sync::Semaphore::new(chain.max_concurrent_shards),
+            )
+        })
+        .collect()
+}
+
 /// Stateful service handles passed into `process_blockchain_index`.
 /// Grouping them avoids exceeding Clippy's `too_many_arguments` limit (7).
 struct IndexerServices<'a> {
-    chain_clients: &'a HashMap<String, BlockchainClient>,
-    ipfs: &'a IpfsStorage,
-    timestamp_client: Option<&'a TimestampClient>,
-    credit_manager: &'a CreditManager,
+    chains: &'a ChainRegistry,
+    chain_clients: &'a ChainClients,
+    shard_budgets: &'a ShardBudgets,
+    ipfs: &'a dyn ContentStore,
+    timestamp_client: Option<&'a dyn HashCommitter>,
+    credit_manager: &'a dyn CreditSpender,
     ai: Option<&'a AIExtractor>,
     ai_timeout: Duration,
 }
 
 async fn run_worker(
     pool: sqlx::PgPool,
+    chains: ChainRegistry,
     credit_manager: CreditManager,
     timestamp_client: Option<TimestampClient>,
     ai: Option<AIExtractor>,
@@ -354,21 +389,14 @@ async fn run_worker(
     let queue = JobQueue::new(pool.clone());
     let crawler = Crawler::new()?;
 
-    let mut chain_clients: HashMap<String, BlockchainClient> = HashMap::new();
-    let eth_rpc_url = env::var("ETHEREUM_RPC_URL").context("ETHEREUM_RPC_URL must be set")?;
-    chain_clients.insert(
-        "ethereum".to_string(),
-        BlockchainClient::new(&eth_rpc_url).await?,
-    );
-    if let Ok(polygon_rpc_url) = env::var("POLYGON_RPC_URL") {
-        if !polygon_rpc_url.is_empty() {
-            match BlockchainClient::new(&polygon_rpc_url).await {
-                Ok(client) => {
-                    chain_clients.insert("polygon".to_string(), client);
-                    tracing::info!("Polygon RPC connected");
-                }
-                Err(e) => tracing::warn!("Failed to connect to Polygon RPC: {:?}", e),
+    let mut chain_clients: ChainClients = HashMap::new();
+    for chain in chains.iter() {
+        match BlockchainClient::from_config(chain).await {
+            Ok(client) => {
+                tracing::info!("{} RPC connected (chain ID {})", chain.name, chain.chain_id);
+                chain_clients.insert(chain.name.clone(), Box::new(client));
             }
+            Err(e) => tracing::error!("{} disabled: {:?}", chain.name, e),
         }
     }
 
@@ -390,15 +418,29 @@ async fn run_worker(
             .unwrap_or(30),
     );
 
+    let timestamp_client = timestamp_client
+        .as_ref()
+        .map(|client| client as &dyn HashCommitter);
+    let shard_budgets = shard_budgets(&chains);
     let svc = IndexerServices {
+        chains: &chains,
         chain_clients: &chain_clients,
+        shard_budgets: &shard_budgets,
         ipfs: &ipfs_storage,
-        timestamp_client: timestamp_client.as_ref(),
+        timestamp_client,
         credit_manager: &credit_manager,
         ai: ai.as_ref(),
         ai_timeout,
     };
 
+    // New-head subscriptions drive live-tail jobs. Chains whose node does not
+    // support `eth_subscribe` fall back to polling in `tail_live_jobs`.
+    let mut new_heads = tokio_stream::StreamMap::new();
+    subscribe_new_heads(&chain_clients, &mut new_heads).await;
+    let mut last_resubscribe = std::time::Instant::now();
+    let mut chain_heads: HashMap<String, u64> = HashMap::new();
+    let mut last_reorg_check = std::time::Instant::now();
+
     tracing::info!("Worker started");
 
     loop {
@@ -408,10 +450,29 @@ async fn run_worker(
         }
 
         // Retry any pending on-chain Merkle commits before processing new jobs.
-        if let Err(e) = retry_pending_commits(timestamp_client.as_ref(), &pool).await {
+        if let Err(e) = retry_pending_commits(timestamp_client, &pool).await {
             tracing::error!("retry_pending_commits error: {:?}", e);
         }
 
+        if last_reorg_check.elapsed() >= REORG_CHECK_INTERVAL {
+            last_reorg_check = std::time::Instant::now();
+            if let Err(e) = detect_reorgs(&chain_clients, &pool).await {
+                tracing::error!("detect_reorgs error: {:?}", e);
+            }
+        }
+
+        // A subscription stream ends when its connection drops. Forget the
+        // chain's head so it is polled, and subscribe again periodically.
+        chain_heads.retain(|chain, _| new_heads.contains_key(chain));
+        if last_resubscribe.elapsed() >= RESUBSCRIBE_INTERVAL {
+            last_resubscribe = std::time::Instant::now();
+            subscribe_new_heads(&chain_clients, &mut new_heads).await;
+        }
+
+        if let Err(e) = tail_live_jobs(&svc, &pool, &chain_heads).await {
+            tracing::error!("tail_live_jobs error: {:?}", e);
+        }
+
         match queue.dequeue().await {
             Ok(Some(job)) => {
                 let _timer = crate::metrics::TimedOperation::new("job_processing_duration_seconds");
@@ -426,7 +487,7 @@ async fn run_worker(
                         let max_pages = p.max_pages;
 
                         let crawl_result =
-                            tokio::time::timeout(crawl_timeout, crawler.crawl(url, max_pages))
+                            tokio::time::timeout(crawl_timeout, crawler.crawl_page(url, max_pages))
                                 .await;
 
                         match crawl_result {
@@ -454,8 +515,12 @@ async fn run_worker(
                                 crate::metrics::record_job_failed();
                                 fire_webhooks(&pool, job.id, job.user_id, "job.failed").await;
                             }
-                            Ok(Ok(links)) => {
-                                tracing::info!("Crawled {} links for job {}", links.len(), job.id);
+                            Ok(Ok(page)) => {
+                                tracing::info!(
+                                    "Crawled {} links for job {}",
+                                    page.links.len(),
+                                    job.id
+                                );
 
                                 let cost = CreditManager::crawl_job_cost();
                                 if let Ok(Some(addr_str)) = sqlx::query_scalar::<_, String>(
@@ -481,7 +546,7 @@ async fn run_worker(
                                                 if let Err(e) = sqlx::query(
                                                     "UPDATE user_credits SET credit_balance = credit_balance - $1, total_spent = total_spent + $1 WHERE user_id = $2"
                                                 )
-                                                .bind(cost.as_u64() as i64)
+                                                .bind(CreditManager::whole_credits(cost))
                                                 .bind(job.user_id)
                                                 .execute(&pool)
                                                 .await
@@ -497,52 +562,77 @@ async fn run_worker(
                                     }
                                 }
 
-                                if !links.is_empty() {
-                                    let mut query_builder = sqlx::QueryBuilder::new(
-                                        "INSERT INTO crawl_results (id, job_id, url, status_code, content_hash, links, created_at) "
-                                    );
-                                    query_builder.push_values(
-                                        links.iter().take(500),
-                                        |mut b, link| {
-                                            b.push_bind(uuid::Uuid::new_v4())
-                                                .push_bind(job.id)
-                                                .push_bind(link)
-                                                .push_bind(200)
-                                                .push_bind("hash")
-                                                .push_bind(serde_json::json!([]))
-                                                .push_bind(Utc::now());
-                                        },
-                                    );
-                                    if let Err(e) = query_builder.build().execute(&pool).await {
-                                        tracing::error!(
-                                            "Failed to insert crawl results for job {}: {:?}",
-                                            job.id,
-                                            e
-                                        );
+                                match store_crawled_page(&svc, &pool, job.id, &page).await {
+                                    Ok(result) => {
+                                        let result_summary = serde_json::json!({
+                                            "total_links": page.links.len(),
+                                            "completed_at": Utc::now().to_rfc3339()
+                                        });
+                                        sqlx::query(
+                                            "UPDATE jobs SET result_summary = $1 WHERE id = $2",
+                                        )
+                                        .bind(result_summary)
+                                        .bind(job.id)
+                                        .execute(&pool)
+                                        .await?;
+
+                                        if matches!(result, IndexResult::PendingCommit) {
+                                            queue
+                                                .update_status(
+                                                    job.id,
+                                                    JobStatus::PendingCommit,
+                                                    None,
+                                                )
+                                                .await?;
+                                            tracing::info!(
+                                                "Job {} crawled; on-chain commit queued for retry",
+                                                job.id
+                                            );
+                                        } else {
+                                            queue
+                                                .update_status(job.id, JobStatus::Completed, None)
+                                                .await?;
+                                            crate::metrics::record_job_completed();
+                                            fire_webhooks(
+                                                &pool,
+                                                job.id,
+                                                job.user_id,
+                                                "job.completed",
+                                            )
+                                            .await;
+                                            tracing::info!("Job {} completed successfully", job.id);
+                                        }
+                                    }
+                                    Err(e) => {
+                                        tracing::error!("Job {} failed: {:?}", job.id, e);
+                                        queue
+                                            .update_status(
+                                                job.id,
+                                                JobStatus::Failed,
+                                                Some(e.to_string()),
+                                            )
+                                            .await?;
+                                        crate::metrics::record_job_failed();
+                                        fire_webhooks(&pool, job.id, job.user_id, "job.failed")
+                                            .await;
                                     }
                                 }
-
-                                let result_summary = serde_json::json!({
-                                    "total_links": links.len(),
-                                    "completed_at": Utc::now().to_rfc3339()
-                                });
-
-                                queue
-                                    .update_status(job.id, JobStatus::Completed, None)
-                                    .await?;
-                                sqlx::query("UPDATE jobs SET result_summary = $1 WHERE id = $2")
-                                    .bind(result_summary)
-                                    .bind(job.id)
-                                    .execute(&pool)
-                                    .await?;
-                                crate::metrics::record_job_completed();
-                                fire_webhooks(&pool, job.id, job.user_id, "job.completed").await;
-                                tracing::info!("Job {} completed successfully", job.id);
                             }
                         }
                     }
-                    JobParams::BlockchainIndex(_) => {
-                        match process_blockchain_index(&svc, &pool, &job).await {
+                    JobParams::BlockchainIndex(_)
+                    | JobParams::TransactionIndex(_)
+                    | JobParams::StateSnapshot(_) => {
+                        let result = match &config.params {
+                            JobParams::TransactionIndex(_) => {
+                                process_transaction_index(&svc, &pool, &job).await
+                            }
+                            JobParams::StateSnapshot(_) => {
+                                process_state_snapshot(&svc, &pool, &job).await
+                            }
+                            _ => process_blockchain_index(&svc, &pool, &job).await,
+                        };
+                        match result {
                             Ok(IndexResult::Completed) => {
                                 queue
                                     .update_status(job.id, JobStatus::Completed, None)
@@ -551,6 +641,27 @@ async fn run_worker(
                                 fire_webhooks(&pool, job.id, job.user_id, "job.completed").await;
                                 tracing::info!("Blockchain job {} completed", job.id);
                             }
+                            Ok(IndexResult::Live) => {
+                                queue.update_status(job.id, JobStatus::Live, None).await?;
+                                tracing::info!(
+                                    "Blockchain job {} backfilled; following chain head",
+                                    job.id
+                                );
+                            }
+                            Ok(IndexResult::Deferred {
+                                finalized_block,
+                                retry_after,
+                            }) => {
+                                let retry_at = Utc::now()
+                                    + chrono::TimeDelta::from_std(retry_after)
+                                        .unwrap_or(chrono::TimeDelta::MAX);
+                                queue.reschedule(job.id, retry_at).await?;
+                                tracing::info!(
+                                    "Blockchain job {} waiting for finality (finalized head {})",
+                                    job.id,
+                                    finalized_block
+                                );
+                            }
                             Ok(IndexResult::PendingCommit) => {
                                 queue
                                     .update_status(job.id, JobStatus::PendingCommit, None)
@@ -575,6 +686,9 @@ async fn run_worker(
             Ok(None) => {
                 tokio::select! {
                     _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                    Some((chain, head)) = new_heads.next(), if !new_heads.is_empty() => {
+                        chain_heads.insert(chain, head);
+                    }
                     _ = shutdown.changed() => {
                         tracing::info!("Worker shutting down");
                         break;
@@ -597,27 +711,71 @@ async fn run_worker(
     Ok(())
 }
 
+/// Subscribes to new heads for every chain that has no active subscription.
+async fn subscribe_new_heads(
+    chain_clients: &ChainClients,
+    new_heads: &mut tokio_stream::StreamMap<
+        String,
+        std::pin::Pin<Box<dyn tokio_stream::Stream<Item = u64> + Send>>,
+    >,
+) {
+    for (chain, client) in chain_clients {
+        if new_heads.contains_key(chain) {
+            continue;
+        }
+        match client.subscribe_new_heads().await {
+            Ok(stream) => {
+                tracing::info!("Subscribed to new heads for {}", chain);
+                new_heads.insert(chain.clone(), stream);
+            }
+            Err(e) => tracing::warn!("No new-heads subscription for {}: {:?}", chain, e),
+        }
+    }
+}
+
 /// Outcome of a blockchain indexing run.
 enum IndexResult {
     /// All events indexed and Merkle root committed on-chain.
     Completed,
     /// Events indexed but the on-chain commit failed; queued for retry.
     PendingCommit,
+    /// Backfill of a live-tail job finished; the job now follows the chain head.
+    Live,
+    /// The requested range is not yet final under the chain's confirmation
+    /// depth; the job is requeued and nothing was indexed or charged.
+    Deferred {
+        finalized_block: u64,
+        retry_after: Duration,
+    },
 }
 
+/// Bounds on how long a deferred job waits before finality is re-checked; the
+/// wait is otherwise the chain's block time times the blocks still unfinalized.
+const MIN_FINALITY_RECHECK_DELAY: Duration = Duration::from_secs(5);
+const MAX_FINALITY_RECHECK_DELAY: Duration = Duration::from_secs(600);
+/// How often stored block hashes are compared against the canonical chain.
+const REORG_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often chains without a live new-heads subscription try to subscribe.
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(30);
+/// Blocks beyond the confirmation depth that are re-checked for reorgs.
+const REORG_CHECK_MARGIN: u64 = 256;
+/// Links of a crawled page stored as `crawl_results` rows; the rest are only
+/// counted in the job's summary.
+const MAX_CRAWL_RESULT_ROWS: usize = 500;
+
 const MAX_COMMIT_RETRIES: i32 = 5;
 
 /// Retries any pending on-chain Merkle commits that are due (next_retry_at <= now()).
 /// Uses exponential backoff: 30s * 2^attempt (30s, 60s, 120s, 240s, 480s).
 async fn retry_pending_commits(
-    timestamp_client: Option<&TimestampClient>,
+    timestamp_client: Option<&dyn HashCommitter>,
     pool: &sqlx::PgPool,
 ) -> anyhow::Result<()> {
     use sqlx::Row;
 
     let rows = sqlx::query(
         "SELECT pmc.id, pmc.job_id, pmc.merkle_root, pmc.event_chain, pmc.attempt_count,
-                j.user_id
+                pmc.tree_version, j.user_id
          FROM pending_merkle_commits pmc
          JOIN jobs j ON j.id = pmc.job_id
          WHERE pmc.status = 'pending' AND pmc.next_retry_at <= now() AND pmc.attempt_count < $1",
@@ -633,40 +791,52 @@ async fn retry_pending_commits(
         let merkle_root: String = row.get("merkle_root");
         let event_chain: String = row.get("event_chain");
         let attempt_count: i32 = row.get("attempt_count");
+        let tree_version: i16 = row.get("tree_version");
         let next_attempt = attempt_count + 1;
 
         match timestamp_client {
-            Some(ts) => match ts.commit_hash(&merkle_root).await {
-                Ok((tx_hash, block_number)) => {
-                    let tx_hash_str = format!("{:?}", tx_hash);
+            Some(ts) => match commit_unless_committed(ts, pool, &merkle_root).await {
+                Ok(commit) => {
+                    if let Some((tx_hash, block_number)) = commit {
+                        let tx_hash_str = format!("{:?}", tx_hash);
+                        let (registry_chain_id, registry_address) = ts.registry();
 
-                    if let Err(e) = sqlx::query(
-                        "INSERT INTO timestamp_commits (content_hash, transaction_hash, block_number, chain, job_id)
-                         VALUES ($1, $2, $3, $4, $5)
-                         ON CONFLICT (content_hash) DO NOTHING",
-                    )
-                    .bind(&merkle_root)
-                    .bind(&tx_hash_str)
-                    .bind(block_number as i64)
-                    .bind(&event_chain)
-                    .bind(job_id)
-                    .execute(pool)
-                    .await
-                    {
-                        tracing::error!("retry: failed to store timestamp_commit for job {}: {:?}", job_id, e);
-                        continue;
+                        if let Err(e) = sqlx::query(
+                            "INSERT INTO timestamp_commits (content_hash, transaction_hash, block_number, chain, job_id, tree_version, registry_chain_id, registry_address)
+                             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                             ON CONFLICT (content_hash) DO NOTHING",
+                        )
+                        .bind(&merkle_root)
+                        .bind(&tx_hash_str)
+                        .bind(block_number as i64)
+                        .bind(&event_chain)
+                        .bind(job_id)
+                        .bind(tree_version)
+                        .bind(registry_chain_id as i64)
+                        .bind(format!("{:?}", registry_address))
+                        .execute(pool)
+                        .await
+                        {
+                            tracing::error!("retry: failed to store timestamp_commit for job {}: {:?}", job_id, e);
+                            continue;
+                        }
                     }
 
-                    // Stamp the batch Merkle root onto all events in this job.
+                    stamp_merkle_root(pool, job_id, &merkle_root).await;
+
+                    // Events may have been orphaned while the commit was pending.
                     if let Err(e) = sqlx::query(
-                        "UPDATE blockchain_events SET merkle_root = $1 WHERE job_id = $2 AND merkle_root IS NULL",
+                        "UPDATE timestamp_commits SET reorg_affected = true
+                         WHERE content_hash = $1
+                           AND (EXISTS (SELECT 1 FROM blockchain_events WHERE merkle_root = $1 AND orphaned)
+                                OR EXISTS (SELECT 1 FROM indexed_transactions WHERE merkle_root = $1 AND orphaned)
+                                OR EXISTS (SELECT 1 FROM state_snapshots WHERE merkle_root = $1 AND orphaned))",
                     )
                     .bind(&merkle_root)
-                    .bind(job_id)
                     .execute(pool)
                     .await
                     {
-                        tracing::error!("retry: failed to stamp merkle_root on events for job {}: {:?}", job_id, e);
+                        tracing::error!("retry: failed to check reorg status for job {}: {:?}", job_id, e);
                     }
 
                     let _ = sqlx::query(
@@ -787,32 +957,412 @@ async fn process_blockchain_index(
     job: &Job,
 ) -> Result<IndexResult> {
     let chain_clients = svc.chain_clients;
-    let ipfs = svc.ipfs;
     let timestamp_client = svc.timestamp_client;
-    let credit_manager = svc.credit_manager;
-    let ai = svc.ai;
-    let ai_timeout = svc.ai_timeout;
 
     let config: JobConfig =
         serde_json::from_value(job.config.clone()).context("Failed to parse job config")?;
     let params = match config.params {
-        JobParams::BlockchainIndex(p) => p,
+        JobParams::BlockchainIndex(p) => *p,
         _ => anyhow::bail!("Expected BlockchainIndex params for this job"),
     };
 
-    if params.events.is_empty() {
+    if params.events.is_empty() && params.factory.is_none() {
         anyhow::bail!("No event signatures specified in job config");
     }
 
-    let client = chain_clients.get(&params.chain).ok_or_else(|| {
-        anyhow::anyhow!(
-            "Chain '{}' is not configured. Set {}_RPC_URL to enable it.",
-            params.chain,
-            params.chain.to_uppercase()
+    let chain = svc
+        .chains
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' is not in the chain registry", params.chain))?;
+    let client = chain_clients
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' RPC is not connected", params.chain))?;
+
+    let to_block =
+        match finalized_end(chain, client.as_ref(), params.from_block, params.to_block).await? {
+            Ok(to_block) => to_block,
+            Err(deferred) => return Ok(deferred),
+        };
+
+    charge_credits(
+        svc,
+        pool,
+        job,
+        CreditManager::event_index_cost(),
+        "blockchain_index",
+    )
+    .await;
+
+    let (all_events, child_hashes) =
+        fetch_sharded(svc, client.as_ref(), pool, job.id, &params, to_block).await?;
+
+    let mut all_content_hashes: Vec<String> = Vec::new();
+    let mut ai_budget = AiBudget::load(pool, job.id, &params).await?;
+    let mut tokens = TokenEnricher::new(&params);
+
+    for event in all_events {
+        let (_, content_hash) =
+            store_event(svc, pool, job.id, event, &mut ai_budget, &mut tokens).await?;
+        all_content_hashes.push(content_hash);
+    }
+    ai_budget.save(pool, job.id).await;
+    all_content_hashes.extend(child_hashes.iter().cloned());
+    // The events are stored; the shards no longer need to keep them.
+    sqlx::query("UPDATE job_shards SET events = NULL, child_hashes = NULL WHERE job_id = $1")
+        .bind(job.id)
+        .execute(pool)
+        .await?;
+
+    if params.live {
+        // Live-tail jobs pick up from here; `tail_live_jobs` follows the head
+        // and appends what it indexes to the same MMR.
+        mmr::append(pool, job.id, &all_content_hashes).await?;
+        sqlx::query("UPDATE jobs SET last_processed_block = $1 WHERE id = $2")
+            .bind(to_block as i64)
+            .bind(job.id)
+            .execute(pool)
+            .await?;
+    }
+
+    // Compute and commit the batch Merkle root on-chain.
+    if !all_content_hashes.is_empty() {
+        let tree_version = params.merkle_mode.tree_version();
+        let merkle_root =
+            proofs::store_tree(pool, job.id, &all_content_hashes, tree_version).await?;
+
+        match commit_merkle_root(
+            timestamp_client,
+            pool,
+            job.id,
+            &params.chain,
+            &merkle_root,
+            tree_version,
+        )
+        .await
+        {
+            CommitOutcome::Committed => stamp_merkle_root(pool, job.id, &merkle_root).await,
+            // A live job keeps tailing while its backfill commit is retried.
+            CommitOutcome::Queued if params.live => return Ok(IndexResult::Live),
+            CommitOutcome::Queued => return Ok(IndexResult::PendingCommit),
+            // No client means no retries are possible. The job completes so it
+            // isn't stuck; the data is indexed and available.
+            CommitOutcome::Unavailable => {}
+        }
+    }
+
+    if params.live {
+        return Ok(IndexResult::Live);
+    }
+    Ok(IndexResult::Completed)
+}
+
+/// Indexes the transactions of a transaction-index job and commits their
+/// Merkle root on-chain. Mirrors `process_blockchain_index` for events.
+async fn process_transaction_index(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    job: &Job,
+) -> Result<IndexResult> {
+    let config: JobConfig =
+        serde_json::from_value(job.config.clone()).context("Failed to parse job config")?;
+    let params = match config.params {
+        JobParams::TransactionIndex(p) => p,
+        _ => anyhow::bail!("Expected TransactionIndex params for this job"),
+    };
+
+    let chain = svc
+        .chains
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' is not in the chain registry", params.chain))?;
+    let client = svc
+        .chain_clients
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' RPC is not connected", params.chain))?;
+
+    let to_block = match finalized_end(
+        chain,
+        client.as_ref(),
+        params.from_block,
+        Some(params.to_block),
+    )
+    .await?
+    {
+        Ok(to_block) => to_block,
+        Err(deferred) => return Ok(deferred),
+    };
+
+    charge_credits(
+        svc,
+        pool,
+        job,
+        CreditManager::event_index_cost(),
+        "transaction_index",
+    )
+    .await;
+
+    let transactions =
+        scan_transactions(client.as_ref(), &params, params.from_block, to_block).await?;
+    tracing::info!(
+        "Job {}: scanned blocks {}-{}, {} transactions",
+        job.id,
+        params.from_block,
+        to_block,
+        transactions.len()
+    );
+
+    let mut content_hashes = Vec::with_capacity(transactions.len());
+    for transaction in &transactions {
+        store_transaction(svc.ipfs, pool, job.id, transaction).await?;
+        content_hashes.push(transaction.content_hash.clone());
+    }
+
+    if !content_hashes.is_empty() {
+        let tree_version = params.merkle_mode.tree_version();
+        let merkle_root = proofs::store_tree(pool, job.id, &content_hashes, tree_version).await?;
+        match commit_merkle_root(
+            svc.timestamp_client,
+            pool,
+            job.id,
+            &params.chain,
+            &merkle_root,
+            tree_version,
         )
-    })?;
+        .await
+        {
+            CommitOutcome::Committed => stamp_merkle_root(pool, job.id, &merkle_root).await,
+            CommitOutcome::Queued => return Ok(IndexResult::PendingCommit),
+            CommitOutcome::Unavailable => {}
+        }
+    }
+
+    Ok(IndexResult::Completed)
+}
+
+/// Pins and upserts a single indexed transaction and returns its row ID.
+/// Re-running a job updates the existing row instead of duplicating it.
+async fn store_transaction(
+    ipfs: &dyn ContentStore,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    transaction: &IndexedTransaction,
+) -> Result<Uuid> {
+    let transaction_json =
+        serde_json::to_vec(transaction).context("Failed to serialize transaction")?;
+    let ipfs_cid = ipfs.store_content(&transaction_json).await?;
+    crate::metrics::record_ipfs_upload(transaction_json.len() as u64);
+    ipfs.pin_content(&ipfs_cid).await?;
+
+    let id = sqlx::query_scalar(
+        "INSERT INTO indexed_transactions (job_id, chain, block_number, block_hash, block_timestamp, transaction_hash, transaction_index, from_address, to_address, value, input, function_name, decoded_call, status, gas_used, effective_gas_price, contract_address, trace, content_hash, ipfs_cid)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10::NUMERIC, $11, $12, $13, $14, $15, $16::NUMERIC, $17, $18, $19, $20)
+         ON CONFLICT (job_id, transaction_hash) DO UPDATE SET
+             block_number = EXCLUDED.block_number,
+             block_hash = EXCLUDED.block_hash,
+             block_timestamp = EXCLUDED.block_timestamp,
+             transaction_index = EXCLUDED.transaction_index,
+             status = EXCLUDED.status,
+             gas_used = EXCLUDED.gas_used,
+             effective_gas_price = EXCLUDED.effective_gas_price,
+             contract_address = EXCLUDED.contract_address,
+             trace = EXCLUDED.trace,
+             content_hash = EXCLUDED.content_hash,
+             ipfs_cid = EXCLUDED.ipfs_cid,
+             orphaned = false,
+             orphaned_at = NULL
+         RETURNING id",
+    )
+    .bind(job_id)
+    .bind(&transaction.chain)
+    .bind(transaction.block_number as i64)
+    .bind(&transaction.block_hash)
+    .bind(transaction.block_timestamp)
+    .bind(&transaction.transaction_hash)
+    .bind(transaction.transaction_index as i64)
+    .bind(&transaction.from_address)
+    .bind(&transaction.to_address)
+    .bind(&transaction.value)
+    .bind(&transaction.input)
+    .bind(
+        transaction
+            .call
+            .as_ref()
+            .and_then(|c| c["function"].as_str()),
+    )
+    .bind(&transaction.call)
+    .bind(transaction.status)
+    .bind(transaction.gas_used.map(|g| g as i64))
+    .bind(&transaction.effective_gas_price)
+    .bind(&transaction.contract_address)
+    .bind(&transaction.trace)
+    .bind(&transaction.content_hash)
+    .bind(&ipfs_cid)
+    .fetch_one(pool)
+    .await
+    .context("Failed to store transaction")?;
+    Ok(id)
+}
+
+/// Runs the view calls of a state-snapshot job at each of its blocks and
+/// commits the Merkle root of the results on-chain.
+async fn process_state_snapshot(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    job: &Job,
+) -> Result<IndexResult> {
+    let config: JobConfig =
+        serde_json::from_value(job.config.clone()).context("Failed to parse job config")?;
+    let params = match config.params {
+        JobParams::StateSnapshot(p) => p,
+        _ => anyhow::bail!("Expected StateSnapshot params for this job"),
+    };
+
+    let mut blocks = params.blocks.clone();
+    blocks.sort_unstable();
+    blocks.dedup();
+    let last_block = *blocks.last().context("State snapshot job has no blocks")?;
+    let calls = prepare_calls(&params.calls)?;
+
+    let chain = svc
+        .chains
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' is not in the chain registry", params.chain))?;
+    let client = svc
+        .chain_clients
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' RPC is not connected", params.chain))?;
+
+    // Every block must be final so the committed values cannot be reorged away.
+    if let Err(deferred) =
+        finalized_end(chain, client.as_ref(), last_block, Some(last_block)).await?
+    {
+        return Ok(deferred);
+    }
+
+    charge_credits(
+        svc,
+        pool,
+        job,
+        CreditManager::event_index_cost(),
+        "state_snapshot",
+    )
+    .await;
+
+    let mut content_hashes = Vec::with_capacity(calls.len() * blocks.len());
+    for block_number in blocks {
+        let values = take_snapshot(client.as_ref(), &params.chain, &calls, block_number).await?;
+        for value in &values {
+            store_snapshot_value(svc.ipfs, pool, job.id, value).await?;
+            content_hashes.push(value.content_hash.clone());
+        }
+    }
+    tracing::info!(
+        "Job {}: stored {} snapshot values",
+        job.id,
+        content_hashes.len()
+    );
+
+    let tree_version = params.merkle_mode.tree_version();
+    let merkle_root = proofs::store_tree(pool, job.id, &content_hashes, tree_version).await?;
+    match commit_merkle_root(
+        svc.timestamp_client,
+        pool,
+        job.id,
+        &params.chain,
+        &merkle_root,
+        tree_version,
+    )
+    .await
+    {
+        CommitOutcome::Committed => stamp_merkle_root(pool, job.id, &merkle_root).await,
+        CommitOutcome::Queued => return Ok(IndexResult::PendingCommit),
+        CommitOutcome::Unavailable => {}
+    }
+
+    Ok(IndexResult::Completed)
+}
+
+/// Pins and upserts a single snapshot value and returns its row ID.
+async fn store_snapshot_value(
+    ipfs: &dyn ContentStore,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    value: &SnapshotValue,
+) -> Result<Uuid> {
+    let value_json = serde_json::to_vec(value).context("Failed to serialize snapshot value")?;
+    let ipfs_cid = ipfs.store_content(&value_json).await?;
+    crate::metrics::record_ipfs_upload(value_json.len() as u64);
+    ipfs.pin_content(&ipfs_cid).await?;
+
+    let id = sqlx::query_scalar(
+        "INSERT INTO state_snapshots (job_id, chain, block_number, block_hash, call_index, contract_address, function_signature, args, calldata, return_data, result, decode_error, content_hash, ipfs_cid)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+         ON CONFLICT (job_id, block_number, call_index) DO UPDATE SET
+             block_hash = EXCLUDED.block_hash,
+             return_data = EXCLUDED.return_data,
+             result = EXCLUDED.result,
+             decode_error = EXCLUDED.decode_error,
+             content_hash = EXCLUDED.content_hash,
+             ipfs_cid = EXCLUDED.ipfs_cid,
+             orphaned = false,
+             orphaned_at = NULL
+         RETURNING id",
+    )
+    .bind(job_id)
+    .bind(&value.chain)
+    .bind(value.block_number as i64)
+    .bind(&value.block_hash)
+    .bind(value.call_index as i32)
+    .bind(&value.contract_address)
+    .bind(&value.function)
+    .bind(serde_json::json!(value.args))
+    .bind(&value.calldata)
+    .bind(&value.return_data)
+    .bind(&value.result)
+    .bind(&value.decode_error)
+    .bind(&value.content_hash)
+    .bind(&ipfs_cid)
+    .fetch_one(pool)
+    .await
+    .context("Failed to store snapshot value")?;
+    Ok(id)
+}
 
-    let cost = CreditManager::event_index_cost();
+/// The last block a job over `[from_block, to_block]` may index now, or the
+/// `Deferred` result to return when the range is not yet final.
+///
+/// Only finalized blocks are indexed, so every committed root covers a range
+/// that is already past the chain's confirmation depth. Without `to_block` the
+/// job runs up to the finalized head.
+async fn finalized_end(
+    chain: &ChainConfig,
+    client: &dyn ChainProvider,
+    from_block: u64,
+    to_block: Option<u64>,
+) -> Result<std::result::Result<u64, IndexResult>> {
+    let finalized_block = client.get_finalized_block().await?;
+    let required_block = to_block.unwrap_or(from_block);
+    if required_block > finalized_block {
+        let blocks_to_wait = (required_block - finalized_block).min(u32::MAX as u64) as u32;
+        let retry_after = (chain.block_time() * blocks_to_wait)
+            .clamp(MIN_FINALITY_RECHECK_DELAY, MAX_FINALITY_RECHECK_DELAY);
+        return Ok(Err(IndexResult::Deferred {
+            finalized_block,
+            retry_after,
+        }));
+    }
+    Ok(Ok(to_block.unwrap_or(finalized_block)))
+}
+
+/// Spends `cost` on-chain credits from the job owner's registered wallet and
+/// mirrors the spend in `user_credits`, in whole credits. Failures are logged;
+/// the job still runs.
+async fn charge_credits(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    job: &Job,
+    cost: U256,
+    job_type: &str,
+) {
     if let Ok(Some(addr_str)) = sqlx::query_scalar::<_, String>(
         "SELECT on_chain_address FROM user_credits WHERE user_id = $1",
     )
@@ -821,8 +1371,9 @@ async fn process_blockchain_index(
     .await
     {
         if let Ok(addr) = addr_str.parse::<Address>() {
-            match credit_manager
-                .spend_credits(addr, cost, "blockchain_index".to_string())
+            match svc
+                .credit_manager
+                .spend_credits(addr, cost, job_type.to_string())
                 .await
             {
                 Err(e) => {
@@ -836,7 +1387,7 @@ async fn process_blockchain_index(
                     if let Err(e) = sqlx::query(
                         "UPDATE user_credits SET credit_balance = credit_balance - $1, total_spent = total_spent + $1 WHERE user_id = $2"
                     )
-                    .bind(cost.as_u64() as i64)
+                    .bind(CreditManager::whole_credits(cost))
                     .bind(job.user_id)
                     .execute(pool)
                     .await
@@ -850,377 +1401,3396 @@ async fn process_blockchain_index(
             }
         }
     }
+}
 
-    let contract_address: Address = params
-        .contract_address
-        .parse()
-        .context("Invalid contract address")?;
-    let to_block = params.to_block.unwrap_or(client.get_latest_block().await?);
-
-    // Fetch events for every requested event signature.
-    let mut all_events = Vec::new();
-    for event_sig in &params.events {
-        let filter = EventFilter {
-            chain: params.chain.clone(),
-            contract_address,
-            event_signature: event_sig.clone(),
-            from_block: params.from_block,
-            to_block,
-        };
-        match client.get_events(filter).await {
-            Ok(events) => all_events.extend(events),
-            Err(e) => tracing::warn!(
-                "Job {}: failed to fetch events for '{}': {:?}",
-                job.id,
-                event_sig,
-                e
-            ),
-        }
+/// Stores a crawled page as one `crawl_results` row per link, up to
+/// `MAX_CRAWL_RESULT_ROWS`, and commits the rows on-chain as the leaves of the
+/// job's tree, in tree version 2. The commit is recorded under the name of the
+/// registry's chain, since a crawl has no chain of its own.
+async fn store_crawled_page(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    page: &CrawlResult,
+) -> Result<IndexResult> {
+    let mut leaves = Vec::new();
+    for document in page.link_documents().iter().take(MAX_CRAWL_RESULT_ROWS) {
+        leaves.push(hash_event_document(document, EVENT_HASH_VERSION)?);
+    }
+    if leaves.is_empty() {
+        return Ok(IndexResult::Completed);
     }
 
-    let mut all_content_hashes: Vec<String> = Vec::new();
-    let mut indexed_event_ids: Vec<uuid::Uuid> = Vec::new();
+    let mut query_builder = sqlx::QueryBuilder::new(
+        "INSERT INTO crawl_results (id, job_id, url, page_url, status_code, content_hash, body_hash, hash_version, links, created_at) ",
+    );
+    query_builder.push_values(page.links.iter().zip(&leaves), |mut b, (link, hash)| {
+        b.push_bind(Uuid::new_v4())
+            .push_bind(job_id)
+            .push_bind(link)
+            .push_bind(&page.url)
+            .push_bind(page.status_code as i32)
+            .push_bind(hash)
+            .push_bind(&page.body_hash)
+            .push_bind(EVENT_HASH_VERSION as i16)
+            .push_bind(serde_json::json!([]))
+            .push_bind(page.timestamp);
+    });
+    query_builder
+        .build()
+        .execute(pool)
+        .await
+        .context("Failed to store crawl results")?;
 
-    let enable_ai = params.enable_ai;
-    let extraction_schema = params.extraction_schema.clone();
-    // Default budget: 100,000 tokens per job when AI is enabled.
-    let mut tokens_remaining: u32 = params.ai_token_budget.unwrap_or(100_000);
+    let merkle_root = proofs::store_tree(pool, job_id, &leaves, MERKLE_TREE_VERSION).await?;
+    let chain = svc
+        .timestamp_client
+        .and_then(|ts| {
+            let (registry_chain_id, _) = ts.registry();
+            svc.chains
+                .iter()
+                .find(|chain| chain.chain_id == registry_chain_id)
+        })
+        .map_or("ethereum", |chain| chain.name.as_str());
+    match commit_merkle_root(
+        svc.timestamp_client,
+        pool,
+        job_id,
+        chain,
+        &merkle_root,
+        MERKLE_TREE_VERSION,
+    )
+    .await
+    {
+        CommitOutcome::Committed => stamp_merkle_root(pool, job_id, &merkle_root).await,
+        CommitOutcome::Queued => return Ok(IndexResult::PendingCommit),
+        CommitOutcome::Unavailable => {}
+    }
 
-    for mut event in all_events {
-        let event_id = uuid::Uuid::new_v4();
-        event.content_hash = hash_content(format!("{:?}", event.event_data).as_bytes());
-        let event_json = serde_json::to_vec(&event).context("Failed to serialize event")?;
-        let ipfs_cid = ipfs.store_content(&event_json).await?;
+    Ok(IndexResult::Completed)
+}
 
-        crate::metrics::record_ipfs_upload(event_json.len() as u64);
-        crate::metrics::record_blockchain_event();
+/// Whether a root already has a recorded on-chain commitment. Jobs that index
+/// the same data build the same tree, so their roots coincide. A failed lookup
+/// counts as not committed; committing again then fails and is retried.
+async fn root_is_committed(pool: &sqlx::PgPool, merkle_root: &str) -> bool {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM timestamp_commits WHERE content_hash = $1)",
+    )
+    .bind(merkle_root)
+    .fetch_one(pool)
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!(
+            "Failed to look up the commitment of Merkle root {}: {:?}",
+            merkle_root,
+            e
+        );
+        false
+    })
+}
 
-        ipfs.pin_content(&ipfs_cid).await?;
+/// Tables whose rows are committed as Merkle leaves, and what their rows are
+/// called in logs.
+const LEAF_TABLES: [(&str, &str); 5] = [
+    ("blockchain_events", "events"),
+    ("job_child_contracts", "child contracts"),
+    ("indexed_transactions", "transactions"),
+    ("state_snapshots", "snapshot values"),
+    ("crawl_results", "crawled links"),
+];
 
-        sqlx::query(
-            "INSERT INTO blockchain_events (id, job_id, chain, contract_address, event_name, block_number, transaction_hash, event_data, content_hash, ipfs_cid)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
-        )
-        .bind(event_id)
-        .bind(job.id)
-        .bind(&event.chain)
-        .bind(&event.contract_address)
-        .bind(&event.event_name)
-        .bind(event.block_number as i64)
-        .bind(&event.transaction_hash)
-        .bind(&event.event_data)
-        .bind(&event.content_hash)
-        .bind(&ipfs_cid)
+/// Stamps a committed root onto the job's rows that are leaves of its tree.
+/// Rows indexed after the tree was built, such as those a live job tails while
+/// its backfill commit is retried, are left alone.
+async fn stamp_merkle_root(pool: &sqlx::PgPool, job_id: Uuid, merkle_root: &str) {
+    for (table, rows) in LEAF_TABLES {
+        if let Err(e) = sqlx::query(&format!(
+            "UPDATE {} SET merkle_root = $1
+             WHERE job_id = $2
+               AND content_hash IN (SELECT content_hash FROM merkle_leaves WHERE merkle_root = $1)",
+            table
+        ))
+        .bind(merkle_root)
+        .bind(job_id)
         .execute(pool)
-        .await?;
+        .await
+        {
+            tracing::error!(
+                "Job {}: failed to set merkle_root on {}: {:?}",
+                job_id,
+                rows,
+                e
+            );
+        }
+    }
+}
 
-        sqlx::query(
-            "INSERT INTO ipfs_content (cid, content_hash, size_bytes, pinned, blockchain_event_id)
-             VALUES ($1, $2, $3, true, $4)
-             ON CONFLICT (cid) DO NOTHING",
-        )
-        .bind(&ipfs_cid)
-        .bind(&event.content_hash)
-        .bind(event_json.len() as i64)
-        .bind(event_id)
-        .execute(pool)
-        .await?;
+/// Commits a root for `retry_pending_commits`, or returns `None` when it has
+/// been committed since it was queued, by this job or another.
+async fn commit_unless_committed(
+    ts: &dyn HashCommitter,
+    pool: &sqlx::PgPool,
+    merkle_root: &str,
+) -> Result<Option<(H256, u64)>> {
+    if root_is_committed(pool, merkle_root).await {
+        return Ok(None);
+    }
+    ts.commit_hash(merkle_root).await.map(Some)
+}
 
-        if enable_ai && tokens_remaining > 0 {
-            if let (Some(schema), Some(ai)) = (&extraction_schema, ai) {
-                let schema_str = serde_json::to_string(schema)?;
-                let event_str = serde_json::to_string(&event.event_data)?;
+/// Result of committing a job's Merkle root on-chain.
+enum CommitOutcome {
+    /// Committed and recorded in `timestamp_commits`.
+    Committed,
+    /// The commit failed and was queued in `pending_merkle_commits` for retry.
+    Queued,
+    /// No timestamp registry is configured, so the root cannot be committed.
+    Unavailable,
+}
 
-                match tokio::time::timeout(
-                    ai_timeout,
-                    ai.extract_structured_data(&event_str, &schema_str),
-                )
-                .await
-                {
-                    Err(_) => {
-                        tracing::warn!(
-                            "Job {}: AI extraction timed out for event {}",
-                            job.id,
-                            event_id
-                        );
-                    }
-                    Ok(Err(e)) => {
-                        tracing::warn!(
-                            "Job {}: AI extraction failed for event {}: {:?}",
-                            job.id,
-                            event_id,
-                            e
-                        );
-                    }
-                    Ok(Ok(result)) => {
-                        tokens_remaining = tokens_remaining.saturating_sub(result.tokens_used);
+/// Commits a job's batch Merkle root, built in tree format `tree_version`,
+/// on-chain and records the commitment, or queues it for
+/// `retry_pending_commits`. Callers stamp the root onto the rows it covers
+/// once it is `Committed`. A root another job already committed is not sent
+/// again, since the registry accepts each hash once.
+async fn commit_merkle_root(
+    timestamp_client: Option<&dyn HashCommitter>,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    chain: &str,
+    merkle_root: &str,
+    tree_version: u16,
+) -> CommitOutcome {
+    if root_is_committed(pool, merkle_root).await {
+        tracing::info!(
+            "Job {}: Merkle root {} is already committed on-chain",
+            job_id,
+            merkle_root
+        );
+        return CommitOutcome::Committed;
+    }
+    let Some(ts) = timestamp_client else {
+        tracing::warn!(
+            "Job {}: TIMESTAMP_REGISTRY_ADDRESS not configured — Merkle root {} not committed on-chain",
+            job_id,
+            merkle_root
+        );
+        return CommitOutcome::Unavailable;
+    };
 
-                        crate::metrics::record_ai_extraction();
+    match ts.commit_hash(merkle_root).await {
+        Ok((tx_hash, block_number)) => {
+            let tx_hash_str = format!("{:?}", tx_hash);
+            let (registry_chain_id, registry_address) = ts.registry();
 
-                        if let Err(e) = sqlx::query(
-                            "INSERT INTO ai_extractions (blockchain_event_id, extraction_type, schema_definition, extracted_data)
-                             VALUES ($1, 'structured', $2, $3)",
-                        )
-                        .bind(event_id)
-                        .bind(schema)
-                        .bind(result.data)
-                        .execute(pool)
-                        .await
-                        {
-                            tracing::error!(
-                                "Job {}: failed to store AI extraction for event {}: {:?}",
-                                job.id, event_id, e
-                            );
-                        }
+            if let Err(e) = sqlx::query(
+                "INSERT INTO timestamp_commits (content_hash, transaction_hash, block_number, chain, job_id, tree_version, registry_chain_id, registry_address)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                 ON CONFLICT (content_hash) DO NOTHING",
+            )
+            .bind(merkle_root)
+            .bind(&tx_hash_str)
+            .bind(block_number as i64)
+            .bind(chain)
+            .bind(job_id)
+            .bind(tree_version as i16)
+            .bind(registry_chain_id as i64)
+            .bind(format!("{:?}", registry_address))
+            .execute(pool)
+            .await
+            {
+                tracing::error!(
+                    "Job {}: failed to store timestamp commit: {:?}",
+                    job_id,
+                    e
+                );
+            }
 
-                        if tokens_remaining == 0 {
-                            tracing::warn!(
-                                "Job {}: AI token budget exhausted after event {}; skipping remaining extractions",
-                                job.id, event_id
-                            );
-                        }
-                    }
-                }
+            tracing::info!(
+                "Job {}: committed Merkle root {} in tx {} at block {}",
+                job_id,
+                merkle_root,
+                tx_hash_str,
+                block_number
+            );
+            CommitOutcome::Committed
+        }
+        Err(e) => {
+            // Indexing succeeded but commit failed. Queue for retry.
+            tracing::error!(
+                "Job {}: on-chain Merkle commitment failed; queuing for retry: {:?}",
+                job_id,
+                e
+            );
+            if let Err(db_err) = sqlx::query(
+                "INSERT INTO pending_merkle_commits (job_id, merkle_root, event_chain, last_error, tree_version)
+                 VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(job_id)
+            .bind(merkle_root)
+            .bind(chain)
+            .bind(e.to_string())
+            .bind(tree_version as i16)
+            .execute(pool)
+            .await
+            {
+                tracing::error!(
+                    "Job {}: failed to insert pending_merkle_commit: {:?}",
+                    job_id,
+                    db_err
+                );
             }
+            CommitOutcome::Queued
         }
-
-        all_content_hashes.push(event.content_hash.clone());
-        indexed_event_ids.push(event_id);
     }
+}
 
-    // Compute and commit the batch Merkle root on-chain.
-    if !all_content_hashes.is_empty() {
-        let merkle_root = compute_merkle_root(&all_content_hashes);
+/// Fetches the events matching `filter` in one combined eth_getLogs filter
+/// per range. Ranges are chunked to stay within provider eth_getLogs limits;
+/// any range still unfetched after retries is an error rather than a silently
+/// incomplete result.
+async fn fetch_job_events(
+    client: &dyn ChainProvider,
+    filter: &EventFilter,
+    job_id: Uuid,
+) -> Result<Vec<BlockchainEvent>> {
+    let (events, cursor) = client.get_events_chunked(filter).await?;
+    tracing::info!(
+        "Job {}: scanned {} event types on {} contracts, blocks {}-{}, {} events",
+        job_id,
+        filter.event_signatures.len(),
+        if filter.contract_addresses.is_empty() {
+            "all".to_string()
+        } else {
+            filter.contract_addresses.len().to_string()
+        },
+        cursor.from_block,
+        cursor.to_block,
+        events.len()
+    );
+    if !cursor.is_complete() {
+        anyhow::bail!(
+            "Failed to fetch logs after retries for blocks {}",
+            cursor.describe_failed()
+        );
+    }
+    Ok(events)
+}
 
-        match timestamp_client {
-            Some(ts) => match ts.commit_hash(&merkle_root).await {
-                Ok((tx_hash, block_number)) => {
-                    let tx_hash_str = format!("{:?}", tx_hash);
+/// Fetches a backfill's events over `[params.from_block, to_block]`.
+///
+/// The range is split into shards of `params.shard_blocks` blocks that are
+/// fetched concurrently, at most `max_concurrent_shards` at a time for the
+/// chain, with each shard's progress recorded in `job_shards`. The results are
+/// merged in `(block, log_index)` order, so the job's Merkle root does not
+/// depend on which shard finished first. Factory jobs run as one shard: the
+/// children found in an earlier range decide which contracts a later range
+/// covers.
+///
+/// A completed shard keeps what it fetched until the job stores it, so a
+/// re-run job reuses the shards its earlier attempt completed over the same
+/// ranges and fetches only the rest.
+async fn fetch_sharded(
+    svc: &IndexerServices<'_>,
+    client: &dyn ChainProvider,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    params: &BlockchainIndexParams,
+    to_block: u64,
+) -> Result<(Vec<BlockchainEvent>, Vec<String>)> {
+    let shard_blocks = match params.factory {
+        Some(_) => u64::MAX,
+        None => params.shard_blocks.unwrap_or(DEFAULT_SHARD_BLOCKS),
+    };
+    let shards = plan_shards(params.from_block, to_block, shard_blocks);
+    let budget = svc
+        .shard_budgets
+        .get(&params.chain)
+        .ok_or_else(|| anyhow::anyhow!("Chain '{}' has no shard budget", params.chain))?;
 
-                    if let Err(e) = sqlx::query(
-                        "INSERT INTO timestamp_commits (content_hash, transaction_hash, block_number, chain, job_id)
-                         VALUES ($1, $2, $3, $4, $5)
-                         ON CONFLICT (content_hash) DO NOTHING",
-                    )
-                    .bind(&merkle_root)
-                    .bind(&tx_hash_str)
-                    .bind(block_number as i64)
-                    .bind(&params.chain)
-                    .bind(job.id)
-                    .execute(pool)
-                    .await
-                    {
-                        tracing::error!(
-                            "Job {}: failed to store timestamp commit: {:?}",
-                            job.id,
-                            e
-                        );
-                    }
+    // A re-run job resumes from the shards its earlier attempt completed
+    // over the same ranges; the others are planned again.
+    let mut completed = completed_shards(pool, job_id).await?;
+    let mut results = Vec::new();
+    let mut pending = Vec::new();
+    for (index, shard) in shards.iter().enumerate() {
+        match completed.remove(&(index as i32, shard.from_block, shard.to_block)) {
+            Some(result) => results.push(result),
+            None => pending.push((index, shard)),
+        }
+    }
+    sqlx::query("DELETE FROM job_shards WHERE job_id = $1 AND shard_index >= $2")
+        .bind(job_id)
+        .bind(shards.len() as i32)
+        .execute(pool)
+        .await?;
+    if !pending.is_empty() {
+        let mut upsert = sqlx::QueryBuilder::new(
+            "INSERT INTO job_shards (job_id, shard_index, from_block, to_block) ",
+        );
+        upsert.push_values(&pending, |mut b, (index, shard)| {
+            b.push_bind(job_id)
+                .push_bind(*index as i32)
+                .push_bind(shard.from_block as i64)
+                .push_bind(shard.to_block as i64);
+        });
+        upsert.push(
+            " ON CONFLICT (job_id, shard_index) DO UPDATE
+              SET from_block = EXCLUDED.from_block, to_block = EXCLUDED.to_block,
+                  status = 'pending', events_found = NULL, error = NULL,
+                  started_at = NULL, completed_at = NULL, events = NULL, child_hashes = NULL",
+        );
+        upsert.build().execute(pool).await?;
+    }
+    if !results.is_empty() {
+        tracing::info!(
+            "Job {}: resuming from {} completed shards, fetching {}",
+            job_id,
+            results.len(),
+            pending.len()
+        );
+    }
 
-                    for event_id in &indexed_event_ids {
-                        if let Err(e) = sqlx::query(
-                            "UPDATE blockchain_events SET merkle_root = $1 WHERE id = $2",
-                        )
-                        .bind(&merkle_root)
-                        .bind(event_id)
-                        .execute(pool)
-                        .await
-                        {
-                            tracing::error!(
-                                "Job {}: failed to set merkle_root on event {}: {:?}",
-                                job.id,
-                                event_id,
-                                e
-                            );
-                        }
-                    }
+    use futures::{StreamExt as _, TryStreamExt as _};
+    let fetched: Result<Vec<(Vec<BlockchainEvent>, Vec<String>)>> =
+        futures::stream::iter(pending.iter().map(|&(index, shard)| async move {
+            let _permit = budget.acquire().await?;
+            sqlx::query(
+                "UPDATE job_shards SET status = 'running', started_at = NOW()
+                     WHERE job_id = $1 AND shard_index = $2",
+            )
+            .bind(job_id)
+            .bind(index as i32)
+            .execute(pool)
+            .await?;
 
-                    tracing::info!(
-                        "Job {}: committed Merkle root {} in tx {} at block {}",
-                        job.id,
-                        merkle_root,
-                        tx_hash_str,
-                        block_number
-                    );
-                }
-                Err(e) => {
-                    // Indexing succeeded but commit failed. Queue for retry.
-                    tracing::error!(
-                        "Job {}: on-chain Merkle commitment failed; queuing for retry: {:?}",
-                        job.id,
-                        e
-                    );
-                    if let Err(db_err) = sqlx::query(
-                        "INSERT INTO pending_merkle_commits (job_id, merkle_root, event_chain, last_error)
-                         VALUES ($1, $2, $3, $4)",
-                    )
-                    .bind(job.id)
-                    .bind(&merkle_root)
-                    .bind(&params.chain)
-                    .bind(e.to_string())
-                    .execute(pool)
-                    .await
-                    {
-                        tracing::error!(
-                            "Job {}: failed to insert pending_merkle_commit: {:?}",
-                            job.id,
-                            db_err
-                        );
-                    }
-                    return Ok(IndexResult::PendingCommit);
-                }
-            },
-            None => {
-                tracing::warn!(
-                    "Job {}: TIMESTAMP_REGISTRY_ADDRESS not configured — Merkle root {} not committed on-chain",
-                    job.id,
-                    merkle_root
-                );
-                // No client means no retries are possible. Return Completed so the
-                // job isn't stuck; the data is indexed and available.
-            }
+            let result = fetch_job_range(
+                client,
+                pool,
+                job_id,
+                params,
+                shard.from_block,
+                shard.to_block,
+            )
+            .await;
+            let (status, events_found, error, events, children) = match &result {
+                Ok((events, children)) => (
+                    "completed",
+                    Some(events.len() as i64),
+                    None,
+                    Some(serde_json::to_value(events)?),
+                    Some(children),
+                ),
+                Err(e) => ("failed", None, Some(format!("{:#}", e)), None, None),
+            };
+            sqlx::query(
+                "UPDATE job_shards SET status = $3, events_found = $4, error = $5,
+                         completed_at = NOW(), events = $6, child_hashes = $7
+                     WHERE job_id = $1 AND shard_index = $2",
+            )
+            .bind(job_id)
+            .bind(index as i32)
+            .bind(status)
+            .bind(events_found)
+            .bind(error)
+            .bind(events)
+            .bind(children)
+            .execute(pool)
+            .await?;
+            result
+        }))
+        .buffer_unordered(pending.len().max(1))
+        .try_collect()
+        .await;
+    match fetched {
+        Ok(fetched) => results.extend(fetched),
+        Err(e) => {
+            // Shards still in flight are dropped with the job.
+            sqlx::query(
+                "UPDATE job_shards SET status = 'cancelled', completed_at = NOW()
+                 WHERE job_id = $1 AND status IN ('pending', 'running')",
+            )
+            .bind(job_id)
+            .execute(pool)
+            .await?;
+            return Err(e);
         }
     }
 
-    Ok(IndexResult::Completed)
+    let mut all_events = Vec::new();
+    let mut child_hashes = Vec::new();
+    for (events, children) in results {
+        all_events.extend(events);
+        child_hashes.extend(children);
+    }
+    all_events.sort_by_key(|e| (e.block_number, e.log_index));
+    all_events
+        .dedup_by(|a, b| a.transaction_hash == b.transaction_hash && a.log_index == b.log_index);
+    if shards.len() > 1 {
+        tracing::info!(
+            "Job {}: merged {} shards, {} events",
+            job_id,
+            shards.len(),
+            all_events.len()
+        );
+    }
+    Ok((all_events, child_hashes))
 }
 
-// ── Webhook dispatch ──────────────────────────────────────────────────────────
-
-/// Fires HMAC-SHA256-signed webhook callbacks for all active subscriptions
-/// matching `event` for `user_id`. Errors are logged and never propagated —
-/// delivery is best-effort. The request body is the same JSON payload for
-/// all subscribers; only the signature differs (per-secret).
-async fn fire_webhooks(pool: &sqlx::PgPool, job_id: Uuid, user_id: Uuid, event: &str) {
+/// What each completed shard of a job with stored results fetched, keyed by
+/// shard index and range.
+async fn completed_shards(
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+) -> Result<HashMap<(i32, u64, u64), (Vec<BlockchainEvent>, Vec<String>)>> {
     use sqlx::Row;
 
-    let rows = match sqlx::query(
-        "SELECT url, secret FROM webhook_subscriptions
-         WHERE user_id = $1 AND is_active = true AND $2 = ANY(events)",
+    let rows = sqlx::query(
+        "SELECT shard_index, from_block, to_block, events, child_hashes FROM job_shards
+         WHERE job_id = $1 AND status = 'completed' AND events IS NOT NULL",
     )
-    .bind(user_id)
-    .bind(event)
+    .bind(job_id)
     .fetch_all(pool)
-    .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            tracing::error!("fire_webhooks: DB error: {:?}", e);
-            return;
-        }
-    };
+    .await?;
+    rows.iter()
+        .map(|row| {
+            let key = (
+                row.get("shard_index"),
+                row.get::<i64, _>("from_block") as u64,
+                row.get::<i64, _>("to_block") as u64,
+            );
+            let events = serde_json::from_value(row.get("events"))
+                .with_context(|| format!("Job {} has a corrupt shard result", job_id))?;
+            let children: Option<Vec<String>> = row.get("child_hashes");
+            Ok((key, (events, children.unwrap_or_default())))
+        })
+        .collect()
+}
 
-    if rows.is_empty() {
-        return;
+/// Fetches a job's events over `[from_block, to_block]` in block order.
+///
+/// For factory jobs the creation events are always fetched; children they
+/// announce are recorded in `job_child_contracts`, and every known child's
+/// events are fetched from its creation block onward. Returns the events and
+/// the content hashes of children recorded by this call, which belong in the
+/// job's Merkle commitment.
+async fn fetch_job_range(
+    client: &dyn ChainProvider,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    params: &BlockchainIndexParams,
+    from_block: u64,
+    to_block: u64,
+) -> Result<(Vec<BlockchainEvent>, Vec<String>)> {
+    use sqlx::Row;
+
+    let addresses = params.addresses()?;
+    let mut events = params.events.clone();
+    let mut all_events = Vec::new();
+    if let Some(factory) = &params.factory {
+        if addresses.is_empty() {
+            anyhow::bail!("A factory job needs a factory contract address");
+        }
+        // Creation events share the job's filter unless topic filters, which
+        // are defined for the job's own events, would exclude them.
+        if params.topic_filters.is_empty() {
+            if !events.contains(&factory.creation_event) {
+                events.push(factory.creation_event.clone());
+            }
+        } else {
+            let creation = EventFilter::new(
+                &params.chain,
+                addresses[..1].to_vec(),
+                vec![factory.creation_event.clone()],
+                from_block,
+                to_block,
+            );
+            all_events.extend(fetch_job_events(client, &creation, job_id).await?);
+        }
+    }
+    if !events.is_empty() {
+        let filter = EventFilter::new(
+            &params.chain,
+            addresses.clone(),
+            events,
+            from_block,
+            to_block,
+        )
+        .with_topic_filters(&params.topic_filters)?;
+        all_events.extend(fetch_job_events(client, &filter, job_id).await?);
     }
 
-    let payload = serde_json::json!({
-        "id": format!("evt_{}", Uuid::new_v4().simple()),
-        "created": Utc::now().timestamp(),
-        "type": event,
-        "data": {
-            "job_id": job_id,
-            "user_id": user_id,
-            "status": event.strip_prefix("job.").unwrap_or(event),
+    let mut child_hashes = Vec::new();
+    if let Some(factory) = &params.factory {
+        let factory_address = format!("{:?}", addresses[0]);
+        let creations: Vec<BlockchainEvent> = all_events
+            .iter()
+            .filter(|e| e.contract_address == factory_address)
+            .cloned()
+            .collect();
+        for child in factory.children(&creations)? {
+            let content_hash = child.content_hash(&params.chain, &params.contract_address);
+            // A child already recorded is skipped unless its creation event was
+            // orphaned, in which case the canonical creation replaces it.
+            let recorded = sqlx::query(
+                "INSERT INTO job_child_contracts (job_id, chain, factory_address, child_address, creation_block, creation_block_hash, creation_transaction_hash, creation_log_index, content_hash)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                 ON CONFLICT (job_id, child_address) DO UPDATE SET
+                     creation_block = EXCLUDED.creation_block,
+                     creation_block_hash = EXCLUDED.creation_block_hash,
+                     creation_transaction_hash = EXCLUDED.creation_transaction_hash,
+                     creation_log_index = EXCLUDED.creation_log_index,
+                     content_hash = EXCLUDED.content_hash,
+                     merkle_root = NULL,
+                     orphaned = false
+                 WHERE job_child_contracts.orphaned
+                 RETURNING id",
+            )
+            .bind(job_id)
+            .bind(&params.chain)
+            .bind(&factory_address)
+            .bind(format!("{:?}", child.address))
+            .bind(child.creation_block as i64)
+            .bind(&child.creation_block_hash)
+            .bind(&child.creation_transaction_hash)
+            .bind(child.creation_log_index as i64)
+            .bind(&content_hash)
+            .fetch_optional(pool)
+            .await?;
+            if recorded.is_some() {
+                tracing::info!(
+                    "Job {}: discovered child contract {:?} at block {}",
+                    job_id,
+                    child.address,
+                    child.creation_block
+                );
+                child_hashes.push(content_hash);
+            }
         }
-    });
 
-    let payload_bytes = match serde_json::to_vec(&payload) {
-        Ok(b) => b,
-        Err(e) => {
-            tracing::error!("fire_webhooks: serialization error: {:?}", e);
-            return;
+        let children = sqlx::query(
+            "SELECT child_address, creation_block FROM job_child_contracts
+             WHERE job_id = $1 AND NOT orphaned AND creation_block <= $2
+             ORDER BY creation_block, creation_log_index",
+        )
+        .bind(job_id)
+        .bind(to_block as i64)
+        .fetch_all(pool)
+        .await?;
+        let mut created_at = HashMap::new();
+        for child in children {
+            let address: Address = child
+                .get::<String, _>("child_address")
+                .parse()
+                .context("Invalid child contract address")?;
+            created_at.insert(address, child.get::<i64, _>("creation_block") as u64);
         }
-    };
+        // One filter covers every child, from the earliest creation in range;
+        // events before a child's own creation block are dropped.
+        if let Some(&first_creation) = created_at.values().min() {
+            let filter = EventFilter::new(
+                &params.chain,
+                created_at.keys().copied().collect(),
+                factory.child_events.clone(),
+                from_block.max(first_creation),
+                to_block,
+            );
+            let child_events = fetch_job_events(client, &filter, job_id).await?;
+            all_events.extend(child_events.into_iter().filter(|e| {
+                e.contract_address
+                    .parse::<Address>()
+                    .ok()
+                    .and_then(|a| created_at.get(&a))
+                    .is_some_and(|&created| e.block_number >= created)
+            }));
+        }
+    }
 
-    let timeout_secs = env::var("WEBHOOK_TIMEOUT_SECS")
-        .ok()
-        .and_then(|v| v.parse::<u64>().ok())
-        .unwrap_or(10);
+    all_events.sort_by_key(|e| (e.block_number, e.log_index));
+    // A log matched by two requested signatures is only indexed once.
+    all_events
+        .dedup_by(|a, b| a.transaction_hash == b.transaction_hash && a.log_index == b.log_index);
+    Ok((all_events, child_hashes))
+}
 
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(timeout_secs))
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::error!("fire_webhooks: failed to build HTTP client: {:?}", e);
-            return;
+/// Token context for a job's transfer events. Detected tokens are cached in
+/// `token_metadata`; contracts found not to be tokens are only remembered for
+/// the batch, because a failed RPC call looks the same as a missing getter.
+struct TokenEnricher {
+    resolve_uris: bool,
+    contracts: HashMap<(String, String), Option<TokenMetadata>>,
+}
+
+/// The token fields stored with an event.
+#[derive(Default)]
+struct EventToken {
+    transfer: Option<TokenTransfer>,
+    uri: Option<String>,
+    metadata: Option<serde_json::Value>,
+}
+
+impl TokenEnricher {
+    fn new(params: &BlockchainIndexParams) -> Self {
+        Self {
+            resolve_uris: params.resolve_token_uris,
+            contracts: HashMap::new(),
         }
-    };
+    }
 
-    for row in &rows {
-        let url: String = row.get("url");
-        let secret: String = row.get("secret");
+    /// The metadata of the contract that emitted `event`, from the cache or,
+    /// on first sight, from the contract itself at the event's block.
+    async fn metadata(
+        &mut self,
+        client: &dyn ChainProvider,
+        pool: &sqlx::PgPool,
+        event: &BlockchainEvent,
+    ) -> Result<Option<TokenMetadata>> {
+        use sqlx::Row;
 
-        let sig = format!(
-            "sha256={}",
-            hex::encode(hmac_sha256(secret.as_bytes(), &payload_bytes))
-        );
+        let key = (event.chain.clone(), event.contract_address.clone());
+        if let Some(known) = self.contracts.get(&key) {
+            return Ok(known.clone());
+        }
 
-        match client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("X-IndexNode-Signature", &sig)
-            .header("X-IndexNode-Event", event)
-            .body(payload_bytes.clone())
-            .send()
-            .await
-        {
-            Ok(resp) if resp.status().is_success() => {
-                tracing::info!(
-                    "Webhook delivered to {} for job {} ({})",
-                    url,
-                    job_id,
-                    event
-                );
+        let cached = sqlx::query(
+            "SELECT standard, name, symbol, decimals FROM token_metadata
+             WHERE chain = $1 AND contract_address = $2",
+        )
+        .bind(&event.chain)
+        .bind(&event.contract_address)
+        .fetch_optional(pool)
+        .await?;
+        let metadata = match cached {
+            Some(row) => TokenStandard::parse(row.get("standard")).map(|standard| TokenMetadata {
+                standard,
+                name: row.get("name"),
+                symbol: row.get("symbol"),
+                decimals: row.get::<Option<i16>, _>("decimals").map(|d| d as u8),
+            }),
+            None => {
+                let contract: Address = event
+                    .contract_address
+                    .parse()
+                    .context("Invalid contract address")?;
+                let block_hash: H256 = event.block_hash.parse().context("Invalid block hash")?;
+                let fetched = TokenMetadata::fetch(client, contract, block_hash).await;
+                if let Some(metadata) = &fetched {
+                    sqlx::query(
+                        "INSERT INTO token_metadata (chain, contract_address, standard, name, symbol, decimals)
+                         VALUES ($1, $2, $3, $4, $5, $6)
+                         ON CONFLICT (chain, contract_address) DO NOTHING",
+                    )
+                    .bind(&event.chain)
+                    .bind(&event.contract_address)
+                    .bind(metadata.standard.as_str())
+                    .bind(&metadata.name)
+                    .bind(&metadata.symbol)
+                    .bind(metadata.decimals.map(i16::from))
+                    .execute(pool)
+                    .await?;
+                }
+                fetched
             }
-            Ok(resp) => {
+        };
+        self.contracts.insert(key, metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Token fields for `event`; empty unless it is a transfer of a token.
+    /// Enrichment is best-effort and never fails the event.
+    async fn enrich(
+        &mut self,
+        svc: &IndexerServices<'_>,
+        pool: &sqlx::PgPool,
+        event: &BlockchainEvent,
+    ) -> EventToken {
+        if !is_transfer_event(event) {
+            return EventToken::default();
+        }
+        let Some(client) = svc.chain_clients.get(&event.chain) else {
+            return EventToken::default();
+        };
+        let metadata = match self.metadata(client.as_ref(), pool, event).await {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => return EventToken::default(),
+            Err(e) => {
                 tracing::warn!(
-                    "Webhook to {} returned {} for job {}",
-                    url,
-                    resp.status(),
-                    job_id
+                    "Token metadata for {} on {} unavailable: {:?}",
+                    event.contract_address,
+                    event.chain,
+                    e
                 );
+                return EventToken::default();
             }
-            Err(e) => {
-                tracing::warn!("Webhook to {} failed for job {}: {:?}", url, job_id, e);
+        };
+        let Some(transfer) = token_transfer(event, &metadata) else {
+            return EventToken::default();
+        };
+
+        let mut token = EventToken::default();
+        if let (true, Some(token_id), Ok(contract), Ok(block_hash)) = (
+            self.resolve_uris,
+            transfer.token_id,
+            event.contract_address.parse::<Address>(),
+            event.block_hash.parse::<H256>(),
+        ) {
+            token.uri = fetch_token_uri(
+                client.as_ref(),
+                contract,
+                transfer.standard,
+                token_id,
+                block_hash,
+            )
+            .await;
+            if let Some(uri) = &token.uri {
+                match resolve_token_uri(svc.ipfs, uri).await {
+                    Ok(document) => token.metadata = Some(document),
+                    Err(e) => tracing::debug!("Token URI {} not resolved: {:?}", uri, e),
+                }
             }
         }
+        token.transfer = Some(transfer);
+        token
     }
 }
 
-/// HMAC-SHA256 computed in-house to avoid an extra crate dependency.
-/// Follows RFC 2104: HMAC(K, m) = H((K' ⊕ opad) ∥ H((K' ⊕ ipad) ∥ m))
-/// where K' is the key zero-padded to the hash block size (64 bytes for SHA-256).
-fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
-    use sha2::{Digest, Sha256};
-    const BLOCK: usize = 64;
+/// AI extraction settings for a job and the token budget it has left.
+/// Usage is persisted on the job so live-tail batches share one budget.
+struct AiBudget {
+    schema: Option<serde_json::Value>,
+    tokens_remaining: u32,
+    tokens_used: u32,
+}
 
-    let mut k = [0u8; BLOCK];
-    if key.len() > BLOCK {
-        let h = Sha256::digest(key);
-        k[..32].copy_from_slice(&h);
-    } else {
-        k[..key.len()].copy_from_slice(key);
+impl AiBudget {
+    async fn load(
+        pool: &sqlx::PgPool,
+        job_id: Uuid,
+        params: &BlockchainIndexParams,
+    ) -> Result<Self> {
+        let already_used: i32 = sqlx::query_scalar("SELECT ai_tokens_used FROM jobs WHERE id = $1")
+            .bind(job_id)
+            .fetch_one(pool)
+            .await?;
+        // Default budget: 100,000 tokens per job when AI is enabled.
+        let budget = params.ai_token_budget.unwrap_or(100_000);
+        Ok(Self {
+            schema: params
+                .enable_ai
+                .then(|| params.extraction_schema.clone())
+                .flatten(),
+            tokens_remaining: budget.saturating_sub(already_used.max(0) as u32),
+            tokens_used: 0,
+        })
     }
 
-    let mut ipad = k;
-    let mut opad = k;
-    for b in &mut ipad {
-        *b ^= 0x36;
+    async fn save(&self, pool: &sqlx::PgPool, job_id: Uuid) {
+        if self.tokens_used == 0 {
+            return;
+        }
+        if let Err(e) =
+            sqlx::query("UPDATE jobs SET ai_tokens_used = ai_tokens_used + $1 WHERE id = $2")
+                .bind(self.tokens_used as i32)
+                .bind(job_id)
+                .execute(pool)
+                .await
+        {
+            tracing::error!("Job {}: failed to record AI token usage: {:?}", job_id, e);
+        }
     }
-    for b in &mut opad {
-        *b ^= 0x5c;
+}
+
+/// Enriches, pins and upserts a single event (plus its IPFS record,
+/// token fields and optional AI extraction). Returns the event's ID and
+/// content hash; an event that was already indexed keeps its existing ID and
+/// is not extracted again.
+async fn store_event(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    job_id: Uuid,
+    event: BlockchainEvent,
+    ai_budget: &mut AiBudget,
+    tokens: &mut TokenEnricher,
+) -> Result<(Uuid, String)> {
+    use sqlx::Row;
+
+    let ipfs = svc.ipfs;
+    let token = tokens.enrich(svc, pool, &event).await;
+    let transfer = token.transfer.as_ref();
+
+    let event_json = serde_json::to_vec(&event).context("Failed to serialize event")?;
+    let ipfs_cid = ipfs.store_content(&event_json).await?;
+
+    crate::metrics::record_ipfs_upload(event_json.len() as u64);
+
+    ipfs.pin_content(&ipfs_cid).await?;
+
+    // (chain, transaction_hash, log_index) is the event's natural key within a
+    // job, so re-running a job or overlapping ranges updates the existing row
+    // instead of duplicating it, while another job indexing the same log gets
+    // a row of its own. A log re-included after a reorg is un-orphaned with
+    // its new block. `xmax = 0` distinguishes a fresh insert from an update.
+    let row = sqlx::query(
+        "INSERT INTO blockchain_events (id, job_id, chain, contract_address, event_name, block_number, block_hash, block_timestamp, transaction_hash, log_index, topics, removed, event_data, content_hash, hash_version, ipfs_cid, token_standard, token_amount_raw, token_amount, token_id, token_uri, token_metadata)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18::NUMERIC, $19::NUMERIC, $20::NUMERIC, $21, $22)
+         ON CONFLICT (job_id, chain, transaction_hash, log_index) DO UPDATE SET
+             block_number = EXCLUDED.block_number,
+             block_hash = EXCLUDED.block_hash,
+             block_timestamp = EXCLUDED.block_timestamp,
+             removed = EXCLUDED.removed,
+             token_standard = EXCLUDED.token_standard,
+             token_amount_raw = EXCLUDED.token_amount_raw,
+             token_amount = EXCLUDED.token_amount,
+             token_id = EXCLUDED.token_id,
+             token_uri = COALESCE(EXCLUDED.token_uri, blockchain_events.token_uri),
+             token_metadata = COALESCE(EXCLUDED.token_metadata, blockchain_events.token_metadata),
+             orphaned = false,
+             orphaned_at = NULL
+         RETURNING id, content_hash, (xmax = 0) AS inserted",
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(job_id)
+    .bind(&event.chain)
+    .bind(&event.contract_address)
+    .bind(&event.event_name)
+    .bind(event.block_number as i64)
+    .bind(&event.block_hash)
+    .bind(event.block_timestamp)
+    .bind(&event.transaction_hash)
+    .bind(event.log_index as i64)
+    .bind(&event.topics)
+    .bind(event.removed)
+    .bind(&event.event_data)
+    .bind(&event.content_hash)
+    .bind(event.hash_version as i16)
+    .bind(&ipfs_cid)
+    .bind(transfer.map(|t| t.standard.as_str()))
+    .bind(transfer.and_then(|t| t.amount_raw.as_ref()))
+    .bind(transfer.and_then(|t| t.amount.as_ref()))
+    .bind(transfer.and_then(|t| t.token_id).map(|id| id.to_string()))
+    .bind(&token.uri)
+    .bind(&token.metadata)
+    .fetch_one(pool)
+    .await?;
+
+    let event_id: Uuid = row.get("id");
+    if !row.get::<bool, _>("inserted") {
+        tracing::debug!(
+            "Job {}: event {}:{} already indexed as {}",
+            job_id,
+            event.transaction_hash,
+            event.log_index,
+            event_id
+        );
+        // An event indexed before canonical hashing keeps its stored hash and
+        // version, and that hash is what Merkle roots over it must cover.
+        return Ok((event_id, row.get("content_hash")));
     }
 
-    let mut inner = Sha256::new();
-    inner.update(ipad);
-    inner.update(message);
-    let inner_hash = inner.finalize();
+    crate::metrics::record_blockchain_event();
 
-    let mut outer = Sha256::new();
-    outer.update(opad);
-    outer.update(inner_hash);
-    outer.finalize().to_vec()
+    sqlx::query(
+        "INSERT INTO ipfs_content (cid, content_hash, size_bytes, pinned, blockchain_event_id)
+         VALUES ($1, $2, $3, true, $4)
+         ON CONFLICT (cid) DO NOTHING",
+    )
+    .bind(&ipfs_cid)
+    .bind(&event.content_hash)
+    .bind(event_json.len() as i64)
+    .bind(event_id)
+    .execute(pool)
+    .await?;
+
+    if ai_budget.tokens_remaining > 0 {
+        if let (Some(schema), Some(ai)) = (&ai_budget.schema, svc.ai) {
+            let schema_str = serde_json::to_string(schema)?;
+            let event_str = serde_json::to_string(&event.event_data)?;
+
+            match tokio::time::timeout(
+                svc.ai_timeout,
+                ai.extract_structured_data(&event_str, &schema_str),
+            )
+            .await
+            {
+                Err(_) => {
+                    tracing::warn!(
+                        "Job {}: AI extraction timed out for event {}",
+                        job_id,
+                        event_id
+                    );
+                }
+                Ok(Err(e)) => {
+                    tracing::warn!(
+                        "Job {}: AI extraction failed for event {}: {:?}",
+                        job_id,
+                        event_id,
+                        e
+                    );
+                }
+                Ok(Ok(result)) => {
+                    ai_budget.tokens_remaining = ai_budget
+                        .tokens_remaining
+                        .saturating_sub(result.tokens_used);
+                    ai_budget.tokens_used =
+                        ai_budget.tokens_used.saturating_add(result.tokens_used);
+
+                    crate::metrics::record_ai_extraction();
+
+                    if let Err(e) = sqlx::query(
+                        "INSERT INTO ai_extractions (blockchain_event_id, extraction_type, schema_definition, extracted_data)
+                         VALUES ($1, 'structured', $2, $3)",
+                    )
+                    .bind(event_id)
+                    .bind(schema)
+                    .bind(result.data)
+                    .execute(pool)
+                    .await
+                    {
+                        tracing::error!(
+                            "Job {}: failed to store AI extraction for event {}: {:?}",
+                            job_id, event_id, e
+                        );
+                    }
+
+                    if ai_budget.tokens_remaining == 0 {
+                        tracing::warn!(
+                            "Job {}: AI token budget exhausted after event {}; skipping remaining extractions",
+                            job_id, event_id
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((event_id, event.content_hash))
+}
+
+/// Advances every live-tail job whose chain head has moved past its cursor.
+///
+/// `heads` holds the latest head seen on each chain's new-heads subscription;
+/// chains without a subscription fall back to polling. Jobs only advance to
+/// the finalized head (head minus the chain's confirmation depth).
+/// A batch that cannot be fully fetched or stored leaves the cursor untouched
+/// so the same range is retried on the next tick, without holding up the
+/// other live jobs.
+async fn tail_live_jobs(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    heads: &HashMap<String, u64>,
+) -> Result<()> {
+    use sqlx::Row;
+
+    let rows = sqlx::query(
+        "SELECT id, config, last_processed_block FROM jobs WHERE status = 'live' ORDER BY created_at",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in rows {
+        let job_id: Uuid = row.get("id");
+        let config: JobConfig = match serde_json::from_value(row.get("config")) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::error!("Live job {}: invalid config: {:?}", job_id, e);
+                continue;
+            }
+        };
+        let Some(params) = config.params.as_blockchain_index() else {
+            continue;
+        };
+        let Some(last_processed) = row.get::<Option<i64>, _>("last_processed_block") else {
+            continue;
+        };
+
+        if let Err(e) = tail_live_job(svc, pool, heads, job_id, params, last_processed as u64).await
+        {
+            tracing::warn!("Live job {}: not advanced, will retry: {:?}", job_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Indexes a live job's blocks after `last_processed` up to the finalized
+/// head and advances its cursor.
+async fn tail_live_job(
+    svc: &IndexerServices<'_>,
+    pool: &sqlx::PgPool,
+    heads: &HashMap<String, u64>,
+    job_id: Uuid,
+    params: &BlockchainIndexParams,
+    last_processed: u64,
+) -> Result<()> {
+    let Some(client) = svc.chain_clients.get(&params.chain) else {
+        return Ok(());
+    };
+
+    let head = match heads.get(&params.chain) {
+        Some(head) => head.saturating_sub(client.confirmations()),
+        None => client.get_finalized_block().await?,
+    };
+    let from_block = last_processed + 1;
+    if head < from_block {
+        return Ok(());
+    }
+
+    let (events, child_hashes) =
+        match fetch_job_range(client.as_ref(), pool, job_id, params, from_block, head).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                tracing::warn!(
+                    "Live job {}: blocks {}-{} not fetched, will retry: {:?}",
+                    job_id,
+                    from_block,
+                    head,
+                    e
+                );
+                return Ok(());
+            }
+        };
+
+    let mut ai_budget = AiBudget::load(pool, job_id, params).await?;
+    let mut tokens = TokenEnricher::new(params);
+    let count = events.len();
+    let mut content_hashes = Vec::with_capacity(count);
+    for event in events {
+        let (_, content_hash) =
+            store_event(svc, pool, job_id, event, &mut ai_budget, &mut tokens).await?;
+        content_hashes.push(content_hash);
+    }
+    ai_budget.save(pool, job_id).await;
+    // Children announced in this batch are leaves too, after its events as in
+    // the backfill.
+    content_hashes.extend(child_hashes);
+    mmr::append(pool, job_id, &content_hashes).await?;
+
+    sqlx::query("UPDATE jobs SET last_processed_block = $1 WHERE id = $2")
+        .bind(head as i64)
+        .bind(job_id)
+        .execute(pool)
+        .await?;
+
+    let interval = params
+        .mmr_commit_blocks
+        .unwrap_or(DEFAULT_MMR_COMMIT_BLOCKS);
+    if let Err(e) = mmr::commit_if_due(
+        svc.timestamp_client,
+        pool,
+        job_id,
+        &params.chain,
+        head,
+        interval,
+    )
+    .await
+    {
+        tracing::warn!(
+            "Live job {}: MMR root not committed, will retry: {:?}",
+            job_id,
+            e
+        );
+    }
+
+    if count > 0 {
+        tracing::info!(
+            "Live job {}: indexed {} events in blocks {}-{}",
+            job_id,
+            count,
+            from_block,
+            head
+        );
+    }
+    Ok(())
+}
+
+/// Compares the block hashes of recently indexed events, transactions and
+/// state snapshots with the canonical chain. Rows in replaced blocks are
+/// marked orphaned, any committed Merkle root covering them is flagged
+/// `reorg_affected`, and live jobs are rewound so the canonical block is
+/// indexed again. A chain whose RPC fails is skipped until the next check.
+async fn detect_reorgs(chain_clients: &ChainClients, pool: &sqlx::PgPool) -> Result<()> {
+    for (chain, client) in chain_clients {
+        if let Err(e) = detect_chain_reorgs(chain, client.as_ref(), pool).await {
+            tracing::warn!("Reorg check on {} failed, will retry: {:?}", chain, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn detect_chain_reorgs(
+    chain: &str,
+    client: &dyn ChainProvider,
+    pool: &sqlx::PgPool,
+) -> Result<()> {
+    use sqlx::Row;
+
+    let latest = client.get_latest_block().await?;
+    let window_start = latest.saturating_sub(client.confirmations() + REORG_CHECK_MARGIN);
+
+    let blocks = sqlx::query(
+        "SELECT block_number, block_hash FROM blockchain_events
+         WHERE chain = $1 AND block_number >= $2 AND NOT orphaned AND block_hash IS NOT NULL
+         UNION
+         SELECT block_number, block_hash FROM indexed_transactions
+         WHERE chain = $1 AND block_number >= $2 AND NOT orphaned
+         UNION
+         SELECT block_number, block_hash FROM state_snapshots
+         WHERE chain = $1 AND block_number >= $2 AND NOT orphaned
+         ORDER BY block_number",
+    )
+    .bind(chain)
+    .bind(window_start as i64)
+    .fetch_all(pool)
+    .await?;
+
+    for block in blocks {
+        let block_number: i64 = block.get("block_number");
+        let stored_hash: String = block.get("block_hash");
+        let Some(canonical_hash) = client.get_block_hash(block_number as u64).await? else {
+            continue;
+        };
+        if canonical_hash == stored_hash {
+            continue;
+        }
+
+        tracing::warn!(
+            "Reorg on {} at block {}: stored {} but canonical is {}",
+            chain,
+            block_number,
+            stored_hash,
+            canonical_hash
+        );
+        mark_orphaned(pool, chain, block_number, &stored_hash).await?;
+    }
+
+    Ok(())
+}
+
+/// Marks the events, transactions and snapshot values of a replaced block as
+/// orphaned and flags what depends on them.
+async fn mark_orphaned(
+    pool: &sqlx::PgPool,
+    chain: &str,
+    block_number: i64,
+    block_hash: &str,
+) -> Result<()> {
+    use sqlx::Row;
+
+    let mut tx = pool.begin().await?;
+
+    let orphaned = sqlx::query(
+        "UPDATE blockchain_events SET orphaned = true, orphaned_at = NOW()
+         WHERE chain = $1 AND block_number = $2 AND block_hash = $3 AND NOT orphaned
+         RETURNING job_id, merkle_root, content_hash",
+    )
+    .bind(chain)
+    .bind(block_number)
+    .bind(block_hash)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Children announced by a replaced creation event go with it.
+    let orphaned_children = sqlx::query(
+        "UPDATE job_child_contracts SET orphaned = true
+         WHERE chain = $1 AND creation_block = $2 AND creation_block_hash = $3 AND NOT orphaned
+         RETURNING job_id, merkle_root",
+    )
+    .bind(chain)
+    .bind(block_number)
+    .bind(block_hash)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let orphaned_transactions = sqlx::query(
+        "UPDATE indexed_transactions SET orphaned = true, orphaned_at = NOW()
+         WHERE chain = $1 AND block_number = $2 AND block_hash = $3 AND NOT orphaned
+         RETURNING merkle_root",
+    )
+    .bind(chain)
+    .bind(block_number)
+    .bind(block_hash)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let orphaned_snapshots = sqlx::query(
+        "UPDATE state_snapshots SET orphaned = true, orphaned_at = NOW()
+         WHERE chain = $1 AND block_number = $2 AND block_hash = $3 AND NOT orphaned
+         RETURNING merkle_root",
+    )
+    .bind(chain)
+    .bind(block_number)
+    .bind(block_hash)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut job_ids: Vec<Uuid> = orphaned.iter().map(|r| r.get("job_id")).collect();
+    job_ids.sort();
+    job_ids.dedup();
+    let merkle_roots: Vec<String> = orphaned
+        .iter()
+        .chain(&orphaned_children)
+        .chain(&orphaned_transactions)
+        .chain(&orphaned_snapshots)
+        .filter_map(|r| r.get::<Option<String>, _>("merkle_root"))
+        .collect();
+
+    if !merkle_roots.is_empty() {
+        sqlx::query(
+            "UPDATE timestamp_commits SET reorg_affected = true WHERE content_hash = ANY($1)",
+        )
+        .bind(&merkle_roots)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // MMR roots stay append-only; those covering an orphaned event are flagged.
+    let orphaned_hashes: Vec<String> = orphaned.iter().map(|r| r.get("content_hash")).collect();
+    let mmr_roots_flagged = if orphaned_hashes.is_empty() {
+        0
+    } else {
+        sqlx::query(
+            "UPDATE timestamp_commits SET reorg_affected = true
+             WHERE content_hash IN (
+                 SELECT r.root FROM mmr_roots r
+                 JOIN mmr_leaves l ON l.job_id = r.job_id AND l.leaf_index < r.leaf_count
+                 WHERE l.job_id = ANY($1) AND l.content_hash = ANY($2)
+             )",
+        )
+        .bind(&job_ids)
+        .bind(&orphaned_hashes)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+    };
+
+    // Rewind live-tail jobs so the canonical version of the block is indexed.
+    sqlx::query(
+        "UPDATE jobs SET last_processed_block = LEAST(last_processed_block, $1)
+         WHERE id = ANY($2) AND status IN ('live', 'paused')",
+    )
+    .bind(block_number - 1)
+    .bind(&job_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    tracing::warn!(
+        "Orphaned {} events, {} transactions and {} snapshot values on {} block {} ({} committed roots flagged)",
+        orphaned.len(),
+        orphaned_transactions.len(),
+        orphaned_snapshots.len(),
+        chain,
+        block_number,
+        merkle_roots.len() + mmr_roots_flagged as usize
+    );
+    Ok(())
+}
+
+// ── Webhook dispatch ──────────────────────────────────────────────────────────
+
+/// Fires HMAC-SHA256-signed webhook callbacks for all active subscriptions
+/// matching `event` for `user_id`. Errors are logged and never propagated —
+/// delivery is best-effort. The request body is the same JSON payload for
+/// all subscribers; only the signature differs (per-secret).
+async fn fire_webhooks(pool: &sqlx::PgPool, job_id: Uuid, user_id: Uuid, event: &str) {
+    use sqlx::Row;
+
+    let rows = match sqlx::query(
+        "SELECT url, secret FROM webhook_subscriptions
+         WHERE user_id = $1 AND is_active = true AND $2 = ANY(events)",
+    )
+    .bind(user_id)
+    .bind(event)
+    .fetch_all(pool)
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("fire_webhooks: DB error: {:?}", e);
+            return;
+        }
+    };
+
+    if rows.is_empty() {
+        return;
+    }
+
+    let payload = serde_json::json!({
+        "id": format!("evt_{}", Uuid::new_v4().simple()),
+        "created": Utc::now().timestamp(),
+        "type": event,
+        "data": {
+            "job_id": job_id,
+            "user_id": user_id,
+            "status": event.strip_prefix("job.").unwrap_or(event),
+        }
+    });
+
+    let payload_bytes = match serde_json::to_vec(&payload) {
+        Ok(b) => b,
+        Err(e) => {
+            tracing::error!("fire_webhooks: serialization error: {:?}", e);
+            return;
+        }
+    };
+
+    let timeout_secs = env::var("WEBHOOK_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(10);
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout_secs))
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!("fire_webhooks: failed to build HTTP client: {:?}", e);
+            return;
+        }
+    };
+
+    for row in &rows {
+        let url: String = row.get("url");
+        let secret: String = row.get("secret");
+
+        let sig = format!(
+            "sha256={}",
+            hex::encode(hmac_sha256(secret.as_bytes(), &payload_bytes))
+        );
+
+        match client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("X-IndexNode-Signature", &sig)
+            .header("X-IndexNode-Event", event)
+            .body(payload_bytes.clone())
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                tracing::info!(
+                    "Webhook delivered to {} for job {} ({})",
+                    url,
+                    job_id,
+                    event
+                );
+            }
+            Ok(resp) => {
+                tracing::warn!(
+                    "Webhook to {} returned {} for job {}",
+                    url,
+                    resp.status(),
+                    job_id
+                );
+            }
+            Err(e) => {
+                tracing::warn!("Webhook to {} failed for job {}: {:?}", url, job_id, e);
+            }
+        }
+    }
+}
+
+/// HMAC-SHA256 computed in-house to avoid an extra crate dependency.
+/// Follows RFC 2104: HMAC(K, m) = H((K' ⊕ opad) ∥ H((K' ⊕ ipad) ∥ m))
+/// where K' is the key zero-padded to the hash block size (64 bytes for SHA-256).
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    const BLOCK: usize = 64;
+
+    let mut k = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        let h = Sha256::digest(key);
+        k[..32].copy_from_slice(&h);
+    } else {
+        k[..key.len()].copy_from_slice(key);
+    }
+
+    let mut ipad = k;
+    let mut opad = k;
+    for b in &mut ipad {
+        *b ^= 0x36;
+    }
+    for b in &mut opad {
+        *b ^= 0x5c;
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(ipad);
+    inner.update(message);
+    let inner_hash = inner.finalize();
+
+    let mut outer = Sha256::new();
+    outer.update(opad);
+    outer.update(inner_hash);
+    outer.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+    use indexnode_core::mock::{
+        MemoryContentStore, MockChain, MockLog, MockTransaction, MOCK_CHAIN_ID,
+        MOCK_REGISTRY_ADDRESS,
+    };
+    use indexnode_core::{
+        compute_versioned_root, verify_mmr_consistency_proof, verify_mmr_inclusion_proof,
+        verify_multiproof, ChainConfig, MerkleMode, MultiproofBundle, Projection, ProofBundle,
+        KECCAK_MERKLE_TREE_VERSION, MERKLE_TREE_VERSION, MMR_TREE_VERSION,
+    };
+
+    const TRANSFER: &str = "Transfer(address,address,uint256)";
+    const CONFIRMATIONS: u64 = 2;
+
+    /// Connects to `DATABASE_URL` and applies migrations. Tests that need a
+    /// database are `#[ignore]`d so a plain `cargo test` reports them as not
+    /// run; `cargo test -- --ignored` runs them and fails without a database.
+    async fn test_pool() -> sqlx::PgPool {
+        let database_url =
+            std::env::var("DATABASE_URL").expect("DATABASE_URL is set for database tests");
+        let pool = sqlx::PgPool::connect(&database_url)
+            .await
+            .expect("Test database is reachable");
+        sqlx::migrate!("../migrations")
+            .run(&pool)
+            .await
+            .expect("Migrations apply to the test database");
+        pool
+    }
+
+    /// A mock chain and the services around it. Each fixture uses its own
+    /// chain name so runs against a shared database do not collide.
+    struct Fixture {
+        chain_name: String,
+        chain: MockChain,
+        registry: ChainRegistry,
+        clients: ChainClients,
+        shard_budgets: ShardBudgets,
+        store: MemoryContentStore,
+        token: Address,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let chain_name = format!("mock-{}", Uuid::new_v4());
+            let chain = MockChain::new(CONFIRMATIONS);
+            let registry = ChainRegistry::new(vec![ChainConfig {
+                name: chain_name.clone(),
+                chain_id: 31337,
+                rpc_urls: vec!["http://127.0.0.1:8545".to_string()],
+                confirmations: CONFIRMATIONS,
+                block_time_ms: 1_000,
+                max_log_range: 1_000,
+                cross_check_heads: false,
+                max_head_lag: 5,
+                max_concurrent_shards: 2,
+            }])
+            .expect("Valid registry");
+            let mut clients: ChainClients = HashMap::new();
+            clients.insert(chain_name.clone(), Box::new(chain.clone()));
+            Self {
+                chain_name,
+                chain,
+                shard_budgets: shard_budgets(&registry),
+                registry,
+                clients,
+                store: MemoryContentStore::new(),
+                token: Address::repeat_byte(0xaa),
+            }
+        }
+
+        fn services(&self) -> IndexerServices<'_> {
+            IndexerServices {
+                chains: &self.registry,
+                chain_clients: &self.clients,
+                shard_budgets: &self.shard_budgets,
+                ipfs: &self.store,
+                timestamp_client: Some(&self.chain),
+                credit_manager: &self.chain,
+                ai: None,
+                ai_timeout: Duration::from_secs(1),
+            }
+        }
+
+        /// A Transfer log with a random amount, so content hashes are unique.
+        fn transfer(&self) -> MockLog {
+            self.transfer_from(self.token)
+        }
+
+        fn transfer_from(&self, token: Address) -> MockLog {
+            let from = H256::from(Address::repeat_byte(1));
+            let to = H256::from(Address::repeat_byte(2));
+            let mut amount = [0u8; 32];
+            amount[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+            MockLog::event(token, TRANSFER, &[from, to], amount.to_vec())
+        }
+
+        fn params(&self, from_block: u64, to_block: Option<u64>) -> BlockchainIndexParams {
+            BlockchainIndexParams {
+                chain: self.chain_name.clone(),
+                contract_address: format!("{:?}", self.token),
+                contract_addresses: Vec::new(),
+                events: vec![TRANSFER.to_string()],
+                topic_filters: Vec::new(),
+                from_block,
+                to_block,
+                enable_ai: false,
+                extraction_schema: None,
+                ai_token_budget: None,
+                live: false,
+                factory: None,
+                resolve_token_uris: false,
+                shard_blocks: None,
+                projections: Vec::new(),
+                merkle_mode: MerkleMode::default(),
+                mmr_commit_blocks: None,
+            }
+        }
+
+        async fn insert_job(&self, pool: &sqlx::PgPool, params: BlockchainIndexParams) -> Job {
+            let config = JobConfig {
+                job_type: indexnode_core::JobType::BlockchainIndex,
+                params: JobParams::BlockchainIndex(Box::new(params)),
+            };
+            self.insert_config(pool, config).await
+        }
+
+        async fn insert_config(&self, pool: &sqlx::PgPool, config: JobConfig) -> Job {
+            let user_id = Uuid::new_v4();
+            sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+                .bind(user_id)
+                .bind(format!("{}@example.com", user_id))
+                .execute(pool)
+                .await
+                .expect("Insert user");
+            sqlx::query("INSERT INTO user_credits (user_id, on_chain_address, credit_balance) VALUES ($1, $2, 1000)")
+                .bind(user_id)
+                .bind(format!("{:?}", Address::repeat_byte(3)))
+                .execute(pool)
+                .await
+                .expect("Insert credits");
+
+            let job_id = Uuid::new_v4();
+            sqlx::query(
+                "INSERT INTO jobs (id, user_id, status, config) VALUES ($1, $2, 'processing', $3)",
+            )
+            .bind(job_id)
+            .bind(user_id)
+            .bind(serde_json::to_value(&config).expect("Serializable config"))
+            .execute(pool)
+            .await
+            .expect("Insert job");
+
+            JobQueue::new(pool.clone())
+                .get_job(job_id)
+                .await
+                .expect("Load job")
+                .expect("Job exists")
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_index_and_commit() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        fx.chain.mine(vec![fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 1);
+        let stamped: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM blockchain_events WHERE job_id = $1 AND merkle_root = $2",
+        )
+        .bind(job.id)
+        .bind(&commits[0])
+        .fetch_one(&pool)
+        .await
+        .expect("Count events");
+        assert_eq!(stamped, 3);
+        assert_eq!(fx.chain.credit_spends().len(), 1);
+
+        let tree_version: i16 = sqlx::query_scalar(
+            "SELECT tree_version FROM timestamp_commits WHERE content_hash = $1",
+        )
+        .bind(&commits[0])
+        .fetch_one(&pool)
+        .await
+        .expect("Load commitment");
+        assert_eq!(tree_version, MERKLE_TREE_VERSION as i16);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_index_job_deducts_whole_credits() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+
+        // 50 credits are spent on-chain in 18-decimal units and deducted from
+        // the stored balance as 50 whole credits.
+        let spends = fx.chain.credit_spends();
+        assert_eq!(spends.len(), 1);
+        assert_eq!(spends[0].1, CreditManager::event_index_cost());
+        let (balance, spent): (i64, i64) = sqlx::query_as(
+            "SELECT credit_balance, total_spent FROM user_credits WHERE user_id = $1",
+        )
+        .bind(job.user_id)
+        .fetch_one(&pool)
+        .await
+        .expect("Load credits");
+        assert_eq!((balance, spent), (950, 50));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_jobs_indexing_the_same_log_keep_their_own_rows() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let other = Address::repeat_byte(0xbb);
+        fx.chain.mine(vec![fx.transfer(), fx.transfer_from(other)]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+
+        // The second job covers the first job's log and one more.
+        let first = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        let mut params = fx.params(1, Some(to_block));
+        params.contract_addresses = vec![format!("{:?}", other)];
+        let second = fx.insert_job(&pool, params).await;
+        for job in [&first, &second] {
+            process_blockchain_index(&fx.services(), &pool, job)
+                .await
+                .expect("Indexing succeeds");
+        }
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 2);
+
+        for (job, root, count) in [(&first, &commits[0], 1), (&second, &commits[1], 2)] {
+            let roots: Vec<Option<String>> =
+                sqlx::query_scalar("SELECT merkle_root FROM blockchain_events WHERE job_id = $1")
+                    .bind(job.id)
+                    .fetch_all(&pool)
+                    .await
+                    .expect("Fetch events");
+            assert_eq!(roots, vec![Some(root.clone()); count]);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_jobs_indexing_identical_logs_share_one_commitment() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+
+        // Each job belongs to its own user, and both build the same tree.
+        let first = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        let second = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        for job in [&first, &second, &first] {
+            let result = process_blockchain_index(&fx.services(), &pool, job)
+                .await
+                .expect("Indexing succeeds");
+            assert!(matches!(result, IndexResult::Completed));
+        }
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 1);
+        let root = &commits[0];
+
+        for job in [&first, &second] {
+            let hashes: Vec<String> = sqlx::query_scalar(
+                "SELECT content_hash FROM blockchain_events
+                 WHERE job_id = $1 AND merkle_root = $2 ORDER BY block_number, log_index",
+            )
+            .bind(job.id)
+            .bind(root)
+            .fetch_all(&pool)
+            .await
+            .expect("Fetch events");
+            assert_eq!(hashes.len(), 2);
+            for hash in &hashes {
+                let proof = proofs::for_content_hash(&pool, hash, job.user_id)
+                    .await
+                    .expect("Owner gets a proof");
+                assert_eq!(&proof.root, root);
+            }
+            let bundles = proofs::bundles_for_job(&pool, job.id, job.user_id, None, 0)
+                .await
+                .expect("Owner gets bundles")
+                .bundles;
+            assert_eq!(bundles.len(), 2);
+            proofs::multiproof(&pool, root, &hashes, job.user_id)
+                .await
+                .expect("Owner gets a multiproof");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_event_hashes_are_canonical_and_versioned() {
+        use sqlx::Row;
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+
+        let rows = sqlx::query(
+            "SELECT id, chain, contract_address, transaction_hash, log_index, event_data, content_hash, hash_version
+             FROM blockchain_events WHERE job_id = $1 ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            let document = serde_json::json!({
+                "chain": row.get::<String, _>("chain"),
+                "contract_address": row.get::<String, _>("contract_address"),
+                "transaction_hash": row.get::<String, _>("transaction_hash"),
+                "log_index": row.get::<i64, _>("log_index"),
+                "data": row.get::<serde_json::Value, _>("event_data"),
+            });
+            let canonical = indexnode_core::to_canonical_json(&document);
+            assert_eq!(
+                row.get::<String, _>("content_hash"),
+                indexnode_core::hash_content(canonical.as_bytes())
+            );
+            assert_eq!(
+                row.get::<i16, _>("hash_version"),
+                indexnode_core::EVENT_HASH_VERSION as i16
+            );
+        }
+
+        // An event hashed before versioning keeps its hash when re-indexed,
+        // and later commitments cover that hash.
+        let legacy = indexnode_core::hash_content(
+            format!("{:?}", rows[0].get::<serde_json::Value, _>("event_data")).as_bytes(),
+        );
+        sqlx::query(
+            "UPDATE blockchain_events SET content_hash = $2, hash_version = 1 WHERE id = $1",
+        )
+        .bind(rows[0].get::<Uuid, _>("id"))
+        .bind(&legacy)
+        .execute(&pool)
+        .await
+        .expect("Downgrade event");
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Re-indexing succeeds");
+        let current = rows[1].get::<String, _>("content_hash");
+        assert_eq!(
+            fx.chain.commits().last(),
+            Some(&compute_versioned_root(&[legacy, current], MERKLE_TREE_VERSION).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_inclusion_proofs_verify_against_commitment() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        fx.chain.mine(vec![fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        let root = fx.chain.commits().pop().expect("Root committed");
+
+        let events: Vec<(Uuid, String)> = sqlx::query_as(
+            "SELECT id, content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+        let leaves: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM merkle_leaves WHERE merkle_root = $1 ORDER BY leaf_index",
+        )
+        .bind(&root)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch leaves");
+        assert_eq!(
+            leaves,
+            events.iter().map(|(_, h)| h.clone()).collect::<Vec<_>>()
+        );
+
+        for (i, (id, hash)) in events.iter().enumerate() {
+            let proof = proofs::for_event(&pool, *id, job.user_id)
+                .await
+                .expect("Event proof");
+            assert_eq!(proof.leaf, *hash);
+            assert_eq!((proof.leaf_index, proof.leaf_count), (i, 3));
+            assert_eq!(proof.root, root);
+            assert!(proof.transaction_hash.is_some() && proof.block_number.is_some());
+            assert!(indexnode_core::verify_versioned_proof(
+                &proof.leaf,
+                &proof.proof,
+                &proof.root,
+                proof.leaf_index,
+                proof.leaf_count,
+                proof.tree_version
+            ));
+
+            let by_hash = proofs::for_content_hash(&pool, &hash.to_uppercase(), job.user_id)
+                .await
+                .expect("Hash proof");
+            assert_eq!(by_hash.proof, proof.proof);
+
+            // Other users cannot prove the job's events.
+            let stranger = Uuid::new_v4();
+            assert!(matches!(
+                proofs::for_event(&pool, *id, stranger).await,
+                Err(proofs::ProofError::NotFound)
+            ));
+            assert!(matches!(
+                proofs::for_content_hash(&pool, hash, stranger).await,
+                Err(proofs::ProofError::NotFound)
+            ));
+        }
+        assert!(matches!(
+            proofs::for_content_hash(&pool, &"0".repeat(64), job.user_id).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_large_trees_are_proven_from_checkpoints() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let job = fx.insert_job(&pool, fx.params(1, None)).await;
+        let leaves: Vec<String> = (0..1000)
+            .map(|_| indexnode_core::hash_content(Uuid::new_v4().as_bytes()))
+            .collect();
+        let root = proofs::store_tree(&pool, job.id, &leaves, MERKLE_TREE_VERSION)
+            .await
+            .expect("Store tree");
+        assert_eq!(
+            root,
+            compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap()
+        );
+
+        // 63 nodes at level 4 and 4 at level 8.
+        let checkpoints: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM merkle_checkpoints WHERE merkle_root = $1")
+                .bind(&root)
+                .fetch_one(&pool)
+                .await
+                .expect("Count checkpoints");
+        assert_eq!(checkpoints, 67);
+
+        for i in [0, 1, 15, 16, 511, 992, 999] {
+            let proof = proofs::for_content_hash(&pool, &leaves[i], job.user_id)
+                .await
+                .expect("Checkpointed proof");
+            assert_eq!((proof.leaf_index, proof.leaf_count), (i, leaves.len()));
+            assert_eq!(
+                proof.proof,
+                indexnode_core::generate_versioned_proof(&leaves, i, MERKLE_TREE_VERSION).unwrap()
+            );
+        }
+
+        // A tree recorded before checkpoints were kept is proven from its leaves.
+        sqlx::query("DELETE FROM merkle_checkpoints WHERE merkle_root = $1 AND level = 8")
+            .bind(&root)
+            .execute(&pool)
+            .await
+            .expect("Drop checkpoints");
+        assert!(matches!(
+            proofs::for_content_hash(&pool, &leaves[0], job.user_id).await,
+            Err(proofs::ProofError::Proof(_))
+        ));
+        sqlx::query("UPDATE merkle_trees SET checkpointed = false WHERE merkle_root = $1")
+            .bind(&root)
+            .execute(&pool)
+            .await
+            .expect("Clear checkpoints");
+        let proof = proofs::for_content_hash(&pool, &leaves[0], job.user_id)
+            .await
+            .expect("Proof from leaves");
+        assert_eq!(
+            proof.proof,
+            indexnode_core::generate_versioned_proof(&leaves, 0, MERKLE_TREE_VERSION).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_mmr_proofs_are_rebuilt_from_checkpoints() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let job = fx.insert_job(&pool, fx.params(1, None)).await;
+        let leaves: Vec<String> = (0..300)
+            .map(|_| indexnode_core::hash_content(Uuid::new_v4().as_bytes()))
+            .collect();
+        for (batch, indexed_through) in [(&leaves[..200], 10), (&leaves[200..], 200)] {
+            mmr::append(&pool, job.id, batch)
+                .await
+                .expect("Append leaves");
+            mmr::commit_if_due(
+                Some(&fx.chain),
+                &pool,
+                job.id,
+                &fx.chain_name,
+                indexed_through,
+                100,
+            )
+            .await
+            .expect("Commit root")
+            .expect("Root is due");
+        }
+
+        // 18 nodes at level 4 and 1 at level 8.
+        let checkpoints: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM mmr_checkpoints WHERE job_id = $1")
+                .bind(job.id)
+                .fetch_one(&pool)
+                .await
+                .expect("Count checkpoints");
+        assert_eq!(checkpoints, 19);
+
+        let roots = mmr::roots(&pool, job.id, job.user_id).await.expect("Roots");
+        assert_eq!(roots.len(), 2);
+        for root in &roots {
+            let count = root.leaf_count as usize;
+            for i in [0, 15, 16, 255, 256, 271, count - 1]
+                .into_iter()
+                .filter(|&i| i < count)
+            {
+                let inclusion =
+                    mmr::inclusion(&pool, job.id, job.user_id, &leaves[i], Some(&root.root))
+                        .await
+                        .expect("Checkpointed proof");
+                assert_eq!(
+                    inclusion.proof,
+                    indexnode_core::generate_mmr_inclusion_proof(&leaves[..count], i as u64)
+                        .unwrap()
+                );
+            }
+        }
+        let consistency =
+            mmr::consistency(&pool, job.id, job.user_id, &roots[0].root, &roots[1].root)
+                .await
+                .expect("Checkpointed consistency proof");
+        assert_eq!(
+            consistency.proof,
+            indexnode_core::generate_mmr_consistency_proof(&leaves, 200).unwrap()
+        );
+
+        // An MMR started before checkpoints were kept is proven from its leaves.
+        sqlx::query("DELETE FROM mmr_checkpoints WHERE job_id = $1 AND level = 4")
+            .bind(job.id)
+            .execute(&pool)
+            .await
+            .expect("Drop checkpoints");
+        assert!(matches!(
+            mmr::inclusion(&pool, job.id, job.user_id, &leaves[0], None).await,
+            Err(proofs::ProofError::Proof(_))
+        ));
+        sqlx::query("UPDATE mmr_accumulators SET checkpointed = false WHERE job_id = $1")
+            .bind(job.id)
+            .execute(&pool)
+            .await
+            .expect("Clear checkpoints");
+        let inclusion = mmr::inclusion(&pool, job.id, job.user_id, &leaves[0], None)
+            .await
+            .expect("Proof from leaves");
+        assert_eq!(
+            inclusion.proof,
+            indexnode_core::generate_mmr_inclusion_proof(&leaves, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_proof_bundles_export_and_verify_offline() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        fx.chain.mine(vec![fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        let root = fx.chain.commits().pop().expect("Root committed");
+
+        let event_ids: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM blockchain_events WHERE job_id = $1 ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+        let bundle = proofs::bundle_for_event(&pool, event_ids[0], job.user_id)
+            .await
+            .expect("Event bundle");
+        assert!(matches!(
+            proofs::bundle_for_event(&pool, event_ids[0], Uuid::new_v4()).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+        bundle.verify().expect("Bundle verifies offline");
+        assert_eq!(bundle.root, root);
+        assert_eq!(bundle.chain_id, MOCK_CHAIN_ID);
+        assert_eq!(
+            bundle.registry_address,
+            format!("{:?}", MOCK_REGISTRY_ADDRESS)
+        );
+        assert!(bundle.ipfs_cid.is_some());
+        let decoded = ProofBundle::from_cbor(&bundle.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, bundle);
+
+        let all = proofs::bundles_for_job(&pool, job.id, job.user_id, None, 0)
+            .await
+            .expect("Job bundles")
+            .bundles;
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0], bundle);
+        for bundle in &all {
+            bundle.verify().expect("Job bundle verifies offline");
+        }
+        let page = proofs::bundles_for_job(&pool, job.id, job.user_id, Some(1), 1)
+            .await
+            .expect("Page of bundles")
+            .bundles;
+        assert_eq!(page, vec![all[1].clone()]);
+        assert!(matches!(
+            proofs::bundles_for_job(&pool, job.id, Uuid::new_v4(), None, 0).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+
+        // An orphaned event, or one no tree has a leaf for, is skipped and
+        // reported without failing the rest of the export.
+        sqlx::query("UPDATE blockchain_events SET orphaned = true WHERE id = $1")
+            .bind(event_ids[1])
+            .execute(&pool)
+            .await
+            .expect("Orphan event");
+        sqlx::query("UPDATE blockchain_events SET content_hash = $2 WHERE id = $1")
+            .bind(event_ids[2])
+            .bind(format!("0x{}", "00".repeat(32)))
+            .execute(&pool)
+            .await
+            .expect("Unknown leaf");
+        let partial = proofs::bundles_for_job(&pool, job.id, job.user_id, None, 0)
+            .await
+            .expect("Job bundles");
+        assert_eq!(partial.bundles, vec![all[0].clone()]);
+        assert_eq!(
+            partial
+                .skipped
+                .iter()
+                .map(|s| s.event_id)
+                .collect::<Vec<_>>(),
+            event_ids[1..].to_vec()
+        );
+        let multi = proofs::multiproof_bundles_for_job(&pool, job.id, job.user_id, None, 0)
+            .await
+            .expect("Multiproof bundles");
+        assert_eq!(multi.bundles.len(), 1);
+        assert_eq!(multi.bundles[0].records.len(), 1);
+        assert_eq!(multi.skipped.len(), 2);
+
+        // Commitments made before registries were recorded cannot be bundled.
+        sqlx::query(
+            "UPDATE timestamp_commits SET registry_chain_id = NULL WHERE content_hash = $1",
+        )
+        .bind(&root)
+        .execute(&pool)
+        .await
+        .expect("Clear registry");
+        assert!(matches!(
+            proofs::bundle_for_event(&pool, event_ids[0], job.user_id).await,
+            Err(proofs::ProofError::NotCommitted)
+        ));
+        let uncommitted = proofs::bundles_for_job(&pool, job.id, job.user_id, None, 0)
+            .await
+            .expect("Job bundles");
+        assert!(uncommitted.bundles.is_empty() && uncommitted.skipped.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_multiproofs_cover_many_events() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain
+            .mine(vec![fx.transfer(), fx.transfer(), fx.transfer()]);
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        let root = fx.chain.commits().pop().expect("Root committed");
+        let hashes: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+
+        let requested = vec![
+            hashes[4].clone(),
+            hashes[1].to_uppercase(),
+            hashes[2].clone(),
+            hashes[4].clone(),
+        ];
+        let proof = proofs::multiproof(&pool, &root, &requested, job.user_id)
+            .await
+            .expect("Multiproof");
+        assert_eq!(proof.multiproof.leaf_indices, vec![1, 2, 4]);
+        assert_eq!(
+            proof.multiproof.leaves,
+            vec![hashes[1].clone(), hashes[2].clone(), hashes[4].clone()]
+        );
+        assert_eq!(proof.registry_chain_id, Some(MOCK_CHAIN_ID as i64));
+        assert!(verify_multiproof(&proof.multiproof, &root));
+        assert!(matches!(
+            proofs::multiproof(&pool, &root, &["0".repeat(64)], job.user_id).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+        assert!(matches!(
+            proofs::multiproof(&pool, &"0".repeat(64), &hashes, job.user_id).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+        // Other users cannot prove leaves of the job's tree.
+        assert!(matches!(
+            proofs::multiproof(&pool, &root, &hashes, Uuid::new_v4()).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+
+        let bundles = proofs::multiproof_bundles_for_job(&pool, job.id, job.user_id, None, 0)
+            .await
+            .expect("Multiproof bundles")
+            .bundles;
+        assert_eq!(bundles.len(), 1);
+        assert_eq!(bundles[0].root, root);
+        assert_eq!(bundles[0].records.len(), hashes.len());
+        bundles[0].verify().expect("Bundle verifies offline");
+        let decoded = MultiproofBundle::from_cbor(&bundles[0].to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, bundles[0]);
+
+        let page = proofs::multiproof_bundles_for_job(&pool, job.id, job.user_id, Some(2), 1)
+            .await
+            .expect("Page of bundles")
+            .bundles;
+        assert_eq!(
+            page[0]
+                .records
+                .iter()
+                .map(|r| r.content_hash.clone())
+                .collect::<Vec<_>>(),
+            hashes[1..3].to_vec()
+        );
+        page[0].verify().expect("Page verifies offline");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_keccak_commitment_verifies_like_openzeppelin() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain
+            .mine(vec![fx.transfer(), fx.transfer(), fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let mut params = fx.params(1, Some(to_block));
+        params.merkle_mode = MerkleMode::Keccak256;
+        let job = fx.insert_job(&pool, params).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+
+        let hashes: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch hashes");
+        let root = compute_versioned_root(&hashes, KECCAK_MERKLE_TREE_VERSION).unwrap();
+        assert_eq!(fx.chain.commits(), vec![root.clone()]);
+        let tree_version: i16 = sqlx::query_scalar(
+            "SELECT tree_version FROM timestamp_commits WHERE content_hash = $1",
+        )
+        .bind(&root)
+        .fetch_one(&pool)
+        .await
+        .expect("Load commitment");
+        assert_eq!(tree_version, KECCAK_MERKLE_TREE_VERSION as i16);
+
+        for (i, hash) in hashes.iter().enumerate() {
+            let proof =
+                indexnode_core::generate_versioned_proof(&hashes, i, KECCAK_MERKLE_TREE_VERSION)
+                    .unwrap();
+            assert!(indexnode_core::verify_versioned_proof(
+                hash,
+                &proof,
+                &root,
+                i,
+                hashes.len(),
+                KECCAK_MERKLE_TREE_VERSION
+            ));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_sharded_backfill_merges_in_block_order() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        for _ in 0..5 {
+            fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        }
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let mut params = fx.params(1, Some(to_block));
+        params.shard_blocks = Some(2);
+        let job = fx.insert_job(&pool, params).await;
+
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+
+        let shards: Vec<(String, Option<i64>)> = sqlx::query_as(
+            "SELECT status, events_found FROM job_shards WHERE job_id = $1 ORDER BY shard_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch shards");
+        assert_eq!(shards.len(), 3);
+        assert!(shards.iter().all(|(status, _)| status == "completed"));
+        assert_eq!(shards.iter().filter_map(|(_, n)| *n).sum::<i64>(), 10);
+
+        let hashes: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch hashes");
+        assert_eq!(hashes.len(), 10);
+        assert_eq!(
+            fx.chain.commits(),
+            vec![compute_versioned_root(&hashes, MERKLE_TREE_VERSION).unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_rerun_backfill_resumes_from_completed_shards() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        for _ in 0..5 {
+            fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        }
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let mut params = fx.params(1, Some(to_block));
+        params.shard_blocks = Some(2);
+        let job = fx.insert_job(&pool, params).await;
+
+        // The shard over blocks 3-4 fails; shards still in flight are
+        // cancelled.
+        fx.chain.fail_next_log_fetch_of(3);
+        assert!(process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .is_err());
+        let shards = || async {
+            sqlx::query_as::<_, (i64, i64, String, Option<chrono::DateTime<chrono::Utc>>)>(
+                "SELECT from_block, to_block, status, completed_at FROM job_shards
+                 WHERE job_id = $1 ORDER BY shard_index",
+            )
+            .bind(job.id)
+            .fetch_all(&pool)
+            .await
+            .expect("Fetch shards")
+        };
+        let first = shards().await;
+        assert_eq!(first.len(), 3);
+        assert_eq!(first[1].2, "failed");
+        let completed: Vec<(u64, u64)> = first
+            .iter()
+            .filter(|(_, _, status, _)| status == "completed")
+            .map(|(from, to, _, _)| (*from as u64, *to as u64))
+            .collect();
+
+        // The re-run fetches the failed shard again, and no completed one. A
+        // cancelled shard whose write was already sent may complete late.
+        let fetched_before = fx.chain.log_fetches().len();
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Re-run succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+        let refetched = fx.chain.log_fetches().split_off(fetched_before);
+        assert!(refetched.contains(&(3, 4)));
+        assert!(refetched.iter().all(|range| !completed.contains(range)));
+        let second = shards().await;
+        assert!(second.iter().all(|(_, _, status, _)| status == "completed"));
+        for (before, after) in first.iter().zip(&second) {
+            if before.2 == "completed" {
+                assert_eq!(before.3, after.3, "A completed shard is reused");
+            }
+        }
+        let staged: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM job_shards WHERE job_id = $1 AND events IS NOT NULL",
+        )
+        .bind(job.id)
+        .fetch_one(&pool)
+        .await
+        .expect("Count staged shards");
+        assert_eq!(staged, 0);
+
+        let hashes: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch hashes");
+        assert_eq!(hashes.len(), 10);
+        assert_eq!(
+            fx.chain.commits(),
+            vec![compute_versioned_root(&hashes, MERKLE_TREE_VERSION).unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_erc20_transfers_are_enriched() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let returns = |tokens: &[ethers::abi::Token]| ethers::abi::encode(tokens);
+        fx.chain.set_call_result(
+            fx.token,
+            ethers::utils::id("decimals()").to_vec(),
+            returns(&[ethers::abi::Token::Uint(U256::from(6))]),
+        );
+        fx.chain.set_call_result(
+            fx.token,
+            ethers::utils::id("symbol()").to_vec(),
+            returns(&[ethers::abi::Token::String("USDC".to_string())]),
+        );
+        let amount = returns(&[ethers::abi::Token::Uint(U256::from(2_500_000))]);
+        fx.chain.mine(vec![MockLog::event(
+            fx.token,
+            TRANSFER,
+            &[H256::zero(), H256::from(Address::repeat_byte(2))],
+            amount,
+        )]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+
+        use sqlx::Row;
+        let row = sqlx::query(
+            "SELECT be.token_standard, be.token_amount_raw::TEXT AS raw, be.token_amount::TEXT AS amount, m.symbol
+             FROM blockchain_events be
+             JOIN token_metadata m ON m.chain = be.chain AND m.contract_address = be.contract_address
+             WHERE be.job_id = $1",
+        )
+        .bind(job.id)
+        .fetch_one(&pool)
+        .await
+        .expect("Load enriched event");
+        assert_eq!(row.get::<String, _>("token_standard"), "erc20");
+        assert_eq!(row.get::<String, _>("raw"), "2500000");
+        assert_eq!(row.get::<String, _>("amount"), "2.5");
+        assert_eq!(row.get::<String, _>("symbol"), "USDC");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_token_projections_follow_reorgs() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.set_call_result(
+            fx.token,
+            ethers::utils::id("decimals()").to_vec(),
+            ethers::abi::encode(&[ethers::abi::Token::Uint(U256::from(18))]),
+        );
+        let (alice, bob) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let transfer = |from: Address, to: Address, amount: u64| {
+            MockLog::event(
+                fx.token,
+                TRANSFER,
+                &[H256::from(from), H256::from(to)],
+                ethers::abi::encode(&[ethers::abi::Token::Uint(U256::from(amount))]),
+            )
+        };
+        fx.chain.mine(vec![transfer(Address::zero(), alice, 100)]);
+        fx.chain.mine(vec![
+            transfer(alice, bob, 30),
+            transfer(bob, Address::zero(), 5),
+        ]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let mut params = fx.params(1, Some(to_block));
+        params.projections = vec![Projection::Balances, Projection::Supply];
+        let job = fx.insert_job(&pool, params).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+
+        let balances = || async {
+            sqlx::query_as::<_, (String, String)>(
+                "SELECT holder, balance::TEXT FROM token_balances WHERE job_id = $1 ORDER BY holder",
+            )
+            .bind(job.id)
+            .fetch_all(&pool)
+            .await
+            .expect("Fetch balances")
+        };
+        let supply = || async {
+            sqlx::query_as::<_, (i64, String, String)>(
+                "SELECT block_number, minted::TEXT, burned::TEXT FROM token_supply_changes
+                 WHERE job_id = $1 ORDER BY block_number",
+            )
+            .bind(job.id)
+            .fetch_all(&pool)
+            .await
+            .expect("Fetch supply")
+        };
+        let (alice, bob) = (format!("{:?}", alice), format!("{:?}", bob));
+        assert_eq!(
+            balances().await,
+            vec![(alice.clone(), "70".to_string()), (bob, "25".to_string())]
+        );
+        assert_eq!(
+            supply().await,
+            vec![
+                (1, "100".to_string(), "0".to_string()),
+                (2, "0".to_string(), "5".to_string())
+            ]
+        );
+
+        // Orphaning block 2 rolls back its transfer and burn.
+        let block_hash: String = sqlx::query_scalar(
+            "SELECT block_hash FROM blockchain_events WHERE job_id = $1 AND block_number = 2 LIMIT 1",
+        )
+        .bind(job.id)
+        .fetch_one(&pool)
+        .await
+        .expect("Load block hash");
+        mark_orphaned(&pool, &fx.chain_name, 2, &block_hash)
+            .await
+            .expect("Events are orphaned");
+        assert_eq!(balances().await, vec![(alice, "100".to_string())]);
+        assert_eq!(
+            supply().await,
+            vec![(1, "100".to_string(), "0".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_factory_children_are_indexed_and_committed() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let factory = Address::repeat_byte(0xfa);
+        let child = Address::repeat_byte(0xc1);
+        // The child's transfer before its creation block is not indexed.
+        fx.chain.mine(vec![fx.transfer_from(child)]);
+        let mut created = [0u8; 32];
+        created[12..].copy_from_slice(child.as_bytes());
+        fx.chain.mine(vec![MockLog::event(
+            factory,
+            "Deployed(address)",
+            &[],
+            created.to_vec(),
+        )]);
+        fx.chain
+            .mine(vec![fx.transfer_from(child), fx.transfer_from(child)]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+
+        let mut params = fx.params(1, Some(to_block));
+        params.contract_address = format!("{:?}", factory);
+        params.events = Vec::new();
+        params.factory = Some(indexnode_core::FactoryConfig {
+            creation_event: "event Deployed(address child)".to_string(),
+            child_address_param: "child".to_string(),
+            child_events: vec![TRANSFER.to_string()],
+        });
+        let job = fx.insert_job(&pool, params).await;
+
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+
+        let child_events: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM blockchain_events WHERE job_id = $1 AND contract_address = $2",
+        )
+        .bind(job.id)
+        .bind(format!("{:?}", child))
+        .fetch_one(&pool)
+        .await
+        .expect("Count child events");
+        assert_eq!(child_events, 2);
+
+        let (creation_block, merkle_root): (i64, Option<String>) = sqlx::query_as(
+            "SELECT creation_block, merkle_root FROM job_child_contracts WHERE job_id = $1",
+        )
+        .bind(job.id)
+        .fetch_one(&pool)
+        .await
+        .expect("Child recorded");
+        assert_eq!(creation_block, 2);
+        assert_eq!(merkle_root, fx.chain.commits().first().cloned());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_factory_children_found_while_tailing_join_the_mmr() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let factory = Address::repeat_byte(0xfa);
+        let child = Address::repeat_byte(0xc2);
+        fx.chain.mine_empty(1 + CONFIRMATIONS);
+
+        let mut params = fx.params(1, None);
+        params.contract_address = format!("{:?}", factory);
+        params.events = Vec::new();
+        params.live = true;
+        params.mmr_commit_blocks = Some(1);
+        params.factory = Some(indexnode_core::FactoryConfig {
+            creation_event: "event Deployed(address child)".to_string(),
+            child_address_param: "child".to_string(),
+            child_events: vec![TRANSFER.to_string()],
+        });
+        let job = fx.insert_job(&pool, params).await;
+        let svc = fx.services();
+        process_blockchain_index(&svc, &pool, &job)
+            .await
+            .expect("Backfill succeeds");
+        sqlx::query("UPDATE jobs SET status = 'live' WHERE id = $1")
+            .bind(job.id)
+            .execute(&pool)
+            .await
+            .expect("Mark job live");
+
+        // The factory deploys a child while the job follows the head.
+        let mut created = [0u8; 32];
+        created[12..].copy_from_slice(child.as_bytes());
+        fx.chain.mine(vec![MockLog::event(
+            factory,
+            "Deployed(address)",
+            &[],
+            created.to_vec(),
+        )]);
+        fx.chain.mine(vec![fx.transfer_from(child)]);
+        fx.chain.mine_empty(CONFIRMATIONS);
+        tail_live_jobs(&svc, &pool, &HashMap::new())
+            .await
+            .expect("Tail succeeds");
+
+        let child_hash: String =
+            sqlx::query_scalar("SELECT content_hash FROM job_child_contracts WHERE job_id = $1")
+                .bind(job.id)
+                .fetch_one(&pool)
+                .await
+                .expect("Child recorded");
+        // The creation event and the child's transfer, then the child.
+        let mut leaves: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+        assert_eq!(leaves.len(), 2);
+        leaves.push(child_hash);
+        let root = mmr::roots(&pool, job.id, job.user_id)
+            .await
+            .expect("List roots")
+            .pop()
+            .expect("Root committed");
+        assert_eq!(root.leaf_count, 3);
+        for (i, hash) in leaves.iter().enumerate() {
+            let proof = mmr::inclusion(&pool, job.id, job.user_id, hash, None)
+                .await
+                .expect("Proof against the latest root");
+            assert_eq!(proof.proof.leaf_index, i as u64);
+            assert!(verify_mmr_inclusion_proof(hash, &proof.proof, &root.root));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_topic_filter_across_contracts() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let other_token = Address::repeat_byte(0x0b);
+        let recipient = Address::repeat_byte(0x42);
+        let to_recipient = |token| {
+            MockLog::event(
+                token,
+                TRANSFER,
+                &[H256::from(Address::repeat_byte(1)), H256::from(recipient)],
+                vec![0u8; 32],
+            )
+        };
+        fx.chain.mine(vec![fx.transfer(), to_recipient(fx.token)]);
+        fx.chain.mine(vec![
+            to_recipient(other_token),
+            fx.transfer_from(other_token),
+        ]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+
+        let mut params = fx.params(1, Some(to_block));
+        params.contract_addresses = vec![format!("{:?}", other_token)];
+        params.events = vec![
+            "event Transfer(address indexed from, address indexed to, uint256 value)".to_string(),
+        ];
+        params.topic_filters = vec![indexnode_core::TopicFilter {
+            param: "to".to_string(),
+            values: vec![format!("{:?}", recipient)],
+        }];
+        let job = fx.insert_job(&pool, params).await;
+
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+
+        let mut contracts: Vec<String> =
+            sqlx::query_scalar("SELECT contract_address FROM blockchain_events WHERE job_id = $1")
+                .bind(job.id)
+                .fetch_all(&pool)
+                .await
+                .expect("Load events");
+        contracts.sort();
+        let mut expected = vec![format!("{:?}", fx.token), format!("{:?}", other_token)];
+        expected.sort();
+        assert_eq!(contracts, expected);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_transaction_index_and_commit() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let sender = Address::repeat_byte(0x5e);
+        let mut transfer = hex::decode("a9059cbb").unwrap();
+        transfer.extend(ethers::abi::encode(&[
+            ethers::abi::Token::Address(Address::repeat_byte(2)),
+            ethers::abi::Token::Uint(U256::from(9)),
+        ]));
+        fx.chain.mine_transactions(vec![
+            MockTransaction::call(sender, fx.token, transfer),
+            MockTransaction::call(Address::repeat_byte(7), Address::repeat_byte(8), Vec::new()),
+        ]);
+        fx.chain.mine_transactions(vec![MockTransaction {
+            from: sender,
+            to: None,
+            ..Default::default()
+        }]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+
+        let job = fx
+            .insert_config(
+                &pool,
+                JobConfig {
+                    job_type: indexnode_core::JobType::TransactionIndex,
+                    params: JobParams::TransactionIndex(indexnode_core::TransactionIndexParams {
+                        chain: fx.chain_name.clone(),
+                        addresses: vec![format!("{:?}", sender)],
+                        abi: vec!["function transfer(address to, uint256 amount)".to_string()],
+                        from_block: 1,
+                        to_block,
+                        include_traces: true,
+                        merkle_mode: MerkleMode::default(),
+                    }),
+                },
+            )
+            .await;
+
+        let result = process_transaction_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 1);
+        let rows = sqlx::query(
+            "SELECT function_name, contract_address, merkle_root, trace
+             FROM indexed_transactions WHERE job_id = $1 ORDER BY block_number",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Load transactions");
+        use sqlx::Row;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].get::<Option<String>, _>("function_name").as_deref(),
+            Some("transfer")
+        );
+        assert!(rows[1]
+            .get::<Option<String>, _>("contract_address")
+            .is_some());
+        for row in &rows {
+            assert_eq!(
+                row.get::<Option<String>, _>("merkle_root"),
+                Some(commits[0].clone())
+            );
+            assert!(row.get::<Option<serde_json::Value>, _>("trace").is_some());
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_state_snapshot_and_commit() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let call = indexnode_core::ViewCall {
+            contract: format!("{:?}", fx.token),
+            function: "function totalSupply() view returns (uint256)".to_string(),
+            args: Vec::new(),
+        };
+        let calldata = indexnode_core::PreparedCall::new(&call)
+            .unwrap()
+            .calldata()
+            .to_vec();
+        let supply = |n: u64| ethers::abi::encode(&[ethers::abi::Token::Uint(U256::from(n))]);
+        fx.chain.mine_empty(1);
+        fx.chain
+            .set_call_result(fx.token, calldata.clone(), supply(100));
+        fx.chain.mine_empty(1);
+        fx.chain.set_call_result(fx.token, calldata, supply(250));
+        fx.chain.mine_empty(CONFIRMATIONS);
+
+        let job = fx
+            .insert_config(
+                &pool,
+                JobConfig {
+                    job_type: indexnode_core::JobType::StateSnapshot,
+                    params: JobParams::StateSnapshot(indexnode_core::StateSnapshotParams {
+                        chain: fx.chain_name.clone(),
+                        calls: vec![call],
+                        blocks: vec![2, 1],
+                        merkle_mode: MerkleMode::default(),
+                    }),
+                },
+            )
+            .await;
+
+        let result = process_state_snapshot(&fx.services(), &pool, &job)
+            .await
+            .expect("Snapshot succeeds");
+        assert!(matches!(result, IndexResult::Completed));
+
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 1);
+        let rows = sqlx::query(
+            "SELECT block_number, result, merkle_root
+             FROM state_snapshots WHERE job_id = $1 ORDER BY block_number",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Load snapshot");
+        use sqlx::Row;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get::<i64, _>("block_number"), 1);
+        assert_eq!(
+            rows[0]
+                .get::<Option<serde_json::Value>, _>("result")
+                .unwrap()["arg0"],
+            "100"
+        );
+        assert_eq!(
+            rows[1]
+                .get::<Option<serde_json::Value>, _>("result")
+                .unwrap()["arg0"],
+            "250"
+        );
+        for row in &rows {
+            assert_eq!(
+                row.get::<Option<String>, _>("merkle_root"),
+                Some(commits[0].clone())
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_crawled_links_are_committed_as_one_tree() {
+        use sqlx::Row;
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let config = JobConfig {
+            job_type: indexnode_core::JobType::HttpCrawl,
+            params: JobParams::HttpCrawl(indexnode_core::HttpCrawlParams {
+                url: "https://example.com/".to_string(),
+                max_pages: 10,
+            }),
+        };
+        let job = fx.insert_config(&pool, config.clone()).await;
+        let page = CrawlResult::new(
+            "https://example.com/",
+            200,
+            Uuid::new_v4().as_bytes(),
+            vec![
+                "https://example.com/about".to_string(),
+                "https://example.com/blog".to_string(),
+                "https://example.com/about".to_string(),
+            ],
+            Utc::now(),
+        );
+
+        let result = store_crawled_page(&fx.services(), &pool, job.id, &page)
+            .await
+            .expect("Page is stored");
+        assert!(matches!(result, IndexResult::Completed));
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 1);
+        let root = &commits[0];
+        let chain: String =
+            sqlx::query_scalar("SELECT chain FROM timestamp_commits WHERE content_hash = $1")
+                .bind(root)
+                .fetch_one(&pool)
+                .await
+                .expect("Load commitment");
+        assert_eq!(chain, fx.chain_name);
+
+        // One row per link, in page order, each the hash of its canonical
+        // document and stamped with the job's root.
+        let rows = sqlx::query(
+            "SELECT id, url, status_code, content_hash, merkle_root FROM crawl_results
+             WHERE job_id = $1 ORDER BY content_hash",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Load crawl results");
+        assert_eq!(rows.len(), 3);
+        let documents = page.link_documents();
+        let leaves: Vec<String> = documents
+            .iter()
+            .map(|document| hash_event_document(document, EVENT_HASH_VERSION).unwrap())
+            .collect();
+        let mut sorted = leaves.clone();
+        sorted.sort();
+        for (row, hash) in rows.iter().zip(&sorted) {
+            assert_eq!(&row.get::<String, _>("content_hash"), hash);
+            assert_eq!(row.get::<i32, _>("status_code"), 200);
+            assert_eq!(
+                row.get::<Option<String>, _>("merkle_root").as_ref(),
+                Some(root)
+            );
+        }
+        assert_eq!(
+            &compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            root
+        );
+
+        let proof = proofs::for_content_hash(&pool, &leaves[1], job.user_id)
+            .await
+            .expect("Link proof");
+        assert_eq!(&proof.root, root);
+        assert!(indexnode_core::verify_versioned_proof(
+            &proof.leaf,
+            &proof.proof,
+            &proof.root,
+            proof.leaf_index,
+            proof.leaf_count,
+            proof.tree_version
+        ));
+        assert!(matches!(
+            proofs::for_content_hash(&pool, &leaves[1], Uuid::new_v4()).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+
+        // A link's bundle rebuilds its document from the stored row.
+        let blog = rows
+            .iter()
+            .find(|row| row.get::<String, _>("url") == "https://example.com/blog")
+            .expect("Blog link stored");
+        let bundle = proofs::bundle_for_crawl_result(&pool, blog.get("id"), job.user_id)
+            .await
+            .expect("Link bundle");
+        bundle.verify().expect("Link bundle verifies offline");
+        assert_eq!(bundle.record, documents[1]);
+        assert_eq!(&bundle.root, root);
+        assert!(bundle.ipfs_cid.is_none());
+        let decoded = ProofBundle::from_cbor(&bundle.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, bundle);
+        assert!(matches!(
+            proofs::bundle_for_crawl_result(&pool, blog.get("id"), Uuid::new_v4()).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+
+        // A page without links stores nothing and commits nothing.
+        let empty = fx.insert_config(&pool, config.clone()).await;
+        let page = CrawlResult::new(
+            "https://example.com/",
+            200,
+            Uuid::new_v4().as_bytes(),
+            Vec::new(),
+            Utc::now(),
+        );
+        let result = store_crawled_page(&fx.services(), &pool, empty.id, &page)
+            .await
+            .expect("Page is stored");
+        assert!(matches!(result, IndexResult::Completed));
+        assert_eq!(fx.chain.commits().len(), 1);
+
+        // A failed commit is retried and stamps the links once it lands.
+        let job = fx.insert_config(&pool, config).await;
+        let page = CrawlResult::new(
+            "https://example.com/",
+            200,
+            Uuid::new_v4().as_bytes(),
+            vec!["https://example.com/contact".to_string()],
+            Utc::now(),
+        );
+        fx.chain.fail_next_commits(1);
+        let result = store_crawled_page(&fx.services(), &pool, job.id, &page)
+            .await
+            .expect("Page is stored");
+        assert!(matches!(result, IndexResult::PendingCommit));
+        retry_pending_commits(Some(&fx.chain), &pool)
+            .await
+            .expect("Retry runs");
+        let stamped: Option<String> =
+            sqlx::query_scalar("SELECT merkle_root FROM crawl_results WHERE job_id = $1")
+                .bind(job.id)
+                .fetch_one(&pool)
+                .await
+                .expect("Load crawl result");
+        assert_eq!(stamped, fx.chain.commits().pop());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_abi_registry_is_scoped_to_owner() {
+        let pool = test_pool().await;
+        let mut users = Vec::new();
+        for _ in 0..2 {
+            let user_id = Uuid::new_v4();
+            sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+                .bind(user_id)
+                .bind(format!("{}@example.com", user_id))
+                .execute(&pool)
+                .await
+                .expect("Insert user");
+            users.push(user_id);
+        }
+        let definition = |name: Option<&str>| abi_registry::AbiDefinition {
+            name: name.map(str::to_string),
+            chain: Some("ethereum".to_string()),
+            contract_address: Some("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".to_string()),
+            abi: serde_json::json!([{"type": "event", "name": "Transfer", "anonymous": false,
+                "inputs": [{"name": "to", "type": "address", "indexed": true}]}]),
+        };
+
+        let stored = abi_registry::create(&pool, users[0], definition(Some("usdc")))
+            .await
+            .expect("ABI is registered");
+        assert_eq!(
+            stored.contract_address.as_deref(),
+            Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")
+        );
+        assert!(matches!(
+            abi_registry::create(&pool, users[0], definition(None)).await,
+            Err(abi_registry::AbiRegistryError::Conflict)
+        ));
+        abi_registry::create(&pool, users[1], definition(Some("usdc")))
+            .await
+            .expect("Names are per user");
+
+        let found = abi_registry::find_for_contract(
+            &pool,
+            users[0],
+            None,
+            "ethereum",
+            "0xA0B86991C6218B36C1D19D4A2E9EB0CE3606EB48",
+        )
+        .await
+        .expect("Lookup succeeds")
+        .expect("ABI is found by contract");
+        assert!(found.resolve_event("Transfer").is_ok());
+        assert!(matches!(
+            abi_registry::get(&pool, users[1], stored.id).await,
+            Err(abi_registry::AbiRegistryError::NotFound)
+        ));
+        assert!(matches!(
+            abi_registry::delete(&pool, users[1], stored.id).await,
+            Err(abi_registry::AbiRegistryError::NotFound)
+        ));
+        abi_registry::delete(&pool, users[0], stored.id)
+            .await
+            .expect("Owner deletes the ABI");
+        assert!(abi_registry::find_by_name(&pool, users[0], "usdc")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_unfinalized_range_is_deferred() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer()]);
+        let job = fx
+            .insert_job(&pool, fx.params(1, Some(fx.chain.head())))
+            .await;
+
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Deferral is not an error");
+        assert!(matches!(result, IndexResult::Deferred { .. }));
+        assert!(fx.chain.credit_spends().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_failed_commit_is_retried() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+
+        fx.chain.fail_next_commits(1);
+        let result = process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+        assert!(matches!(result, IndexResult::PendingCommit));
+        assert!(fx.chain.commits().is_empty());
+        sqlx::query("UPDATE jobs SET status = 'pending_commit' WHERE id = $1")
+            .bind(job.id)
+            .execute(&pool)
+            .await
+            .expect("Update job status");
+
+        retry_pending_commits(Some(&fx.chain), &pool)
+            .await
+            .expect("Retry runs");
+
+        let commits = fx.chain.commits();
+        assert_eq!(commits.len(), 1);
+        let tree_version: i16 = sqlx::query_scalar(
+            "SELECT tree_version FROM timestamp_commits WHERE content_hash = $1",
+        )
+        .bind(&commits[0])
+        .fetch_one(&pool)
+        .await
+        .expect("Load commitment");
+        assert_eq!(tree_version, MERKLE_TREE_VERSION as i16);
+        let status: String = sqlx::query_scalar("SELECT status FROM jobs WHERE id = $1")
+            .bind(job.id)
+            .fetch_one(&pool)
+            .await
+            .expect("Load job status");
+        assert_eq!(status, "completed");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_retried_commit_stamps_only_its_tree() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let mut params = fx.params(1, None);
+        params.live = true;
+        params.mmr_commit_blocks = Some(1_000);
+        let job = fx.insert_job(&pool, params).await;
+        let svc = fx.services();
+
+        // The backfill commit fails, and the job tails on while it waits.
+        fx.chain.fail_next_commits(1);
+        let result = process_blockchain_index(&svc, &pool, &job)
+            .await
+            .expect("Backfill succeeds");
+        assert!(matches!(result, IndexResult::Live));
+        sqlx::query("UPDATE jobs SET status = 'live' WHERE id = $1")
+            .bind(job.id)
+            .execute(&pool)
+            .await
+            .expect("Mark job live");
+        fx.chain.mine(vec![fx.transfer()]);
+        fx.chain.mine_empty(CONFIRMATIONS);
+        tail_live_jobs(&svc, &pool, &HashMap::new())
+            .await
+            .expect("Tail succeeds");
+
+        retry_pending_commits(Some(&fx.chain), &pool)
+            .await
+            .expect("Retry runs");
+        let root = fx.chain.commits().pop().expect("Backfill root committed");
+        let stamps: Vec<Option<String>> = sqlx::query_scalar(
+            "SELECT merkle_root FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+        assert_eq!(stamps, vec![Some(root.clone()), Some(root), None]);
+
+        let bundles = proofs::bundles_for_job(&pool, job.id, job.user_id, None, 0)
+            .await
+            .expect("Job bundles")
+            .bundles;
+        assert_eq!(bundles.len(), 2);
+        for bundle in &bundles {
+            bundle.verify().expect("Bundle verifies offline");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_reorg_orphans_events() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer()]);
+        let to_block = fx.chain.head();
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let job = fx.insert_job(&pool, fx.params(1, Some(to_block))).await;
+        process_blockchain_index(&fx.services(), &pool, &job)
+            .await
+            .expect("Indexing succeeds");
+
+        // Replace the indexed block and everything after it. Another chain's
+        // RPC is down, which must not hold up this chain's check.
+        fx.chain.reorg(CONFIRMATIONS + 1);
+        fx.chain.mine_empty(CONFIRMATIONS + 1);
+        let unreachable = MockChain::new(CONFIRMATIONS);
+        unreachable.fail_next_head_fetches(u32::MAX);
+        let mut clients: ChainClients = HashMap::new();
+        clients.insert("unreachable".to_string(), Box::new(unreachable));
+        clients.insert(fx.chain_name.clone(), Box::new(fx.chain.clone()));
+        detect_reorgs(&clients, &pool)
+            .await
+            .expect("Reorg check runs");
+
+        let orphaned: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM blockchain_events WHERE job_id = $1 AND orphaned",
+        )
+        .bind(job.id)
+        .fetch_one(&pool)
+        .await
+        .expect("Count orphaned events");
+        assert_eq!(orphaned, 1);
+        let reorg_affected: bool =
+            sqlx::query_scalar("SELECT reorg_affected FROM timestamp_commits WHERE job_id = $1")
+                .bind(job.id)
+                .fetch_one(&pool)
+                .await
+                .expect("Load commit");
+        assert!(reorg_affected);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_live_job_failure_does_not_stop_others() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        let other = Address::repeat_byte(0xbb);
+        fx.chain.mine(vec![fx.transfer(), fx.transfer_from(other)]);
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let svc = fx.services();
+        let mut jobs = Vec::new();
+        for token in [fx.token, other] {
+            let mut params = fx.params(1, None);
+            params.contract_address = format!("{:?}", token);
+            params.live = true;
+            let job = fx.insert_job(&pool, params).await;
+            process_blockchain_index(&svc, &pool, &job)
+                .await
+                .expect("Backfill succeeds");
+            sqlx::query("UPDATE jobs SET status = 'live' WHERE id = $1")
+                .bind(job.id)
+                .execute(&pool)
+                .await
+                .expect("Mark job live");
+            jobs.push(job);
+        }
+        let cursor = |job_id: Uuid| {
+            sqlx::query_scalar::<_, i64>("SELECT last_processed_block FROM jobs WHERE id = $1")
+                .bind(job_id)
+                .fetch_one(&pool)
+        };
+        let backfilled = cursor(jobs[0].id).await.expect("Load cursor");
+
+        // The first job cannot reach the chain head; the second still advances.
+        fx.chain.mine(vec![fx.transfer(), fx.transfer_from(other)]);
+        let head = fx.chain.mine_empty(CONFIRMATIONS) - CONFIRMATIONS;
+        fx.chain.fail_next_head_fetches(1);
+        tail_live_jobs(&svc, &pool, &HashMap::new())
+            .await
+            .expect("Tail pass completes");
+        assert_eq!(cursor(jobs[0].id).await.expect("Load cursor"), backfilled);
+        assert_eq!(cursor(jobs[1].id).await.expect("Load cursor"), head as i64);
+
+        tail_live_jobs(&svc, &pool, &HashMap::new())
+            .await
+            .expect("Tail pass completes");
+        assert_eq!(cursor(jobs[0].id).await.expect("Load cursor"), head as i64);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_live_job_commits_mmr_roots() {
+        let pool = test_pool().await;
+        let fx = Fixture::new();
+        fx.chain.mine(vec![fx.transfer(), fx.transfer()]);
+        fx.chain.mine_empty(CONFIRMATIONS);
+        let mut params = fx.params(1, None);
+        params.live = true;
+        params.mmr_commit_blocks = Some(2);
+        let job = fx.insert_job(&pool, params).await;
+        let svc = fx.services();
+        process_blockchain_index(&svc, &pool, &job)
+            .await
+            .expect("Backfill succeeds");
+        sqlx::query("UPDATE jobs SET status = 'live' WHERE id = $1")
+            .bind(job.id)
+            .execute(&pool)
+            .await
+            .expect("Mark job live");
+
+        // Each tail batch is appended; a root is committed once the interval
+        // has passed since the last one.
+        let heads = HashMap::new();
+        let tail = |logs: Vec<MockLog>| {
+            fx.chain.mine(logs);
+            fx.chain.mine_empty(CONFIRMATIONS);
+            tail_live_jobs(&svc, &pool, &heads)
+        };
+        tail(vec![fx.transfer()]).await.expect("Tail succeeds");
+        tail(vec![fx.transfer()]).await.expect("Tail succeeds");
+
+        let roots = mmr::roots(&pool, job.id, job.user_id)
+            .await
+            .expect("List roots");
+        assert_eq!(
+            roots.iter().map(|r| r.leaf_count).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        let commits = fx.chain.commits();
+        for root in &roots {
+            assert!(commits.contains(&root.root));
+            assert_eq!(root.tree_version, MMR_TREE_VERSION);
+            assert_eq!(root.registry_chain_id, Some(MOCK_CHAIN_ID as i64));
+        }
+        let (first, latest) = (&roots[0], &roots[1]);
+
+        let hashes: Vec<String> = sqlx::query_scalar(
+            "SELECT content_hash FROM blockchain_events WHERE job_id = $1
+             ORDER BY block_number, log_index",
+        )
+        .bind(job.id)
+        .fetch_all(&pool)
+        .await
+        .expect("Fetch events");
+        for (i, hash) in hashes.iter().enumerate() {
+            let proof = mmr::inclusion(&pool, job.id, job.user_id, hash, None)
+                .await
+                .expect("Proof against the latest root");
+            assert_eq!(proof.root.root, latest.root);
+            assert_eq!(proof.proof.leaf_index, i as u64);
+            assert!(verify_mmr_inclusion_proof(hash, &proof.proof, &latest.root));
+        }
+        let proof = mmr::inclusion(&pool, job.id, job.user_id, &hashes[0], Some(&first.root))
+            .await
+            .expect("Proof against an earlier root");
+        assert!(verify_mmr_inclusion_proof(
+            &hashes[0],
+            &proof.proof,
+            &first.root
+        ));
+        assert!(matches!(
+            mmr::inclusion(&pool, job.id, job.user_id, &hashes[3], Some(&first.root)).await,
+            Err(proofs::ProofError::NotCommitted)
+        ));
+        assert!(matches!(
+            mmr::roots(&pool, job.id, Uuid::new_v4()).await,
+            Err(proofs::ProofError::NotFound)
+        ));
+
+        let consistency = mmr::consistency(&pool, job.id, job.user_id, &first.root, &latest.root)
+            .await
+            .expect("Consistency proof");
+        assert!(verify_mmr_consistency_proof(
+            &first.root,
+            &latest.root,
+            &consistency.proof
+        ));
+        assert!(matches!(
+            mmr::consistency(&pool, job.id, job.user_id, &latest.root, &first.root).await,
+            Err(proofs::ProofError::InvalidRange)
+        ));
+
+        // Orphaning the last event flags only the root that covers it.
+        let (block_number, block_hash): (i64, String) = sqlx::query_as(
+            "SELECT block_number, block_hash FROM blockchain_events WHERE content_hash = $1",
+        )
+        .bind(&hashes[3])
+        .fetch_one(&pool)
+        .await
+        .expect("Load block");
+        mark_orphaned(&pool, &fx.chain_name, block_number, &block_hash)
+            .await
+            .expect("Events are orphaned");
+        let roots = mmr::roots(&pool, job.id, job.user_id)
+            .await
+            .expect("List roots");
+        assert_eq!(
+            roots.iter().map(|r| r.reorg_affected).collect::<Vec<_>>(),
+            vec![false, true]
+        );
+    }
 }
@@ -0,0 +1,569 @@
+use anyhow::Context;
+use indexnode_core::{
+    checkpoint_bands, generate_mmr_consistency_proof, generate_mmr_inclusion_proof,
+    mmr_checkpoint_spans, mmr_consistency_proof_from_paths, mmr_mountains,
+    mmr_path_from_checkpoints, HashCommitter, MerkleMountainRange, MmrCheckpoint,
+    MmrConsistencyProof, MmrInclusionProof, MMR_TREE_VERSION,
+};
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::proofs::ProofError;
+
+/// A committed root of a job's Merkle Mountain Range and its on-chain
+/// commitment.
+#[derive(Debug, Clone, Serialize)]
+pub struct MmrRoot {
+    pub root: String,
+    pub leaf_count: i64,
+    /// The job's last indexed block when the root was committed.
+    pub indexed_through_block: i64,
+    pub tree_version: u16,
+    pub chain: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub block_number: Option<i64>,
+    pub committed_at: Option<String>,
+    pub registry_chain_id: Option<i64>,
+    pub registry_address: Option<String>,
+    /// True when a reorg orphaned an event covered by the root.
+    pub reorg_affected: bool,
+}
+
+/// A leaf's inclusion proof against a committed MMR root.
+///
+/// `verify_mmr_inclusion_proof(leaf, proof, root.root)` checks it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MmrInclusion {
+    pub leaf: String,
+    pub root: MmrRoot,
+    pub proof: MmrInclusionProof,
+}
+
+/// Shows that the MMR committed as `from` is a prefix of the one committed as
+/// `to`. `verify_mmr_consistency_proof(from.root, to.root, proof)` checks it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MmrConsistency {
+    pub from: MmrRoot,
+    pub to: MmrRoot,
+    pub proof: MmrConsistencyProof,
+}
+
+/// Appends the content hashes that are not yet in the job's MMR, in order,
+/// with the checkpoint nodes they form, and returns its new leaf count.
+pub async fn append(pool: &PgPool, job_id: Uuid, hashes: &[String]) -> anyhow::Result<u64> {
+    let mut tx = pool.begin().await?;
+    // An MMR is checkpointed from its first leaf or not at all.
+    sqlx::query(
+        "INSERT INTO mmr_accumulators (job_id, checkpointed) VALUES ($1, true)
+         ON CONFLICT (job_id) DO UPDATE SET checkpointed = true
+         WHERE mmr_accumulators.leaf_count = 0",
+    )
+    .bind(job_id)
+    .execute(&mut *tx)
+    .await?;
+    let row =
+        sqlx::query("SELECT leaf_count, peaks FROM mmr_accumulators WHERE job_id = $1 FOR UPDATE")
+            .bind(job_id)
+            .fetch_one(&mut *tx)
+            .await?;
+    let mut mmr = MerkleMountainRange::from_peaks(
+        row.get::<i64, _>("leaf_count") as u64,
+        &row.get::<Vec<String>, _>("peaks"),
+    )
+    .with_context(|| format!("Job {} has a corrupt MMR accumulator", job_id))?;
+
+    // A rewound live job indexes some events again; each is a leaf once.
+    let existing: Vec<String> = sqlx::query_scalar(
+        "SELECT content_hash FROM mmr_leaves WHERE job_id = $1 AND content_hash = ANY($2)",
+    )
+    .bind(job_id)
+    .bind(hashes)
+    .fetch_all(&mut *tx)
+    .await?;
+    let first_index = mmr.leaf_count();
+    let mut checkpoints = Vec::new();
+    let appended = append_new(&mut mmr, hashes, &existing, &mut checkpoints)?;
+    if appended.is_empty() {
+        return Ok(mmr.leaf_count());
+    }
+
+    sqlx::query(
+        "INSERT INTO mmr_leaves (job_id, leaf_index, content_hash)
+         SELECT $1, $2 + leaf.ordinality - 1, leaf.content_hash
+         FROM UNNEST($3::TEXT[]) WITH ORDINALITY AS leaf(content_hash, ordinality)",
+    )
+    .bind(job_id)
+    .bind(first_index as i64)
+    .bind(&appended)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to record MMR leaves")?;
+    if !checkpoints.is_empty() {
+        sqlx::query(
+            "INSERT INTO mmr_checkpoints (job_id, level, node_index, node)
+             SELECT $1, c.level, c.node_index, c.node
+             FROM UNNEST($2::SMALLINT[], $3::BIGINT[], $4::TEXT[]) AS c(level, node_index, node)",
+        )
+        .bind(job_id)
+        .bind(
+            checkpoints
+                .iter()
+                .map(|c| c.level as i16)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            checkpoints
+                .iter()
+                .map(|c| c.index as i64)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            checkpoints
+                .iter()
+                .map(|c| c.node.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to record MMR checkpoints")?;
+    }
+    sqlx::query(
+        "UPDATE mmr_accumulators SET leaf_count = $2, peaks = $3, updated_at = NOW()
+         WHERE job_id = $1",
+    )
+    .bind(job_id)
+    .bind(mmr.leaf_count() as i64)
+    .bind(mmr.peaks())
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(mmr.leaf_count())
+}
+
+/// Appends each of `hashes` that is neither in `existing` nor earlier in
+/// `hashes` to `mmr`, collecting the checkpoints formed, and returns the
+/// appended hashes in order.
+fn append_new<'a>(
+    mmr: &mut MerkleMountainRange,
+    hashes: &'a [String],
+    existing: &[String],
+    checkpoints: &mut Vec<MmrCheckpoint>,
+) -> anyhow::Result<Vec<&'a str>> {
+    let mut seen: HashSet<&str> = existing.iter().map(String::as_str).collect();
+    let mut appended = Vec::new();
+    for hash in hashes {
+        if seen.insert(hash) {
+            mmr.append_with_checkpoints(hash, checkpoints)?;
+            appended.push(hash.as_str());
+        }
+    }
+    Ok(appended)
+}
+
+/// Whether a root over `leaf_count` leaves is due: leaves were appended since
+/// `committed_leaf_count`, and the MMR was never committed or `interval`
+/// blocks have been indexed since `committed_through`.
+fn commit_due(
+    leaf_count: i64,
+    committed_leaf_count: i64,
+    committed_through: Option<i64>,
+    indexed_through: u64,
+    interval: u64,
+) -> bool {
+    leaf_count != committed_leaf_count
+        && committed_through.is_none_or(|b| indexed_through >= b as u64 + interval)
+}
+
+/// Commits the root of the job's MMR on-chain if leaves were appended since
+/// the last commitment and `interval` blocks have been indexed since, or it
+/// was never committed. `indexed_through` is the job's last indexed block.
+///
+/// Returns the committed root. A failed commitment is not queued: the next
+/// call commits a root covering the same leaves and any new ones.
+pub async fn commit_if_due(
+    timestamp_client: Option<&dyn HashCommitter>,
+    pool: &PgPool,
+    job_id: Uuid,
+    chain: &str,
+    indexed_through: u64,
+    interval: u64,
+) -> anyhow::Result<Option<String>> {
+    let Some(ts) = timestamp_client else {
+        return Ok(None);
+    };
+    let Some(row) = sqlx::query(
+        "SELECT leaf_count, peaks, committed_leaf_count, committed_through_block
+         FROM mmr_accumulators WHERE job_id = $1",
+    )
+    .bind(job_id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let leaf_count: i64 = row.get("leaf_count");
+    if !commit_due(
+        leaf_count,
+        row.get("committed_leaf_count"),
+        row.get("committed_through_block"),
+        indexed_through,
+        interval,
+    ) {
+        return Ok(None);
+    }
+    let peaks: Vec<String> = row.get("peaks");
+    let Some(root) = MerkleMountainRange::from_peaks(leaf_count as u64, &peaks)?.root() else {
+        return Ok(None);
+    };
+
+    let (tx_hash, block_number) = ts
+        .commit_hash(&root)
+        .await
+        .with_context(|| format!("Failed to commit MMR root {}", root))?;
+    let tx_hash = format!("{:?}", tx_hash);
+    let (registry_chain_id, registry_address) = ts.registry();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO timestamp_commits (content_hash, transaction_hash, block_number, chain, job_id, tree_version, registry_chain_id, registry_address)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (content_hash) DO NOTHING",
+    )
+    .bind(&root)
+    .bind(&tx_hash)
+    .bind(block_number as i64)
+    .bind(chain)
+    .bind(job_id)
+    .bind(MMR_TREE_VERSION as i16)
+    .bind(registry_chain_id as i64)
+    .bind(format!("{:?}", registry_address))
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO mmr_roots (root, job_id, leaf_count, indexed_through_block, peaks)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (root) DO NOTHING",
+    )
+    .bind(&root)
+    .bind(job_id)
+    .bind(leaf_count)
+    .bind(indexed_through as i64)
+    .bind(&peaks)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE mmr_accumulators SET committed_leaf_count = $2, committed_through_block = $3
+         WHERE job_id = $1",
+    )
+    .bind(job_id)
+    .bind(leaf_count)
+    .bind(indexed_through as i64)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "Job {}: committed MMR root {} over {} leaves in tx {} at block {}",
+        job_id,
+        root,
+        leaf_count,
+        tx_hash,
+        block_number
+    );
+    Ok(Some(root))
+}
+
+const ROOT_QUERY: &str = "SELECT r.root, r.leaf_count, r.indexed_through_block,
+                tc.tree_version, tc.chain, tc.transaction_hash, tc.block_number,
+                tc.committed_at, tc.registry_chain_id, tc.registry_address,
+                COALESCE(tc.reorg_affected, false) AS reorg_affected
+         FROM mmr_roots r
+         LEFT JOIN timestamp_commits tc ON tc.content_hash = r.root
+         WHERE r.job_id = $1";
+
+/// The committed MMR roots of a job owned by `user_id`, oldest first.
+pub async fn roots(pool: &PgPool, job_id: Uuid, user_id: Uuid) -> Result<Vec<MmrRoot>, ProofError> {
+    check_owner(pool, job_id, user_id).await?;
+    let rows = sqlx::query(&format!("{} ORDER BY r.leaf_count", ROOT_QUERY))
+        .bind(job_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(mmr_root).collect())
+}
+
+/// The proof that `content_hash` is in the job's MMR as committed in `root`,
+/// or in its latest committed root.
+pub async fn inclusion(
+    pool: &PgPool,
+    job_id: Uuid,
+    user_id: Uuid,
+    content_hash: &str,
+    root: Option<&str>,
+) -> Result<MmrInclusion, ProofError> {
+    check_owner(pool, job_id, user_id).await?;
+    let content_hash = content_hash.to_lowercase();
+    let leaf_index: i64 = sqlx::query_scalar(
+        "SELECT leaf_index FROM mmr_leaves WHERE job_id = $1 AND content_hash = $2",
+    )
+    .bind(job_id)
+    .bind(&content_hash)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ProofError::NotFound)?;
+    let root = match root {
+        Some(root) => find_root(pool, job_id, root).await?,
+        None => {
+            let row = sqlx::query(&format!(
+                "{} ORDER BY r.leaf_count DESC LIMIT 1",
+                ROOT_QUERY
+            ))
+            .bind(job_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(ProofError::NotCommitted)?;
+            mmr_root(&row)
+        }
+    };
+    // The leaf was appended after the root was committed.
+    if leaf_index >= root.leaf_count {
+        return Err(ProofError::NotCommitted);
+    }
+
+    let proof = match checkpointed_peaks(pool, job_id, &root.root).await? {
+        Some(peaks) => MmrInclusionProof {
+            leaf_index: leaf_index as u64,
+            leaf_count: root.leaf_count as u64,
+            path: checkpoint_path(pool, job_id, leaf_index as u64, root.leaf_count as u64).await?,
+            peaks,
+        },
+        None => {
+            let leaves = leaves(pool, job_id, root.leaf_count).await?;
+            generate_mmr_inclusion_proof(&leaves, leaf_index as u64).map_err(ProofError::Proof)?
+        }
+    };
+    Ok(MmrInclusion {
+        leaf: content_hash,
+        root,
+        proof,
+    })
+}
+
+/// The proof that the job's MMR committed as `from` is a prefix of the one
+/// committed as `to`.
+pub async fn consistency(
+    pool: &PgPool,
+    job_id: Uuid,
+    user_id: Uuid,
+    from: &str,
+    to: &str,
+) -> Result<MmrConsistency, ProofError> {
+    check_owner(pool, job_id, user_id).await?;
+    let from = find_root(pool, job_id, from).await?;
+    let to = find_root(pool, job_id, to).await?;
+    if from.leaf_count > to.leaf_count {
+        return Err(ProofError::InvalidRange);
+    }
+    let proof = match (
+        checkpointed_peaks(pool, job_id, &from.root).await?,
+        checkpointed_peaks(pool, job_id, &to.root).await?,
+    ) {
+        (Some(old_peaks), Some(new_peaks)) => {
+            let mut paths = Vec::new();
+            for (start, _) in mmr_mountains(from.leaf_count as u64) {
+                paths.push(checkpoint_path(pool, job_id, start, to.leaf_count as u64).await?);
+            }
+            mmr_consistency_proof_from_paths(
+                from.leaf_count as u64,
+                to.leaf_count as u64,
+                old_peaks,
+                new_peaks,
+                paths,
+            )
+            .map_err(ProofError::Proof)?
+        }
+        _ => {
+            let leaves = leaves(pool, job_id, to.leaf_count).await?;
+            generate_mmr_consistency_proof(&leaves, from.leaf_count as u64)
+                .map_err(ProofError::Proof)?
+        }
+    };
+    Ok(MmrConsistency { from, to, proof })
+}
+
+async fn check_owner(pool: &PgPool, job_id: Uuid, user_id: Uuid) -> Result<(), ProofError> {
+    sqlx::query("SELECT 1 FROM jobs WHERE id = $1 AND user_id = $2")
+        .bind(job_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(ProofError::NotFound)?;
+    Ok(())
+}
+
+async fn find_root(pool: &PgPool, job_id: Uuid, root: &str) -> Result<MmrRoot, ProofError> {
+    let row = sqlx::query(&format!("{} AND r.root = $2", ROOT_QUERY))
+        .bind(job_id)
+        .bind(root.trim_start_matches("0x").to_lowercase())
+        .fetch_optional(pool)
+        .await?
+        .ok_or(ProofError::NotFound)?;
+    Ok(mmr_root(&row))
+}
+
+/// The stored peaks of a committed root, when the job's MMR has checkpoints
+/// to prove against them.
+async fn checkpointed_peaks(
+    pool: &PgPool,
+    job_id: Uuid,
+    root: &str,
+) -> Result<Option<Vec<String>>, ProofError> {
+    let peaks: Option<Option<Vec<String>>> = sqlx::query_scalar(
+        "SELECT r.peaks FROM mmr_roots r
+         JOIN mmr_accumulators a ON a.job_id = r.job_id
+         WHERE r.job_id = $1 AND r.root = $2 AND a.checkpointed",
+    )
+    .bind(job_id)
+    .bind(root)
+    .fetch_optional(pool)
+    .await?;
+    Ok(peaks.flatten())
+}
+
+/// Reads the leaves and checkpoints of each of the leaf's
+/// `mmr_checkpoint_spans` and rebuilds its path from them.
+async fn checkpoint_path(
+    pool: &PgPool,
+    job_id: Uuid,
+    leaf_index: u64,
+    leaf_count: u64,
+) -> Result<Vec<String>, ProofError> {
+    let spans = mmr_checkpoint_spans(leaf_index, leaf_count).map_err(ProofError::Proof)?;
+    let leaves: Vec<String> = sqlx::query_scalar(
+        "SELECT content_hash FROM mmr_leaves
+         WHERE job_id = $1 AND leaf_index >= $2 AND leaf_index < $3
+         ORDER BY leaf_index",
+    )
+    .bind(job_id)
+    .bind(spans[0].start as i64)
+    .bind(spans[0].end as i64)
+    .fetch_all(pool)
+    .await?;
+
+    let checkpoints = &spans[1..];
+    let rows = sqlx::query(
+        "SELECT c.level, c.node
+         FROM mmr_checkpoints c
+         JOIN UNNEST($2::SMALLINT[], $3::BIGINT[], $4::BIGINT[]) AS s(level, first, last)
+           ON c.level = s.level AND c.node_index >= s.first AND c.node_index < s.last
+         WHERE c.job_id = $1
+         ORDER BY c.level, c.node_index",
+    )
+    .bind(job_id)
+    .bind(
+        checkpoints
+            .iter()
+            .map(|s| s.level as i16)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        checkpoints
+            .iter()
+            .map(|s| s.start as i64)
+            .collect::<Vec<_>>(),
+    )
+    .bind(checkpoints.iter().map(|s| s.end as i64).collect::<Vec<_>>())
+    .fetch_all(pool)
+    .await?;
+    let nodes = checkpoint_bands(
+        spans.len(),
+        &leaves,
+        rows.iter()
+            .map(|row| (row.get::<i16, _>("level") as u32, row.get("node"))),
+    )
+    .map_err(ProofError::Proof)?;
+    mmr_path_from_checkpoints(leaf_index, leaf_count, &nodes)
+        .with_context(|| format!("The MMR of job {} is missing checkpoints", job_id))
+        .map_err(ProofError::Proof)
+}
+
+/// The first `leaf_count` leaves of the job's MMR, in order.
+async fn leaves(pool: &PgPool, job_id: Uuid, leaf_count: i64) -> Result<Vec<String>, ProofError> {
+    let leaves: Vec<String> = sqlx::query_scalar(
+        "SELECT content_hash FROM mmr_leaves WHERE job_id = $1 AND leaf_index < $2
+         ORDER BY leaf_index",
+    )
+    .bind(job_id)
+    .bind(leaf_count)
+    .fetch_all(pool)
+    .await?;
+    if leaves.len() as i64 != leaf_count {
+        return Err(ProofError::Proof(anyhow::anyhow!(
+            "The MMR of job {} is missing leaves",
+            job_id
+        )));
+    }
+    Ok(leaves)
+}
+
+fn mmr_root(row: &PgRow) -> MmrRoot {
+    MmrRoot {
+        root: row.get("root"),
+        leaf_count: row.get("leaf_count"),
+        indexed_through_block: row.get("indexed_through_block"),
+        tree_version: row
+            .get::<Option<i16>, _>("tree_version")
+            .map_or(MMR_TREE_VERSION, |v| v as u16),
+        chain: row.get("chain"),
+        transaction_hash: row.get("transaction_hash"),
+        block_number: row.get("block_number"),
+        committed_at: row
+            .get::<Option<chrono::DateTime<chrono::Utc>>, _>("committed_at")
+            .map(|t| t.to_rfc3339()),
+        registry_chain_id: row.get("registry_chain_id"),
+        registry_address: row.get("registry_address"),
+        reorg_affected: row.get("reorg_affected"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexnode_core::hash_content;
+
+    #[test]
+    fn test_append_new_skips_known_hashes() {
+        let hashes: Vec<String> = (0u8..6).map(|i| hash_content(&[i])).collect();
+        let mut mmr = MerkleMountainRange::new();
+        let mut checkpoints = Vec::new();
+        let appended = append_new(&mut mmr, &hashes[..3], &[], &mut checkpoints).unwrap();
+        assert_eq!(appended.len(), 3);
+
+        // A rewound job sends known hashes again, some more than once.
+        let batch = vec![
+            hashes[1].clone(),
+            hashes[3].clone(),
+            hashes[4].clone(),
+            hashes[3].clone(),
+            hashes[5].clone(),
+        ];
+        let appended = append_new(&mut mmr, &batch, &hashes[..2], &mut checkpoints).unwrap();
+        assert_eq!(appended, vec![&*hashes[3], &*hashes[4], &*hashes[5]]);
+        assert_eq!(mmr.leaf_count(), 6);
+        assert_eq!(mmr.root(), indexnode_core::mmr_root(&hashes).ok());
+    }
+
+    #[test]
+    fn test_commit_due() {
+        // Never committed: due as soon as there is a leaf.
+        assert!(commit_due(1, 0, None, 10, 100));
+        assert!(!commit_due(0, 0, None, 10, 100));
+        // Nothing appended since the last commitment.
+        assert!(!commit_due(5, 5, Some(10), 500, 100));
+        // New leaves wait for the interval.
+        assert!(!commit_due(6, 5, Some(10), 109, 100));
+        assert!(commit_due(6, 5, Some(10), 110, 100));
+    }
+}
@@ -0,0 +1,824 @@
+use anyhow::Context;
+use indexnode_core::{
+    canonical_event_document, canonical_link_document, checkpoint_bands, checkpoint_spans,
+    compute_versioned_root, generate_multiproof, generate_versioned_proof, proof_from_checkpoints,
+    BundleRecord, MerkleBuilder, MerkleMultiproof, MultiproofBundle, ProofBundle,
+    RegistryCommitment, CHECKPOINT_STRIDE, MERKLE_TREE_VERSION, PROOF_BUNDLE_VERSION,
+};
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProofError {
+    #[error("No recorded Merkle tree contains this leaf")]
+    NotFound,
+    #[error("The Merkle root has no on-chain commitment with a recorded registry")]
+    NotCommitted,
+    #[error("The first MMR root covers more leaves than the second")]
+    InvalidRange,
+    #[error("Failed to build proof: {0:#}")]
+    Proof(anyhow::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A leaf's inclusion proof in a job's Merkle tree, with the tree's on-chain
+/// commitment if it has been made.
+///
+/// `verify_versioned_proof(leaf, proof, root, leaf_index, leaf_count,
+/// tree_version)` checks it; for tree version 1 that is `verify_merkle_proof`.
+#[derive(Debug, Clone, Serialize)]
+pub struct InclusionProof {
+    pub leaf: String,
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// Sibling hashes from the leaf up to the root.
+    pub proof: Vec<String>,
+    pub root: String,
+    pub tree_version: u16,
+    pub chain: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub block_number: Option<i64>,
+    pub committed_at: Option<String>,
+    /// Chain ID and address of the registry the root was committed to.
+    /// Commitments made before they were recorded have neither.
+    pub registry_chain_id: Option<i64>,
+    pub registry_address: Option<String>,
+    /// True when a reorg orphaned data covered by the committed root.
+    pub reorg_affected: bool,
+}
+
+/// Proves a set of leaves of one tree at once, with the tree's on-chain
+/// commitment if it has been made.
+///
+/// `verify_multiproof(multiproof, root)` checks it.
+#[derive(Debug, Clone, Serialize)]
+pub struct InclusionMultiproof {
+    pub multiproof: MerkleMultiproof,
+    pub root: String,
+    pub chain: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub block_number: Option<i64>,
+    pub committed_at: Option<String>,
+    pub registry_chain_id: Option<i64>,
+    pub registry_address: Option<String>,
+    pub reorg_affected: bool,
+}
+
+/// The commitment columns read with a tree.
+const COMMIT_COLUMNS: &str = "tc.chain, tc.transaction_hash, tc.block_number, tc.committed_at,
+                tc.registry_chain_id, tc.registry_address,
+                COALESCE(tc.reorg_affected, false) AS reorg_affected";
+
+/// Computes the root of a job's tree over `leaves` and records the leaves in
+/// order, so proofs can be generated once the root is committed. A version 2
+/// tree also records its checkpoints, so each proof reads O(log n) nodes. A
+/// tree another job already recorded is shared, and only its job is added.
+pub async fn store_tree(
+    pool: &PgPool,
+    job_id: Uuid,
+    leaves: &[String],
+    tree_version: u16,
+) -> anyhow::Result<String> {
+    let (root, checkpoints) = if tree_version == MERKLE_TREE_VERSION {
+        let mut builder = MerkleBuilder::with_checkpoints();
+        for leaf in leaves {
+            builder
+                .push_hex(leaf)
+                .context("Failed to compute Merkle root")?;
+        }
+        (
+            hex::encode(builder.root()),
+            Some(builder.into_checkpoints()),
+        )
+    } else {
+        let root = compute_versioned_root(leaves, tree_version)
+            .context("Failed to compute Merkle root")?;
+        (root, None)
+    };
+
+    let mut tx = pool.begin().await?;
+    // A tree with the same root already has the same leaves.
+    let inserted = sqlx::query(
+        "INSERT INTO merkle_trees (merkle_root, tree_version, leaf_count, checkpointed)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (merkle_root) DO NOTHING",
+    )
+    .bind(&root)
+    .bind(tree_version as i16)
+    .bind(leaves.len() as i32)
+    .bind(checkpoints.is_some())
+    .execute(&mut *tx)
+    .await
+    .context("Failed to record Merkle tree")?
+    .rows_affected()
+        > 0;
+    if inserted {
+        sqlx::query(
+            "INSERT INTO merkle_leaves (merkle_root, leaf_index, content_hash)
+             SELECT $1, leaf.ordinality - 1, leaf.content_hash
+             FROM UNNEST($2::TEXT[]) WITH ORDINALITY AS leaf(content_hash, ordinality)",
+        )
+        .bind(&root)
+        .bind(leaves)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to record Merkle leaves")?;
+
+        if let Some(checkpoints) = checkpoints {
+            let mut levels = Vec::new();
+            let mut indices = Vec::new();
+            let mut nodes = Vec::new();
+            for (band, level) in checkpoints.iter().enumerate() {
+                for (index, node) in level.iter().enumerate() {
+                    levels.push((CHECKPOINT_STRIDE * (band as u32 + 1)) as i16);
+                    indices.push(index as i32);
+                    nodes.push(hex::encode(node));
+                }
+            }
+            sqlx::query(
+                "INSERT INTO merkle_checkpoints (merkle_root, level, node_index, node)
+                 SELECT $1, c.level, c.node_index, c.node
+                 FROM UNNEST($2::SMALLINT[], $3::INTEGER[], $4::TEXT[])
+                      AS c(level, node_index, node)",
+            )
+            .bind(&root)
+            .bind(&levels)
+            .bind(&indices)
+            .bind(&nodes)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record Merkle checkpoints")?;
+        }
+    }
+    sqlx::query(
+        "INSERT INTO merkle_tree_jobs (merkle_root, job_id) VALUES ($1, $2)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(&root)
+    .bind(job_id)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to record the job's Merkle tree")?;
+    tx.commit().await?;
+    Ok(root)
+}
+
+/// The proof for an event of a job owned by `user_id`, in the tree its
+/// content hash was committed in.
+pub async fn for_event(
+    pool: &PgPool,
+    event_id: Uuid,
+    user_id: Uuid,
+) -> Result<InclusionProof, ProofError> {
+    let row = sqlx::query(
+        "SELECT e.content_hash, e.merkle_root FROM blockchain_events e
+         JOIN jobs j ON j.id = e.job_id
+         WHERE e.id = $1 AND j.user_id = $2",
+    )
+    .bind(event_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ProofError::NotFound)?;
+    find(
+        pool,
+        row.get("content_hash"),
+        row.get("merkle_root"),
+        user_id,
+    )
+    .await
+}
+
+/// The proof for a content hash in a tree of a job owned by `user_id`. When
+/// several trees contain it, committed trees are preferred, then the most
+/// recent.
+pub async fn for_content_hash(
+    pool: &PgPool,
+    content_hash: &str,
+    user_id: Uuid,
+) -> Result<InclusionProof, ProofError> {
+    find(pool, content_hash.to_lowercase(), None, user_id).await
+}
+
+/// Selects the tree of a job owned by `user_id` a leaf is proven in,
+/// preferring `preferred_root`, then committed trees, then the most recent.
+async fn locate(
+    pool: &PgPool,
+    content_hash: &str,
+    preferred_root: Option<&str>,
+    user_id: Uuid,
+) -> Result<PgRow, ProofError> {
+    sqlx::query(&format!(
+        "SELECT t.merkle_root, t.tree_version, t.leaf_count, t.checkpointed, l.leaf_index, {}
+         FROM merkle_leaves l
+         JOIN merkle_trees t ON t.merkle_root = l.merkle_root
+         JOIN merkle_tree_jobs tj ON tj.merkle_root = t.merkle_root
+         JOIN jobs j ON j.id = tj.job_id
+         LEFT JOIN timestamp_commits tc ON tc.content_hash = t.merkle_root
+         WHERE l.content_hash = $1 AND j.user_id = $3
+         ORDER BY t.merkle_root IS NOT DISTINCT FROM $2::TEXT DESC,
+                  tc.content_hash IS NOT NULL DESC,
+                  tj.created_at DESC,
+                  l.leaf_index
+         LIMIT 1",
+        COMMIT_COLUMNS
+    ))
+    .bind(content_hash)
+    .bind(preferred_root)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ProofError::NotFound)
+}
+
+/// The leaves of the tree with `root`, in order.
+async fn tree_leaves(
+    pool: &PgPool,
+    root: &str,
+    leaf_count: i32,
+) -> Result<Vec<String>, ProofError> {
+    let leaves: Vec<String> = sqlx::query_scalar(
+        "SELECT content_hash FROM merkle_leaves WHERE merkle_root = $1 ORDER BY leaf_index",
+    )
+    .bind(root)
+    .fetch_all(pool)
+    .await?;
+    if leaves.len() != leaf_count as usize {
+        return Err(ProofError::Proof(anyhow::anyhow!(
+            "Tree {} is missing leaves",
+            root
+        )));
+    }
+    Ok(leaves)
+}
+
+/// The sibling path of the leaf `row` locates. A checkpointed tree rebuilds it
+/// from O(log n) stored nodes; any other tree needs all its leaves, which
+/// `trees` keeps by root for the next leaf of the same tree.
+async fn proof_path(
+    pool: &PgPool,
+    row: &PgRow,
+    trees: &mut HashMap<String, Vec<String>>,
+) -> Result<Vec<String>, ProofError> {
+    let root: String = row.get("merkle_root");
+    let leaf_index = row.get::<i32, _>("leaf_index") as usize;
+    if row.get("checkpointed") {
+        let leaf_count = row.get::<i32, _>("leaf_count") as usize;
+        return checkpoint_path(pool, &root, leaf_index, leaf_count).await;
+    }
+    if !trees.contains_key(&root) {
+        let leaves = tree_leaves(pool, &root, row.get("leaf_count")).await?;
+        trees.insert(root.clone(), leaves);
+    }
+    generate_versioned_proof(
+        &trees[&root],
+        leaf_index,
+        row.get::<i16, _>("tree_version") as u16,
+    )
+    .map_err(ProofError::Proof)
+}
+
+/// Reads the leaves and checkpoints of each of the leaf's `checkpoint_spans`
+/// and rebuilds its path from them.
+async fn checkpoint_path(
+    pool: &PgPool,
+    root: &str,
+    leaf_index: usize,
+    leaf_count: usize,
+) -> Result<Vec<String>, ProofError> {
+    let spans = checkpoint_spans(leaf_index, leaf_count).map_err(ProofError::Proof)?;
+    let leaves: Vec<String> = sqlx::query_scalar(
+        "SELECT content_hash FROM merkle_leaves
+         WHERE merkle_root = $1 AND leaf_index >= $2 AND leaf_index < $3
+         ORDER BY leaf_index",
+    )
+    .bind(root)
+    .bind(spans[0].start as i32)
+    .bind(spans[0].end as i32)
+    .fetch_all(pool)
+    .await?;
+
+    let checkpoints = &spans[1..];
+    let rows = sqlx::query(
+        "SELECT c.level, c.node
+         FROM merkle_checkpoints c
+         JOIN UNNEST($2::SMALLINT[], $3::INTEGER[], $4::INTEGER[]) AS s(level, first, last)
+           ON c.level = s.level AND c.node_index >= s.first AND c.node_index < s.last
+         WHERE c.merkle_root = $1
+         ORDER BY c.level, c.node_index",
+    )
+    .bind(root)
+    .bind(
+        checkpoints
+            .iter()
+            .map(|s| s.level as i16)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        checkpoints
+            .iter()
+            .map(|s| s.start as i32)
+            .collect::<Vec<_>>(),
+    )
+    .bind(checkpoints.iter().map(|s| s.end as i32).collect::<Vec<_>>())
+    .fetch_all(pool)
+    .await?;
+    let nodes = checkpoint_bands(
+        spans.len(),
+        &leaves,
+        rows.iter()
+            .map(|row| (row.get::<i16, _>("level") as u32, row.get("node"))),
+    )
+    .map_err(ProofError::Proof)?;
+    proof_from_checkpoints(leaf_index, leaf_count, &nodes)
+        .with_context(|| format!("Tree {} is missing checkpoints", root))
+        .map_err(ProofError::Proof)
+}
+
+fn build_proof(content_hash: String, row: &PgRow, proof: Vec<String>) -> InclusionProof {
+    InclusionProof {
+        leaf: content_hash,
+        leaf_index: row.get::<i32, _>("leaf_index") as usize,
+        leaf_count: row.get::<i32, _>("leaf_count") as usize,
+        proof,
+        root: row.get("merkle_root"),
+        tree_version: row.get::<i16, _>("tree_version") as u16,
+        chain: row.get("chain"),
+        transaction_hash: row.get("transaction_hash"),
+        block_number: row.get("block_number"),
+        committed_at: row
+            .get::<Option<chrono::DateTime<chrono::Utc>>, _>("committed_at")
+            .map(|t| t.to_rfc3339()),
+        registry_chain_id: row.get("registry_chain_id"),
+        registry_address: row.get("registry_address"),
+        reorg_affected: row.get("reorg_affected"),
+    }
+}
+
+async fn find(
+    pool: &PgPool,
+    content_hash: String,
+    preferred_root: Option<String>,
+    user_id: Uuid,
+) -> Result<InclusionProof, ProofError> {
+    let row = locate(pool, &content_hash, preferred_root.as_deref(), user_id).await?;
+    let proof = proof_path(pool, &row, &mut HashMap::new()).await?;
+    Ok(build_proof(content_hash, &row, proof))
+}
+
+/// The multiproof for `content_hashes` in the tree with `root`, which must
+/// belong to a job owned by `user_id`. A hash listed twice is proven once.
+pub async fn multiproof(
+    pool: &PgPool,
+    root: &str,
+    content_hashes: &[String],
+    user_id: Uuid,
+) -> Result<InclusionMultiproof, ProofError> {
+    let root = root.trim_start_matches("0x").to_lowercase();
+    let tree = sqlx::query(&format!(
+        "SELECT t.tree_version, t.leaf_count, {}
+         FROM merkle_trees t
+         LEFT JOIN timestamp_commits tc ON tc.content_hash = t.merkle_root
+         WHERE t.merkle_root = $1
+           AND EXISTS (SELECT 1 FROM merkle_tree_jobs tj
+                       JOIN jobs j ON j.id = tj.job_id
+                       WHERE tj.merkle_root = t.merkle_root AND j.user_id = $2)",
+        COMMIT_COLUMNS
+    ))
+    .bind(&root)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ProofError::NotFound)?;
+    let leaves = tree_leaves(pool, &root, tree.get("leaf_count")).await?;
+
+    // A hash that is a leaf more than once is proven at its first position.
+    let positions: HashMap<&str, usize> = leaves
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, leaf)| (leaf.as_str(), i))
+        .collect();
+    let mut indices = content_hashes
+        .iter()
+        .map(|hash| {
+            positions
+                .get(hash.to_lowercase().as_str())
+                .copied()
+                .ok_or(ProofError::NotFound)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    indices.sort_unstable();
+    indices.dedup();
+    let multiproof =
+        generate_multiproof(&leaves, &indices, tree.get::<i16, _>("tree_version") as u16)
+            .map_err(ProofError::Proof)?;
+
+    Ok(InclusionMultiproof {
+        multiproof,
+        root,
+        chain: tree.get("chain"),
+        transaction_hash: tree.get("transaction_hash"),
+        block_number: tree.get("block_number"),
+        committed_at: tree
+            .get::<Option<chrono::DateTime<chrono::Utc>>, _>("committed_at")
+            .map(|t| t.to_rfc3339()),
+        registry_chain_id: tree.get("registry_chain_id"),
+        registry_address: tree.get("registry_address"),
+        reorg_affected: tree.get("reorg_affected"),
+    })
+}
+
+const EVENT_COLUMNS: &str = "chain, contract_address, transaction_hash, log_index, event_data,
+                             content_hash, hash_version, ipfs_cid, merkle_root";
+
+/// The proof bundle for an event of a job owned by `user_id`; see
+/// `ProofBundle`.
+pub async fn bundle_for_event(
+    pool: &PgPool,
+    event_id: Uuid,
+    user_id: Uuid,
+) -> Result<ProofBundle, ProofError> {
+    let event = sqlx::query(&format!(
+        "SELECT {} FROM blockchain_events e
+         JOIN jobs j ON j.id = e.job_id
+         WHERE e.id = $1 AND j.user_id = $2",
+        EVENT_COLUMNS
+    ))
+    .bind(event_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ProofError::NotFound)?;
+    let proof = find(
+        pool,
+        event.get("content_hash"),
+        event.get("merkle_root"),
+        user_id,
+    )
+    .await?;
+    bundle(bundle_record(&event), proof)
+}
+
+/// The proof bundle for a crawled link, a `crawl_results` row, of a job owned
+/// by `user_id`. Its record is the link's canonical document; see
+/// `canonical_link_document`. Links crawled before they were committed as
+/// leaves have no bundle.
+pub async fn bundle_for_crawl_result(
+    pool: &PgPool,
+    crawl_result_id: Uuid,
+    user_id: Uuid,
+) -> Result<ProofBundle, ProofError> {
+    let link = sqlx::query(
+        "SELECT c.url, c.page_url, c.status_code, c.body_hash, c.created_at, c.content_hash,
+                c.hash_version, c.merkle_root
+         FROM crawl_results c
+         JOIN jobs j ON j.id = c.job_id
+         WHERE c.id = $1 AND j.user_id = $2 AND c.page_url IS NOT NULL",
+    )
+    .bind(crawl_result_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ProofError::NotFound)?;
+    let content_hash: String = link.get("content_hash");
+    let record = BundleRecord {
+        record: canonical_link_document(
+            link.get::<&str, _>("url"),
+            link.get::<&str, _>("page_url"),
+            link.get::<i32, _>("status_code") as u16,
+            link.get::<&str, _>("body_hash"),
+            &link.get::<chrono::DateTime<chrono::Utc>, _>("created_at"),
+        ),
+        hash_version: link.get::<i16, _>("hash_version") as u16,
+        content_hash: content_hash.clone(),
+        ipfs_cid: None,
+    };
+    let proof = find(pool, content_hash, link.get("merkle_root"), user_id).await?;
+    bundle(record, proof)
+}
+
+/// A job's proof bundles, with the events left out of them.
+#[derive(Debug)]
+pub struct JobBundles<T> {
+    pub bundles: Vec<T>,
+    pub skipped: Vec<SkippedEvent>,
+}
+
+/// An event of a job export that could not be bundled, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedEvent {
+    pub event_id: Uuid,
+    pub reason: String,
+}
+
+impl SkippedEvent {
+    fn new(event: &PgRow, reason: impl ToString) -> Self {
+        Self {
+            event_id: event.get("id"),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Whether an event's proof failed on its own data rather than on the
+/// database, so a job export can skip it and go on.
+fn skippable(e: &ProofError) -> bool {
+    !matches!(e, ProofError::Database(_))
+}
+
+/// Proof bundles for the committed events of a job owned by `user_id`, in
+/// chain order. `limit` and `offset` select a page; without `limit` every
+/// committed event is included. Events whose root was committed before
+/// registries were recorded are left out; events orphaned by a reorg, or
+/// whose proof cannot be built, are skipped one by one and reported.
+pub async fn bundles_for_job(
+    pool: &PgPool,
+    job_id: Uuid,
+    user_id: Uuid,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<JobBundles<ProofBundle>, ProofError> {
+    let events = committed_events(pool, job_id, user_id, limit, offset).await?;
+
+    // Events of a job share a handful of trees; load each tree's leaves once
+    // when it has no checkpoints.
+    let mut trees: HashMap<String, Vec<String>> = HashMap::new();
+    let mut bundles = Vec::with_capacity(events.len());
+    let mut skipped = Vec::new();
+    for event in &events {
+        if event.get("orphaned") {
+            skipped.push(SkippedEvent::new(event, ORPHANED));
+            continue;
+        }
+        match event_bundle(pool, event, user_id, &mut trees).await {
+            Ok(bundle) => bundles.push(bundle),
+            Err(e) if skippable(&e) => skipped.push(SkippedEvent::new(event, e)),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(JobBundles { bundles, skipped })
+}
+
+/// Why orphaned events are left out of job exports.
+const ORPHANED: &str = "The event was orphaned by a reorg";
+
+/// The bundle of one of the events `committed_events` selects.
+async fn event_bundle(
+    pool: &PgPool,
+    event: &PgRow,
+    user_id: Uuid,
+    trees: &mut HashMap<String, Vec<String>>,
+) -> Result<ProofBundle, ProofError> {
+    let content_hash: String = event.get("content_hash");
+    let row = locate(pool, &content_hash, event.get("merkle_root"), user_id).await?;
+    let proof = proof_path(pool, &row, trees).await?;
+    bundle(bundle_record(event), build_proof(content_hash, &row, proof))
+}
+
+/// The events `bundles_for_job` selects, proven with one `MultiproofBundle`
+/// per tree instead of a bundle per event. Bundles are in the order of each
+/// tree's first event. Events are skipped like there, and so is every event
+/// of a tree whose multiproof cannot be built.
+pub async fn multiproof_bundles_for_job(
+    pool: &PgPool,
+    job_id: Uuid,
+    user_id: Uuid,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<JobBundles<MultiproofBundle>, ProofError> {
+    let events = committed_events(pool, job_id, user_id, limit, offset).await?;
+
+    // The tree each event is proven in, and the events at each leaf index.
+    let mut trees: Vec<(PgRow, HashMap<usize, &PgRow>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut skipped = Vec::new();
+    for event in &events {
+        if event.get("orphaned") {
+            skipped.push(SkippedEvent::new(event, ORPHANED));
+            continue;
+        }
+        let located = locate(
+            pool,
+            event.get::<&str, _>("content_hash"),
+            event.get("merkle_root"),
+            user_id,
+        )
+        .await;
+        let row = match located {
+            Ok(row) => row,
+            Err(e) if skippable(&e) => {
+                skipped.push(SkippedEvent::new(event, e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let leaf_index = row.get::<i32, _>("leaf_index") as usize;
+        let position = *positions.entry(row.get("merkle_root")).or_insert_with(|| {
+            trees.push((row, HashMap::new()));
+            trees.len() - 1
+        });
+        trees[position].1.entry(leaf_index).or_insert(event);
+    }
+
+    let mut bundles = Vec::with_capacity(trees.len());
+    for (tree, events) in trees {
+        match tree_multiproof_bundle(pool, &tree, &events).await {
+            Ok(bundle) => bundles.push(bundle),
+            Err(e) if skippable(&e) => {
+                let mut events: Vec<_> = events.into_iter().collect();
+                events.sort_unstable_by_key(|(leaf_index, _)| *leaf_index);
+                skipped.extend(
+                    events
+                        .into_iter()
+                        .map(|(_, event)| SkippedEvent::new(event, &e)),
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(JobBundles { bundles, skipped })
+}
+
+/// The multiproof bundle of `events`, keyed by leaf index, in `tree`.
+async fn tree_multiproof_bundle(
+    pool: &PgPool,
+    tree: &PgRow,
+    events: &HashMap<usize, &PgRow>,
+) -> Result<MultiproofBundle, ProofError> {
+    let commitment = RegistryCommitment::from_columns(
+        tree.get("registry_chain_id"),
+        tree.get("registry_address"),
+        tree.get("transaction_hash"),
+        tree.get("block_number"),
+    )
+    .ok_or(ProofError::NotCommitted)?;
+    let root: String = tree.get("merkle_root");
+    let leaves = tree_leaves(pool, &root, tree.get("leaf_count")).await?;
+    let records = events
+        .iter()
+        .map(|(&leaf_index, event)| (leaf_index, bundle_record(event)))
+        .collect();
+    MultiproofBundle::assemble(
+        &leaves,
+        tree.get::<i16, _>("tree_version") as u16,
+        root,
+        &records,
+        commitment,
+    )
+    .map_err(ProofError::Proof)
+}
+
+/// The events `bundles_for_job` covers, after checking that `user_id` owns
+/// the job.
+async fn committed_events(
+    pool: &PgPool,
+    job_id: Uuid,
+    user_id: Uuid,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<PgRow>, ProofError> {
+    sqlx::query("SELECT 1 FROM jobs WHERE id = $1 AND user_id = $2")
+        .bind(job_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(ProofError::NotFound)?;
+
+    let events = sqlx::query(&format!(
+        "SELECT e.id, e.orphaned, {} FROM blockchain_events e
+         WHERE e.job_id = $1
+           AND EXISTS (SELECT 1 FROM merkle_tree_jobs tj
+                       WHERE tj.merkle_root = e.merkle_root AND tj.job_id = e.job_id)
+           AND EXISTS (SELECT 1 FROM timestamp_commits tc
+                       WHERE tc.content_hash = e.merkle_root AND tc.registry_chain_id IS NOT NULL)
+         ORDER BY e.block_number, e.log_index, e.id
+         LIMIT $2 OFFSET $3",
+        EVENT_COLUMNS
+    ))
+    .bind(job_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    Ok(events)
+}
+
+fn bundle(record: BundleRecord, proof: InclusionProof) -> Result<ProofBundle, ProofError> {
+    let commitment = RegistryCommitment::from_columns(
+        proof.registry_chain_id,
+        proof.registry_address,
+        proof.transaction_hash,
+        proof.block_number,
+    )
+    .ok_or(ProofError::NotCommitted)?;
+
+    let BundleRecord {
+        record,
+        hash_version,
+        ipfs_cid,
+        ..
+    } = record;
+    Ok(ProofBundle {
+        bundle_version: PROOF_BUNDLE_VERSION,
+        record,
+        hash_version,
+        content_hash: proof.leaf,
+        ipfs_cid,
+        leaf_index: proof.leaf_index,
+        leaf_count: proof.leaf_count,
+        proof: proof.proof,
+        root: proof.root,
+        tree_version: proof.tree_version,
+        chain_id: commitment.chain_id,
+        registry_address: commitment.registry_address,
+        transaction_hash: commitment.transaction_hash,
+        block_number: commitment.block_number,
+    })
+}
+
+fn bundle_record(event: &PgRow) -> BundleRecord {
+    let record = canonical_event_document(
+        event.get::<&str, _>("chain"),
+        event.get::<&str, _>("contract_address"),
+        event.get::<&str, _>("transaction_hash"),
+        // Only events hashed under version 1, which ignores it, lack a log
+        // index.
+        event.get::<Option<i64>, _>("log_index").unwrap_or_default() as u64,
+        event.get("event_data"),
+    );
+    BundleRecord {
+        record,
+        hash_version: event.get::<i16, _>("hash_version") as u16,
+        content_hash: event.get("content_hash"),
+        ipfs_cid: event.get("ipfs_cid"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexnode_core::{hash_event_document, EVENT_HASH_VERSION};
+    use serde_json::json;
+
+    #[test]
+    fn test_bundle_needs_a_recorded_registry() {
+        let record = canonical_event_document(
+            "ethereum",
+            "0x00000000000000000000000000000000000000aa",
+            &format!("0x{}", "11".repeat(32)),
+            2,
+            json!({"event": "Transfer", "params": {"value": "5"}}),
+        );
+        let content_hash = hash_event_document(&record, EVENT_HASH_VERSION).unwrap();
+        let leaves = vec!["aa".repeat(32), content_hash.clone()];
+        let proof = InclusionProof {
+            leaf: content_hash.clone(),
+            leaf_index: 1,
+            leaf_count: leaves.len(),
+            proof: generate_versioned_proof(&leaves, 1, MERKLE_TREE_VERSION).unwrap(),
+            root: compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            tree_version: MERKLE_TREE_VERSION,
+            chain: Some("ethereum".to_string()),
+            transaction_hash: Some(format!("0x{}", "22".repeat(32))),
+            block_number: Some(7),
+            committed_at: None,
+            registry_chain_id: Some(1),
+            registry_address: Some(format!("0x{}", "33".repeat(20))),
+            reorg_affected: false,
+        };
+        let record = BundleRecord {
+            record,
+            hash_version: EVENT_HASH_VERSION,
+            content_hash,
+            ipfs_cid: None,
+        };
+
+        let bundle = bundle(record.clone(), proof.clone()).unwrap();
+        bundle.verify().unwrap();
+        assert_eq!(bundle.block_number, 7);
+
+        let legacy = InclusionProof {
+            registry_chain_id: None,
+            ..proof
+        };
+        assert!(matches!(
+            super::bundle(record, legacy),
+            Err(ProofError::NotCommitted)
+        ));
+    }
+
+    #[test]
+    fn test_only_database_errors_abort_exports() {
+        assert!(skippable(&ProofError::NotFound));
+        assert!(skippable(&ProofError::NotCommitted));
+        assert!(skippable(&ProofError::Proof(anyhow::anyhow!(
+            "missing leaves"
+        ))));
+        assert!(!skippable(&ProofError::Database(sqlx::Error::PoolTimedOut)));
+    }
+}
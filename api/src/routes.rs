@@ -39,7 +39,31 @@ pub fn create_routes(pool: PgPool) -> Router {
         // Jobs
         .route("/api/v1/jobs", post(handlers::create_job))
         .route("/api/v1/jobs/{id}", get(handlers::get_job))
+        .route(
+            "/api/v1/jobs/{id}/proof-bundles",
+            get(handlers::job_bundles),
+        )
+        .route("/api/v1/jobs/{id}/mmr", get(handlers::job_mmr_roots))
+        .route(
+            "/api/v1/jobs/{id}/mmr/proofs/{content_hash}",
+            get(handlers::job_mmr_proof),
+        )
+        .route(
+            "/api/v1/jobs/{id}/mmr/consistency",
+            get(handlers::job_mmr_consistency),
+        )
         .route("/api/v1/verify", post(handlers::verify_hash))
+        .route("/api/v1/proofs/events/{id}", get(handlers::event_proof))
+        .route(
+            "/api/v1/proofs/events/{id}/bundle",
+            get(handlers::event_bundle),
+        )
+        .route(
+            "/api/v1/proofs/crawl-results/{id}/bundle",
+            get(handlers::crawl_result_bundle),
+        )
+        .route("/api/v1/proofs/multiproof", post(handlers::multiproof))
+        .route("/api/v1/proofs/{content_hash}", get(handlers::hash_proof))
         // API keys
         .route(
             "/api/v1/api-keys",
@@ -52,6 +76,17 @@ pub fn create_routes(pool: PgPool) -> Router {
             post(handlers::create_webhook).get(handlers::list_webhooks),
         )
         .route("/api/v1/webhooks/{id}", delete(handlers::delete_webhook))
+        // ABI registry
+        .route(
+            "/api/v1/abis",
+            post(handlers::create_abi).get(handlers::list_abis),
+        )
+        .route(
+            "/api/v1/abis/{id}",
+            get(handlers::get_abi)
+                .put(handlers::update_abi)
+                .delete(handlers::delete_abi),
+        )
         .route_layer(middleware::from_fn(require_auth))
         .with_state(state)
 }
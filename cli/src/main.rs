@@ -2,6 +2,9 @@
 use anyhow::Result;
 use reqwest::Client;
 use serde_json::json;
+use std::path::PathBuf;
+
+mod verify;
 
 #[derive(Parser)]
 #[command(name = "indexnode")]
@@ -23,6 +26,28 @@ enum Commands {
         #[arg(short, long)]
         job_id: String,
     },
+    /// Verify a proof bundle, or a file and its inclusion proof, without the API.
+    ///
+    /// Exits 0 when every check passes, 1 when one fails, 2 when the input
+    /// cannot be read and 3 when the chain cannot be queried.
+    Verify {
+        /// Proof bundle exported from the API, as JSON or CBOR.
+        #[arg(short, long, conflicts_with_all = ["file", "proof"])]
+        bundle: Option<PathBuf>,
+        /// File whose SHA-256 is the proven content hash. For an event, the
+        /// JCS serialization of its canonical document.
+        #[arg(short, long, requires = "proof")]
+        file: Option<PathBuf>,
+        /// Inclusion proof JSON for --file, as returned by /api/v1/proofs.
+        #[arg(short, long, requires = "file")]
+        proof: Option<PathBuf>,
+        /// RPC URL of the registry's chain, to check the root was committed.
+        #[arg(long)]
+        rpc_url: Option<String>,
+        /// TimestampRegistry address, when the proof does not name one.
+        #[arg(long)]
+        registry: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -56,6 +81,23 @@ async fn main() -> Result<()> {
             let body: serde_json::Value = response.json().await?;
             println!("Job status: {}", serde_json::to_string_pretty(&body)?);
         }
+        Commands::Verify {
+            bundle,
+            file,
+            proof,
+            rpc_url,
+            registry,
+        } => {
+            let code = verify::run(verify::VerifyArgs {
+                bundle,
+                file,
+                proof,
+                rpc_url,
+                registry,
+            })
+            .await;
+            std::process::exit(code);
+        }
     }
 
     Ok(())
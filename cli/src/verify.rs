@@ -0,0 +1,600 @@
+use anyhow::{Context, Result};
+use ethers::types::{Address, H256};
+use indexnode_core::{
+    from_cbor, hash_content, hash_event_document, registry_committed_block, verify_multiproof,
+    verify_versioned_proof, BlockchainClient, BundleError, ChainProvider, MultiproofBundle,
+    ProofBundle, PROOF_BUNDLE_VERSION,
+};
+use serde::Deserialize;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Every check passed.
+pub const EXIT_VERIFIED: i32 = 0;
+/// A check failed: the data, the proof and the commitment do not agree.
+pub const EXIT_FAILED: i32 = 1;
+/// The input could not be read or parsed, so nothing was verified.
+pub const EXIT_INVALID_INPUT: i32 = 2;
+/// The offline checks passed but the chain could not be queried.
+pub const EXIT_UNCONFIRMED: i32 = 3;
+
+/// What to verify and, optionally, where to check the commitment.
+pub struct VerifyArgs {
+    pub bundle: Option<PathBuf>,
+    pub file: Option<PathBuf>,
+    pub proof: Option<PathBuf>,
+    pub rpc_url: Option<String>,
+    pub registry: Option<String>,
+}
+
+/// An inclusion proof as returned by `/api/v1/proofs/...`.
+#[derive(Deserialize)]
+struct InclusionProof {
+    leaf: String,
+    leaf_index: usize,
+    leaf_count: usize,
+    proof: Vec<String>,
+    root: String,
+    tree_version: u16,
+    block_number: Option<u64>,
+    registry_address: Option<String>,
+}
+
+/// A proof bundle for one event, or a multiproof bundle for many.
+enum Bundle {
+    Single(ProofBundle),
+    Multi(MultiproofBundle),
+}
+
+impl Bundle {
+    fn version(&self) -> u16 {
+        match self {
+            Self::Single(b) => b.bundle_version,
+            Self::Multi(b) => b.bundle_version,
+        }
+    }
+
+    /// The registry's chain ID and address, and the transaction and block
+    /// that committed the root.
+    fn commitment(&self) -> (u64, &str, &str, u64) {
+        match self {
+            Self::Single(b) => (
+                b.chain_id,
+                &b.registry_address,
+                &b.transaction_hash,
+                b.block_number,
+            ),
+            Self::Multi(b) => (
+                b.chain_id,
+                &b.registry_address,
+                &b.transaction_hash,
+                b.block_number,
+            ),
+        }
+    }
+
+    async fn verify_commitment(&self, provider: &dyn ChainProvider) -> Result<(), BundleError> {
+        match self {
+            Self::Single(b) => b.verify_commitment(provider).await,
+            Self::Multi(b) => b.verify_commitment(provider).await,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    failed: bool,
+    unconfirmed: bool,
+}
+
+impl Report {
+    fn pass(&mut self, check: &str, detail: impl Display) {
+        println!("  PASS   {:<14} {}", check, detail);
+    }
+
+    fn fail(&mut self, check: &str, detail: impl Display) {
+        self.failed = true;
+        println!("  FAIL   {:<14} {}", check, detail);
+    }
+
+    fn skip(&mut self, check: &str, detail: impl Display) {
+        println!("  SKIP   {:<14} {}", check, detail);
+    }
+
+    fn error(&mut self, check: &str, detail: impl Display) {
+        self.unconfirmed = true;
+        println!("  ERROR  {:<14} {}", check, detail);
+    }
+
+    /// Prints the verdict and returns the exit code.
+    fn finish(&self) -> i32 {
+        let (verdict, code) = if self.failed {
+            ("FAILED", EXIT_FAILED)
+        } else if self.unconfirmed {
+            (
+                "UNCONFIRMED: the chain could not be queried",
+                EXIT_UNCONFIRMED,
+            )
+        } else {
+            ("VERIFIED", EXIT_VERIFIED)
+        };
+        println!("\nResult: {}", verdict);
+        code
+    }
+}
+
+/// Runs the checks, prints a report and returns the process exit code.
+pub async fn run(args: VerifyArgs) -> i32 {
+    let result = match (&args.bundle, &args.file, &args.proof) {
+        (Some(bundle), None, None) => verify_bundle(bundle, args.rpc_url.as_deref()).await,
+        (None, Some(file), Some(proof)) => {
+            verify_file(
+                file,
+                proof,
+                args.rpc_url.as_deref(),
+                args.registry.as_deref(),
+            )
+            .await
+        }
+        _ => Err(anyhow::anyhow!(
+            "Pass either --bundle, or --file together with --proof"
+        )),
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("Error: {:#}", e);
+        EXIT_INVALID_INPUT
+    })
+}
+
+fn read_bundle(path: &Path) -> Result<Bundle> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // A JSON bundle is an object; a CBOR map never starts with `{` (0x7b).
+    let value = if bytes.trim_ascii_start().first() == Some(&b'{') {
+        serde_json::from_slice(&bytes).context("Invalid JSON proof bundle")?
+    } else {
+        from_cbor(&bytes).context("Invalid CBOR proof bundle")?
+    };
+    // Only a multiproof bundle carries several records.
+    if value.get("records").is_some() {
+        serde_json::from_value(value)
+            .map(Bundle::Multi)
+            .context("Invalid multiproof bundle")
+    } else {
+        serde_json::from_value(value)
+            .map(Bundle::Single)
+            .context("Invalid proof bundle")
+    }
+}
+
+async fn verify_bundle(path: &Path, rpc_url: Option<&str>) -> Result<i32> {
+    let bundle = read_bundle(path)?;
+    anyhow::ensure!(
+        bundle.version() == PROOF_BUNDLE_VERSION,
+        "Unsupported proof bundle version {}",
+        bundle.version()
+    );
+    println!("Proof bundle {}", path.display());
+    let mut report = Report::default();
+
+    match &bundle {
+        Bundle::Single(bundle) => {
+            check_content_hash(
+                &mut report,
+                &bundle.record,
+                bundle.hash_version,
+                &bundle.content_hash,
+            );
+            check_proof(
+                &mut report,
+                &bundle.content_hash,
+                &bundle.proof,
+                &bundle.root,
+                bundle.leaf_index,
+                bundle.leaf_count,
+                bundle.tree_version,
+            );
+        }
+        Bundle::Multi(bundle) => check_multiproof_bundle(&mut report, bundle),
+    }
+
+    let Some(rpc_url) = rpc_url else {
+        report.skip("Commitment", "pass --rpc-url to check it on-chain");
+        return Ok(report.finish());
+    };
+    let client = match BlockchainClient::new(rpc_url).await {
+        Ok(client) => client,
+        Err(e) => {
+            report.error("Commitment", format!("{:#}", e));
+            return Ok(report.finish());
+        }
+    };
+    let (bundle_chain_id, registry_address, transaction_hash, block_number) = bundle.commitment();
+    match client.chain_id().await {
+        Ok(chain_id) if chain_id != bundle_chain_id => {
+            report.fail(
+                "Commitment",
+                format!(
+                    "RPC serves chain {}, the bundle's registry is on chain {}",
+                    chain_id, bundle_chain_id
+                ),
+            );
+            return Ok(report.finish());
+        }
+        Ok(_) => {}
+        Err(e) => {
+            report.error("Commitment", format!("{:#}", e));
+            return Ok(report.finish());
+        }
+    }
+    match bundle.verify_commitment(&client).await {
+        Ok(()) => report.pass(
+            "Commitment",
+            format!(
+                "root committed to {} on chain {} in tx {} at block {}",
+                registry_address, bundle_chain_id, transaction_hash, block_number
+            ),
+        ),
+        Err(BundleError::Rpc(e)) => report.error("Commitment", format!("{:#}", e)),
+        Err(e) => report.fail("Commitment", e),
+    }
+    Ok(report.finish())
+}
+
+async fn verify_file(
+    file: &Path,
+    proof_path: &Path,
+    rpc_url: Option<&str>,
+    registry: Option<&str>,
+) -> Result<i32> {
+    let bytes =
+        std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let proof: InclusionProof = serde_json::from_slice(
+        &std::fs::read(proof_path)
+            .with_context(|| format!("Failed to read {}", proof_path.display()))?,
+    )
+    .context("Invalid inclusion proof")?;
+    println!(
+        "File {} with proof {}",
+        file.display(),
+        proof_path.display()
+    );
+    let mut report = Report::default();
+
+    let hash = hash_content(&bytes);
+    if hash.eq_ignore_ascii_case(proof.leaf.trim_start_matches("0x")) {
+        report.pass("Content hash", &hash);
+    } else {
+        report.fail(
+            "Content hash",
+            format!("file hashes to {}, proof is for {}", hash, proof.leaf),
+        );
+    }
+    check_proof(
+        &mut report,
+        &proof.leaf,
+        &proof.proof,
+        &proof.root,
+        proof.leaf_index,
+        proof.leaf_count,
+        proof.tree_version,
+    );
+
+    let Some(rpc_url) = rpc_url else {
+        report.skip("Commitment", "pass --rpc-url to check it on-chain");
+        return Ok(report.finish());
+    };
+    let registry: Address = registry
+        .or(proof.registry_address.as_deref())
+        .context("The proof names no registry; pass --registry")?
+        .parse()
+        .context("Invalid registry address")?;
+    let root: H256 = proof.root.parse().context("Root is not a 32-byte hash")?;
+    match committed_at_head(rpc_url, registry, root).await {
+        Ok(0) => report.fail("Commitment", "registry has no record of the root"),
+        Ok(block) if proof.block_number.is_some_and(|expected| expected != block) => report.fail(
+            "Commitment",
+            format!(
+                "registry recorded the root at block {}, the proof claims {}",
+                block,
+                proof.block_number.unwrap_or_default()
+            ),
+        ),
+        Ok(block) => report.pass(
+            "Commitment",
+            format!("root committed to {:?} at block {}", registry, block),
+        ),
+        Err(e) => report.error("Commitment", format!("{:#}", e)),
+    }
+    Ok(report.finish())
+}
+
+/// Asks the registry, at the chain head, in which block `root` was committed.
+async fn committed_at_head(rpc_url: &str, registry: Address, root: H256) -> Result<u64> {
+    let client = BlockchainClient::new(rpc_url).await?;
+    let head = client.get_latest_block().await?;
+    let head_hash: H256 = client
+        .get_block_hash(head)
+        .await?
+        .context("Head block not found")?
+        .parse()
+        .context("Invalid block hash")?;
+    registry_committed_block(&client, registry, root, head_hash).await
+}
+
+fn check_content_hash(
+    report: &mut Report,
+    record: &serde_json::Value,
+    hash_version: u16,
+    content_hash: &str,
+) {
+    match hash_event_document(record, hash_version) {
+        Ok(hash) if hash == content_hash => report.pass(
+            "Content hash",
+            format!("{} (hash version {})", hash, hash_version),
+        ),
+        Ok(hash) => report.fail(
+            "Content hash",
+            format!("record hashes to {}, bundle claims {}", hash, content_hash),
+        ),
+        Err(e) => report.fail("Content hash", e),
+    }
+}
+
+/// Checks every record of a multiproof bundle, reporting the first mismatch
+/// rather than a line per record, then the multiproof.
+fn check_multiproof_bundle(report: &mut Report, bundle: &MultiproofBundle) {
+    let mismatch = bundle.records.iter().enumerate().find_map(|(i, record)| {
+        match hash_event_document(&record.record, record.hash_version) {
+            Ok(hash) if hash == record.content_hash => None,
+            Ok(hash) => Some(format!(
+                "record {} hashes to {}, bundle claims {}",
+                i, hash, record.content_hash
+            )),
+            Err(e) => Some(format!("record {}: {:#}", i, e)),
+        }
+    });
+    match mismatch {
+        Some(detail) => report.fail("Content hash", detail),
+        None => report.pass(
+            "Content hash",
+            format!("all {} records", bundle.records.len()),
+        ),
+    }
+
+    let leaves_match = bundle.records.len() == bundle.multiproof.leaves.len()
+        && bundle
+            .records
+            .iter()
+            .zip(&bundle.multiproof.leaves)
+            .all(|(record, leaf)| record.content_hash == *leaf);
+    if !leaves_match {
+        report.fail("Merkle proof", "records are not the multiproof's leaves");
+    } else if verify_multiproof(&bundle.multiproof, &bundle.root) {
+        report.pass(
+            "Merkle proof",
+            format!(
+                "{} of {} leaves lead to root {} (tree version {})",
+                bundle.multiproof.leaves.len(),
+                bundle.multiproof.leaf_count,
+                bundle.root,
+                bundle.multiproof.tree_version
+            ),
+        );
+    } else {
+        report.fail(
+            "Merkle proof",
+            format!("multiproof does not lead to root {}", bundle.root),
+        );
+    }
+}
+
+fn check_proof(
+    report: &mut Report,
+    leaf: &str,
+    proof: &[String],
+    root: &str,
+    leaf_index: usize,
+    leaf_count: usize,
+    tree_version: u16,
+) {
+    if verify_versioned_proof(leaf, proof, root, leaf_index, leaf_count, tree_version) {
+        report.pass(
+            "Merkle proof",
+            format!(
+                "leaf {} of {} leads to root {} (tree version {})",
+                leaf_index, leaf_count, root, tree_version
+            ),
+        );
+    } else {
+        report.fail(
+            "Merkle proof",
+            format!("path does not lead to root {}", root),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexnode_core::{
+        compute_versioned_root, generate_multiproof, generate_versioned_proof, to_canonical_cbor,
+        BundleRecord, EVENT_HASH_VERSION, MERKLE_TREE_VERSION,
+    };
+
+    fn records() -> Vec<BundleRecord> {
+        (0..5)
+            .map(|i| {
+                let record = serde_json::json!({ "chain": "ethereum", "log_index": i });
+                BundleRecord {
+                    content_hash: hash_event_document(&record, EVENT_HASH_VERSION).unwrap(),
+                    record,
+                    hash_version: EVENT_HASH_VERSION,
+                    ipfs_cid: None,
+                }
+            })
+            .collect()
+    }
+
+    fn leaves() -> Vec<String> {
+        records().into_iter().map(|r| r.content_hash).collect()
+    }
+
+    fn bundle() -> ProofBundle {
+        let leaves = leaves();
+        let record = records().swap_remove(2);
+        ProofBundle {
+            bundle_version: PROOF_BUNDLE_VERSION,
+            record: record.record,
+            hash_version: record.hash_version,
+            content_hash: record.content_hash,
+            ipfs_cid: None,
+            leaf_index: 2,
+            leaf_count: leaves.len(),
+            proof: generate_versioned_proof(&leaves, 2, MERKLE_TREE_VERSION).unwrap(),
+            root: compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            tree_version: MERKLE_TREE_VERSION,
+            chain_id: 31337,
+            registry_address: format!("{:?}", Address::repeat_byte(0x11)),
+            transaction_hash: format!("{:?}", H256::repeat_byte(0x22)),
+            block_number: 7,
+        }
+    }
+
+    fn multiproof_bundle() -> MultiproofBundle {
+        let leaves = leaves();
+        let multiproof = generate_multiproof(&leaves, &[1, 3], MERKLE_TREE_VERSION).unwrap();
+        let records = records();
+        MultiproofBundle {
+            bundle_version: PROOF_BUNDLE_VERSION,
+            records: vec![records[1].clone(), records[3].clone()],
+            multiproof,
+            root: compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            chain_id: 31337,
+            registry_address: format!("{:?}", Address::repeat_byte(0x11)),
+            transaction_hash: format!("{:?}", H256::repeat_byte(0x22)),
+            block_number: 7,
+        }
+    }
+
+    /// Writes `bytes` to a file named after the test in the temp directory.
+    fn write(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("indexnode-{}-{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn json(value: &impl serde::Serialize) -> Vec<u8> {
+        serde_json::to_vec_pretty(value).unwrap()
+    }
+
+    fn cbor(value: &impl serde::Serialize) -> Vec<u8> {
+        to_canonical_cbor(&serde_json::to_value(value).unwrap())
+    }
+
+    /// Verifies the bundle at `bundle` and removes it.
+    async fn verify(bundle: PathBuf, rpc_url: Option<&str>) -> i32 {
+        let code = run(VerifyArgs {
+            bundle: Some(bundle.clone()),
+            file: None,
+            proof: None,
+            rpc_url: rpc_url.map(str::to_string),
+            registry: None,
+        })
+        .await;
+        let _ = std::fs::remove_file(bundle);
+        code
+    }
+
+    #[tokio::test]
+    async fn test_intact_bundles_verify() {
+        assert_eq!(
+            verify(write("ok.json", &json(&bundle())), None).await,
+            EXIT_VERIFIED
+        );
+        assert_eq!(
+            verify(write("ok.cbor", &cbor(&bundle())), None).await,
+            EXIT_VERIFIED
+        );
+        assert_eq!(
+            verify(write("ok-multi.json", &json(&multiproof_bundle())), None).await,
+            EXIT_VERIFIED
+        );
+        assert_eq!(
+            verify(write("ok-multi.cbor", &cbor(&multiproof_bundle())), None).await,
+            EXIT_VERIFIED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tampered_bundles_fail() {
+        let mut record = bundle();
+        record.record["log_index"] = serde_json::json!(9);
+        let mut proof = bundle();
+        proof.proof[0] = hash_content(b"forged sibling");
+        let mut root = bundle();
+        root.root = hash_content(b"forged root");
+        for (name, tampered) in [("record", record), ("proof", proof), ("root", root)] {
+            let path = write(&format!("{}.json", name), &json(&tampered));
+            assert_eq!(verify(path, None).await, EXIT_FAILED, "{} in JSON", name);
+            let path = write(&format!("{}.cbor", name), &cbor(&tampered));
+            assert_eq!(verify(path, None).await, EXIT_FAILED, "{} in CBOR", name);
+        }
+
+        let mut multi = multiproof_bundle();
+        multi.records[0].record["log_index"] = serde_json::json!(9);
+        assert_eq!(
+            verify(write("multi.json", &json(&multi)), None).await,
+            EXIT_FAILED
+        );
+        let mut multi = multiproof_bundle();
+        multi.records.swap(0, 1);
+        assert_eq!(
+            verify(write("multi.cbor", &cbor(&multi)), None).await,
+            EXIT_FAILED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_input_is_invalid() {
+        let missing = std::env::temp_dir().join("indexnode-no-such-bundle.json");
+        assert_eq!(verify(missing, None).await, EXIT_INVALID_INPUT);
+        assert_eq!(
+            verify(write("garbage.cbor", b"\xff\x00garbage"), None).await,
+            EXIT_INVALID_INPUT
+        );
+        assert_eq!(
+            verify(write("partial.json", br#"{"bundle_version": 1}"#), None).await,
+            EXIT_INVALID_INPUT
+        );
+        let mut future = bundle();
+        future.bundle_version = PROOF_BUNDLE_VERSION + 1;
+        assert_eq!(
+            verify(write("future.json", &json(&future)), None).await,
+            EXIT_INVALID_INPUT
+        );
+        let neither = VerifyArgs {
+            bundle: None,
+            file: None,
+            proof: None,
+            rpc_url: None,
+            registry: None,
+        };
+        assert_eq!(run(neither).await, EXIT_INVALID_INPUT);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_chain_is_unconfirmed() {
+        // Nothing listens on port 1, so the offline checks pass and the
+        // commitment cannot be checked.
+        let path = write("unreachable.json", &json(&bundle()));
+        assert_eq!(
+            verify(path, Some("http://127.0.0.1:1")).await,
+            EXIT_UNCONFIRMED
+        );
+        let mut tampered = bundle();
+        tampered.proof[0] = hash_content(b"forged sibling");
+        let path = write("unreachable-tampered.json", &json(&tampered));
+        assert_eq!(verify(path, Some("http://127.0.0.1:1")).await, EXIT_FAILED);
+    }
+}
@@ -4,6 +4,7 @@ pragma solidity ^0.8.0;
 import "@openzeppelin/contracts-upgradeable/proxy/utils/Initializable.sol";
 import "@openzeppelin/contracts-upgradeable/proxy/utils/UUPSUpgradeable.sol";
 import "@openzeppelin/contracts-upgradeable/access/OwnableUpgradeable.sol";
+import "@openzeppelin/contracts/utils/cryptography/MerkleProof.sol";
 
 /**
  * @title TimestampRegistry
@@ -37,6 +38,22 @@ contract TimestampRegistry is Initializable, OwnableUpgradeable, UUPSUpgradeable
         return timestamps[contentHash];
     }
 
+    /// @notice Returns the block number when `root` was committed if `proof` shows
+    ///         that `contentHash` is one of its leaves, or 0 otherwise. Only roots
+    ///         of keccak256 sorted-pair trees (OpenZeppelin StandardMerkleTree
+    ///         over bytes32 values) can be checked this way.
+    function verifyInclusion(bytes32 root, bytes32[] calldata proof, bytes32 contentHash)
+        external
+        view
+        returns (uint256)
+    {
+        bytes32 leaf = keccak256(bytes.concat(keccak256(abi.encode(contentHash))));
+        if (!MerkleProof.verifyCalldata(proof, root, leaf)) {
+            return 0;
+        }
+        return timestamps[root];
+    }
+
     // ── UUPS ──────────────────────────────────────────────────────────────────
     function _authorizeUpgrade(address newImplementation) internal override onlyOwner {}
 
@@ -0,0 +1,264 @@
+use anyhow::{Context, Result};
+use ethers::abi::{Abi, Event, Function};
+use serde_json::Value;
+
+use crate::decoder::FunctionDecoder;
+
+/// A contract ABI validated for the ABI registry.
+///
+/// Jobs reference its events and functions by name; references are resolved
+/// to JSON ABI fragments, which keep parameter names and `indexed` flags, so
+/// decoding uses the ABI's definitions.
+#[derive(Debug, Clone)]
+pub struct ContractAbi {
+    abi: Abi,
+    json: Value,
+}
+
+impl ContractAbi {
+    /// Parses a JSON ABI array, or a compiler artifact with an `abi` field.
+    pub fn parse(value: &Value) -> Result<Self> {
+        let json = match value.get("abi") {
+            Some(abi) if value.is_object() => abi.clone(),
+            _ => value.clone(),
+        };
+        if !json.is_array() {
+            anyhow::bail!("An ABI must be a JSON array or an artifact with an `abi` array");
+        }
+        let abi: Abi = serde_json::from_value(json.clone()).context("Invalid JSON ABI")?;
+        if abi.events.is_empty() && abi.functions.is_empty() {
+            anyhow::bail!("The ABI defines no events or functions");
+        }
+        Ok(Self { abi, json })
+    }
+
+    /// The ABI as a JSON array.
+    pub fn json(&self) -> &Value {
+        &self.json
+    }
+
+    /// The events, in name order.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.abi.events()
+    }
+
+    /// The functions, in name order.
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.abi.functions()
+    }
+
+    /// Resolves an event reference from a job to a definition accepted by
+    /// `EventDecoder::parse`.
+    ///
+    /// A name (`Transfer`) or canonical signature of an event in the ABI
+    /// becomes its JSON fragment. Other signatures and full definitions are
+    /// returned unchanged; a name the ABI does not define is an error.
+    pub fn resolve_event(&self, reference: &str) -> Result<String> {
+        let reference = reference.trim();
+        let matches: Vec<&Event> = self
+            .events()
+            .filter(|e| e.name == reference || event_signature(e) == reference)
+            .collect();
+        match matches.as_slice() {
+            [event] => self.fragment("event", *event, |abi| abi.events().next().cloned()),
+            [] if is_bare_name(reference) => {
+                anyhow::bail!("Event '{}' is not defined in the ABI", reference)
+            }
+            [] => Ok(reference.to_string()),
+            _ => anyhow::bail!(
+                "Event '{}' is overloaded in the ABI; reference it by signature",
+                reference
+            ),
+        }
+    }
+
+    /// Resolves a function reference to a definition accepted by
+    /// `decoder::parse_function`, like `resolve_event` does for events.
+    pub fn resolve_function(&self, reference: &str) -> Result<String> {
+        let reference = reference.trim();
+        let matches: Vec<&Function> = self
+            .functions()
+            .filter(|f| f.name == reference || f.signature() == reference)
+            .collect();
+        match matches.as_slice() {
+            [function] => {
+                self.fragment("function", *function, |abi| abi.functions().next().cloned())
+            }
+            [] if is_bare_name(reference) => {
+                anyhow::bail!("Function '{}' is not defined in the ABI", reference)
+            }
+            [] => Ok(reference.to_string()),
+            _ => anyhow::bail!(
+                "Function '{}' is overloaded in the ABI; reference it by signature",
+                reference
+            ),
+        }
+    }
+
+    /// The ABI's own JSON entry for `item`, which keeps the names of tuple
+    /// components that `ethers::abi` types drop; re-serializing `item` is the
+    /// fallback.
+    fn fragment<T: PartialEq + serde::Serialize>(
+        &self,
+        kind: &str,
+        item: &T,
+        parse: impl Fn(&Abi) -> Option<T>,
+    ) -> Result<String> {
+        let entry = self.json.as_array().into_iter().flatten().find(|entry| {
+            entry.get("type").and_then(Value::as_str) == Some(kind)
+                && serde_json::from_value::<Abi>(Value::Array(vec![(*entry).clone()]))
+                    .ok()
+                    .and_then(|abi| parse(&abi))
+                    .is_some_and(|parsed| parsed == *item)
+        });
+        match entry {
+            Some(entry) => Ok(entry.to_string()),
+            None => fragment(kind, item),
+        }
+    }
+}
+
+/// Serializes an ABI item as a JSON fragment with its `type` set.
+fn fragment<T: serde::Serialize>(kind: &str, item: &T) -> Result<String> {
+    let mut value = serde_json::to_value(item)?;
+    if let Some(object) = value.as_object_mut() {
+        object.insert("type".to_string(), Value::String(kind.to_string()));
+    }
+    Ok(value.to_string())
+}
+
+/// Adds the JSON of each of `abis` to the function `definitions` a
+/// transaction job decodes calldata with, skipping definitions already
+/// given, and checks that `FunctionDecoder::parse` accepts the result.
+pub fn merge_function_definitions<'a>(
+    mut definitions: Vec<String>,
+    abis: impl IntoIterator<Item = &'a ContractAbi>,
+) -> Result<Vec<String>> {
+    for abi in abis {
+        let json = abi.json().to_string();
+        if !definitions.contains(&json) {
+            definitions.push(json);
+        }
+    }
+    FunctionDecoder::parse(&definitions)?;
+    Ok(definitions)
+}
+
+/// The canonical signature of an event, e.g. `Transfer(address,address,uint256)`.
+pub fn event_signature(event: &Event) -> String {
+    let types: Vec<String> = event.inputs.iter().map(|p| p.kind.to_string()).collect();
+    format!("{}({})", event.name, types.join(","))
+}
+
+/// Whether `reference` is a plain identifier rather than a signature or
+/// definition.
+pub fn is_bare_name(reference: &str) -> bool {
+    !reference.is_empty() && reference.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{parse_function, EventDecoder};
+    use ethers::abi::Token;
+    use ethers::types::{Address, U256};
+    use serde_json::json;
+
+    fn erc20() -> Value {
+        json!([
+            {"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+                {"name": "from", "type": "address", "indexed": true},
+                {"name": "to", "type": "address", "indexed": true},
+                {"name": "value", "type": "uint256", "indexed": false}
+            ]},
+            {"type": "function", "name": "balanceOf", "stateMutability": "view",
+             "inputs": [{"name": "owner", "type": "address"}],
+             "outputs": [{"name": "balance", "type": "uint256"}]}
+        ])
+    }
+
+    #[test]
+    fn test_parse_validates_abi() {
+        assert!(ContractAbi::parse(&erc20()).is_ok());
+        assert!(ContractAbi::parse(&json!({"abi": erc20(), "bytecode": "0x"})).is_ok());
+        assert!(ContractAbi::parse(&json!([])).is_err());
+        assert!(ContractAbi::parse(&json!({"name": "Transfer"})).is_err());
+        assert!(ContractAbi::parse(&json!([{"type": "event", "name": 5}])).is_err());
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let abi = ContractAbi::parse(&erc20()).unwrap();
+
+        let transfer = abi.resolve_event("Transfer").unwrap();
+        let decoder = EventDecoder::parse(&transfer).unwrap();
+        assert_eq!(decoder.signature(), "Transfer(address,address,uint256)");
+        assert!(decoder.indexed_topic("to").is_some());
+        assert_eq!(
+            abi.resolve_event("Transfer(address,address,uint256)")
+                .unwrap(),
+            transfer
+        );
+        assert_eq!(
+            abi.resolve_event("Approval(address,address,uint256)")
+                .unwrap(),
+            "Approval(address,address,uint256)"
+        );
+        assert!(abi.resolve_event("Approval").is_err());
+
+        let balance_of = parse_function(&abi.resolve_function("balanceOf").unwrap()).unwrap();
+        assert_eq!(balance_of.outputs[0].name, "balance");
+        assert!(abi.resolve_function("totalSupply").is_err());
+    }
+
+    #[test]
+    fn test_merge_function_definitions() {
+        let abi = ContractAbi::parse(&erc20()).unwrap();
+        let given = vec!["function transfer(address to, uint256 amount)".to_string()];
+        let merged = merge_function_definitions(given.clone(), [&abi, &abi]).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0], given[0]);
+
+        let decoder = FunctionDecoder::parse(&merged).unwrap();
+        let function = parse_function("function balanceOf(address)").unwrap();
+        let mut input = function.short_signature().to_vec();
+        input.extend(ethers::abi::encode(&[Token::Address(Address::zero())]));
+        let call = decoder
+            .decode_call(&input)
+            .expect("balanceOf comes from the ABI");
+        assert!(call["params"]["owner"].is_string());
+        let mut input = hex::decode("a9059cbb").unwrap();
+        input.extend(ethers::abi::encode(&[
+            Token::Address(Address::zero()),
+            Token::Uint(U256::one()),
+        ]));
+        assert_eq!(
+            decoder.decode_call(&input).unwrap()["params"]["amount"],
+            "1"
+        );
+
+        assert_eq!(
+            merge_function_definitions(merged.clone(), [&abi]).unwrap(),
+            merged
+        );
+        assert!(merge_function_definitions(vec!["function (".to_string()], [&abi]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_keeps_component_names() {
+        let abi = ContractAbi::parse(&json!([
+            {"type": "event", "name": "Filled", "anonymous": false, "inputs": [
+                {"name": "order", "type": "tuple", "indexed": false, "components": [
+                    {"name": "maker", "type": "address"},
+                    {"name": "amount", "type": "uint256"}
+                ]}
+            ]}
+        ]))
+        .unwrap();
+        let filled: Value = serde_json::from_str(&abi.resolve_event("Filled").unwrap()).unwrap();
+        assert_eq!(
+            filled["inputs"][0]["components"][1]["name"],
+            json!("amount")
+        );
+    }
+}
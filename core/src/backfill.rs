@@ -0,0 +1,330 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+/// Tuning for chunked `eth_getLogs` backfills.
+#[derive(Debug, Clone)]
+pub struct RangeScanConfig {
+    /// Number of blocks requested in the first chunk.
+    pub initial_range: u64,
+    /// Upper bound the chunk size may grow back to after successful requests.
+    pub max_range: u64,
+    /// Retries per chunk for transient errors (timeouts, rate limits, dropped connections).
+    pub max_retries: u32,
+    /// Base delay for exponential backoff between retries.
+    pub base_backoff: Duration,
+}
+
+impl Default for RangeScanConfig {
+    fn default() -> Self {
+        Self {
+            initial_range: 2_000,
+            max_range: 10_000,
+            max_retries: 5,
+            base_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Progress of a chunked scan over an inclusive block range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanCursor {
+    /// First block of the requested range.
+    pub from_block: u64,
+    /// Last block of the requested range (inclusive).
+    pub to_block: u64,
+    /// Next block that has not been scanned yet. Equals `to_block + 1` when done.
+    pub next_block: u64,
+    /// Inclusive ranges that could not be fetched after exhausting retries.
+    pub failed_ranges: Vec<(u64, u64)>,
+}
+
+impl ScanCursor {
+    pub fn new(from_block: u64, to_block: u64) -> Self {
+        Self {
+            from_block,
+            to_block,
+            next_block: from_block,
+            failed_ranges: Vec::new(),
+        }
+    }
+
+    /// True when every block in the range was fetched successfully.
+    pub fn is_complete(&self) -> bool {
+        self.next_block > self.to_block && self.failed_ranges.is_empty()
+    }
+
+    /// Human-readable list of unfetched ranges, e.g. `100-199, 400-450`.
+    pub fn describe_failed(&self) -> String {
+        self.failed_ranges
+            .iter()
+            .map(|(from, to)| format!("{}-{}", from, to))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Blocks per shard when a backfill job does not set `shard_blocks`.
+pub const DEFAULT_SHARD_BLOCKS: u64 = 100_000;
+
+/// An inclusive block range scanned as one shard of a backfill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRange {
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+/// Splits `[from_block, to_block]` into consecutive shards of at most
+/// `shard_blocks` blocks, in block order.
+pub fn plan_shards(from_block: u64, to_block: u64, shard_blocks: u64) -> Vec<BlockRange> {
+    let shard_blocks = shard_blocks.max(1);
+    let mut shards = Vec::new();
+    let mut start = from_block;
+    while start <= to_block {
+        let end = start.saturating_add(shard_blocks - 1).min(to_block);
+        shards.push(BlockRange {
+            from_block: start,
+            to_block: end,
+        });
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+    }
+    shards
+}
+
+/// Returns true when a provider error means the request covered too many blocks
+/// or returned too many logs, so the range should be split rather than retried.
+pub fn is_range_limit_error(message: &str) -> bool {
+    let msg = message.to_lowercase();
+    if is_rate_limit_error(&msg) {
+        return false;
+    }
+    [
+        "more than 10000 results",
+        "query returned more than",
+        "block range",
+        "range is too large",
+        "range too large",
+        "response size exceeded",
+        "response size is larger",
+        "too many results",
+        "log response size",
+        "limit exceeded",
+        "-32005",
+    ]
+    .iter()
+    .any(|needle| msg.contains(needle))
+}
+
+pub(crate) fn is_rate_limit_error(msg: &str) -> bool {
+    msg.contains("rate limit")
+        || msg.contains("too many requests")
+        || msg.contains("429")
+        || msg.contains("compute units")
+}
+
+/// Extracts a provider-suggested range such as Alchemy's
+/// `this block range should work: [0x1, 0x7cf]`.
+fn suggested_range_end(message: &str) -> Option<u64> {
+    let start = message.find('[')?;
+    let end = message[start..].find(']')? + start;
+    let mut parts = message[start + 1..end].split(',');
+    let _from = parts.next()?;
+    let to = parts.next()?.trim();
+    u64::from_str_radix(to.trim_start_matches("0x"), 16).ok()
+}
+
+/// Scans `[cursor.next_block, cursor.to_block]` in adaptive chunks.
+///
+/// `fetch` is called with inclusive `(from, to)` block ranges. Range-limit
+/// errors halve the chunk (or jump to a provider-suggested range) and retry
+/// immediately; other errors are retried with exponential backoff up to
+/// `config.max_retries` times, after which the range is recorded in
+/// `cursor.failed_ranges` and the scan moves on. The chunk size doubles again
+/// after each success, up to `config.max_range`.
+pub async fn scan_range<T, F, Fut>(
+    cursor: &mut ScanCursor,
+    config: &RangeScanConfig,
+    mut fetch: F,
+) -> Vec<T>
+where
+    F: FnMut(u64, u64) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    let mut results = Vec::new();
+    let mut chunk = config.initial_range.clamp(1, config.max_range.max(1));
+
+    while cursor.next_block <= cursor.to_block {
+        let start = cursor.next_block;
+        let mut end = start.saturating_add(chunk - 1).min(cursor.to_block);
+        let mut attempt = 0u32;
+
+        loop {
+            match fetch(start, end).await {
+                Ok(items) => {
+                    results.extend(items);
+                    cursor.next_block = end + 1;
+                    chunk = chunk.saturating_mul(2).min(config.max_range.max(1));
+                    break;
+                }
+                Err(e) => {
+                    let message = format!("{:#}", e);
+                    if is_range_limit_error(&message) && end > start {
+                        end = match suggested_range_end(&message) {
+                            Some(suggested) if suggested >= start && suggested < end => suggested,
+                            _ => start + (end - start) / 2,
+                        };
+                        chunk = end - start + 1;
+                        tracing::debug!(
+                            "Range limit hit; retrying blocks {}-{} ({} blocks)",
+                            start,
+                            end,
+                            chunk
+                        );
+                        continue;
+                    }
+
+                    attempt += 1;
+                    if attempt > config.max_retries {
+                        tracing::warn!(
+                            "Giving up on blocks {}-{} after {} retries: {}",
+                            start,
+                            end,
+                            config.max_retries,
+                            message
+                        );
+                        cursor.failed_ranges.push((start, end));
+                        cursor.next_block = end + 1;
+                        break;
+                    }
+
+                    let backoff = config.base_backoff * 2u32.saturating_pow(attempt - 1);
+                    let jitter =
+                        Duration::from_millis(fastrand::u64(0..=backoff.as_millis() as u64 / 4));
+                    tracing::warn!(
+                        "Fetching blocks {}-{} failed (attempt {}/{}), retrying in {:?}: {}",
+                        start,
+                        end,
+                        attempt,
+                        config.max_retries,
+                        backoff + jitter,
+                        message
+                    );
+                    tokio::time::sleep(backoff + jitter).await;
+                }
+            }
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn fast_config() -> RangeScanConfig {
+        RangeScanConfig {
+            initial_range: 1_000,
+            max_range: 1_000,
+            max_retries: 2,
+            base_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn test_plan_shards_covers_range() {
+        let shards = plan_shards(10, 34, 10);
+        assert_eq!(
+            shards,
+            vec![
+                BlockRange {
+                    from_block: 10,
+                    to_block: 19
+                },
+                BlockRange {
+                    from_block: 20,
+                    to_block: 29
+                },
+                BlockRange {
+                    from_block: 30,
+                    to_block: 34
+                },
+            ]
+        );
+        assert_eq!(plan_shards(5, 5, 100).len(), 1);
+        assert!(plan_shards(6, 5, 100).is_empty());
+        assert_eq!(plan_shards(u64::MAX - 1, u64::MAX, 1).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_splits_on_range_limit() {
+        let calls = RefCell::new(Vec::new());
+        let mut cursor = ScanCursor::new(0, 999);
+        let blocks = scan_range(&mut cursor, &fast_config(), |from, to| {
+            calls.borrow_mut().push((from, to));
+            async move {
+                if to - from >= 300 {
+                    anyhow::bail!("query returned more than 10000 results");
+                }
+                Ok((from..=to).collect::<Vec<u64>>())
+            }
+        })
+        .await;
+
+        assert!(cursor.is_complete());
+        assert_eq!(blocks, (0..=999).collect::<Vec<u64>>());
+        assert!(calls.borrow().iter().all(|(_, to)| *to <= 999));
+    }
+
+    #[tokio::test]
+    async fn test_uses_suggested_range() {
+        let mut cursor = ScanCursor::new(0, 4_999);
+        let blocks = scan_range(&mut cursor, &fast_config(), |from, to| async move {
+            if to - from >= 500 {
+                anyhow::bail!(
+                    "Log response size exceeded. this block range should work: [{:#x}, {:#x}]",
+                    from,
+                    from + 499
+                );
+            }
+            Ok(vec![(from, to)])
+        })
+        .await;
+
+        assert!(cursor.is_complete());
+        assert_eq!(blocks.first(), Some(&(0, 499)));
+    }
+
+    #[tokio::test]
+    async fn test_records_failed_ranges_after_retries() {
+        let mut cursor = ScanCursor::new(0, 2_499);
+        let blocks = scan_range(&mut cursor, &fast_config(), |from, to| async move {
+            if from == 1_000 {
+                anyhow::bail!("connection reset by peer");
+            }
+            Ok(vec![(from, to)])
+        })
+        .await;
+
+        assert!(!cursor.is_complete());
+        assert_eq!(cursor.failed_ranges, vec![(1_000, 1_999)]);
+        assert_eq!(cursor.next_block, 2_500);
+        assert_eq!(blocks, vec![(0, 999), (2_000, 2_499)]);
+    }
+
+    #[test]
+    fn test_rate_limit_is_not_a_range_error() {
+        assert!(is_range_limit_error(
+            "query returned more than 10000 results"
+        ));
+        assert!(is_range_limit_error("eth_getLogs block range is too large"));
+        assert!(!is_range_limit_error(
+            "429 Too Many Requests: rate limit exceeded"
+        ));
+    }
+}
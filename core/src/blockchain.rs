@@ -1,30 +1,267 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use ethers::prelude::*;
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
 
+use crate::backfill::{scan_range, RangeScanConfig, ScanCursor};
+use crate::canonical::to_canonical_json;
+use crate::chains::ChainConfig;
+use crate::decoder::{encode_topic, EventDecoder};
+use crate::job::TopicFilter;
 use crate::merkle::hash_content;
+use crate::rpc::FailoverTransport;
 
 /// Client for interacting with blockchain RPC nodes.
 pub struct BlockchainClient {
-    provider: Arc<Provider<Ws>>,
+    provider: Arc<Provider<FailoverTransport>>,
+    cross_check_heads: bool,
+    scan_config: RangeScanConfig,
+    confirmations: u64,
+    block_timestamps: Mutex<BlockTimestampCache>,
 }
 
-/// Filter parameters for blockchain event indexing.
+/// Number of block timestamps kept in memory per client.
+const BLOCK_TIMESTAMP_CACHE_SIZE: usize = 4_096;
+/// Concurrent `eth_getBlockByHash` requests when resolving timestamps.
+const BLOCK_FETCH_CONCURRENCY: usize = 8;
+
+/// Bounded FIFO cache of block timestamps keyed by block hash. Keying by hash
+/// rather than number keeps entries valid across reorgs.
+#[derive(Debug)]
+struct BlockTimestampCache {
+    entries: HashMap<H256, u64>,
+    order: VecDeque<H256>,
+    capacity: usize,
+}
+
+impl BlockTimestampCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn get(&self, hash: &H256) -> Option<u64> {
+        self.entries.get(hash).copied()
+    }
+
+    fn insert(&mut self, hash: H256, timestamp: u64) {
+        if self.entries.insert(hash, timestamp).is_some() {
+            return;
+        }
+        self.order.push_back(hash);
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+}
+
+/// Default confirmation depth for a chain: how many blocks behind the head a
+/// block must be before its events are treated as final.
+pub fn default_confirmations(chain: &str) -> u64 {
+    match chain {
+        "ethereum" => 12,
+        "polygon" => 128,
+        _ => 64,
+    }
+}
+
+/// Filter parameters for blockchain event indexing. Addresses, events and
+/// topic constraints are combined into a single `eth_getLogs` filter.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventFilter {
     /// The chain name (e.g., "ethereum", "polygon").
     pub chain: String,
-    /// The smart contract address to monitor.
-    pub contract_address: Address,
-    /// The signature of the event to filter (e.g., "Transfer(address,address,uint256)").
-    pub event_signature: String,
+    /// The smart contracts to monitor; empty matches every contract.
+    pub contract_addresses: Vec<Address>,
+    /// The events to match, each a bare signature (e.g., "Transfer(address,address,uint256)"),
+    /// a human-readable declaration with `indexed` parameter names, or a JSON ABI fragment.
+    pub event_signatures: Vec<String>,
+    /// Accepted values for topics 1-3, i.e. the indexed parameters. `None`
+    /// matches any value.
+    #[serde(default)]
+    pub topics: [Option<Vec<H256>>; 3],
     /// The starting block number for the filter.
     pub from_block: u64,
     /// The ending block number for the filter.
     pub to_block: u64,
 }
 
+impl EventFilter {
+    /// Matches `event_signatures` emitted by any of `contract_addresses` over
+    /// `[from_block, to_block]`.
+    pub fn new(
+        chain: &str,
+        contract_addresses: Vec<Address>,
+        event_signatures: Vec<String>,
+        from_block: u64,
+        to_block: u64,
+    ) -> Self {
+        Self {
+            chain: chain.to_string(),
+            contract_addresses,
+            event_signatures,
+            topics: Default::default(),
+            from_block,
+            to_block,
+        }
+    }
+
+    /// Checks that every log matching the filter's events can be told which
+    /// event it is: none is anonymous, and events sharing a `topic0` declare
+    /// different numbers of indexed parameters.
+    pub fn validate_events(&self) -> Result<()> {
+        ParsedFilter::new(self).map(|_| ())
+    }
+
+    /// Restricts indexed parameters to the values in `filters`. Each parameter
+    /// must be declared `indexed` at the same topic position in every event.
+    pub fn with_topic_filters(mut self, filters: &[TopicFilter]) -> Result<Self> {
+        let decoders = self
+            .event_signatures
+            .iter()
+            .map(|e| EventDecoder::parse(e))
+            .collect::<Result<Vec<_>>>()?;
+        for filter in filters {
+            if filter.values.is_empty() {
+                anyhow::bail!("Topic filter on '{}' has no values", filter.param);
+            }
+            let mut position = None;
+            let mut values = Vec::new();
+            for decoder in &decoders {
+                let (topic, kind) = decoder.indexed_topic(&filter.param).with_context(|| {
+                    format!(
+                        "{} has no indexed parameter '{}' (declare it as `event {}(... indexed {} ...)`)",
+                        decoder.name(),
+                        filter.param,
+                        decoder.name(),
+                        filter.param
+                    )
+                })?;
+                if position.is_some_and(|p| p != topic) {
+                    anyhow::bail!(
+                        "Parameter '{}' is at different topic positions across events",
+                        filter.param
+                    );
+                }
+                position = Some(topic);
+                for value in &filter.values {
+                    let topic = encode_topic(kind, value)
+                        .with_context(|| format!("In topic filter on '{}'", filter.param))?;
+                    if !values.contains(&topic) {
+                        values.push(topic);
+                    }
+                }
+            }
+            let Some(position @ 1..=3) = position else {
+                anyhow::bail!("Parameter '{}' cannot be filtered", filter.param);
+            };
+            let slot = &mut self.topics[position - 1];
+            if slot.is_some() {
+                anyhow::bail!("More than one filter on topic {}", position);
+            }
+            *slot = Some(values);
+        }
+        Ok(self)
+    }
+}
+
+/// An `EventFilter` with its event definitions parsed, keyed by `topic0`.
+/// Events that share a `topic0`, such as the ERC-20 and ERC-721 `Transfer`,
+/// are told apart by how many topics their logs have.
+pub(crate) struct ParsedFilter<'a> {
+    filter: &'a EventFilter,
+    decoders: HashMap<H256, Vec<EventDecoder>>,
+}
+
+impl<'a> ParsedFilter<'a> {
+    /// Parses the filter's event definitions. Anonymous events have no
+    /// `topic0` to match logs by, and events whose logs cannot be told apart
+    /// by `topic0` and topic count are ambiguous; both are rejected.
+    pub(crate) fn new(filter: &'a EventFilter) -> Result<Self> {
+        if filter.event_signatures.is_empty() {
+            anyhow::bail!("No event signatures in filter");
+        }
+        let mut decoders: HashMap<H256, Vec<EventDecoder>> = HashMap::new();
+        for signature in &filter.event_signatures {
+            let decoder = EventDecoder::parse(signature)?;
+            if decoder.event().anonymous {
+                anyhow::bail!(
+                    "Event {} is anonymous; its logs have no topic0 to match them by",
+                    decoder.name()
+                );
+            }
+            let same_topic0 = decoders.entry(decoder.topic0()).or_default();
+            if same_topic0.iter().any(|d| d.event() == decoder.event()) {
+                continue;
+            }
+            if same_topic0.iter().any(|d| {
+                d.topic_count().is_none()
+                    || decoder.topic_count().is_none()
+                    || d.topic_count() == decoder.topic_count()
+            }) {
+                anyhow::bail!(
+                    "Definitions of {} cannot be told apart; each must declare a different \
+                     number of indexed parameters",
+                    decoder.signature()
+                );
+            }
+            same_topic0.push(decoder);
+        }
+        Ok(Self { filter, decoders })
+    }
+
+    /// The `eth_getLogs` filter for `[from_block, to_block]`.
+    pub(crate) fn to_ethers(&self, from_block: u64, to_block: u64) -> Filter {
+        let mut ethers_filter = Filter::new()
+            .from_block(from_block)
+            .to_block(to_block)
+            .topic0(self.decoders.keys().copied().collect::<Vec<_>>());
+        if !self.filter.contract_addresses.is_empty() {
+            ethers_filter = ethers_filter.address(self.filter.contract_addresses.clone());
+        }
+        let [topic1, topic2, topic3] = &self.filter.topics;
+        if let Some(values) = topic1 {
+            ethers_filter = ethers_filter.topic1(values.clone());
+        }
+        if let Some(values) = topic2 {
+            ethers_filter = ethers_filter.topic2(values.clone());
+        }
+        if let Some(values) = topic3 {
+            ethers_filter = ethers_filter.topic3(values.clone());
+        }
+        ethers_filter
+    }
+
+    /// The decoder for `log`, if its `topic0` and topic count are those of
+    /// one of the filter's events.
+    pub(crate) fn decoder(&self, log: &Log) -> Option<&EventDecoder> {
+        self.decoders.get(log.topics.first()?)?.iter().find(|d| {
+            d.topic_count()
+                .is_none_or(|count| count == log.topics.len())
+        })
+    }
+
+    /// Whether `log` matches the filter's addresses, events and topics.
+    pub(crate) fn matches(&self, log: &Log) -> bool {
+        let address_ok = self.filter.contract_addresses.is_empty()
+            || self.filter.contract_addresses.contains(&log.address);
+        let topics_ok = self.filter.topics.iter().enumerate().all(|(i, values)| {
+            values
+                .as_ref()
+                .is_none_or(|v| log.topics.get(i + 1).is_some_and(|t| v.contains(t)))
+        });
+        address_ok && topics_ok && self.decoder(log).is_some()
+    }
+}
+
 /// Represents an indexed blockchain event.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockchainEvent {
@@ -32,36 +269,210 @@ pub struct BlockchainEvent {
     pub chain: String,
     /// The address of the contract that emitted the event.
     pub contract_address: String,
-    /// The name/signature of the event.
+    /// The name of the event as declared in its ABI (e.g., "Transfer").
     pub event_name: String,
     /// The block number where the event was emitted.
     pub block_number: u64,
     /// The hash of the transaction that emitted the event.
     pub transaction_hash: String,
-    /// The raw data of the event as a JSON value.
+    /// The position of the log within its block. Together with `chain` and
+    /// `transaction_hash` it uniquely identifies the event.
+    pub log_index: u64,
+    /// The raw log topics; `topics[0]` is the event signature hash unless the
+    /// event is anonymous.
+    pub topics: Vec<String>,
+    /// The hash of the block containing the event, used to detect reorgs.
+    pub block_hash: String,
+    /// The timestamp of the block containing the event.
+    pub block_timestamp: DateTime<Utc>,
+    /// True when the node reported the log as removed by a reorg.
+    pub removed: bool,
+    /// The decoded event parameters together with the raw topics and data.
     pub event_data: serde_json::Value,
-    /// A cryptographic hash of the event content for integrity verification.
+    /// A cryptographic hash of the event content for integrity verification,
+    /// computed as described by `hash_version`.
     pub content_hash: String,
+    /// How `content_hash` was computed; see `BlockchainEvent::compute_content_hash`.
+    /// Events serialized before versioning used version 1.
+    #[serde(default = "legacy_hash_version")]
+    pub hash_version: u16,
+}
+
+/// Event content hashes computed from Rust's `Debug` rendering of `event_data`.
+/// They can only be recomputed by this codebase and are kept for verifying
+/// events indexed before canonical hashing.
+pub const LEGACY_EVENT_HASH_VERSION: u16 = 1;
+/// Event content hashes computed from the canonical document; the version
+/// stamped on newly indexed events.
+pub const EVENT_HASH_VERSION: u16 = 2;
+
+fn legacy_hash_version() -> u16 {
+    LEGACY_EVENT_HASH_VERSION
+}
+
+/// Builds the canonical document of an event from its identifying fields and
+/// decoded data; see `BlockchainEvent::canonical_document`.
+pub fn canonical_event_document(
+    chain: &str,
+    contract_address: &str,
+    transaction_hash: &str,
+    log_index: u64,
+    data: serde_json::Value,
+) -> serde_json::Value {
+    serde_json::json!({
+        "chain": chain.to_lowercase(),
+        "contract_address": contract_address.to_lowercase(),
+        "transaction_hash": transaction_hash.to_lowercase(),
+        "log_index": log_index,
+        "data": data,
+    })
+}
+
+/// Computes the content hash of an event from its canonical document (see
+/// `BlockchainEvent::canonical_document`) under `version`.
+///
+/// Version 2 is the hex SHA-256 of the document serialized with the JSON
+/// Canonicalization Scheme (RFC 8785). Version 1 hashes the `Debug` rendering
+/// of the document's `data`.
+pub fn hash_event_document(document: &serde_json::Value, version: u16) -> Result<String> {
+    match version {
+        LEGACY_EVENT_HASH_VERSION => Ok(hash_content(format!("{:?}", document["data"]).as_bytes())),
+        EVENT_HASH_VERSION => Ok(hash_content(to_canonical_json(document).as_bytes())),
+        _ => anyhow::bail!("Unknown event hash version {}", version),
+    }
+}
+
+impl BlockchainEvent {
+    /// The document hashed by `EVENT_HASH_VERSION`: a JSON object with the
+    /// lowercased `chain`, `contract_address` and `transaction_hash`, the
+    /// numeric `log_index`, and `event_data` as `data`. Block fields are left
+    /// out, so a log re-included in another block after a reorg keeps its hash.
+    pub fn canonical_document(&self) -> serde_json::Value {
+        canonical_event_document(
+            &self.chain,
+            &self.contract_address,
+            &self.transaction_hash,
+            self.log_index,
+            self.event_data.clone(),
+        )
+    }
+
+    /// Computes the content hash of the event under `version`; see
+    /// `hash_event_document`.
+    pub fn compute_content_hash(&self, version: u16) -> Result<String> {
+        hash_event_document(&self.canonical_document(), version)
+    }
+
+    /// Whether `content_hash` matches the event's content under `hash_version`.
+    pub fn verify_content_hash(&self) -> bool {
+        self.compute_content_hash(self.hash_version)
+            .is_ok_and(|hash| hash == self.content_hash)
+    }
 }
 
 impl BlockchainClient {
     /// Creates a new `BlockchainClient` connected to the specified RPC URL.
     pub async fn new(rpc_url: &str) -> Result<Self> {
-        let provider = Provider::<Ws>::connect(rpc_url)
+        let transport = FailoverTransport::connect(&[rpc_url.to_string()], 0)
             .await
             .context("Failed to connect to blockchain RPC")?;
-        Ok(Self {
-            provider: Arc::new(provider),
-        })
+        Ok(Self::with_transport(transport))
+    }
+
+    fn with_transport(transport: FailoverTransport) -> Self {
+        Self {
+            provider: Arc::new(Provider::new(transport)),
+            cross_check_heads: false,
+            scan_config: RangeScanConfig::default(),
+            confirmations: 0,
+            block_timestamps: Mutex::new(BlockTimestampCache::new(BLOCK_TIMESTAMP_CACHE_SIZE)),
+        }
     }
 
-    /// Fetches events from the blockchain based on the provided filter.
+    /// Connects to all RPC endpoints of `chain`, failing over between them per
+    /// request, and applies its confirmation depth and log-range limits.
+    ///
+    /// Fails if no endpoint is reachable or an endpoint reports a chain ID other
+    /// than the configured one.
+    pub async fn from_config(chain: &ChainConfig) -> Result<Self> {
+        let transport = FailoverTransport::connect(&chain.rpc_urls, chain.max_head_lag)
+            .await
+            .with_context(|| format!("Failed to connect to {}", chain.name))?;
+        transport
+            .verify_chain_id(chain.chain_id)
+            .await
+            .with_context(|| format!("{}: chain ID check failed", chain.name))?;
+        let mut client = Self::with_transport(transport)
+            .with_confirmations(chain.confirmations)
+            .with_scan_config(chain.scan_config());
+        client.cross_check_heads = chain.cross_check_heads;
+        Ok(client)
+    }
+
+    fn transport(&self) -> &FailoverTransport {
+        (*self.provider).as_ref()
+    }
+
+    /// Returns the EIP-155 chain ID reported by the node.
+    pub async fn chain_id(&self) -> Result<u64> {
+        let chain_id = self
+            .provider
+            .get_chainid()
+            .await
+            .context("Failed to get chain ID")?;
+        Ok(chain_id.as_u64())
+    }
+
+    /// Overrides the chunking and retry behaviour used by `get_events_chunked`.
+    pub fn with_scan_config(mut self, scan_config: RangeScanConfig) -> Self {
+        self.scan_config = scan_config;
+        self
+    }
+
+    /// Sets how many blocks behind the head a block must be to count as final.
+    pub fn with_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    /// The confirmation depth applied by `get_finalized_block`.
+    pub fn confirmations(&self) -> u64 {
+        self.confirmations
+    }
+
+    /// Fetches events from the blockchain based on the provided filter and decodes
+    /// them against the filter's event definition.
     pub async fn get_events(&self, filter: EventFilter) -> Result<Vec<BlockchainEvent>> {
-        let ethers_filter = ethers::types::Filter::new()
-            .address(filter.contract_address)
-            .from_block(filter.from_block)
-            .to_block(filter.to_block)
-            .event(&filter.event_signature);
+        let parsed = ParsedFilter::new(&filter)?;
+        self.fetch_events(&parsed, filter.from_block, filter.to_block)
+            .await
+    }
+
+    /// Fetches events over the filter's block range in adaptive chunks, splitting
+    /// ranges the provider rejects as too large and retrying transient failures.
+    ///
+    /// Returns the events that were fetched together with the scan cursor. Callers
+    /// must check `ScanCursor::is_complete` before treating the range as indexed.
+    pub async fn get_events_chunked(
+        &self,
+        filter: &EventFilter,
+    ) -> Result<(Vec<BlockchainEvent>, ScanCursor)> {
+        let parsed = ParsedFilter::new(filter)?;
+        let mut cursor = ScanCursor::new(filter.from_block, filter.to_block);
+        let events = scan_range(&mut cursor, &self.scan_config, |from, to| {
+            self.fetch_events(&parsed, from, to)
+        })
+        .await;
+        Ok((events, cursor))
+    }
+
+    async fn fetch_events(
+        &self,
+        filter: &ParsedFilter<'_>,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<BlockchainEvent>> {
+        let ethers_filter = filter.to_ethers(from_block, to_block);
 
         let logs = self
             .provider
@@ -69,25 +480,90 @@ impl BlockchainClient {
             .await
             .context("Failed to fetch logs from provider")?;
 
-        let mut events = Vec::new();
-        for log in logs {
-            let event = BlockchainEvent {
-                chain: filter.chain.clone(),
-                contract_address: format!("{:?}", log.address),
-                event_name: filter.event_signature.clone(),
-                block_number: log.block_number.context("Missing block number")?.as_u64(),
-                transaction_hash: format!("{:?}", log.transaction_hash.context("Missing tx hash")?),
-                event_data: serde_json::to_value(&log.data).unwrap_or(serde_json::Value::Null),
-                content_hash: hash_content(log.data.as_ref()),
+        let block_hashes: Vec<H256> = logs.iter().filter_map(|log| log.block_hash).collect();
+        let timestamps = self.get_block_timestamps(&block_hashes).await?;
+
+        let mut events = Vec::with_capacity(logs.len());
+        for log in &logs {
+            let Some(decoder) = filter.decoder(log) else {
+                continue;
             };
-            events.push(event);
+            let block_hash = log.block_hash.context("Missing block hash")?;
+            let block_timestamp = timestamps
+                .get(&block_hash)
+                .copied()
+                .context("Missing block timestamp")?;
+            events.push(decode_log(
+                &filter.filter.chain,
+                decoder,
+                log,
+                block_timestamp,
+            )?);
         }
 
         Ok(events)
     }
 
+    /// Resolves the timestamps of the given blocks, fetching each distinct
+    /// block at most once and serving repeats from the per-client cache.
+    async fn get_block_timestamps(&self, block_hashes: &[H256]) -> Result<HashMap<H256, u64>> {
+        let mut timestamps = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let cache = self
+                .block_timestamps
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Block timestamp cache poisoned"))?;
+            for hash in block_hashes {
+                if timestamps.contains_key(hash) || missing.contains(hash) {
+                    continue;
+                }
+                match cache.get(hash) {
+                    Some(ts) => {
+                        timestamps.insert(*hash, ts);
+                    }
+                    None => missing.push(*hash),
+                }
+            }
+        }
+
+        let fetched: Vec<(H256, u64)> = futures::stream::iter(missing)
+            .map(|hash| async move {
+                let block = self
+                    .provider
+                    .get_block(hash)
+                    .await
+                    .with_context(|| format!("Failed to fetch block {:?}", hash))?
+                    .with_context(|| format!("Block {:?} not found", hash))?;
+                Ok::<_, anyhow::Error>((hash, block.timestamp.as_u64()))
+            })
+            .buffer_unordered(BLOCK_FETCH_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut cache = self
+            .block_timestamps
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Block timestamp cache poisoned"))?;
+        for (hash, ts) in fetched {
+            cache.insert(hash, ts);
+            timestamps.insert(hash, ts);
+        }
+        Ok(timestamps)
+    }
+
     /// Returns the latest block number from the connected blockchain.
+    ///
+    /// With head cross-checking enabled every endpoint is queried, endpoints
+    /// trailing the best head are deprioritised, and the best head is returned.
     pub async fn get_latest_block(&self) -> Result<u64> {
+        if self.cross_check_heads {
+            return self
+                .transport()
+                .cross_check_heads()
+                .await
+                .context("Failed to get latest block number");
+        }
         let block_number = self
             .provider
             .get_block_number()
@@ -95,12 +571,155 @@ impl BlockchainClient {
             .context("Failed to get latest block number")?;
         Ok(block_number.as_u64())
     }
+
+    /// Returns the highest block considered final under this client's
+    /// confirmation depth.
+    pub async fn get_finalized_block(&self) -> Result<u64> {
+        Ok(self
+            .get_latest_block()
+            .await?
+            .saturating_sub(self.confirmations))
+    }
+
+    /// Returns the canonical hash of block `number`, or `None` if the node
+    /// does not have that block.
+    pub async fn get_block_hash(&self, number: u64) -> Result<Option<String>> {
+        let block = self
+            .provider
+            .get_block(number)
+            .await
+            .with_context(|| format!("Failed to fetch block {}", number))?;
+        Ok(block.and_then(|b| b.hash).map(|h| format!("{:?}", h)))
+    }
+
+    /// Returns block `number` with its full transactions, or `None` if the
+    /// node does not have that block.
+    pub async fn get_block_with_transactions(
+        &self,
+        number: u64,
+    ) -> Result<Option<Block<Transaction>>> {
+        self.provider
+            .get_block_with_txs(number)
+            .await
+            .with_context(|| format!("Failed to fetch block {} with transactions", number))
+    }
+
+    /// Returns the receipt of a mined transaction.
+    pub async fn get_transaction_receipt(&self, hash: H256) -> Result<Option<TransactionReceipt>> {
+        self.provider
+            .get_transaction_receipt(hash)
+            .await
+            .with_context(|| format!("Failed to fetch receipt for {:?}", hash))
+    }
+
+    /// Returns the call trace of a transaction from `debug_traceTransaction`
+    /// with the built-in `callTracer`.
+    pub async fn trace_transaction(&self, hash: H256) -> Result<serde_json::Value> {
+        self.provider
+            .request(
+                "debug_traceTransaction",
+                (hash, serde_json::json!({ "tracer": "callTracer" })),
+            )
+            .await
+            .with_context(|| format!("Failed to trace transaction {:?}", hash))
+    }
+
+    /// Runs `eth_call` at the block with hash `block_hash`. The block is
+    /// addressed by hash (EIP-1898) so the result cannot come from a block
+    /// that replaced it in a reorg.
+    pub async fn call_contract(&self, to: Address, data: Bytes, block_hash: H256) -> Result<Bytes> {
+        let tx: ethers::types::transaction::eip2718::TypedTransaction =
+            TransactionRequest::new().to(to).data(data).into();
+        self.provider
+            .call(&tx, Some(BlockId::Hash(block_hash)))
+            .await
+            .with_context(|| format!("eth_call to {:?} at block {:?} failed", to, block_hash))
+    }
+
+    /// Subscribes to new block headers on the healthiest WebSocket endpoint
+    /// and yields each new head's block number.
+    ///
+    /// The subscription uses its own connection; the stream ends if that
+    /// connection drops, and the caller is expected to subscribe again.
+    pub async fn subscribe_new_heads(&self) -> Result<futures::stream::BoxStream<'static, u64>> {
+        let mut last_error = None;
+        for url in self.transport().ws_urls() {
+            match subscribe_new_heads_on(&url).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No WebSocket RPC endpoint configured")))
+    }
+}
+
+async fn subscribe_new_heads_on(url: &str) -> Result<futures::stream::BoxStream<'static, u64>> {
+    let ws = Ws::connect(url)
+        .await
+        .context("Failed to connect for new-heads subscription")?;
+    let id: U256 = JsonRpcClient::request(&ws, "eth_subscribe", ["newHeads"])
+        .await
+        .context("Failed to subscribe to new heads")?;
+    let notifications =
+        PubsubClient::subscribe(&ws, id).context("Failed to subscribe to new heads")?;
+    // The stream owns a handle to the connection to keep it open.
+    Ok(notifications
+        .filter_map(move |raw| {
+            let _connection = &ws;
+            let number = serde_json::from_str::<Block<TxHash>>(raw.get())
+                .ok()
+                .and_then(|block| block.number)
+                .map(|n| n.as_u64());
+            futures::future::ready(number)
+        })
+        .boxed())
+}
+
+/// Builds a `BlockchainEvent` from a raw log matched by `decoder`.
+/// `block_timestamp` is the Unix timestamp of the log's block.
+pub(crate) fn decode_log(
+    chain: &str,
+    decoder: &EventDecoder,
+    log: &Log,
+    block_timestamp: u64,
+) -> Result<BlockchainEvent> {
+    let mut event = BlockchainEvent {
+        chain: chain.to_string(),
+        contract_address: format!("{:?}", log.address),
+        event_name: decoder.name().to_string(),
+        block_number: log.block_number.context("Missing block number")?.as_u64(),
+        transaction_hash: format!("{:?}", log.transaction_hash.context("Missing tx hash")?),
+        log_index: log.log_index.context("Missing log index")?.as_u64(),
+        topics: log.topics.iter().map(|t| format!("{:?}", t)).collect(),
+        block_hash: format!("{:?}", log.block_hash.context("Missing block hash")?),
+        block_timestamp: DateTime::from_timestamp(block_timestamp as i64, 0)
+            .context("Invalid block timestamp")?,
+        removed: log.removed.unwrap_or(false),
+        event_data: decoder.event_data(&log.topics, &log.data),
+        content_hash: String::new(),
+        hash_version: EVENT_HASH_VERSION,
+    };
+    event.content_hash = event.compute_content_hash(EVENT_HASH_VERSION)?;
+    Ok(event)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_block_timestamp_cache_evicts_oldest() {
+        let mut cache = BlockTimestampCache::new(2);
+        cache.insert(H256::repeat_byte(1), 100);
+        cache.insert(H256::repeat_byte(2), 112);
+        cache.insert(H256::repeat_byte(1), 100);
+        cache.insert(H256::repeat_byte(3), 124);
+
+        assert_eq!(cache.get(&H256::repeat_byte(1)), None);
+        assert_eq!(cache.get(&H256::repeat_byte(2)), Some(112));
+        assert_eq!(cache.get(&H256::repeat_byte(3)), Some(124));
+    }
+
     #[tokio::test]
     async fn test_rpc_connection() {
         // Use a public Sepolia RPC for testing if available, or skip if not in CI
@@ -126,15 +745,213 @@ mod tests {
                 .get_latest_block()
                 .await
                 .expect("Failed to get latest block in test");
-            let filter = EventFilter {
-                chain: "ethereum".to_string(),
-                contract_address: address,
-                event_signature: "Transfer(address,address,uint256)".to_string(),
-                from_block: latest - 100,
-                to_block: latest,
-            };
+            let filter = EventFilter::new(
+                "ethereum",
+                vec![address],
+                vec!["Transfer(address,address,uint256)".to_string()],
+                latest - 100,
+                latest,
+            );
             let events = client.get_events(filter).await;
             assert!(events.is_ok());
         }
     }
+
+    #[test]
+    fn test_topic_filters_combine_into_one_filter() {
+        const TRANSFER: &str =
+            "event Transfer(address indexed from, address indexed to, uint256 value)";
+        const APPROVAL: &str =
+            "event Approval(address indexed owner, address indexed spender, uint256 value)";
+        let recipient = Address::repeat_byte(0x22);
+        let filter = EventFilter::new(
+            "ethereum",
+            vec![Address::repeat_byte(0xaa), Address::repeat_byte(0xbb)],
+            vec![TRANSFER.to_string()],
+            0,
+            100,
+        )
+        .with_topic_filters(&[TopicFilter {
+            param: "to".to_string(),
+            values: vec![format!("{:?}", recipient)],
+        }])
+        .expect("'to' is indexed");
+        assert_eq!(filter.topics[1], Some(vec![H256::from(recipient)]));
+
+        let parsed = ParsedFilter::new(&filter).unwrap();
+        let ethers_filter = parsed.to_ethers(0, 100);
+        assert!(matches!(
+            ethers_filter.address,
+            Some(ValueOrArray::Array(ref addresses)) if addresses.len() == 2
+        ));
+
+        let log = |to: Address| Log {
+            address: Address::repeat_byte(0xbb),
+            topics: vec![
+                EventDecoder::parse(TRANSFER).unwrap().topic0(),
+                H256::from(Address::repeat_byte(0x11)),
+                H256::from(to),
+            ],
+            ..Default::default()
+        };
+        assert!(parsed.matches(&log(recipient)));
+        assert!(!parsed.matches(&log(Address::repeat_byte(0x33))));
+
+        // `from` and `owner` share topic 1, but `to` must exist in every event.
+        let mixed = EventFilter::new(
+            "ethereum",
+            Vec::new(),
+            vec![TRANSFER.to_string(), APPROVAL.to_string()],
+            0,
+            100,
+        );
+        assert!(mixed
+            .clone()
+            .with_topic_filters(&[TopicFilter {
+                param: "to".to_string(),
+                values: vec![format!("{:?}", recipient)],
+            }])
+            .is_err());
+        assert!(EventFilter::new(
+            "ethereum",
+            Vec::new(),
+            vec!["Transfer(address,address,uint256)".to_string()],
+            0,
+            100
+        )
+        .with_topic_filters(&[TopicFilter {
+            param: "arg1".to_string(),
+            values: vec![format!("{:?}", recipient)],
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn test_events_sharing_topic0_are_told_apart_by_topic_count() {
+        const ERC20_TRANSFER: &str =
+            "event Transfer(address indexed from, address indexed to, uint256 value)";
+        const ERC721_TRANSFER: &str =
+            "event Transfer(address indexed from, address indexed to, uint256 indexed tokenId)";
+        let filter = EventFilter::new(
+            "ethereum",
+            Vec::new(),
+            vec![ERC20_TRANSFER.to_string(), ERC721_TRANSFER.to_string()],
+            0,
+            100,
+        );
+        let parsed = ParsedFilter::new(&filter).unwrap();
+        let topic0 = EventDecoder::parse(ERC20_TRANSFER).unwrap().topic0();
+        let mut topics = vec![
+            topic0,
+            H256::from(Address::repeat_byte(0x11)),
+            H256::from(Address::repeat_byte(0x22)),
+        ];
+        let amount = Log {
+            topics: topics.clone(),
+            data: ethers::abi::encode(&[ethers::abi::Token::Uint(U256::from(5))]).into(),
+            ..Default::default()
+        };
+        topics.push(H256::from_low_u64_be(7));
+        let token = Log {
+            topics,
+            ..Default::default()
+        };
+
+        let decode = |log: &Log| {
+            let decoder = parsed.decoder(log).expect("Log has a decoder");
+            decoder.decode(&log.topics, &log.data).unwrap()
+        };
+        assert_eq!(decode(&amount)["value"], "5");
+        assert_eq!(decode(&token)["tokenId"], "7");
+        assert!(decode(&token).get("value").is_none());
+
+        // A log with a topic count neither declares is not one of them.
+        let mut other = token.clone();
+        other.topics.pop();
+        other.topics.pop();
+        assert!(parsed.decoder(&other).is_none());
+        assert!(!parsed.matches(&other));
+    }
+
+    #[test]
+    fn test_anonymous_and_ambiguous_events_are_rejected() {
+        let parse = |signatures: &[&str]| {
+            let filter = EventFilter::new(
+                "ethereum",
+                Vec::new(),
+                signatures.iter().map(|s| s.to_string()).collect(),
+                0,
+                100,
+            );
+            filter.validate_events()
+        };
+        const TRANSFER: &str =
+            "event Transfer(address indexed from, address indexed to, uint256 value)";
+
+        let anonymous = r#"{"type":"event","name":"Log","anonymous":true,"inputs":[{"name":"x","type":"uint256","indexed":true}]}"#;
+        assert!(parse(&[anonymous])
+            .unwrap_err()
+            .to_string()
+            .contains("anonymous"));
+
+        // A bare signature matches logs with any topic count.
+        assert!(parse(&[TRANSFER, "Transfer(address,address,uint256)"]).is_err());
+        // The same topic count with different parameters indexed.
+        assert!(parse(&[
+            TRANSFER,
+            "event Transfer(address indexed from, address to, uint256 indexed value)"
+        ])
+        .is_err());
+        // Repeating a definition is not ambiguous.
+        assert!(parse(&[TRANSFER, TRANSFER]).is_ok());
+    }
+
+    #[test]
+    fn test_content_hash_versions() {
+        const TRANSFER: &str =
+            "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = EventDecoder::parse(TRANSFER).unwrap();
+        let log = Log {
+            address: Address::repeat_byte(0xaa),
+            topics: vec![
+                decoder.topic0(),
+                H256::from(Address::repeat_byte(0x11)),
+                H256::from(Address::repeat_byte(0x22)),
+            ],
+            data: Bytes::from(H256::from_low_u64_be(42).as_bytes().to_vec()),
+            block_number: Some(7.into()),
+            block_hash: Some(H256::repeat_byte(2)),
+            transaction_hash: Some(H256::repeat_byte(1)),
+            log_index: Some(3.into()),
+            ..Default::default()
+        };
+        let mut event = decode_log("Ethereum", &decoder, &log, 0).unwrap();
+        assert_eq!(event.hash_version, EVENT_HASH_VERSION);
+        assert!(event.verify_content_hash());
+
+        let document = to_canonical_json(&event.canonical_document());
+        assert!(document.starts_with(&format!(
+            "{{\"chain\":\"ethereum\",\"contract_address\":\"{:?}\",\"data\":{{",
+            log.address
+        )));
+        assert!(document.ends_with(&format!(
+            "}},\"log_index\":3,\"transaction_hash\":\"{:?}\"}}",
+            H256::repeat_byte(1)
+        )));
+        assert_eq!(event.content_hash, hash_content(document.as_bytes()));
+
+        // The block is not part of the content.
+        event.block_number = 8;
+        event.block_hash = format!("{:?}", H256::repeat_byte(3));
+        assert!(event.verify_content_hash());
+
+        let legacy = hash_content(format!("{:?}", event.event_data).as_bytes());
+        event.content_hash = legacy;
+        assert!(!event.verify_content_hash());
+        event.hash_version = LEGACY_EVENT_HASH_VERSION;
+        assert!(event.verify_content_hash());
+
+        event.hash_version = 9;
+        assert!(!event.verify_content_hash());
+    }
 }
@@ -0,0 +1,569 @@
+use anyhow::Context;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::blockchain::hash_event_document;
+use crate::cbor::{from_cbor, to_canonical_cbor};
+use crate::merkle::{
+    generate_multiproof, verify_multiproof, verify_versioned_proof, MerkleMultiproof,
+};
+use crate::provider::ChainProvider;
+use crate::timestamp::VerifyHashCall;
+
+/// The proof bundle format produced by this version.
+pub const PROOF_BUNDLE_VERSION: u16 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("Unsupported proof bundle version {0}")]
+    UnsupportedVersion(u16),
+    #[error("Record does not hash to the content hash: {0:#}")]
+    ContentHash(anyhow::Error),
+    #[error("Merkle proof does not lead from the content hash to the root")]
+    InvalidProof,
+    #[error("Commitment not confirmed on-chain: {0}")]
+    Commitment(String),
+    #[error("Failed to query the chain: {0:#}")]
+    Rpc(#[from] anyhow::Error),
+}
+
+/// Everything needed to show that an indexed event or crawled link existed
+/// when its job's Merkle root was committed on-chain, without trusting the API
+/// that exported it.
+///
+/// `verify` checks the bundle offline: the record hashes to `content_hash`,
+/// and the Merkle path leads from it to `root`. `verify_commitment` then
+/// checks, against a node of the chain with ID `chain_id`, that the registry
+/// at `registry_address` recorded `root` in `transaction_hash` at
+/// `block_number`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofBundle {
+    pub bundle_version: u16,
+    /// The event's canonical document, see
+    /// `BlockchainEvent::canonical_document`, or the link's, see
+    /// `canonical_link_document`.
+    pub record: serde_json::Value,
+    /// How `content_hash` is computed from `record`; see
+    /// `hash_event_document`.
+    pub hash_version: u16,
+    pub content_hash: String,
+    /// CID of the full event, including block fields, on IPFS. Crawled links
+    /// have none.
+    pub ipfs_cid: Option<String>,
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// Sibling hashes from the leaf up to the root.
+    pub proof: Vec<String>,
+    pub root: String,
+    pub tree_version: u16,
+    /// ID of the chain the registry is deployed on.
+    pub chain_id: u64,
+    pub registry_address: String,
+    /// The transaction that committed `root`.
+    pub transaction_hash: String,
+    pub block_number: u64,
+}
+
+impl ProofBundle {
+    /// Serializes the bundle as pretty-printed JSON.
+    pub fn to_json(&self) -> anyhow::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).context("Failed to serialize proof bundle")
+    }
+
+    /// Serializes the bundle as deterministic CBOR; see `to_canonical_cbor`.
+    pub fn to_cbor(&self) -> anyhow::Result<Vec<u8>> {
+        let value = serde_json::to_value(self).context("Failed to serialize proof bundle")?;
+        Ok(to_canonical_cbor(&value))
+    }
+
+    pub fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_slice(bytes).context("Invalid JSON proof bundle")
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_value(from_cbor(bytes)?).context("Invalid CBOR proof bundle")
+    }
+
+    /// Checks that the record hashes to `content_hash` and that the Merkle
+    /// path leads from it to `root`. Needs no network access.
+    pub fn verify(&self) -> Result<(), BundleError> {
+        if self.bundle_version != PROOF_BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(self.bundle_version));
+        }
+        let content_hash = hash_event_document(&self.record, self.hash_version)
+            .map_err(BundleError::ContentHash)?;
+        if content_hash != self.content_hash {
+            return Err(BundleError::ContentHash(anyhow::anyhow!(
+                "record hashes to {}, the bundle claims {}",
+                content_hash,
+                self.content_hash
+            )));
+        }
+        if !verify_versioned_proof(
+            &self.content_hash,
+            &self.proof,
+            &self.root,
+            self.leaf_index,
+            self.leaf_count,
+            self.tree_version,
+        ) {
+            return Err(BundleError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Checks that `transaction_hash` succeeded, was sent to the registry and
+    /// is canonical at `block_number`, and that the registry reports `root`
+    /// as committed in that block.
+    ///
+    /// `provider` must be connected to the chain with ID `chain_id`; a
+    /// `ChainProvider` cannot report which chain it serves.
+    pub async fn verify_commitment(&self, provider: &dyn ChainProvider) -> Result<(), BundleError> {
+        verify_commitment(
+            provider,
+            &self.registry_address,
+            &self.transaction_hash,
+            &self.root,
+            self.block_number,
+        )
+        .await
+    }
+}
+
+/// An event covered by a `MultiproofBundle`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleRecord {
+    /// The event's canonical document; see
+    /// `BlockchainEvent::canonical_document`.
+    pub record: serde_json::Value,
+    /// How `content_hash` is computed from `record`; see
+    /// `hash_event_document`.
+    pub hash_version: u16,
+    pub content_hash: String,
+    /// CID of the full event, including block fields, on IPFS.
+    pub ipfs_cid: Option<String>,
+}
+
+/// Proves many events under one committed Merkle root with a single
+/// multiproof, instead of a `ProofBundle` per event. The commitment fields
+/// are those of `ProofBundle`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiproofBundle {
+    pub bundle_version: u16,
+    /// The events, in the order of `multiproof.leaves`.
+    pub records: Vec<BundleRecord>,
+    pub multiproof: MerkleMultiproof,
+    pub root: String,
+    /// ID of the chain the registry is deployed on.
+    pub chain_id: u64,
+    pub registry_address: String,
+    /// The transaction that committed `root`.
+    pub transaction_hash: String,
+    pub block_number: u64,
+}
+
+impl MultiproofBundle {
+    /// Bundles `records`, keyed by leaf index, with their multiproof in the
+    /// tree of `leaves` committed as `root`.
+    pub fn assemble(
+        leaves: &[String],
+        tree_version: u16,
+        root: String,
+        records: &HashMap<usize, BundleRecord>,
+        commitment: RegistryCommitment,
+    ) -> anyhow::Result<Self> {
+        let indices: Vec<usize> = records.keys().copied().collect();
+        let multiproof = generate_multiproof(leaves, &indices, tree_version)?;
+        Ok(Self {
+            bundle_version: PROOF_BUNDLE_VERSION,
+            records: multiproof
+                .leaf_indices
+                .iter()
+                .map(|i| records[i].clone())
+                .collect(),
+            multiproof,
+            root,
+            chain_id: commitment.chain_id,
+            registry_address: commitment.registry_address,
+            transaction_hash: commitment.transaction_hash,
+            block_number: commitment.block_number,
+        })
+    }
+
+    /// Serializes the bundle as pretty-printed JSON.
+    pub fn to_json(&self) -> anyhow::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).context("Failed to serialize proof bundle")
+    }
+
+    /// Serializes the bundle as deterministic CBOR; see `to_canonical_cbor`.
+    pub fn to_cbor(&self) -> anyhow::Result<Vec<u8>> {
+        let value = serde_json::to_value(self).context("Failed to serialize proof bundle")?;
+        Ok(to_canonical_cbor(&value))
+    }
+
+    pub fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_slice(bytes).context("Invalid JSON proof bundle")
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_value(from_cbor(bytes)?).context("Invalid CBOR proof bundle")
+    }
+
+    /// Checks that every record hashes to its content hash, that the records
+    /// are the multiproof's leaves, and that the multiproof leads to `root`.
+    /// Needs no network access.
+    pub fn verify(&self) -> Result<(), BundleError> {
+        if self.bundle_version != PROOF_BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(self.bundle_version));
+        }
+        if self.records.len() != self.multiproof.leaves.len() {
+            return Err(BundleError::InvalidProof);
+        }
+        for (record, leaf) in self.records.iter().zip(&self.multiproof.leaves) {
+            let content_hash = hash_event_document(&record.record, record.hash_version)
+                .map_err(BundleError::ContentHash)?;
+            if content_hash != record.content_hash {
+                return Err(BundleError::ContentHash(anyhow::anyhow!(
+                    "record hashes to {}, the bundle claims {}",
+                    content_hash,
+                    record.content_hash
+                )));
+            }
+            if *leaf != record.content_hash {
+                return Err(BundleError::InvalidProof);
+            }
+        }
+        if !verify_multiproof(&self.multiproof, &self.root) {
+            return Err(BundleError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Checks the commitment of `root` as `ProofBundle::verify_commitment`
+    /// does.
+    pub async fn verify_commitment(&self, provider: &dyn ChainProvider) -> Result<(), BundleError> {
+        verify_commitment(
+            provider,
+            &self.registry_address,
+            &self.transaction_hash,
+            &self.root,
+            self.block_number,
+        )
+        .await
+    }
+}
+
+/// The commitment of a root to a registry, as bundles carry it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistryCommitment {
+    pub chain_id: u64,
+    pub registry_address: String,
+    pub transaction_hash: String,
+    pub block_number: u64,
+}
+
+impl RegistryCommitment {
+    /// The commitment from its stored columns, or `None` when it is missing
+    /// or was made before registries were recorded.
+    pub fn from_columns(
+        chain_id: Option<i64>,
+        registry_address: Option<String>,
+        transaction_hash: Option<String>,
+        block_number: Option<i64>,
+    ) -> Option<Self> {
+        Some(Self {
+            chain_id: chain_id? as u64,
+            registry_address: registry_address?,
+            transaction_hash: transaction_hash?,
+            block_number: block_number? as u64,
+        })
+    }
+}
+
+async fn verify_commitment(
+    provider: &dyn ChainProvider,
+    registry_address: &str,
+    transaction_hash: &str,
+    root: &str,
+    block_number: u64,
+) -> Result<(), BundleError> {
+    let registry: Address = registry_address
+        .parse()
+        .map_err(|_| BundleError::Commitment("invalid registry address".to_string()))?;
+    let tx_hash: H256 = transaction_hash
+        .parse()
+        .map_err(|_| BundleError::Commitment("invalid transaction hash".to_string()))?;
+    let root: H256 = root
+        .parse()
+        .map_err(|_| BundleError::Commitment("root is not a 32-byte hash".to_string()))?;
+
+    let receipt = provider
+        .get_transaction_receipt(tx_hash)
+        .await?
+        .ok_or_else(|| BundleError::Commitment("transaction not found".to_string()))?;
+    if receipt.status.map(|s| s.as_u64()) != Some(1) {
+        return Err(BundleError::Commitment("transaction failed".to_string()));
+    }
+    if receipt.to != Some(registry) {
+        return Err(BundleError::Commitment(
+            "transaction was not sent to the registry".to_string(),
+        ));
+    }
+    if receipt.block_number.map(|n| n.as_u64()) != Some(block_number) {
+        return Err(BundleError::Commitment(format!(
+            "transaction is not in block {}",
+            block_number
+        )));
+    }
+    let block_hash = receipt
+        .block_hash
+        .ok_or_else(|| BundleError::Commitment("receipt has no block hash".to_string()))?;
+    let canonical = provider.get_block_hash(block_number).await?;
+    if canonical.and_then(|hash| hash.parse::<H256>().ok()) != Some(block_hash) {
+        return Err(BundleError::Commitment(format!(
+            "block {} was reorged out",
+            block_number
+        )));
+    }
+
+    let committed_at = registry_committed_block(provider, registry, root, block_hash).await?;
+    if committed_at != block_number {
+        return Err(BundleError::Commitment(format!(
+            "registry does not record the root at block {}",
+            block_number
+        )));
+    }
+    Ok(())
+}
+
+/// The block in which the registry at `registry` recorded `root`, as its
+/// `verifyHash` reports in the state at `block_hash`, or 0 if it has not.
+pub async fn registry_committed_block(
+    provider: &dyn ChainProvider,
+    registry: Address,
+    root: H256,
+    block_hash: H256,
+) -> anyhow::Result<u64> {
+    let call = VerifyHashCall {
+        content_hash: root.0,
+    };
+    let result = provider
+        .call_contract(registry, Bytes::from(call.encode()), block_hash)
+        .await?;
+    let block = U256::decode(result.as_ref()).context("Invalid verifyHash return data")?;
+    anyhow::ensure!(
+        block <= U256::from(u64::MAX),
+        "verifyHash returned {}",
+        block
+    );
+    Ok(block.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{
+        compute_versioned_root, generate_multiproof, generate_versioned_proof, MERKLE_TREE_VERSION,
+    };
+    use crate::mock::{MockChain, MockTransaction, MOCK_CHAIN_ID, MOCK_REGISTRY_ADDRESS};
+    use crate::provider::ChainProvider;
+    use crate::timestamp::CommitHashCall;
+    use crate::EVENT_HASH_VERSION;
+    use serde_json::json;
+
+    fn bundle() -> ProofBundle {
+        let record = json!({
+            "chain": "ethereum",
+            "contract_address": "0x00000000000000000000000000000000000000aa",
+            "transaction_hash": format!("0x{}", "11".repeat(32)),
+            "log_index": 3,
+            "data": {"from": "0x01", "value": "1000"},
+        });
+        let content_hash = hash_event_document(&record, EVENT_HASH_VERSION).unwrap();
+        let leaves = vec!["aa".repeat(32), content_hash.clone(), "bb".repeat(32)];
+        ProofBundle {
+            bundle_version: PROOF_BUNDLE_VERSION,
+            record,
+            hash_version: EVENT_HASH_VERSION,
+            content_hash,
+            ipfs_cid: None,
+            leaf_index: 1,
+            leaf_count: leaves.len(),
+            proof: generate_versioned_proof(&leaves, 1, MERKLE_TREE_VERSION).unwrap(),
+            root: compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            tree_version: MERKLE_TREE_VERSION,
+            chain_id: MOCK_CHAIN_ID,
+            registry_address: format!("{:?}", MOCK_REGISTRY_ADDRESS),
+            transaction_hash: String::new(),
+            block_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trips_and_verifies_offline() {
+        let bundle = bundle();
+        bundle.verify().unwrap();
+        assert_eq!(
+            ProofBundle::from_json(&bundle.to_json().unwrap()).unwrap(),
+            bundle
+        );
+        assert_eq!(
+            ProofBundle::from_cbor(&bundle.to_cbor().unwrap()).unwrap(),
+            bundle
+        );
+
+        let mut tampered = bundle.clone();
+        tampered.record["data"]["value"] = json!("1001");
+        assert!(matches!(
+            tampered.verify(),
+            Err(BundleError::ContentHash(_))
+        ));
+
+        let mut tampered = bundle.clone();
+        tampered.leaf_index = 0;
+        assert!(matches!(tampered.verify(), Err(BundleError::InvalidProof)));
+
+        let mut tampered = bundle;
+        tampered.bundle_version = PROOF_BUNDLE_VERSION + 1;
+        assert!(matches!(
+            tampered.verify(),
+            Err(BundleError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_multiproof_bundle_verifies_offline() {
+        let records: Vec<BundleRecord> = (0..3)
+            .map(|log_index| {
+                let record = json!({
+                    "chain": "ethereum",
+                    "contract_address": "0x00000000000000000000000000000000000000aa",
+                    "transaction_hash": format!("0x{}", "11".repeat(32)),
+                    "log_index": log_index,
+                    "data": {"value": log_index.to_string()},
+                });
+                BundleRecord {
+                    content_hash: hash_event_document(&record, EVENT_HASH_VERSION).unwrap(),
+                    record,
+                    hash_version: EVENT_HASH_VERSION,
+                    ipfs_cid: None,
+                }
+            })
+            .collect();
+        let mut leaves: Vec<String> = records.iter().map(|r| r.content_hash.clone()).collect();
+        leaves.insert(1, "aa".repeat(32));
+        let multiproof = generate_multiproof(&leaves, &[0, 2, 3], MERKLE_TREE_VERSION).unwrap();
+        let bundle = MultiproofBundle {
+            bundle_version: PROOF_BUNDLE_VERSION,
+            records,
+            multiproof,
+            root: compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            chain_id: MOCK_CHAIN_ID,
+            registry_address: format!("{:?}", MOCK_REGISTRY_ADDRESS),
+            transaction_hash: String::new(),
+            block_number: 0,
+        };
+        bundle.verify().unwrap();
+        assert_eq!(
+            MultiproofBundle::from_json(&bundle.to_json().unwrap()).unwrap(),
+            bundle
+        );
+        assert_eq!(
+            MultiproofBundle::from_cbor(&bundle.to_cbor().unwrap()).unwrap(),
+            bundle
+        );
+
+        let mut tampered = bundle.clone();
+        tampered.records[2].record["data"]["value"] = json!("7");
+        assert!(matches!(
+            tampered.verify(),
+            Err(BundleError::ContentHash(_))
+        ));
+
+        // Each record must be the leaf the multiproof places it at.
+        let mut tampered = bundle.clone();
+        tampered.records.swap(0, 1);
+        assert!(matches!(tampered.verify(), Err(BundleError::InvalidProof)));
+
+        let by_index = [0, 2, 3].into_iter().zip(bundle.records.clone()).collect();
+        let commitment = RegistryCommitment::from_columns(
+            Some(MOCK_CHAIN_ID as i64),
+            Some(bundle.registry_address.clone()),
+            Some(String::new()),
+            Some(0),
+        )
+        .unwrap();
+        let assembled = MultiproofBundle::assemble(
+            &leaves,
+            MERKLE_TREE_VERSION,
+            bundle.root.clone(),
+            &by_index,
+            commitment.clone(),
+        )
+        .unwrap();
+        assert_eq!(assembled, bundle);
+        let mut outside = by_index;
+        outside.insert(4, bundle.records[0].clone());
+        assert!(MultiproofBundle::assemble(
+            &leaves,
+            MERKLE_TREE_VERSION,
+            bundle.root.clone(),
+            &outside,
+            commitment,
+        )
+        .is_err());
+        assert!(
+            RegistryCommitment::from_columns(Some(1), None, Some(String::new()), Some(0)).is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bundle_commitment_is_checked_on_chain() {
+        let chain = MockChain::new(0);
+        let mut bundle = bundle();
+        let root: H256 = bundle.root.parse().unwrap();
+        let block = chain.mine_transactions(vec![MockTransaction::call(
+            Address::repeat_byte(1),
+            MOCK_REGISTRY_ADDRESS,
+            CommitHashCall {
+                content_hash: root.0,
+            }
+            .encode(),
+        )]);
+        let tx = chain
+            .get_block_with_transactions(block)
+            .await
+            .unwrap()
+            .unwrap()
+            .transactions[0]
+            .hash;
+        bundle.transaction_hash = format!("{:?}", tx);
+        bundle.block_number = block;
+
+        // The registry has no record of the root yet.
+        assert!(bundle.verify_commitment(&chain).await.is_err());
+
+        let call = VerifyHashCall {
+            content_hash: root.0,
+        };
+        chain.set_call_result(
+            MOCK_REGISTRY_ADDRESS,
+            call.encode(),
+            U256::from(block).encode(),
+        );
+        bundle.verify_commitment(&chain).await.unwrap();
+
+        let mut wrong_block = bundle.clone();
+        wrong_block.block_number = block + 1;
+        assert!(matches!(
+            wrong_block.verify_commitment(&chain).await,
+            Err(BundleError::Commitment(_))
+        ));
+
+        chain.reorg(1);
+        chain.mine(Vec::new());
+        assert!(bundle.verify_commitment(&chain).await.is_err());
+    }
+}
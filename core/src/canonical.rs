@@ -0,0 +1,110 @@
+use serde_json::Value;
+
+/// Serializes a JSON value in the JSON Canonicalization Scheme (RFC 8785).
+///
+/// The output has no insignificant whitespace, object members sorted by the
+/// UTF-16 code units of their names, strings with only the mandatory escapes,
+/// and numbers in their shortest ECMAScript form. Any JCS implementation
+/// produces the same bytes for the same value, so hashes of it can be
+/// recomputed outside this codebase.
+pub fn to_canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&format_number(n.as_f64().unwrap_or_default())),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut members: Vec<(&String, &Value)> = map.iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (name, item)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, name);
+                out.push(':');
+                write_value(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// serde_json escapes exactly what JCS requires: `"`, `\`, and control
+/// characters, using the short forms where JSON has them and lowercase
+/// `\u00xx` otherwise.
+fn write_string(out: &mut String, s: &str) {
+    out.push_str(&serde_json::to_string(s).unwrap_or_default());
+}
+
+/// Formats a number like ECMAScript's `Number.prototype.toString`, which JCS
+/// mandates. All numbers are treated as IEEE 754 doubles, as in JavaScript.
+fn format_number(n: f64) -> String {
+    if n == 0.0 {
+        // Covers negative zero, which ECMAScript prints as "0".
+        return "0".to_string();
+    }
+    let magnitude = n.abs();
+    if (1e-6..1e21).contains(&magnitude) {
+        // Rust prints the shortest round-tripping digits without an exponent.
+        return n.to_string();
+    }
+    let formatted = format!("{:e}", n);
+    match formatted.split_once('e') {
+        Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+            format!("{}e+{}", mantissa, exponent)
+        }
+        _ => formatted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    // The literal is written as in the RFC.
+    #[allow(clippy::excessive_precision)]
+    fn test_canonical_json_matches_rfc_8785() {
+        // The example from RFC 8785 section 3.2.2.
+        let value = json!({
+            "numbers": [333333333.333_333_29, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "€$\u{f}\nA'B\"\\\\\"/",
+            "literals": [null, true, false]
+        });
+        assert_eq!(
+            to_canonical_json(&value),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_by_utf16_code_units() {
+        // U+1F600 sorts before U+FB33 in UTF-16 (a surrogate pair starts with
+        // 0xD83D) but after it in UTF-8.
+        let value = json!({"\u{fb33}": 1, "\u{1f600}": 2, "b": [], "a": {"z": -0.0, "y": 1e21}});
+        assert_eq!(
+            to_canonical_json(&value),
+            "{\"a\":{\"y\":1e+21,\"z\":0},\"b\":[],\"\u{1f600}\":2,\"\u{fb33}\":1}"
+        );
+        assert_eq!(to_canonical_json(&json!(u64::MAX)), "18446744073709552000");
+        assert_eq!(to_canonical_json(&json!(1e-7)), "1e-7");
+    }
+}
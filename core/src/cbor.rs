@@ -0,0 +1,332 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Number, Value};
+
+/// Deepest nesting of arrays and maps `from_cbor` accepts.
+const MAX_DEPTH: usize = 128;
+
+/// Encodes a JSON value as CBOR (RFC 8949) using the core deterministic
+/// encoding rules of section 4.2.1.
+///
+/// Lengths are definite, integers and floats take their shortest exact form,
+/// and map entries are sorted by the bytes of their encoded keys, so equal
+/// values always encode to the same bytes.
+pub fn to_canonical_cbor(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// Decodes a single CBOR data item into a JSON value.
+///
+/// Only the JSON data model is supported: unsigned and negative integers, text
+/// strings, arrays, maps with text keys, booleans, null and finite floats.
+/// Byte strings, tags, undefined, indefinite lengths and trailing bytes are
+/// rejected.
+pub fn from_cbor(bytes: &[u8]) -> Result<Value> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let value = reader.read_value(0)?;
+    if reader.pos != bytes.len() {
+        anyhow::bail!("Trailing bytes after CBOR item at offset {}", reader.pos);
+    }
+    Ok(value)
+}
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+fn write_head(out: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    if argument < 24 {
+        out.push(major | argument as u8);
+    } else if argument <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(argument as u8);
+    } else if argument <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(argument as u16).to_be_bytes());
+    } else if argument <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(argument as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&argument.to_be_bytes());
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                write_head(out, MAJOR_UNSIGNED, u);
+            } else if let Some(i) = n.as_i64() {
+                // A negative integer n is encoded as -1 - n.
+                write_head(out, MAJOR_NEGATIVE, !i as u64);
+            } else {
+                write_float(out, n.as_f64().unwrap_or_default());
+            }
+        }
+        Value::String(s) => {
+            write_head(out, MAJOR_TEXT, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            write_head(out, MAJOR_ARRAY, items.len() as u64);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(Vec<u8>, &Value)> = map
+                .iter()
+                .map(|(key, item)| {
+                    let mut encoded = Vec::new();
+                    write_value(&mut encoded, &Value::String(key.clone()));
+                    (encoded, item)
+                })
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            write_head(out, MAJOR_MAP, entries.len() as u64);
+            for (key, item) in entries {
+                out.extend_from_slice(&key);
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+/// Writes `f` as the shortest of half, single and double precision that
+/// represents it exactly.
+fn write_float(out: &mut Vec<u8>, f: f64) {
+    let single = f as f32;
+    if single as f64 != f {
+        out.push(0xfb);
+        out.extend_from_slice(&f.to_bits().to_be_bytes());
+    } else if let Some(half) = to_half(single) {
+        out.push(0xf9);
+        out.extend_from_slice(&half.to_be_bytes());
+    } else {
+        out.push(0xfa);
+        out.extend_from_slice(&single.to_bits().to_be_bytes());
+    }
+}
+
+/// The IEEE 754 half-precision bits of `f`, if it converts without loss.
+fn to_half(f: f32) -> Option<u16> {
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // Infinity; JSON values cannot hold NaN.
+        return (mantissa == 0).then_some(sign | 0x7c00);
+    }
+    if exponent == 0 {
+        // Zero; single-precision subnormals are too small for a half.
+        return (mantissa == 0).then_some(sign);
+    }
+    match exponent - 127 {
+        e @ -14..=15 => (mantissa & 0x1fff == 0)
+            .then(|| sign | (((e + 15) as u16) << 10) | (mantissa >> 13) as u16),
+        e @ -24..=-15 => {
+            // Half subnormals are multiples of 2^-24.
+            let significand = mantissa | 0x80_0000;
+            let shift = (-1 - e) as u32;
+            (significand & ((1 << shift) - 1) == 0).then(|| sign | (significand >> shift) as u16)
+        }
+        _ => None,
+    }
+}
+
+fn from_half(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        e => (1024.0 + mantissa) * 2f64.powi(e - 25),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .context("Unexpected end of CBOR input")?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Reads an initial byte and its argument as `(major, additional, argument)`.
+    fn read_head(&mut self) -> Result<(u8, u8, u64)> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let additional = initial & 0x1f;
+        let argument = match additional {
+            0..=23 => additional as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into()?) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into()?) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into()?),
+            31 => anyhow::bail!("Indefinite-length CBOR items are not supported"),
+            _ => anyhow::bail!("Malformed CBOR initial byte {:#04x}", initial),
+        };
+        Ok((major, additional, argument))
+    }
+
+    fn read_length(&self, argument: u64) -> Result<usize> {
+        usize::try_from(argument)
+            .ok()
+            .filter(|len| *len <= self.remaining())
+            .context("CBOR length exceeds the input")
+    }
+
+    fn read_text(&mut self, argument: u64) -> Result<String> {
+        let len = self.read_length(argument)?;
+        let bytes = self.take(len)?;
+        Ok(std::str::from_utf8(bytes)
+            .context("CBOR text string is not valid UTF-8")?
+            .to_string())
+    }
+
+    fn read_value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("CBOR nesting exceeds {} levels", MAX_DEPTH);
+        }
+        let (major, additional, argument) = self.read_head()?;
+        match major {
+            MAJOR_UNSIGNED => Ok(Value::from(argument)),
+            MAJOR_NEGATIVE => i64::try_from(argument)
+                .map(|n| Value::from(-1 - n))
+                .map_err(|_| anyhow::anyhow!("CBOR negative integer is out of range")),
+            MAJOR_TEXT => Ok(Value::String(self.read_text(argument)?)),
+            MAJOR_ARRAY => {
+                // Every item takes at least one byte.
+                let len = self.read_length(argument)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.read_value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            MAJOR_MAP => {
+                let len = self.read_length(argument)?;
+                let mut map = Map::new();
+                for _ in 0..len {
+                    let (key_major, _, key_argument) = self.read_head()?;
+                    if key_major != MAJOR_TEXT {
+                        anyhow::bail!("CBOR map keys must be text strings");
+                    }
+                    let key = self.read_text(key_argument)?;
+                    let item = self.read_value(depth + 1)?;
+                    if map.insert(key.clone(), item).is_some() {
+                        anyhow::bail!("Duplicate CBOR map key {:?}", key);
+                    }
+                }
+                Ok(Value::Object(map))
+            }
+            MAJOR_SIMPLE => {
+                let float = match additional {
+                    20 => return Ok(Value::Bool(false)),
+                    21 => return Ok(Value::Bool(true)),
+                    22 => return Ok(Value::Null),
+                    25 => from_half(argument as u16),
+                    26 => f32::from_bits(argument as u32) as f64,
+                    27 => f64::from_bits(argument),
+                    _ => anyhow::bail!("Unsupported CBOR simple value {}", argument),
+                };
+                Number::from_f64(float)
+                    .map(Value::Number)
+                    .context("CBOR float is not a finite number")
+            }
+            MAJOR_BYTES => anyhow::bail!("CBOR byte strings are not supported"),
+            MAJOR_TAG => anyhow::bail!("CBOR tags are not supported"),
+            _ => unreachable!("major type is three bits"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hex(value: Value) -> String {
+        hex::encode(to_canonical_cbor(&value))
+    }
+
+    #[test]
+    fn test_cbor_matches_rfc_8949_examples() {
+        // From RFC 8949 appendix A.
+        assert_eq!(hex(json!(0)), "00");
+        assert_eq!(hex(json!(23)), "17");
+        assert_eq!(hex(json!(24)), "1818");
+        assert_eq!(hex(json!(1000)), "1903e8");
+        assert_eq!(hex(json!(u64::MAX)), "1bffffffffffffffff");
+        assert_eq!(hex(json!(-1)), "20");
+        assert_eq!(hex(json!(-1000)), "3903e7");
+        assert_eq!(hex(json!(1.5)), "f93e00");
+        assert_eq!(hex(json!(-4.0)), "f9c400");
+        assert_eq!(hex(json!(65504.0)), "f97bff");
+        assert_eq!(hex(json!(5.960464477539063e-8)), "f90001");
+        assert_eq!(hex(json!(0.00006103515625)), "f90400");
+        assert_eq!(hex(json!(100000.0)), "fa47c35000");
+        assert_eq!(hex(json!(1.1)), "fb3ff199999999999a");
+        assert_eq!(hex(json!("IETF")), "6449455446");
+        assert_eq!(hex(json!([1, [2, 3], [4, 5]])), "8301820203820405");
+        assert_eq!(hex(json!({"a": 1, "b": [2, 3]})), "a26161016162820203");
+        assert_eq!(hex(json!([null, true, false])), "83f6f5f4");
+    }
+
+    #[test]
+    fn test_cbor_sorts_map_keys_by_encoding() {
+        // Shorter keys encode with a smaller head, so they sort first.
+        assert_eq!(hex(json!({"aa": 1, "b": 2})), "a261620262616101");
+    }
+
+    #[test]
+    fn test_cbor_round_trips_and_rejects_malformed_input() {
+        let value = json!({
+            "hash": "0xabc",
+            "numbers": [0, -1, 255, 65536, -4294967297i64, 0.5, 1e300],
+            "nested": {"empty": [], "flag": true, "none": null}
+        });
+        assert_eq!(from_cbor(&to_canonical_cbor(&value)).unwrap(), value);
+
+        let encoded = to_canonical_cbor(&value);
+        assert!(from_cbor(&encoded[..encoded.len() - 1]).is_err());
+        assert!(from_cbor(&[encoded.as_slice(), &[0x00]].concat()).is_err());
+        // A byte string, a tag, an indefinite-length array and NaN.
+        for bytes in [
+            &[0x41, 0x00][..],
+            &[0xc1, 0x00],
+            &[0x9f, 0xff],
+            &[0xf9, 0x7e, 0x00],
+        ] {
+            assert!(from_cbor(bytes).is_err());
+        }
+        // An array claiming more items than there are bytes.
+        assert!(from_cbor(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::backfill::RangeScanConfig;
+
+/// Configuration for one indexable chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainConfig {
+    /// Name used in job configs and the GraphQL API (e.g. "arbitrum").
+    pub name: String,
+    /// EIP-155 chain ID the RPC endpoints must report.
+    pub chain_id: u64,
+    /// HTTP and WebSocket RPC endpoints. Requests fail over between them,
+    /// preferring the healthiest; new-head subscriptions need a WebSocket one.
+    pub rpc_urls: Vec<String>,
+    /// Blocks behind the head before events are indexed and committed.
+    pub confirmations: u64,
+    /// Average block time in milliseconds.
+    pub block_time_ms: u64,
+    /// Largest block range requested in a single `eth_getLogs` call.
+    #[serde(default = "default_max_log_range")]
+    pub max_log_range: u64,
+    /// Query every endpoint for the latest block and deprioritise endpoints
+    /// that trail the best head by more than `max_head_lag` blocks.
+    #[serde(default)]
+    pub cross_check_heads: bool,
+    /// Blocks an endpoint may trail the best head before it counts as lagging.
+    #[serde(default = "default_max_head_lag")]
+    pub max_head_lag: u64,
+    /// Backfill shards of one job that may fetch from this chain at once.
+    #[serde(default = "default_max_concurrent_shards")]
+    pub max_concurrent_shards: usize,
+}
+
+fn default_max_log_range() -> u64 {
+    RangeScanConfig::default().max_range
+}
+
+fn default_max_head_lag() -> u64 {
+    5
+}
+
+fn default_max_concurrent_shards() -> usize {
+    4
+}
+
+impl ChainConfig {
+    /// Average block time.
+    pub fn block_time(&self) -> Duration {
+        Duration::from_millis(self.block_time_ms)
+    }
+
+    /// Chunking settings for backfills on this chain.
+    pub fn scan_config(&self) -> RangeScanConfig {
+        let defaults = RangeScanConfig::default();
+        RangeScanConfig {
+            initial_range: defaults.initial_range.min(self.max_log_range),
+            max_range: self.max_log_range,
+            ..defaults
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryFile {
+    chains: Vec<ChainConfig>,
+}
+
+/// The set of chains this node can index.
+#[derive(Debug, Clone, Default)]
+pub struct ChainRegistry {
+    chains: Vec<ChainConfig>,
+}
+
+impl ChainRegistry {
+    /// Builds a registry from chain configs, rejecting duplicates and invalid entries.
+    pub fn new(chains: Vec<ChainConfig>) -> Result<Self> {
+        let mut names = HashSet::new();
+        for chain in &chains {
+            if chain.name.is_empty() {
+                anyhow::bail!("Chain name must not be empty");
+            }
+            if !names.insert(chain.name.as_str()) {
+                anyhow::bail!("Chain '{}' is configured more than once", chain.name);
+            }
+            if chain.rpc_urls.is_empty() {
+                anyhow::bail!("Chain '{}' has no RPC endpoints", chain.name);
+            }
+            if chain.max_log_range == 0 {
+                anyhow::bail!("Chain '{}' must have a non-zero max_log_range", chain.name);
+            }
+            if chain.max_concurrent_shards == 0 {
+                anyhow::bail!(
+                    "Chain '{}' must have a non-zero max_concurrent_shards",
+                    chain.name
+                );
+            }
+        }
+        Ok(Self { chains })
+    }
+
+    /// Parses a registry from JSON of the form `{"chains": [ChainConfig, ...]}`.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let file: RegistryFile = serde_json::from_str(json).context("Invalid chain registry")?;
+        Self::new(file.chains)
+    }
+
+    /// Loads the registry from the JSON file named by `CHAINS_CONFIG`.
+    ///
+    /// Without `CHAINS_CONFIG`, falls back to the legacy `ETHEREUM_RPC_URL` and
+    /// `POLYGON_RPC_URL` variables with their mainnet defaults.
+    pub fn from_env() -> Result<Self> {
+        if let Some(path) = std::env::var("CHAINS_CONFIG")
+            .ok()
+            .filter(|p| !p.is_empty())
+        {
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read CHAINS_CONFIG file {}", path))?;
+            return Self::from_json(&json).with_context(|| format!("In {}", path));
+        }
+
+        let mut chains = Vec::new();
+        for (name, var, chain_id, confirmations, block_time_ms) in [
+            ("ethereum", "ETHEREUM_RPC_URL", 1, 12, 12_000),
+            ("polygon", "POLYGON_RPC_URL", 137, 128, 2_000),
+        ] {
+            if let Some(url) = std::env::var(var).ok().filter(|u| !u.is_empty()) {
+                chains.push(ChainConfig {
+                    name: name.to_string(),
+                    chain_id,
+                    rpc_urls: vec![url],
+                    confirmations,
+                    block_time_ms,
+                    max_log_range: default_max_log_range(),
+                    cross_check_heads: false,
+                    max_head_lag: default_max_head_lag(),
+                    max_concurrent_shards: default_max_concurrent_shards(),
+                });
+            }
+        }
+        Self::new(chains)
+    }
+
+    /// Looks up a chain by name.
+    pub fn get(&self, name: &str) -> Option<&ChainConfig> {
+        self.chains.iter().find(|c| c.name == name)
+    }
+
+    /// Whether `name` is a configured chain.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Configured chain names, in configuration order.
+    pub fn names(&self) -> Vec<&str> {
+        self.chains.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// All configured chains, in configuration order.
+    pub fn iter(&self) -> impl Iterator<Item = &ChainConfig> {
+        self.chains.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_registry() {
+        let registry = ChainRegistry::from_json(
+            r#"{"chains": [
+                {"name": "arbitrum", "chain_id": 42161, "rpc_urls": ["wss://arb.example"],
+                 "confirmations": 20, "block_time_ms": 250, "max_log_range": 500},
+                {"name": "base", "chain_id": 8453, "rpc_urls": ["wss://base.example"],
+                 "confirmations": 10, "block_time_ms": 2000}
+            ]}"#,
+        )
+        .expect("Valid registry");
+
+        assert_eq!(registry.names(), vec!["arbitrum", "base"]);
+        let arbitrum = registry.get("arbitrum").expect("arbitrum configured");
+        assert_eq!(arbitrum.chain_id, 42161);
+        assert_eq!(arbitrum.scan_config().initial_range, 500);
+        assert_eq!(arbitrum.scan_config().max_range, 500);
+        assert_eq!(
+            registry.get("base").map(|c| c.max_log_range),
+            Some(default_max_log_range())
+        );
+        assert!(!registry.contains("ethereum"));
+    }
+
+    #[test]
+    fn test_rejects_duplicate_chains() {
+        let chain = ChainConfig {
+            name: "optimism".to_string(),
+            chain_id: 10,
+            rpc_urls: vec!["wss://op.example".to_string()],
+            confirmations: 10,
+            block_time_ms: 2_000,
+            max_log_range: 1_000,
+            cross_check_heads: false,
+            max_head_lag: 5,
+            max_concurrent_shards: 4,
+        };
+        assert!(ChainRegistry::new(vec![chain.clone(), chain]).is_err());
+    }
+}
@@ -3,6 +3,8 @@ use reqwest::Client;
 use scraper::{Html, Selector};
 use url::Url;
 
+use crate::job::CrawlResult;
+
 pub struct Crawler {
     client: Client,
 }
@@ -20,6 +22,11 @@ impl Crawler {
     }
 
     pub async fn crawl(&self, url: &str, max_pages: usize) -> Result<Vec<String>> {
+        Ok(self.crawl_page(url, max_pages).await?.links)
+    }
+
+    /// Fetches `url` and returns the page with up to `max_pages` of its links.
+    pub async fn crawl_page(&self, url: &str, max_pages: usize) -> Result<CrawlResult> {
         let base_url = Url::parse(url).context("Invalid URL provided")?;
 
         let response = self
@@ -32,6 +39,7 @@ impl Crawler {
         if !response.status().is_success() {
             anyhow::bail!("HTTP error: {}", response.status());
         }
+        let status_code = response.status().as_u16();
 
         let html = response
             .text()
@@ -53,7 +61,13 @@ impl Crawler {
             .map(|url| url.to_string())
             .collect();
 
-        Ok(links)
+        Ok(CrawlResult::new(
+            url,
+            status_code,
+            html.as_bytes(),
+            links,
+            chrono::Utc::now(),
+        ))
     }
 }
 
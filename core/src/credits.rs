@@ -73,4 +73,28 @@ impl CreditManager {
     pub fn event_index_cost() -> U256 {
         U256::from(50) * U256::exp10(18)
     }
+
+    /// `amount` of the 18-decimal credit token in whole credits, the unit
+    /// `user_credits` balances are kept in. Fractions of a credit are dropped.
+    pub fn whole_credits(amount: U256) -> i64 {
+        (amount / U256::exp10(18)).as_u64() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_costs_in_whole_credits() {
+        assert_eq!(
+            CreditManager::whole_credits(CreditManager::crawl_job_cost()),
+            100
+        );
+        assert_eq!(
+            CreditManager::whole_credits(CreditManager::event_index_cost()),
+            50
+        );
+        assert_eq!(CreditManager::whole_credits(U256::exp10(18) - 1), 0);
+    }
 }
@@ -0,0 +1,657 @@
+use anyhow::{Context, Result};
+use ethers::abi::{Abi, Event, Function, HumanReadableParser, Param, ParamType, RawLog, Token};
+use ethers::types::{Address, Bytes, H256, I256, U256};
+use ethers::utils::keccak256;
+use ethers::utils::to_checksum;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Decodes raw EVM logs into named, typed event parameters using an ABI event definition.
+#[derive(Debug, Clone)]
+pub struct EventDecoder {
+    event: Event,
+    /// True when the definition did not mark any parameter as `indexed`
+    /// (e.g. a bare `Transfer(address,address,uint256)` signature). The indexed
+    /// parameters are then inferred from the number of topics on each log.
+    infer_indexed: bool,
+    /// The component names of each parameter; empty unless the definition
+    /// was a JSON fragment.
+    components: Vec<Components>,
+}
+
+impl EventDecoder {
+    /// Parses an event definition.
+    ///
+    /// Accepts a JSON ABI fragment (`{"type":"event","name":"Transfer","inputs":[...]}`),
+    /// a human-readable declaration (`event Transfer(address indexed from, address indexed to, uint256 value)`)
+    /// or a bare signature (`Transfer(address,address,uint256)`).
+    pub fn parse(definition: &str) -> Result<Self> {
+        let definition = definition.trim();
+        let (event, components) = if definition.starts_with('{') {
+            let mut fragment: Value =
+                serde_json::from_str(definition).context("Invalid JSON ABI event fragment")?;
+            // `anonymous` is optional in hand-written fragments; default it like solc does.
+            if let Some(obj) = fragment.as_object_mut() {
+                obj.entry("anonymous").or_insert(Value::Bool(false));
+            }
+            let components = Components::of_params(fragment.get("inputs"));
+            let event = serde_json::from_value::<Event>(fragment)
+                .context("Invalid JSON ABI event fragment")?;
+            (event, components)
+        } else {
+            let event = HumanReadableParser::parse_event(definition)
+                .map_err(|e| anyhow::anyhow!("Invalid event signature '{}': {}", definition, e))?;
+            (event, Vec::new())
+        };
+        let infer_indexed = !event.inputs.iter().any(|p| p.indexed);
+        Ok(Self {
+            event,
+            infer_indexed,
+            components,
+        })
+    }
+
+    /// The event name, e.g. `Transfer`.
+    pub fn name(&self) -> &str {
+        &self.event.name
+    }
+
+    /// The canonical signature, e.g. `Transfer(address,address,uint256)`.
+    pub fn signature(&self) -> String {
+        let types: Vec<String> = self
+            .event
+            .inputs
+            .iter()
+            .map(|p| p.kind.to_string())
+            .collect();
+        format!("{}({})", self.event.name, types.join(","))
+    }
+
+    /// The keccak256 hash of the canonical signature, i.e. the log's `topic0`.
+    pub fn topic0(&self) -> H256 {
+        self.event.signature()
+    }
+
+    /// The number of topics on this event's logs, or `None` for a bare
+    /// signature, whose indexed parameters are inferred from each log.
+    pub fn topic_count(&self) -> Option<usize> {
+        if self.infer_indexed {
+            return None;
+        }
+        let indexed = self.event.inputs.iter().filter(|p| p.indexed).count();
+        Some(indexed + usize::from(!self.event.anonymous))
+    }
+
+    /// The topic position and type of the parameter `name` (or `argN` by
+    /// position), if it is declared `indexed`. Bare signatures declare no
+    /// indexed parameters, so this is always `None` for them.
+    pub fn indexed_topic(&self, name: &str) -> Option<(usize, &ParamType)> {
+        let mut topic = usize::from(!self.event.anonymous);
+        for (i, param) in self.event.inputs.iter().enumerate() {
+            if !param.indexed {
+                continue;
+            }
+            if param.name == name || format!("arg{}", i) == name {
+                return Some((topic, &param.kind));
+            }
+            topic += 1;
+        }
+        None
+    }
+
+    /// The parsed ABI event definition.
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// Decodes a log's topics and data into a JSON object of named parameters.
+    ///
+    /// Unnamed parameters are keyed `arg0`, `arg1`, ... by position. Tuples
+    /// are objects when the definition names their components; see
+    /// `named_token_to_json`.
+    pub fn decode(&self, topics: &[H256], data: &[u8]) -> Result<Map<String, Value>> {
+        let mut event = self.event.clone();
+        if self.infer_indexed {
+            let indexed_count = topics.len().saturating_sub(usize::from(!event.anonymous));
+            for param in event.inputs.iter_mut().take(indexed_count) {
+                param.indexed = true;
+            }
+        }
+
+        let log = event
+            .parse_log(RawLog {
+                topics: topics.to_vec(),
+                data: data.to_vec(),
+            })
+            .with_context(|| format!("Failed to decode log as {}", self.signature()))?;
+
+        let mut params = Map::new();
+        for (i, param) in log.params.into_iter().enumerate() {
+            let name = if param.name.is_empty() {
+                format!("arg{}", i)
+            } else {
+                param.name
+            };
+            let components = self.components.get(i).unwrap_or(&Components::Unnamed);
+            params.insert(name, named_token_to_json(&param.value, components));
+        }
+        Ok(params)
+    }
+
+    /// Builds the structured `event_data` stored for an indexed log.
+    ///
+    /// The raw topics and data are always kept alongside the decoded parameters
+    /// so the event can be re-verified against the chain. If decoding fails the
+    /// parameters are `null` and the error is recorded under `decode_error`.
+    pub fn event_data(&self, topics: &[H256], data: &Bytes) -> Value {
+        let raw = json!({
+            "topics": topics.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>(),
+            "data": format!("0x{}", hex::encode(data.as_ref())),
+        });
+        match self.decode(topics, data.as_ref()) {
+            Ok(params) => json!({
+                "event": self.name(),
+                "signature": self.signature(),
+                "params": params,
+                "raw": raw,
+            }),
+            Err(e) => json!({
+                "event": self.name(),
+                "signature": self.signature(),
+                "params": Value::Null,
+                "decode_error": e.to_string(),
+                "raw": raw,
+            }),
+        }
+    }
+}
+
+/// Decodes transaction calldata into named, typed function arguments using
+/// ABI function definitions, matched by 4-byte selector.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionDecoder {
+    functions: HashMap<[u8; 4], NamedFunction>,
+}
+
+impl FunctionDecoder {
+    /// Parses function definitions.
+    ///
+    /// Each entry is a JSON ABI (an array, of which only the functions are
+    /// used), a JSON ABI function fragment, a human-readable declaration
+    /// (`function transfer(address to, uint256 amount)`) or a bare signature
+    /// (`transfer(address,uint256)`).
+    pub fn parse(definitions: &[String]) -> Result<Self> {
+        let mut functions = HashMap::new();
+        for definition in definitions {
+            let definition = definition.trim();
+            let parsed = if definition.starts_with('[') {
+                let items: Vec<Value> =
+                    serde_json::from_str(definition).context("Invalid JSON ABI")?;
+                serde_json::from_value::<Abi>(Value::Array(items.clone()))
+                    .context("Invalid JSON ABI")?;
+                items
+                    .iter()
+                    .filter(|item| item.get("type").and_then(Value::as_str) == Some("function"))
+                    .map(|item| parse_named_function(&item.to_string()))
+                    .collect::<Result<Vec<_>>>()?
+            } else {
+                vec![parse_named_function(definition)?]
+            };
+            for function in parsed {
+                functions.insert(function.function.short_signature(), function);
+            }
+        }
+        Ok(Self { functions })
+    }
+
+    /// Whether no functions are defined.
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Builds the structured call data stored for a transaction, or `None` if
+    /// `input` is shorter than a selector or matches no known function.
+    ///
+    /// If decoding the arguments fails the parameters are `null` and the error
+    /// is recorded under `decode_error`.
+    pub fn decode_call(&self, input: &[u8]) -> Option<Value> {
+        let selector: [u8; 4] = input.get(..4)?.try_into().ok()?;
+        let NamedFunction {
+            function, inputs, ..
+        } = self.functions.get(&selector)?;
+        let types: Vec<String> = function.inputs.iter().map(|p| p.kind.to_string()).collect();
+        let signature = format!("{}({})", function.name, types.join(","));
+        let selector = format!("0x{}", hex::encode(selector));
+        Some(match function.decode_input(&input[4..]) {
+            Ok(tokens) => json!({
+                "function": function.name,
+                "signature": signature,
+                "selector": selector,
+                "params": params_to_json(&function.inputs, inputs, &tokens),
+            }),
+            Err(e) => json!({
+                "function": function.name,
+                "signature": signature,
+                "selector": selector,
+                "params": Value::Null,
+                "decode_error": format!("Failed to decode calldata as {}: {}", signature, e),
+            }),
+        })
+    }
+}
+
+/// Parses a single function definition: a JSON ABI function fragment, a
+/// human-readable declaration (`function balanceOf(address owner) view returns
+/// (uint256)`) or a bare signature (`balanceOf(address)`). Only declarations
+/// and fragments can name return types.
+pub fn parse_function(definition: &str) -> Result<Function> {
+    parse_named_function(definition).map(|named| named.function)
+}
+
+/// A function definition with the component names of its inputs and
+/// outputs, which are empty unless it was a JSON fragment.
+#[derive(Debug, Clone)]
+pub(crate) struct NamedFunction {
+    pub(crate) function: Function,
+    pub(crate) inputs: Vec<Components>,
+    pub(crate) outputs: Vec<Components>,
+}
+
+/// Parses a function definition like `parse_function`, keeping the names of
+/// its tuple components.
+pub(crate) fn parse_named_function(definition: &str) -> Result<NamedFunction> {
+    let definition = definition.trim();
+    if definition.starts_with('{') {
+        let fragment: Value =
+            serde_json::from_str(definition).context("Invalid JSON ABI function fragment")?;
+        let inputs = Components::of_params(fragment.get("inputs"));
+        let outputs = Components::of_params(fragment.get("outputs"));
+        let abi: Abi = serde_json::from_value(Value::Array(vec![fragment]))
+            .context("Invalid JSON ABI function fragment")?;
+        let function = abi
+            .functions()
+            .next()
+            .cloned()
+            .context("JSON ABI fragment is not a function")?;
+        return Ok(NamedFunction {
+            function,
+            inputs,
+            outputs,
+        });
+    }
+    let function = HumanReadableParser::parse_function(definition)
+        .map_err(|e| anyhow::anyhow!("Invalid function signature '{}': {}", definition, e))?;
+    Ok(NamedFunction {
+        function,
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+    })
+}
+
+/// The names of a parameter's tuple components, read from a JSON ABI.
+/// `ethers::abi` keeps only the components' types.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) enum Components {
+    /// Not a tuple, or a tuple with an unnamed component.
+    #[default]
+    Unnamed,
+    Tuple(Vec<(String, Components)>),
+    /// An array, fixed or not, of the inner type.
+    Array(Box<Components>),
+}
+
+impl Components {
+    /// The components of each parameter of a JSON ABI `inputs` or `outputs`
+    /// list.
+    pub(crate) fn of_params(params: Option<&Value>) -> Vec<Self> {
+        params
+            .and_then(Value::as_array)
+            .map(|params| params.iter().map(Self::of_param).collect())
+            .unwrap_or_default()
+    }
+
+    fn of_param(param: &Value) -> Self {
+        let kind = param
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let mut components = Self::Unnamed;
+        if kind.starts_with("tuple") {
+            let fields: Vec<(String, Self)> = param
+                .get("components")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|component| {
+                    let name = component.get("name").and_then(Value::as_str);
+                    (
+                        name.unwrap_or_default().to_string(),
+                        Self::of_param(component),
+                    )
+                })
+                .collect();
+            if !fields.is_empty() && fields.iter().all(|(name, _)| !name.is_empty()) {
+                components = Self::Tuple(fields);
+            }
+        }
+        for _ in 0..kind.matches('[').count() {
+            components = Self::Array(Box::new(components));
+        }
+        components
+    }
+}
+
+/// Converts decoded function inputs or outputs into a JSON object keyed by
+/// parameter name, or `argN` for unnamed parameters. `components` names the
+/// components of tuple parameters; see `named_token_to_json`.
+pub(crate) fn params_to_json(
+    params: &[Param],
+    components: &[Components],
+    tokens: &[Token],
+) -> Map<String, Value> {
+    params
+        .iter()
+        .zip(tokens)
+        .enumerate()
+        .map(|(i, (param, token))| {
+            let name = if param.name.is_empty() {
+                format!("arg{}", i)
+            } else {
+                param.name.clone()
+            };
+            let components = components.get(i).unwrap_or(&Components::Unnamed);
+            (name, named_token_to_json(token, components))
+        })
+        .collect()
+}
+
+/// Encodes `value` as the topic of an indexed parameter of type `kind`.
+///
+/// Addresses and 32-byte values are hex, integers are decimal or `0x` hex, and
+/// booleans are `true`/`false`. Dynamic types (strings, bytes, arrays) are
+/// stored in topics as their keccak256 hash, so `value` is hashed: strings as
+/// UTF-8 and bytes as `0x` hex.
+pub fn encode_topic(kind: &ParamType, value: &str) -> Result<H256> {
+    let value = value.trim();
+    let invalid = || format!("Invalid {} value '{}'", kind, value);
+    Ok(match kind {
+        ParamType::Address => H256::from(value.parse::<Address>().with_context(invalid)?),
+        ParamType::Uint(_) => {
+            let n = if let Some(hex) = value.strip_prefix("0x") {
+                U256::from_str_radix(hex, 16)
+            } else {
+                U256::from_dec_str(value).map_err(|e| e.into())
+            }
+            .map_err(|_| anyhow::anyhow!(invalid()))?;
+            u256_topic(n)
+        }
+        ParamType::Int(_) => {
+            let n = I256::from_dec_str(value).with_context(invalid)?;
+            u256_topic(n.into_raw())
+        }
+        ParamType::Bool => match value {
+            "true" => H256::from_low_u64_be(1),
+            "false" => H256::zero(),
+            _ => anyhow::bail!(invalid()),
+        },
+        ParamType::FixedBytes(len) => {
+            let bytes = hex::decode(value.trim_start_matches("0x")).with_context(invalid)?;
+            if bytes.len() != *len {
+                anyhow::bail!(invalid());
+            }
+            let mut topic = [0u8; 32];
+            topic[..bytes.len()].copy_from_slice(&bytes);
+            H256::from(topic)
+        }
+        ParamType::Bytes => {
+            let bytes = hex::decode(value.trim_start_matches("0x")).with_context(invalid)?;
+            H256::from(keccak256(bytes))
+        }
+        ParamType::String => H256::from(keccak256(value.as_bytes())),
+        _ => anyhow::bail!("Filtering on {} parameters is not supported", kind),
+    })
+}
+
+fn u256_topic(value: U256) -> H256 {
+    let mut topic = [0u8; 32];
+    value.to_big_endian(&mut topic);
+    H256::from(topic)
+}
+
+/// Converts a decoded ABI token into JSON like `token_to_json`, except that a
+/// tuple whose components are all named becomes an object keyed by those
+/// names.
+pub(crate) fn named_token_to_json(token: &Token, components: &Components) -> Value {
+    match (token, components) {
+        (Token::Tuple(items), Components::Tuple(fields)) if items.len() == fields.len() => {
+            Value::Object(
+                fields
+                    .iter()
+                    .zip(items)
+                    .map(|((name, inner), item)| (name.clone(), named_token_to_json(item, inner)))
+                    .collect(),
+            )
+        }
+        (Token::Array(items) | Token::FixedArray(items), Components::Array(inner)) => Value::Array(
+            items
+                .iter()
+                .map(|item| named_token_to_json(item, inner))
+                .collect(),
+        ),
+        _ => token_to_json(token),
+    }
+}
+
+/// Converts a decoded ABI token into JSON.
+///
+/// Addresses are EIP-55 checksummed, integers are decimal strings (so uint256
+/// values never lose precision), bytes are `0x`-prefixed hex, and arrays and
+/// tuples become JSON arrays; see `named_token_to_json` for named tuples.
+pub fn token_to_json(token: &Token) -> Value {
+    match token {
+        Token::Address(a) => Value::String(to_checksum(a, None)),
+        Token::Uint(u) => Value::String(u.to_string()),
+        Token::Int(i) => Value::String(I256::from_raw(*i).to_string()),
+        Token::Bool(b) => Value::Bool(*b),
+        Token::String(s) => Value::String(s.clone()),
+        Token::Bytes(b) | Token::FixedBytes(b) => Value::String(format!("0x{}", hex::encode(b))),
+        Token::Array(items) | Token::FixedArray(items) | Token::Tuple(items) => {
+            Value::Array(items.iter().map(token_to_json).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    fn address_topic(addr: Address) -> H256 {
+        H256::from(addr)
+    }
+
+    #[test]
+    fn test_decode_transfer_with_named_params() {
+        let decoder = EventDecoder::parse(
+            "event Transfer(address indexed from, address indexed to, uint256 value)",
+        )
+        .expect("Valid event declaration");
+        assert_eq!(decoder.signature(), "Transfer(address,address,uint256)");
+
+        let from = Address::repeat_byte(0x11);
+        let to = Address::repeat_byte(0x22);
+        let value = U256::exp10(24) + 7;
+        let mut data = [0u8; 32];
+        value.to_big_endian(&mut data);
+
+        let params = decoder
+            .decode(
+                &[decoder.topic0(), address_topic(from), address_topic(to)],
+                &data,
+            )
+            .expect("Transfer log decodes");
+        assert_eq!(params["from"], "0x1111111111111111111111111111111111111111");
+        assert_eq!(params["to"], Value::String(to_checksum(&to, None)));
+        assert_eq!(params["value"], "1000000000000000000000007");
+    }
+
+    #[test]
+    fn test_bare_signature_infers_indexed_params() {
+        let decoder =
+            EventDecoder::parse("Transfer(address,address,uint256)").expect("Valid signature");
+        let from = Address::repeat_byte(0x11);
+        let to = Address::repeat_byte(0x22);
+        let mut data = [0u8; 32];
+        U256::from(42).to_big_endian(&mut data);
+
+        let event_data = decoder.event_data(
+            &[decoder.topic0(), address_topic(from), address_topic(to)],
+            &Bytes::from(data.to_vec()),
+        );
+        assert_eq!(event_data["event"], "Transfer");
+        assert_eq!(event_data["params"]["arg2"], "42");
+        assert_eq!(
+            event_data["raw"]["topics"].as_array().map(Vec::len),
+            Some(3)
+        );
+        assert!(event_data["raw"]["data"]
+            .as_str()
+            .is_some_and(|d| d.ends_with("2a")));
+    }
+
+    #[test]
+    fn test_json_fragment_with_dynamic_types() {
+        let decoder = EventDecoder::parse(
+            r#"{"type":"event","name":"Posted","inputs":[
+                {"name":"author","type":"address","indexed":true},
+                {"name":"ids","type":"int256[]","indexed":false},
+                {"name":"payload","type":"bytes","indexed":false}
+            ]}"#,
+        )
+        .expect("Valid JSON fragment");
+
+        let author = Address::repeat_byte(0xab);
+        let data = ethers::abi::encode(&[
+            Token::Array(vec![
+                Token::Int(I256::from(-5).into_raw()),
+                Token::Int(U256::from(9)),
+            ]),
+            Token::Bytes(vec![0xde, 0xad]),
+        ]);
+
+        let params = decoder
+            .decode(&[decoder.topic0(), address_topic(author)], &data)
+            .expect("Posted log decodes");
+        assert_eq!(params["ids"], json!(["-5", "9"]));
+        assert_eq!(params["payload"], "0xdead");
+    }
+
+    #[test]
+    fn test_decode_calldata_by_selector() {
+        let decoder = FunctionDecoder::parse(&[
+            "function transfer(address to, uint256 amount)".to_string(),
+            r#"[{"type":"function","name":"approve","inputs":[
+                {"name":"spender","type":"address"},{"name":"","type":"uint256"}
+            ],"outputs":[],"stateMutability":"nonpayable"}]"#
+                .to_string(),
+        ])
+        .expect("Valid function definitions");
+
+        let to = Address::repeat_byte(0x22);
+        let mut input = hex::decode("a9059cbb").unwrap();
+        input.extend(ethers::abi::encode(&[
+            Token::Address(to),
+            Token::Uint(U256::from(7)),
+        ]));
+        let call = decoder.decode_call(&input).expect("transfer is known");
+        assert_eq!(call["function"], "transfer");
+        assert_eq!(call["selector"], "0xa9059cbb");
+        assert_eq!(call["params"]["to"], Value::String(to_checksum(&to, None)));
+        assert_eq!(call["params"]["amount"], "7");
+
+        let mut approve = hex::decode("095ea7b3").unwrap();
+        approve.extend(ethers::abi::encode(&[
+            Token::Address(to),
+            Token::Uint(U256::one()),
+        ]));
+        let call = decoder.decode_call(&approve).expect("approve is known");
+        assert_eq!(call["params"]["arg1"], "1");
+
+        assert!(decoder.decode_call(&input[..2]).is_none());
+        assert!(decoder.decode_call(&[0xde, 0xad, 0xbe, 0xef]).is_none());
+        let truncated = decoder.decode_call(&input[..8]).expect("Selector matches");
+        assert!(truncated["params"].is_null());
+        assert!(truncated["decode_error"].is_string());
+    }
+
+    #[test]
+    fn test_named_tuple_components_decode_to_objects() {
+        let decoder = EventDecoder::parse(
+            r#"{"type":"event","name":"Filled","inputs":[
+                {"name":"order","type":"tuple","indexed":false,"components":[
+                    {"name":"maker","type":"address"},{"name":"amount","type":"uint256"}
+                ]},
+                {"name":"fills","type":"tuple[]","indexed":false,"components":[
+                    {"name":"price","type":"uint256"}
+                ]},
+                {"name":"pair","type":"tuple","indexed":false,"components":[
+                    {"name":"","type":"uint256"},{"name":"","type":"uint256"}
+                ]}
+            ]}"#,
+        )
+        .expect("Valid JSON fragment");
+
+        let maker = Address::repeat_byte(0x11);
+        let data = ethers::abi::encode(&[
+            Token::Tuple(vec![Token::Address(maker), Token::Uint(U256::from(5))]),
+            Token::Array(vec![
+                Token::Tuple(vec![Token::Uint(U256::from(1))]),
+                Token::Tuple(vec![Token::Uint(U256::from(2))]),
+            ]),
+            Token::Tuple(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+        ]);
+
+        let params = decoder
+            .decode(&[decoder.topic0()], &data)
+            .expect("Filled log decodes");
+        assert_eq!(
+            params["order"],
+            json!({"maker": to_checksum(&maker, None), "amount": "5"})
+        );
+        assert_eq!(params["fills"], json!([{"price": "1"}, {"price": "2"}]));
+        assert_eq!(params["pair"], json!(["3", "4"]));
+    }
+
+    #[test]
+    fn test_decode_struct_calldata_to_object() {
+        let decoder = FunctionDecoder::parse(&[r#"[{"type":"function","name":"fill",
+            "inputs":[{"name":"order","type":"tuple","components":[
+                {"name":"maker","type":"address"},{"name":"amount","type":"uint256"}
+            ]}],"outputs":[],"stateMutability":"nonpayable"}]"#
+            .to_string()])
+        .expect("Valid function definitions");
+
+        let maker = Address::repeat_byte(0x11);
+        let function = parse_function("function fill((address,uint256))").unwrap();
+        let mut input = function.short_signature().to_vec();
+        input.extend(ethers::abi::encode(&[Token::Tuple(vec![
+            Token::Address(maker),
+            Token::Uint(U256::from(9)),
+        ])]));
+        let call = decoder.decode_call(&input).expect("fill is known");
+        assert_eq!(
+            call["params"]["order"],
+            json!({"maker": to_checksum(&maker, None), "amount": "9"})
+        );
+    }
+
+    #[test]
+    fn test_mismatched_log_keeps_raw_data() {
+        let decoder = EventDecoder::parse("event Approval(address indexed owner, uint256 value)")
+            .expect("Valid event declaration");
+        let event_data = decoder.event_data(&[H256::zero()], &Bytes::default());
+        assert!(event_data["params"].is_null());
+        assert!(event_data["decode_error"].is_string());
+        assert_eq!(event_data["raw"]["data"], "0x");
+    }
+}
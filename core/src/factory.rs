@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use ethers::abi::ParamType;
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::BlockchainEvent;
+use crate::decoder::EventDecoder;
+use crate::merkle::hash_content;
+
+/// Follows contracts deployed by a factory. The job's `contract_address` is the
+/// factory; every child announced by `creation_event` is indexed from its
+/// creation block onward.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactoryConfig {
+    /// The factory's creation event, in any form accepted by `EventDecoder`
+    /// (e.g. `event PoolCreated(address indexed token0, address indexed token1,
+    /// uint24 indexed fee, int24 tickSpacing, address pool)`).
+    pub creation_event: String,
+    /// The creation event parameter holding the child address, by name or as
+    /// `argN` for unnamed parameters (e.g. `pool`).
+    pub child_address_param: String,
+    /// Events to index on every child contract.
+    pub child_events: Vec<String>,
+}
+
+/// A contract discovered from a factory creation event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildContract {
+    pub address: Address,
+    pub creation_block: u64,
+    pub creation_block_hash: String,
+    pub creation_transaction_hash: String,
+    pub creation_log_index: u64,
+}
+
+impl ChildContract {
+    /// Hash committed in the job's Merkle tree for this discovery.
+    pub fn content_hash(&self, chain: &str, factory: &str) -> String {
+        hash_content(
+            format!(
+                "{}:{}:{:?}:{}:{}:{}",
+                chain,
+                factory.to_lowercase(),
+                self.address,
+                self.creation_block,
+                self.creation_transaction_hash,
+                self.creation_log_index
+            )
+            .as_bytes(),
+        )
+    }
+}
+
+impl FactoryConfig {
+    /// Checks that the events parse, that `child_address_param` names an
+    /// address parameter of the creation event, and that at least one child
+    /// event is given.
+    pub fn validate(&self) -> Result<()> {
+        let decoder =
+            EventDecoder::parse(&self.creation_event).context("Invalid creation_event")?;
+        let param = decoder
+            .event()
+            .inputs
+            .iter()
+            .enumerate()
+            .find(|(i, p)| {
+                p.name == self.child_address_param
+                    || format!("arg{}", i) == self.child_address_param
+            })
+            .map(|(_, p)| p)
+            .with_context(|| {
+                format!(
+                    "{} has no parameter '{}'",
+                    decoder.name(),
+                    self.child_address_param
+                )
+            })?;
+        if param.kind != ParamType::Address {
+            anyhow::bail!(
+                "Parameter '{}' of {} is {}, not an address",
+                self.child_address_param,
+                decoder.name(),
+                param.kind
+            );
+        }
+        if self.child_events.is_empty() {
+            anyhow::bail!("At least one child event must be specified");
+        }
+        for event in &self.child_events {
+            EventDecoder::parse(event).context("Invalid child event")?;
+        }
+        Ok(())
+    }
+
+    /// Extracts the children announced by creation events among `events`.
+    /// Other events, and creation events that failed to decode, are skipped.
+    pub fn children(&self, events: &[BlockchainEvent]) -> Result<Vec<ChildContract>> {
+        let decoder = EventDecoder::parse(&self.creation_event)?;
+        let topic0 = format!("{:?}", decoder.topic0());
+        Ok(events
+            .iter()
+            .filter(|e| e.topics.first() == Some(&topic0))
+            .filter_map(|e| {
+                let address = e.event_data["params"][&self.child_address_param]
+                    .as_str()?
+                    .parse::<Address>()
+                    .ok()?;
+                Some(ChildContract {
+                    address,
+                    creation_block: e.block_number,
+                    creation_block_hash: e.block_hash.clone(),
+                    creation_transaction_hash: e.transaction_hash.clone(),
+                    creation_log_index: e.log_index,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Bytes, Log, H256, U256, U64};
+
+    const POOL_CREATED: &str = "event PoolCreated(address indexed token0, address indexed token1, uint24 indexed fee, int24 tickSpacing, address pool)";
+
+    fn factory() -> FactoryConfig {
+        FactoryConfig {
+            creation_event: POOL_CREATED.to_string(),
+            child_address_param: "pool".to_string(),
+            child_events: vec![
+                "Swap(address,address,int256,int256,uint160,uint128,int24)".to_string()
+            ],
+        }
+    }
+
+    #[test]
+    fn test_validate_child_param() {
+        assert!(factory().validate().is_ok());
+
+        let mut wrong_type = factory();
+        wrong_type.child_address_param = "tickSpacing".to_string();
+        assert!(wrong_type.validate().is_err());
+
+        let mut missing = factory();
+        missing.child_address_param = "vault".to_string();
+        assert!(missing.validate().is_err());
+
+        let mut positional = factory();
+        positional.child_address_param = "arg4".to_string();
+        positional.creation_event = "PoolCreated(address,address,uint24,int24,address)".to_string();
+        assert!(positional.validate().is_ok());
+    }
+
+    #[test]
+    fn test_children_from_creation_events() {
+        let config = factory();
+        let decoder = EventDecoder::parse(POOL_CREATED).unwrap();
+        let pool = Address::repeat_byte(0x77);
+        let mut data = [0u8; 64];
+        data[31] = 60;
+        data[44..].copy_from_slice(pool.as_bytes());
+        let log = Log {
+            address: Address::repeat_byte(0x1f),
+            topics: vec![
+                decoder.topic0(),
+                H256::from(Address::repeat_byte(1)),
+                H256::from(Address::repeat_byte(2)),
+                H256::from_low_u64_be(3000),
+            ],
+            data: Bytes::from(data.to_vec()),
+            block_hash: Some(H256::repeat_byte(9)),
+            block_number: Some(U64::from(42)),
+            transaction_hash: Some(H256::repeat_byte(8)),
+            log_index: Some(U256::from(3)),
+            ..Default::default()
+        };
+        let event = crate::blockchain::decode_log("ethereum", &decoder, &log, 0).unwrap();
+
+        let children = config.children(&[event]).unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].address, pool);
+        assert_eq!(children[0].creation_block, 42);
+        assert_eq!(children[0].creation_log_index, 3);
+    }
+}
@@ -1,8 +1,11 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::factory::FactoryConfig;
+use crate::merkle::{hash_content, MerkleMode};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Job {
     pub id: Uuid,
@@ -29,6 +32,11 @@ pub enum JobStatus {
     /// Events indexed successfully but the on-chain Merkle commitment is pending
     /// retry. Job will transition to Completed or Failed by the retry worker.
     PendingCommit,
+    /// Backfill finished; the job keeps following the chain head.
+    Live,
+    /// A live-tail job suspended by its owner. Its block cursor is kept so it
+    /// resumes where it stopped.
+    Paused,
 }
 
 impl FromStr for JobStatus {
@@ -42,6 +50,8 @@ impl FromStr for JobStatus {
             "completed" => Ok(Self::Completed),
             "failed" => Ok(Self::Failed),
             "pending_commit" => Ok(Self::PendingCommit),
+            "live" => Ok(Self::Live),
+            "paused" => Ok(Self::Paused),
             _ => Err(anyhow::anyhow!("Invalid status")),
         }
     }
@@ -56,6 +66,8 @@ impl std::fmt::Display for JobStatus {
             Self::Completed => write!(f, "completed"),
             Self::Failed => write!(f, "failed"),
             Self::PendingCommit => write!(f, "pending_commit"),
+            Self::Live => write!(f, "live"),
+            Self::Paused => write!(f, "paused"),
         }
     }
 }
@@ -69,12 +81,15 @@ pub struct JobConfig {
 
 /// Typed union of all supported job parameter shapes.
 /// Uses untagged serde so the existing JSON stored in the database is compatible:
-/// HttpCrawl params contain `url`, BlockchainIndex params contain `contract_address`.
+/// HttpCrawl params contain `url`, BlockchainIndex params contain `contract_address`,
+/// TransactionIndex params contain `addresses` and StateSnapshot params contain `calls`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JobParams {
     HttpCrawl(HttpCrawlParams),
-    BlockchainIndex(BlockchainIndexParams),
+    BlockchainIndex(Box<BlockchainIndexParams>),
+    TransactionIndex(TransactionIndexParams),
+    StateSnapshot(StateSnapshotParams),
 }
 
 impl JobParams {
@@ -91,6 +106,20 @@ impl JobParams {
             _ => None,
         }
     }
+
+    pub fn as_transaction_index(&self) -> Option<&TransactionIndexParams> {
+        match self {
+            Self::TransactionIndex(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn as_state_snapshot(&self) -> Option<&StateSnapshotParams> {
+        match self {
+            Self::StateSnapshot(p) => Some(p),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +127,8 @@ impl JobParams {
 pub enum JobType {
     HttpCrawl,
     BlockchainIndex,
+    TransactionIndex,
+    StateSnapshot,
 }
 
 /// Parameters for an HTTP crawl job.
@@ -114,12 +145,62 @@ fn default_max_pages() -> usize {
     100
 }
 
+/// Contract address that matches events emitted by any contract.
+pub const ANY_ADDRESS: &str = "*";
+
+/// Restricts an indexed event parameter to a set of values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicFilter {
+    /// Name of a parameter declared `indexed`, at the same topic position in
+    /// every event of the job.
+    pub param: String,
+    /// Accepted values, any of which matches. See `decoder::encode_topic`.
+    pub values: Vec<String>,
+}
+
+/// An aggregate maintained from a job's ERC-20 and ERC-721 transfer events.
+///
+/// Projections are updated by a database trigger as events are stored and
+/// rolled back when events are orphaned by a reorg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    /// The current balance of every holder of each token contract.
+    Balances,
+    /// Tokens minted and burned per block, from which supply over time follows.
+    Supply,
+}
+
+impl Projection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Balances => "balances",
+            Self::Supply => "supply",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "balances" => Some(Self::Balances),
+            "supply" => Some(Self::Supply),
+            _ => None,
+        }
+    }
+}
+
 /// Parameters for a blockchain event indexing job.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockchainIndexParams {
     pub chain: String,
+    /// The contract to index, or `ANY_ADDRESS` to match every contract.
     pub contract_address: String,
+    /// Further contracts indexed with the same events and filters.
+    #[serde(default)]
+    pub contract_addresses: Vec<String>,
     pub events: Vec<String>,
+    /// Filters on indexed event parameters; all must match.
+    #[serde(default)]
+    pub topic_filters: Vec<TopicFilter>,
     pub from_block: u64,
     pub to_block: Option<u64>,
     /// Whether to run AI extraction on each indexed event.
@@ -133,13 +214,182 @@ pub struct BlockchainIndexParams {
     /// Defaults to 100,000 when enable_ai is true and no budget is specified.
     #[serde(default)]
     pub ai_token_budget: Option<u32>,
+    /// Keep following the chain head after the backfill instead of completing.
+    /// Live jobs must not set `to_block`.
+    #[serde(default)]
+    pub live: bool,
+    /// Treat `contract_address` as a factory and also index the contracts it
+    /// creates.
+    #[serde(default)]
+    pub factory: Option<FactoryConfig>,
+    /// Fetch the metadata document behind each transferred NFT's token URI.
+    #[serde(default)]
+    pub resolve_token_uris: bool,
+    /// Blocks per backfill shard; shards are fetched concurrently and merged
+    /// in block order. Defaults to `DEFAULT_SHARD_BLOCKS`.
+    #[serde(default)]
+    pub shard_blocks: Option<u64>,
+    /// Token projections maintained from the job's transfer events.
+    #[serde(default)]
+    pub projections: Vec<Projection>,
+    /// The tree the job's Merkle commitment is built in.
+    #[serde(default)]
+    pub merkle_mode: MerkleMode,
+    /// Blocks between on-chain commitments of a live job's Merkle Mountain
+    /// Range root. Defaults to `DEFAULT_MMR_COMMIT_BLOCKS`.
+    #[serde(default)]
+    pub mmr_commit_blocks: Option<u64>,
+}
+
+impl BlockchainIndexParams {
+    /// Every address the job indexes, or an empty list if any is `ANY_ADDRESS`.
+    pub fn addresses(&self) -> anyhow::Result<Vec<ethers::types::Address>> {
+        let all = std::iter::once(&self.contract_address).chain(&self.contract_addresses);
+        if all.clone().any(|a| a == ANY_ADDRESS) {
+            return Ok(Vec::new());
+        }
+        let mut addresses = Vec::new();
+        for address in all {
+            let address = address
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid contract address '{}'", address))?;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        Ok(addresses)
+    }
+}
+
+/// Parameters for a transaction indexing job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionIndexParams {
+    pub chain: String,
+    /// Transactions sent from or to any of these addresses are indexed.
+    pub addresses: Vec<String>,
+    /// Function definitions used to decode calldata, in any form accepted by
+    /// `FunctionDecoder::parse`. Calls to unknown functions are kept undecoded.
+    #[serde(default)]
+    pub abi: Vec<String>,
+    pub from_block: u64,
+    /// Every block in the range is fetched, so the range is always bounded.
+    pub to_block: u64,
+    /// Attach a `debug_traceTransaction` call trace to each transaction where
+    /// the node supports it.
+    #[serde(default)]
+    pub include_traces: bool,
+    /// The tree the job's Merkle commitment is built in.
+    #[serde(default)]
+    pub merkle_mode: MerkleMode,
+}
+
+impl TransactionIndexParams {
+    /// The parsed, deduplicated addresses.
+    pub fn addresses(&self) -> anyhow::Result<Vec<ethers::types::Address>> {
+        let mut addresses = Vec::new();
+        for address in &self.addresses {
+            let address = address
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid address '{}'", address))?;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        Ok(addresses)
+    }
 }
 
+/// A view call made by a state snapshot job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewCall {
+    /// The contract to call.
+    pub contract: String,
+    /// The function, in any form accepted by `decoder::parse_function`.
+    /// Results are decoded when the definition declares return types, e.g.
+    /// `function balanceOf(address owner) view returns (uint256)`.
+    pub function: String,
+    /// Arguments, one per function input, as text (e.g. `0x...` for
+    /// addresses, decimal for integers, `[1,2]` for arrays).
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Parameters for a contract state snapshot job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshotParams {
+    pub chain: String,
+    /// Calls made at every block in `blocks`.
+    pub calls: Vec<ViewCall>,
+    /// Block heights to snapshot. Historical blocks need an archive node.
+    pub blocks: Vec<u64>,
+    /// The tree the job's Merkle commitment is built in.
+    #[serde(default)]
+    pub merkle_mode: MerkleMode,
+}
+
+/// A fetched page. `body_hash` is the SHA-256 of the response body. Each of
+/// its links is stored as a `crawl_results` row and committed as a leaf; see
+/// `canonical_link_document`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrawlResult {
     pub url: String,
     pub status_code: u16,
-    pub content_hash: String,
+    pub body_hash: String,
     pub links: Vec<String>,
     pub timestamp: DateTime<Utc>,
 }
+
+impl CrawlResult {
+    pub fn new(
+        url: &str,
+        status_code: u16,
+        body: &[u8],
+        links: Vec<String>,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            status_code,
+            body_hash: hash_content(body),
+            links,
+            timestamp,
+        }
+    }
+
+    /// The canonical document of each link, in page order.
+    pub fn link_documents(&self) -> Vec<serde_json::Value> {
+        self.links
+            .iter()
+            .map(|link| {
+                canonical_link_document(
+                    link,
+                    &self.url,
+                    self.status_code,
+                    &self.body_hash,
+                    &self.timestamp,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Builds the canonical document of a link found on a crawled page: the
+/// link's `url`, the `page_url` it was found on, the page's `status_code` and
+/// `body_hash`, and `fetched_at` to the second. Its content hash is that of an
+/// event document under `EVENT_HASH_VERSION`, the SHA-256 of its JCS
+/// serialization, so crawl bundles verify like event bundles.
+pub fn canonical_link_document(
+    url: &str,
+    page_url: &str,
+    status_code: u16,
+    body_hash: &str,
+    fetched_at: &DateTime<Utc>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "url": url,
+        "page_url": page_url,
+        "status_code": status_code,
+        "body_hash": body_hash.to_lowercase(),
+        "fetched_at": fetched_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+    })
+}
@@ -1,30 +1,84 @@
+pub mod abi;
 pub mod advanced_crawler;
 pub mod ai_extractor;
+pub mod backfill;
 pub mod blockchain;
+pub mod bundle;
+pub mod canonical;
+pub mod cbor;
+pub mod chains;
 pub mod crawler;
 pub mod credits;
+pub mod decoder;
 pub mod distributed;
+pub mod factory;
 pub mod ipfs;
 pub mod job;
 pub mod marketplace;
 pub mod merkle;
+pub mod merkle_builder;
+pub mod mmr;
+pub mod mock;
+pub mod provider;
 pub mod queue;
+pub mod rpc;
+pub mod snapshot;
 pub mod timestamp;
+pub mod token;
+pub mod transactions;
 
+pub use abi::{merge_function_definitions, ContractAbi};
 pub use advanced_crawler::{
     BrowserPool, BrowserSession, CaptchaSolver, ProxyManager, StealthConfig,
 };
 pub use ai_extractor::{AIExtractor, ExtractionResult};
-pub use blockchain::{BlockchainClient, BlockchainEvent, EventFilter};
+pub use backfill::{plan_shards, BlockRange, RangeScanConfig, ScanCursor, DEFAULT_SHARD_BLOCKS};
+pub use blockchain::{
+    canonical_event_document, hash_event_document, BlockchainClient, BlockchainEvent, EventFilter,
+    EVENT_HASH_VERSION, LEGACY_EVENT_HASH_VERSION,
+};
+pub use bundle::{
+    registry_committed_block, BundleError, BundleRecord, MultiproofBundle, ProofBundle,
+    RegistryCommitment, PROOF_BUNDLE_VERSION,
+};
+pub use canonical::to_canonical_json;
+pub use cbor::{from_cbor, to_canonical_cbor};
+pub use chains::{ChainConfig, ChainRegistry};
 pub use crawler::Crawler;
 pub use credits::CreditManager;
+pub use decoder::{EventDecoder, FunctionDecoder};
 pub use distributed::{Coordinator, DistributedQueue, Worker, WorkerConfig};
+pub use factory::{ChildContract, FactoryConfig};
 pub use ipfs::IpfsStorage;
 pub use job::{
-    BlockchainIndexParams, CrawlResult, HttpCrawlParams, Job, JobConfig, JobParams, JobStatus,
-    JobType,
+    canonical_link_document, BlockchainIndexParams, CrawlResult, HttpCrawlParams, Job, JobConfig,
+    JobParams, JobStatus, JobType, Projection, StateSnapshotParams, TopicFilter,
+    TransactionIndexParams, ViewCall, ANY_ADDRESS,
 };
 pub use marketplace::MarketplaceClient;
-pub use merkle::{compute_merkle_root, generate_merkle_proof, hash_content, verify_merkle_proof};
+pub use merkle::{
+    compute_merkle_root, compute_versioned_root, generate_merkle_proof, generate_multiproof,
+    generate_versioned_proof, hash_content, verify_merkle_proof, verify_multiproof,
+    verify_versioned_proof, MerkleMode, MerkleMultiproof, KECCAK_MERKLE_TREE_VERSION,
+    LEGACY_MERKLE_TREE_VERSION, MERKLE_TREE_VERSION,
+};
+pub use merkle_builder::{
+    checkpoint_bands, checkpoint_spans, decode_checkpoint_nodes, proof_from_checkpoints,
+    CheckpointSpan, MerkleBuilder, CHECKPOINT_STRIDE,
+};
+pub use mmr::{
+    generate_mmr_consistency_proof, generate_mmr_inclusion_proof, mmr_checkpoint_spans,
+    mmr_consistency_proof_from_paths, mmr_mountains, mmr_path_from_checkpoints, mmr_root,
+    verify_mmr_consistency_proof, verify_mmr_inclusion_proof, MerkleMountainRange, MmrCheckpoint,
+    MmrConsistencyProof, MmrInclusionProof, DEFAULT_MMR_COMMIT_BLOCKS, MMR_TREE_VERSION,
+};
+pub use provider::{ChainProvider, ContentStore, CreditSpender, HashCommitter};
 pub use queue::JobQueue;
+pub use rpc::FailoverTransport;
+pub use snapshot::{prepare_calls, take_snapshot, PreparedCall, SnapshotValue};
 pub use timestamp::TimestampClient;
+pub use token::{
+    fetch_token_uri, is_transfer_event, resolve_token_uri, scale_amount, token_transfer,
+    TokenMetadata, TokenStandard, TokenTransfer,
+};
+pub use transactions::{scan_transactions, IndexedTransaction};
@@ -1,4 +1,63 @@
+use anyhow::{Context, Result};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+
+use crate::merkle_builder::MerkleBuilder;
+
+/// The original tree format: SHA-256 over the concatenated hex strings of two
+/// nodes, with the last node of an odd level duplicated. Kept so commitments
+/// made in this format can still be verified.
+pub const LEGACY_MERKLE_TREE_VERSION: u16 = 1;
+/// The tree format of new commitments: the RFC 6962 Merkle tree hash over the
+/// raw 32-byte leaf digests. See `compute_versioned_root`.
+pub const MERKLE_TREE_VERSION: u16 = 2;
+/// The keccak256 sorted-pair tree of OpenZeppelin's `StandardMerkleTree`,
+/// whose proofs verify with `MerkleProof.verify` on-chain.
+pub const KECCAK_MERKLE_TREE_VERSION: u16 = 3;
+
+/// The tree a job commits its content hashes in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MerkleMode {
+    /// The SHA-256 tree, `MERKLE_TREE_VERSION`.
+    #[default]
+    Sha256,
+    /// The keccak256 sorted-pair tree, `KECCAK_MERKLE_TREE_VERSION`, for
+    /// inclusion checks in Solidity.
+    Keccak256,
+}
+
+impl MerkleMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Keccak256 => "keccak256",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(Self::Sha256),
+            "keccak256" => Some(Self::Keccak256),
+            _ => None,
+        }
+    }
+
+    /// The tree version commitments in this mode are built in.
+    pub fn tree_version(&self) -> u16 {
+        match self {
+            Self::Sha256 => MERKLE_TREE_VERSION,
+            Self::Keccak256 => KECCAK_MERKLE_TREE_VERSION,
+        }
+    }
+}
+
+/// Prefix of a leaf node in tree version 2.
+const LEAF_PREFIX: u8 = 0x00;
+/// Prefix of an internal node in tree version 2.
+const NODE_PREFIX: u8 = 0x01;
 
 /// Hashes the provided data using SHA-256 and returns a hex-encoded string.
 pub fn hash_content(data: &[u8]) -> String {
@@ -100,6 +159,455 @@ pub fn verify_merkle_proof(
     current_hash == root
 }
 
+/// Computes the Merkle root of `leaves` in tree format `version`.
+///
+/// In version 2 each leaf must be a hex-encoded 32-byte digest. A leaf hashes
+/// to `SHA-256(0x00 || leaf)` and an internal node to
+/// `SHA-256(0x01 || left || right)`, so no leaf can be passed off as a node.
+/// The last node of an odd level is promoted to the next level unchanged
+/// rather than duplicated, which makes the root that of RFC 6962. An empty
+/// tree's root is the SHA-256 of no input.
+///
+/// Version 3 follows OpenZeppelin's `StandardMerkleTree` for `bytes32`
+/// values: a leaf is `keccak256(keccak256(leaf))`, the leaves are sorted, and
+/// a node is the keccak256 of its two children in ascending order. It needs
+/// at least one leaf.
+pub fn compute_versioned_root(leaves: &[String], version: u16) -> Result<String> {
+    match version {
+        KECCAK_MERKLE_TREE_VERSION => Ok(hex::encode(keccak_tree(leaves)?[0])),
+        LEGACY_MERKLE_TREE_VERSION => Ok(compute_merkle_root(leaves)),
+        MERKLE_TREE_VERSION => {
+            let mut builder = MerkleBuilder::new();
+            for leaf in leaves {
+                builder.push_hex(leaf)?;
+            }
+            Ok(hex::encode(builder.root()))
+        }
+        _ => anyhow::bail!("Unknown Merkle tree version {}", version),
+    }
+}
+
+/// Generates the proof for the leaf at `index` in tree format `version`.
+///
+/// A version 2 proof lists the sibling of each node on the path to the root,
+/// skipping levels where the node is promoted without a sibling. A version 3
+/// proof is the `bytes32[]` that OpenZeppelin's `MerkleProof.verify` takes,
+/// with the leaf `keccak256(bytes.concat(keccak256(abi.encode(leaf))))`.
+pub fn generate_versioned_proof(
+    leaves: &[String],
+    index: usize,
+    version: u16,
+) -> Result<Vec<String>> {
+    if index >= leaves.len() {
+        anyhow::bail!(
+            "Leaf index {} is out of range for {} leaves",
+            index,
+            leaves.len()
+        );
+    }
+    match version {
+        LEGACY_MERKLE_TREE_VERSION => Ok(generate_merkle_proof(leaves, index)),
+        KECCAK_MERKLE_TREE_VERSION => {
+            let tree = keccak_tree(leaves)?;
+            let leaf = keccak_leaf(&leaves[index])?;
+            // Leaves fill the end of the array in reverse sorted order.
+            let mut node = tree
+                .iter()
+                .rposition(|n| *n == leaf)
+                .context("Leaf is missing from the tree")?;
+            let mut proof = Vec::new();
+            while node > 0 {
+                let sibling = if node % 2 == 1 { node + 1 } else { node - 1 };
+                proof.push(hex::encode(tree[sibling]));
+                node = (node - 1) / 2;
+            }
+            Ok(proof)
+        }
+        MERKLE_TREE_VERSION => {
+            let mut proof = Vec::new();
+            let mut level = leaf_nodes(leaves)?;
+            let mut index = index;
+            while level.len() > 1 {
+                let sibling = index ^ 1;
+                if sibling < level.len() {
+                    proof.push(hex::encode(level[sibling]));
+                }
+                level = parent_level(&level);
+                index /= 2;
+            }
+            Ok(proof)
+        }
+        _ => anyhow::bail!("Unknown Merkle tree version {}", version),
+    }
+}
+
+/// Verifies a proof produced by `generate_versioned_proof` against `root`.
+/// Malformed input and unknown versions do not verify. Version 3 proofs do
+/// not depend on the leaf's position, so `leaf_index` and `total_leaves`
+/// only need to be in range.
+pub fn verify_versioned_proof(
+    leaf: &str,
+    proof: &[String],
+    root: &str,
+    leaf_index: usize,
+    total_leaves: usize,
+    version: u16,
+) -> bool {
+    if leaf_index >= total_leaves {
+        return false;
+    }
+    match version {
+        LEGACY_MERKLE_TREE_VERSION => {
+            verify_merkle_proof(leaf, proof, root, leaf_index, total_leaves)
+        }
+        KECCAK_MERKLE_TREE_VERSION => {
+            let Ok(mut current) = keccak_leaf(leaf) else {
+                return false;
+            };
+            for sibling in proof {
+                let Ok(sibling) = digest(sibling) else {
+                    return false;
+                };
+                current = keccak_pair(&current, &sibling);
+            }
+            hex::encode(current) == root.trim_start_matches("0x").to_lowercase()
+        }
+        MERKLE_TREE_VERSION => {
+            let Ok(mut current) = leaf_node(leaf) else {
+                return false;
+            };
+            let mut siblings = proof.iter();
+            let mut index = leaf_index;
+            let mut level_size = total_leaves;
+            while level_size > 1 {
+                let sibling = index ^ 1;
+                if sibling < level_size {
+                    let Some(Ok(sibling_node)) = siblings.next().map(|s| digest(s)) else {
+                        return false;
+                    };
+                    current = if index.is_multiple_of(2) {
+                        node_hash(&current, &sibling_node)
+                    } else {
+                        node_hash(&sibling_node, &current)
+                    };
+                }
+                index /= 2;
+                level_size = level_size.div_ceil(2);
+            }
+            siblings.next().is_none()
+                && hex::encode(current) == root.trim_start_matches("0x").to_lowercase()
+        }
+        _ => false,
+    }
+}
+
+/// Proves several leaves of one tree at once; see `generate_multiproof`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleMultiproof {
+    pub tree_version: u16,
+    pub leaf_count: usize,
+    /// The proven leaves in the order the verifier consumes them: by
+    /// position for version 2, and as OpenZeppelin's `multiProofVerify`
+    /// takes them (once hashed) for version 3.
+    pub leaves: Vec<String>,
+    /// The position of each proven leaf in the tree's leaf list.
+    pub leaf_indices: Vec<usize>,
+    /// The nodes the proven leaves do not determine, in the order they are
+    /// consumed.
+    pub proof: Vec<String>,
+    /// OpenZeppelin's `proofFlags`; empty for version 2.
+    #[serde(default)]
+    pub proof_flags: Vec<bool>,
+}
+
+/// Generates one proof for the leaves at `indices` in tree format `version`.
+///
+/// Nodes that can be computed from the proven leaves are left out, so the
+/// proof repeats no hash and is far shorter than separate proofs for dense
+/// subsets. A version 2 multiproof lists the missing siblings level by level
+/// from the leaves up, left to right; a leaf's own proof is the multiproof of
+/// just that leaf. A version 3 multiproof is what OpenZeppelin's
+/// `MerkleProof.multiProofVerify(proof, proofFlags, root, leaves)` takes,
+/// with each leaf hashed as for a single proof. Version 1 has no multiproofs.
+pub fn generate_multiproof(
+    leaves: &[String],
+    indices: &[usize],
+    version: u16,
+) -> Result<MerkleMultiproof> {
+    anyhow::ensure!(!indices.is_empty(), "A multiproof needs at least one leaf");
+    if let Some(index) = indices.iter().find(|&&index| index >= leaves.len()) {
+        anyhow::bail!(
+            "Leaf index {} is out of range for {} leaves",
+            index,
+            leaves.len()
+        );
+    }
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    anyhow::ensure!(
+        sorted.len() == indices.len(),
+        "A multiproof cannot prove a leaf twice"
+    );
+
+    match version {
+        MERKLE_TREE_VERSION => {
+            let mut levels = vec![leaf_nodes(leaves)?];
+            while levels[levels.len() - 1].len() > 1 {
+                levels.push(parent_level(&levels[levels.len() - 1]));
+            }
+            let known = sorted.iter().map(|&i| (i, levels[0][i])).collect();
+            let mut proof = Vec::new();
+            multiproof_root(known, leaves.len(), |level, index| {
+                proof.push(hex::encode(levels[level][index]));
+                Some(levels[level][index])
+            });
+            Ok(MerkleMultiproof {
+                tree_version: version,
+                leaf_count: leaves.len(),
+                leaves: sorted.iter().map(|&i| leaves[i].clone()).collect(),
+                leaf_indices: sorted,
+                proof,
+                proof_flags: Vec::new(),
+            })
+        }
+        KECCAK_MERKLE_TREE_VERSION => {
+            let tree = keccak_tree(leaves)?;
+            let hashed = leaves
+                .iter()
+                .map(|leaf| keccak_leaf(leaf))
+                .collect::<Result<Vec<_>>>()?;
+            // The sorted leaves fill the end of the array in reverse order.
+            let mut by_hash: Vec<usize> = (0..leaves.len()).collect();
+            by_hash.sort_by_key(|&i| (hashed[i], i));
+            let mut tree_index = vec![0; leaves.len()];
+            for (position, &i) in by_hash.iter().enumerate() {
+                tree_index[i] = tree.len() - 1 - position;
+            }
+
+            // OpenZeppelin's `getMultiProof`: consume nodes from the deepest
+            // up, pairing each with the next when they are siblings.
+            sorted.sort_by_key(|&i| std::cmp::Reverse(tree_index[i]));
+            let mut queue: VecDeque<usize> = sorted.iter().map(|&i| tree_index[i]).collect();
+            let mut proof = Vec::new();
+            let mut proof_flags = Vec::new();
+            while let Some(node) = queue.pop_front().filter(|&node| node > 0) {
+                let sibling = if node % 2 == 1 { node + 1 } else { node - 1 };
+                if queue.front() == Some(&sibling) {
+                    proof_flags.push(true);
+                    queue.pop_front();
+                } else {
+                    proof_flags.push(false);
+                    proof.push(hex::encode(tree[sibling]));
+                }
+                queue.push_back((node - 1) / 2);
+            }
+            Ok(MerkleMultiproof {
+                tree_version: version,
+                leaf_count: leaves.len(),
+                leaves: sorted.iter().map(|&i| leaves[i].clone()).collect(),
+                leaf_indices: sorted,
+                proof,
+                proof_flags,
+            })
+        }
+        LEGACY_MERKLE_TREE_VERSION => {
+            anyhow::bail!("Merkle tree version 1 has no multiproofs")
+        }
+        _ => anyhow::bail!("Unknown Merkle tree version {}", version),
+    }
+}
+
+/// Verifies a multiproof produced by `generate_multiproof` against `root`.
+/// Malformed input and unknown versions do not verify. Version 3 multiproofs
+/// do not depend on the leaves' positions, so `leaf_indices` only need to be
+/// distinct and in range.
+pub fn verify_multiproof(multiproof: &MerkleMultiproof, root: &str) -> bool {
+    let mut indices = multiproof.leaf_indices.clone();
+    indices.sort_unstable();
+    indices.dedup();
+    if multiproof.leaves.is_empty()
+        || indices.len() != multiproof.leaves.len()
+        || multiproof.leaf_indices.len() != multiproof.leaves.len()
+        || indices
+            .last()
+            .is_some_and(|&last| last >= multiproof.leaf_count)
+    {
+        return false;
+    }
+    let Ok(proof) = multiproof
+        .proof
+        .iter()
+        .map(|node| digest(node))
+        .collect::<Result<Vec<_>>>()
+    else {
+        return false;
+    };
+    let mut proof = proof.into_iter();
+
+    let computed = match multiproof.tree_version {
+        MERKLE_TREE_VERSION => {
+            // Leaves are consumed in ascending position.
+            if indices != multiproof.leaf_indices || !multiproof.proof_flags.is_empty() {
+                return false;
+            }
+            let Ok(known) = indices
+                .iter()
+                .zip(&multiproof.leaves)
+                .map(|(&i, leaf)| leaf_node(leaf).map(|node| (i, node)))
+                .collect::<Result<Vec<_>>>()
+            else {
+                return false;
+            };
+            multiproof_root(known, multiproof.leaf_count, |_, _| proof.next())
+        }
+        KECCAK_MERKLE_TREE_VERSION => {
+            // OpenZeppelin's `processMultiProof`.
+            if multiproof.leaves.len() + multiproof.proof.len() != multiproof.proof_flags.len() + 1
+            {
+                return false;
+            }
+            let Ok(mut queue) = multiproof
+                .leaves
+                .iter()
+                .map(|leaf| keccak_leaf(leaf))
+                .collect::<Result<VecDeque<_>>>()
+            else {
+                return false;
+            };
+            for &flag in &multiproof.proof_flags {
+                let (Some(a), Some(b)) = (
+                    queue.pop_front(),
+                    if flag {
+                        queue.pop_front()
+                    } else {
+                        proof.next()
+                    },
+                ) else {
+                    return false;
+                };
+                queue.push_back(keccak_pair(&a, &b));
+            }
+            queue.pop_back().or_else(|| proof.next())
+        }
+        _ => return false,
+    };
+    proof.next().is_none()
+        && computed.is_some_and(|computed| {
+            hex::encode(computed) == root.trim_start_matches("0x").to_lowercase()
+        })
+}
+
+/// Computes a version 2 root from the `known` nodes of the leaf level, in
+/// ascending position, taking each sibling they do not determine from
+/// `sibling(level, index)`.
+fn multiproof_root(
+    mut known: Vec<(usize, [u8; 32])>,
+    leaf_count: usize,
+    mut sibling: impl FnMut(usize, usize) -> Option<[u8; 32]>,
+) -> Option<[u8; 32]> {
+    let mut level = 0;
+    let mut level_size = leaf_count;
+    while level_size > 1 {
+        let mut parents = Vec::with_capacity(known.len());
+        let mut nodes = known.into_iter().peekable();
+        while let Some((index, node)) = nodes.next() {
+            let parent = if index % 2 == 1 {
+                node_hash(&sibling(level, index - 1)?, &node)
+            } else if index + 1 == level_size {
+                node
+            } else if let Some((_, right)) = nodes.next_if(|&(i, _)| i == index + 1) {
+                node_hash(&node, &right)
+            } else {
+                node_hash(&node, &sibling(level, index + 1)?)
+            };
+            parents.push((index / 2, parent));
+        }
+        known = parents;
+        level += 1;
+        level_size = level_size.div_ceil(2);
+    }
+    known.first().map(|&(_, node)| node)
+}
+
+/// Decodes a hex-encoded 32-byte digest, with or without a `0x` prefix.
+pub(crate) fn digest(hex_digest: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(hex_digest.trim_start_matches("0x"))
+        .with_context(|| format!("Merkle leaf {} is not hex-encoded", hex_digest))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Merkle leaf {} is not a 32-byte digest", hex_digest))
+}
+
+pub(crate) fn leaf_node(leaf: &str) -> Result<[u8; 32]> {
+    Ok(leaf_hash(&digest(leaf)?))
+}
+
+pub(crate) fn leaf_hash(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn leaf_nodes(leaves: &[String]) -> Result<Vec<[u8; 32]>> {
+    leaves.iter().map(|leaf| leaf_node(leaf)).collect()
+}
+
+fn keccak_leaf(leaf: &str) -> Result<[u8; 32]> {
+    // `abi.encode` of a `bytes32` is the value itself.
+    Ok(keccak256(keccak256(digest(leaf)?)))
+}
+
+fn keccak_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    keccak256([low.as_slice(), high.as_slice()].concat())
+}
+
+/// Builds a version 3 tree as a flat array like `StandardMerkleTree`: the
+/// root at index 0, the children of node `i` at `2i + 1` and `2i + 2`, and
+/// the sorted leaves at the end in reverse order.
+fn keccak_tree(leaves: &[String]) -> Result<Vec<[u8; 32]>> {
+    if leaves.is_empty() {
+        anyhow::bail!("A keccak256 Merkle tree needs at least one leaf");
+    }
+    let mut sorted = leaves
+        .iter()
+        .map(|leaf| keccak_leaf(leaf))
+        .collect::<Result<Vec<_>>>()?;
+    sorted.sort_unstable();
+
+    let mut tree = vec![[0u8; 32]; 2 * sorted.len() - 1];
+    let len = tree.len();
+    for (i, leaf) in sorted.into_iter().enumerate() {
+        tree[len - 1 - i] = leaf;
+    }
+    for i in (0..len - leaves.len()).rev() {
+        tree[i] = keccak_pair(&tree[2 * i + 1], &tree[2 * i + 2]);
+    }
+    Ok(tree)
+}
+
+/// Pairs up the nodes of a level, promoting an unpaired last node.
+pub(crate) fn parent_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+pub(crate) fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,4 +651,237 @@ mod tests {
             leaves.len()
         ));
     }
+
+    /// RFC 6962's recursive definition of the Merkle tree hash, splitting at
+    /// the largest power of two below the leaf count.
+    fn rfc6962_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+        if leaves.len() == 1 {
+            let mut hasher = Sha256::new();
+            hasher.update([LEAF_PREFIX]);
+            hasher.update(leaves[0]);
+            return hasher.finalize().into();
+        }
+        let split = leaves.len().next_power_of_two() / 2;
+        node_hash(
+            &rfc6962_root(&leaves[..split]),
+            &rfc6962_root(&leaves[split..]),
+        )
+    }
+
+    #[test]
+    fn test_versioned_root_matches_rfc_6962() {
+        let leaves: Vec<String> = (0..13u8).map(|i| hash_content(&[i])).collect();
+        assert_eq!(
+            compute_versioned_root(&[], MERKLE_TREE_VERSION).unwrap(),
+            hash_content(b"")
+        );
+        for n in 1..=leaves.len() {
+            let digests: Vec<[u8; 32]> = leaves[..n].iter().map(|l| digest(l).unwrap()).collect();
+            let root = compute_versioned_root(&leaves[..n], MERKLE_TREE_VERSION).unwrap();
+            assert_eq!(root, hex::encode(rfc6962_root(&digests)));
+
+            for index in 0..n {
+                let proof =
+                    generate_versioned_proof(&leaves[..n], index, MERKLE_TREE_VERSION).unwrap();
+                assert!(verify_versioned_proof(
+                    &leaves[index],
+                    &proof,
+                    &root,
+                    index,
+                    n,
+                    MERKLE_TREE_VERSION
+                ));
+                let other = (index + 1) % n;
+                assert_eq!(
+                    verify_versioned_proof(
+                        &leaves[other],
+                        &proof,
+                        &root,
+                        index,
+                        n,
+                        MERKLE_TREE_VERSION
+                    ),
+                    other == index
+                );
+            }
+        }
+        assert!(compute_versioned_root(&["leaf".to_string()], MERKLE_TREE_VERSION).is_err());
+        assert!(compute_versioned_root(&leaves, 99).is_err());
+    }
+
+    #[test]
+    fn test_versioned_root_resists_ambiguity() {
+        let leaves: Vec<String> = (0..3u8).map(|i| hash_content(&[i])).collect();
+
+        // Duplicating the last leaf changes the root in version 2 only.
+        let mut padded = leaves.clone();
+        padded.push(leaves[2].clone());
+        assert_eq!(compute_merkle_root(&leaves), compute_merkle_root(&padded));
+        assert_ne!(
+            compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap(),
+            compute_versioned_root(&padded, MERKLE_TREE_VERSION).unwrap()
+        );
+
+        // An internal node presented as a leaf does not reproduce the root.
+        let pair = compute_versioned_root(&leaves[..2], MERKLE_TREE_VERSION).unwrap();
+        assert_ne!(
+            compute_versioned_root(std::slice::from_ref(&pair), MERKLE_TREE_VERSION).unwrap(),
+            pair
+        );
+
+        // Legacy commitments still verify in their own format.
+        let legacy_root = compute_versioned_root(&leaves, LEGACY_MERKLE_TREE_VERSION).unwrap();
+        assert_eq!(legacy_root, compute_merkle_root(&leaves));
+        let proof = generate_versioned_proof(&leaves, 1, LEGACY_MERKLE_TREE_VERSION).unwrap();
+        assert!(verify_versioned_proof(
+            &leaves[1],
+            &proof,
+            &legacy_root,
+            1,
+            3,
+            LEGACY_MERKLE_TREE_VERSION
+        ));
+        assert!(!verify_versioned_proof(
+            &leaves[1],
+            &proof,
+            &legacy_root,
+            1,
+            3,
+            MERKLE_TREE_VERSION
+        ));
+    }
+
+    #[test]
+    fn test_keccak_tree_matches_openzeppelin() {
+        let leaves: Vec<String> = (0..5u8).map(|i| hash_content(&[i])).collect();
+        let node = |leaf: &String| keccak256(keccak256(digest(leaf).unwrap()));
+
+        // Two leaves: the root hashes their leaf nodes in ascending order.
+        let (a, b) = (node(&leaves[0]), node(&leaves[1]));
+        let (low, high) = if a < b { (a, b) } else { (b, a) };
+        let expected = keccak256([low, high].concat());
+        assert_eq!(
+            compute_versioned_root(&leaves[..2], KECCAK_MERKLE_TREE_VERSION).unwrap(),
+            hex::encode(expected)
+        );
+        assert_eq!(
+            generate_versioned_proof(&leaves[..2], 0, KECCAK_MERKLE_TREE_VERSION).unwrap(),
+            vec![hex::encode(b)]
+        );
+
+        // The root does not depend on leaf order, and every proof verifies
+        // by folding sorted pairs, as `MerkleProof.verify` does.
+        let root = compute_versioned_root(&leaves, KECCAK_MERKLE_TREE_VERSION).unwrap();
+        let mut reversed = leaves.clone();
+        reversed.reverse();
+        assert_eq!(
+            compute_versioned_root(&reversed, KECCAK_MERKLE_TREE_VERSION).unwrap(),
+            root
+        );
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = generate_versioned_proof(&leaves, i, KECCAK_MERKLE_TREE_VERSION).unwrap();
+            let folded = proof.iter().fold(node(leaf), |acc, sibling| {
+                keccak_pair(&acc, &digest(sibling).unwrap())
+            });
+            assert_eq!(hex::encode(folded), root);
+            assert!(verify_versioned_proof(
+                leaf,
+                &proof,
+                &format!("0x{}", root),
+                i,
+                leaves.len(),
+                KECCAK_MERKLE_TREE_VERSION
+            ));
+            assert!(!verify_versioned_proof(
+                &leaves[(i + 1) % leaves.len()],
+                &proof,
+                &root,
+                i,
+                leaves.len(),
+                KECCAK_MERKLE_TREE_VERSION
+            ));
+        }
+        assert!(compute_versioned_root(&[], KECCAK_MERKLE_TREE_VERSION).is_err());
+        assert_eq!(
+            MerkleMode::parse("keccak256").map(|m| m.tree_version()),
+            Some(3)
+        );
+        assert_eq!(MerkleMode::default().tree_version(), MERKLE_TREE_VERSION);
+    }
+
+    #[test]
+    fn test_multiproofs_cover_every_subset() {
+        let all: Vec<String> = (0..8u8).map(|i| hash_content(&[i])).collect();
+        for version in [MERKLE_TREE_VERSION, KECCAK_MERKLE_TREE_VERSION] {
+            for n in 1..=all.len() {
+                let leaves = &all[..n];
+                let root = compute_versioned_root(leaves, version).unwrap();
+                for subset in 1..1u32 << n {
+                    let indices: Vec<usize> =
+                        (0..n).filter(|i| subset >> i & 1 == 1).rev().collect();
+                    let multiproof = generate_multiproof(leaves, &indices, version).unwrap();
+                    assert!(
+                        verify_multiproof(&multiproof, &root),
+                        "version {}, leaves {:?} of {}",
+                        version,
+                        indices,
+                        n
+                    );
+                    for (leaf, &i) in multiproof.leaves.iter().zip(&multiproof.leaf_indices) {
+                        assert_eq!(*leaf, leaves[i]);
+                    }
+                    // The multiproof of one leaf is its ordinary proof.
+                    if let [index] = indices[..] {
+                        assert_eq!(
+                            multiproof.proof,
+                            generate_versioned_proof(leaves, index, version).unwrap()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiproofs_are_compact_and_tamper_evident() {
+        let leaves: Vec<String> = (0..256u32)
+            .map(|i| hash_content(&i.to_be_bytes()))
+            .collect();
+        let indices: Vec<usize> = (32..160).collect();
+        for version in [MERKLE_TREE_VERSION, KECCAK_MERKLE_TREE_VERSION] {
+            let root = compute_versioned_root(&leaves, version).unwrap();
+            let multiproof = generate_multiproof(&leaves, &indices, version).unwrap();
+            assert!(verify_multiproof(&multiproof, &root));
+            let separate: usize = indices
+                .iter()
+                .map(|&i| generate_versioned_proof(&leaves, i, version).unwrap().len())
+                .sum();
+            assert!(multiproof.proof.len() * 5 < separate);
+
+            let mut tampered = multiproof.clone();
+            tampered.leaves[7] = hash_content(b"forged");
+            assert!(!verify_multiproof(&tampered, &root));
+            let mut tampered = multiproof.clone();
+            tampered.proof.pop();
+            assert!(!verify_multiproof(&tampered, &root));
+            let mut tampered = multiproof.clone();
+            tampered.leaves.pop();
+            tampered.leaf_indices.pop();
+            assert!(!verify_multiproof(&tampered, &root));
+            let mut tampered = multiproof;
+            tampered.leaf_indices[0] = leaves.len();
+            assert!(!verify_multiproof(&tampered, &root));
+        }
+
+        // Version 2 multiproofs are bound to the leaves' positions.
+        let mut moved = generate_multiproof(&leaves, &[1, 2], MERKLE_TREE_VERSION).unwrap();
+        moved.leaf_indices = vec![2, 3];
+        let root = compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap();
+        assert!(!verify_multiproof(&moved, &root));
+
+        assert!(generate_multiproof(&leaves, &[], MERKLE_TREE_VERSION).is_err());
+        assert!(generate_multiproof(&leaves, &[3, 3], MERKLE_TREE_VERSION).is_err());
+        assert!(generate_multiproof(&leaves, &[1000], MERKLE_TREE_VERSION).is_err());
+        assert!(generate_multiproof(&leaves, &[1], LEGACY_MERKLE_TREE_VERSION).is_err());
+    }
 }
@@ -0,0 +1,367 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::merkle::{digest, leaf_hash, node_hash, parent_level};
+
+/// Levels between the checkpoints a `MerkleBuilder` records. Every node at a
+/// level that is a multiple of this is kept, about one node per fifteen
+/// leaves, and a proof is rebuilt from at most `2^CHECKPOINT_STRIDE` of them
+/// per band of this many levels.
+pub const CHECKPOINT_STRIDE: u32 = 4;
+
+/// Builds a tree version 2 root from leaves pushed one at a time.
+///
+/// Only the roots of the perfect subtrees pushed so far are kept, one for
+/// each set bit of the leaf count, so the root of n leaves needs O(log n)
+/// memory and no hex encoding along the way. The root is
+/// `compute_versioned_root(leaves, MERKLE_TREE_VERSION)`: folding the subtree
+/// roots from the right gives the RFC 6962 tree hash.
+///
+/// A builder made with `with_checkpoints` also keeps every node at levels
+/// that are multiples of `CHECKPOINT_STRIDE`. With the leaves, those nodes
+/// are enough to rebuild any proof from O(log n) of them; see
+/// `checkpoint_spans`.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleBuilder {
+    leaf_count: u64,
+    subtrees: Vec<[u8; 32]>,
+    checkpoints: Option<Vec<Vec<[u8; 32]>>>,
+}
+
+impl MerkleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder that records checkpoints; see `into_checkpoints`.
+    pub fn with_checkpoints() -> Self {
+        Self {
+            checkpoints: Some(Vec::new()),
+            ..Self::default()
+        }
+    }
+
+    /// Appends a leaf, the raw 32-byte content hash.
+    pub fn push(&mut self, leaf: &[u8; 32]) {
+        let mut node = leaf_hash(leaf);
+        let mut height = 0;
+        // Each trailing set bit is a subtree of the new node's height to merge.
+        while self.leaf_count >> height & 1 == 1 {
+            let left = self
+                .subtrees
+                .pop()
+                .expect("every set bit of the leaf count has a subtree");
+            node = node_hash(&left, &node);
+            height += 1;
+            if height % CHECKPOINT_STRIDE == 0 {
+                if let Some(checkpoints) = &mut self.checkpoints {
+                    // Nodes of a level complete in order, lowest level first.
+                    let band = (height / CHECKPOINT_STRIDE - 1) as usize;
+                    if checkpoints.len() == band {
+                        checkpoints.push(Vec::new());
+                    }
+                    checkpoints[band].push(node);
+                }
+            }
+        }
+        self.subtrees.push(node);
+        self.leaf_count += 1;
+    }
+
+    /// Appends a hex-encoded leaf.
+    pub fn push_hex(&mut self, leaf: &str) -> Result<()> {
+        self.push(&digest(leaf)?);
+        Ok(())
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// The root of the leaves pushed so far. An empty tree's root is the
+    /// SHA-256 of no input.
+    pub fn root(&self) -> [u8; 32] {
+        fold(&self.subtrees).unwrap_or_else(|| Sha256::digest(b"").into())
+    }
+
+    /// The recorded checkpoints: for each level `CHECKPOINT_STRIDE * (k + 1)`
+    /// below the root, every node of that level, the last one completed from
+    /// the unfinished subtrees. Empty when the builder was made with `new`.
+    pub fn into_checkpoints(self) -> Vec<Vec<[u8; 32]>> {
+        let Some(mut checkpoints) = self.checkpoints else {
+            return Vec::new();
+        };
+        let levels = checkpoint_levels(self.leaf_count);
+        checkpoints.truncate(levels);
+        for (band, nodes) in checkpoints.iter_mut().enumerate() {
+            let level = CHECKPOINT_STRIDE * (band as u32 + 1);
+            // The subtrees below this level are the last node's leaves.
+            let partial = (self.leaf_count & ((1 << level) - 1)).count_ones() as usize;
+            if let Some(node) = fold(&self.subtrees[self.subtrees.len() - partial..]) {
+                nodes.push(node);
+            }
+        }
+        checkpoints
+    }
+}
+
+/// Nodes a proof is rebuilt from: nodes `start..end` of `level`. Level 0 is
+/// the leaves themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointSpan {
+    pub level: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The spans the proof for the leaf at `leaf_index` of `leaf_count` leaves
+/// is rebuilt from, one for each band of `CHECKPOINT_STRIDE` levels from the
+/// leaves up: the nodes at the band's bottom level under the leaf's ancestor
+/// at its top. There are O(log n) of them with at most
+/// `2^CHECKPOINT_STRIDE` nodes each.
+pub fn checkpoint_spans(leaf_index: usize, leaf_count: usize) -> Result<Vec<CheckpointSpan>> {
+    if leaf_index >= leaf_count {
+        anyhow::bail!(
+            "Leaf index {} is out of range for {} leaves",
+            leaf_index,
+            leaf_count
+        );
+    }
+    Ok((0..=checkpoint_levels(leaf_count as u64) as u32)
+        .map(|band| {
+            let level = CHECKPOINT_STRIDE * band;
+            let start = (leaf_index >> (level + CHECKPOINT_STRIDE)) << CHECKPOINT_STRIDE;
+            let end = (start + (1 << CHECKPOINT_STRIDE)).min(level_size(leaf_count, level));
+            CheckpointSpan { level, start, end }
+        })
+        .collect())
+}
+
+/// Rebuilds the version 2 proof for the leaf at `leaf_index` of `leaf_count`
+/// leaves from the nodes of each of its `checkpoint_spans`, in order: the
+/// raw leaves for the first span and checkpoint nodes for the rest. The proof
+/// is the one `generate_versioned_proof` returns.
+pub fn proof_from_checkpoints(
+    leaf_index: usize,
+    leaf_count: usize,
+    spans: &[Vec<[u8; 32]>],
+) -> Result<Vec<String>> {
+    let expected = checkpoint_spans(leaf_index, leaf_count)?;
+    if spans.len() != expected.len() {
+        anyhow::bail!(
+            "A proof in a tree of {} leaves needs {} checkpoint spans, got {}",
+            leaf_count,
+            expected.len(),
+            spans.len()
+        );
+    }
+
+    let mut proof = Vec::new();
+    for (span, nodes) in expected.iter().zip(spans) {
+        if nodes.len() != span.end - span.start {
+            anyhow::bail!(
+                "Checkpoint span at level {} needs {} nodes, got {}",
+                span.level,
+                span.end - span.start,
+                nodes.len()
+            );
+        }
+        let mut level = if span.level == 0 {
+            nodes.iter().map(leaf_hash).collect()
+        } else {
+            nodes.clone()
+        };
+        // The span is aligned to its band, so it pairs up as the whole level
+        // does and a node without a sibling here has none in the tree.
+        let mut index = (leaf_index >> span.level) - span.start;
+        while level.len() > 1 {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                proof.push(hex::encode(level[sibling]));
+            }
+            level = parent_level(&level);
+            index /= 2;
+        }
+    }
+    Ok(proof)
+}
+
+/// Decodes hex-encoded leaves or checkpoint nodes for
+/// `proof_from_checkpoints`.
+pub fn decode_checkpoint_nodes(nodes: &[String]) -> Result<Vec<[u8; 32]>> {
+    nodes
+        .iter()
+        .map(|node| digest(node).context("Malformed Merkle node"))
+        .collect()
+}
+
+/// Groups the spans of a proof's `checkpoint_spans` as read from storage:
+/// the hex-encoded leaves of the first span, and the stored checkpoint
+/// nodes of the others as `(level, node)` pairs in node order. The result is
+/// what `proof_from_checkpoints` takes.
+pub fn checkpoint_bands(
+    span_count: usize,
+    leaves: &[String],
+    checkpoints: impl IntoIterator<Item = (u32, String)>,
+) -> Result<Vec<Vec<[u8; 32]>>> {
+    let mut bands = vec![Vec::new(); span_count.max(1)];
+    bands[0] = decode_checkpoint_nodes(leaves)?;
+    for (level, node) in checkpoints {
+        let band = (level / CHECKPOINT_STRIDE) as usize;
+        if level == 0 || level % CHECKPOINT_STRIDE != 0 || band >= span_count {
+            anyhow::bail!("No checkpoint span is at level {}", level);
+        }
+        bands[band].push(digest(&node).context("Malformed Merkle node")?);
+    }
+    Ok(bands)
+}
+
+/// The root of the tree whose perfect subtrees are `subtrees`, largest first.
+fn fold(subtrees: &[[u8; 32]]) -> Option<[u8; 32]> {
+    subtrees
+        .iter()
+        .rev()
+        .copied()
+        .reduce(|right, left| node_hash(&left, &right))
+}
+
+/// The number of checkpoint levels of a tree: those with more than one node.
+fn checkpoint_levels(leaf_count: u64) -> usize {
+    (1..)
+        .map(|band| CHECKPOINT_STRIDE * band)
+        .take_while(|&level| level < 64 && leaf_count > 1 << level)
+        .count()
+}
+
+fn level_size(leaf_count: usize, level: u32) -> usize {
+    leaf_count.div_ceil(1 << level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{
+        generate_versioned_proof, hash_content, verify_versioned_proof, MERKLE_TREE_VERSION,
+    };
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| hash_content(&i.to_le_bytes())).collect()
+    }
+
+    #[test]
+    fn test_builder_root_matches_level_by_level_tree() {
+        let all = leaves(300);
+        let mut builder = MerkleBuilder::new();
+        assert_eq!(hex::encode(builder.root()), hash_content(b""));
+        for n in 1..=all.len() {
+            builder.push_hex(&all[n - 1]).unwrap();
+            assert_eq!(builder.leaf_count(), n as u64);
+            assert_eq!(builder.subtrees.len(), n.count_ones() as usize);
+
+            let mut level: Vec<_> = all[..n]
+                .iter()
+                .map(|leaf| leaf_hash(&digest(leaf).unwrap()))
+                .collect();
+            while level.len() > 1 {
+                level = parent_level(&level);
+            }
+            assert_eq!(builder.root(), level[0], "{} leaves", n);
+        }
+        assert!(builder.push_hex("not hex").is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_proofs_match_full_proofs() {
+        for n in [1, 2, 3, 15, 16, 17, 31, 255, 256, 257, 300, 1100] {
+            let all = leaves(n);
+            let mut builder = MerkleBuilder::with_checkpoints();
+            for leaf in &all {
+                builder.push_hex(leaf).unwrap();
+            }
+            let root = hex::encode(builder.root());
+            let checkpoints = builder.into_checkpoints();
+            assert_eq!(checkpoints.len(), checkpoint_levels(n as u64));
+            for (band, nodes) in checkpoints.iter().enumerate() {
+                let level = CHECKPOINT_STRIDE * (band as u32 + 1);
+                assert_eq!(nodes.len(), level_size(n, level), "{} leaves", n);
+            }
+
+            let raw = decode_checkpoint_nodes(&all).unwrap();
+            let step = n / 40 + 1;
+            for i in (0..n).step_by(step).chain([n - 1]) {
+                let spans: Vec<_> = checkpoint_spans(i, n)
+                    .unwrap()
+                    .iter()
+                    .map(|span| {
+                        assert!(span.end - span.start <= 1 << CHECKPOINT_STRIDE);
+                        let nodes = match span.level {
+                            0 => &raw,
+                            level => &checkpoints[(level / CHECKPOINT_STRIDE - 1) as usize],
+                        };
+                        nodes[span.start..span.end].to_vec()
+                    })
+                    .collect();
+                let proof = proof_from_checkpoints(i, n, &spans).unwrap();
+                assert_eq!(
+                    proof,
+                    generate_versioned_proof(&all, i, MERKLE_TREE_VERSION).unwrap(),
+                    "leaf {} of {}",
+                    i,
+                    n
+                );
+                assert!(verify_versioned_proof(
+                    &all[i],
+                    &proof,
+                    &root,
+                    i,
+                    n,
+                    MERKLE_TREE_VERSION
+                ));
+                assert!(proof_from_checkpoints(i, n, &spans[1..]).is_err());
+            }
+        }
+        assert!(checkpoint_spans(3, 3).is_err());
+        assert!(MerkleBuilder::new().into_checkpoints().is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint_bands_group_stored_rows() {
+        let n = 300;
+        let all = leaves(n);
+        let mut builder = MerkleBuilder::with_checkpoints();
+        for leaf in &all {
+            builder.push_hex(leaf).unwrap();
+        }
+        let checkpoints = builder.into_checkpoints();
+
+        // Rows as a query over the spans returns them: by level, then node.
+        let i = 201;
+        let spans = checkpoint_spans(i, n).unwrap();
+        let rows: Vec<(u32, String)> = spans[1..]
+            .iter()
+            .flat_map(|span| {
+                let nodes = &checkpoints[(span.level / CHECKPOINT_STRIDE - 1) as usize];
+                nodes[span.start..span.end]
+                    .iter()
+                    .map(move |node| (span.level, hex::encode(node)))
+            })
+            .collect();
+        let bands = checkpoint_bands(
+            spans.len(),
+            &all[spans[0].start..spans[0].end],
+            rows.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            proof_from_checkpoints(i, n, &bands).unwrap(),
+            generate_versioned_proof(&all, i, MERKLE_TREE_VERSION).unwrap()
+        );
+
+        // A missing row leaves its span short rather than misplacing nodes.
+        let bands = checkpoint_bands(spans.len(), &all[..16], rows[1..].to_vec()).unwrap();
+        assert!(proof_from_checkpoints(i, n, &bands).is_err());
+        assert!(checkpoint_bands(spans.len(), &all[..16], [(3, all[0].clone())]).is_err());
+        assert!(checkpoint_bands(spans.len(), &all[..16], [(64, all[0].clone())]).is_err());
+        assert!(checkpoint_bands(spans.len(), &all[..16], [(4, "zz".to_string())]).is_err());
+    }
+}
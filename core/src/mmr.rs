@@ -0,0 +1,643 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::merkle::{digest, leaf_node, node_hash, parent_level};
+use crate::merkle_builder::{
+    checkpoint_spans, proof_from_checkpoints, CheckpointSpan, CHECKPOINT_STRIDE,
+};
+
+/// The `tree_version` recorded for a committed MMR root; see
+/// `MerkleMountainRange`.
+pub const MMR_TREE_VERSION: u16 = 4;
+/// Blocks between on-chain commitments of a live job's MMR root when the job
+/// does not set `mmr_commit_blocks`.
+pub const DEFAULT_MMR_COMMIT_BLOCKS: u64 = 100;
+
+/// Prefix of the root that bags an MMR's peaks.
+const ROOT_PREFIX: u8 = 0x02;
+
+/// An append-only Merkle Mountain Range over hex-encoded 32-byte leaves.
+///
+/// The leaves form perfect binary trees ("mountains"), one for each set bit
+/// of the leaf count, largest first. Leaves and nodes hash as in tree version
+/// 2, so each mountain is the RFC 6962 tree of its leaves. The root is
+/// `SHA-256(0x02 || leaf_count || bag)`, with `leaf_count` a big-endian u64
+/// and `bag` the peaks folded from the right as `node_hash(peak, bag)`.
+///
+/// Appending only merges peaks, so the accumulator is just the leaf count and
+/// the peaks. Every root it has had stays provable from the leaves: see
+/// `generate_mmr_inclusion_proof` and `generate_mmr_consistency_proof`. With
+/// the nodes `append_with_checkpoints` records and a root's peaks, a proof is
+/// rebuilt from O(log n) of them instead; see `mmr_checkpoint_spans`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MerkleMountainRange {
+    leaf_count: u64,
+    peaks: Vec<[u8; 32]>,
+}
+
+impl MerkleMountainRange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restores an accumulator from its leaf count and hex-encoded peaks, as
+    /// returned by `leaf_count` and `peaks`.
+    pub fn from_peaks(leaf_count: u64, peaks: &[String]) -> Result<Self> {
+        Ok(Self {
+            leaf_count,
+            peaks: decode_peaks(leaf_count, peaks)?,
+        })
+    }
+
+    /// Appends a leaf and returns its index.
+    pub fn append(&mut self, leaf: &str) -> Result<u64> {
+        self.append_with_checkpoints(leaf, &mut Vec::new())
+    }
+
+    /// Appends a leaf like `append` and adds the checkpoint nodes it forms to
+    /// `checkpoints`.
+    pub fn append_with_checkpoints(
+        &mut self,
+        leaf: &str,
+        checkpoints: &mut Vec<MmrCheckpoint>,
+    ) -> Result<u64> {
+        let mut node = leaf_node(leaf)?;
+        let mut height = 0;
+        // Each trailing set bit is a mountain of the new leaf's height to merge.
+        while self.leaf_count >> height & 1 == 1 {
+            let left = self
+                .peaks
+                .pop()
+                .expect("every set bit of the leaf count has a peak");
+            node = node_hash(&left, &node);
+            height += 1;
+            if height % CHECKPOINT_STRIDE == 0 {
+                checkpoints.push(MmrCheckpoint {
+                    level: height,
+                    index: self.leaf_count >> height,
+                    node: hex::encode(node),
+                });
+            }
+        }
+        self.peaks.push(node);
+        self.leaf_count += 1;
+        Ok(self.leaf_count - 1)
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// The hex-encoded peaks, largest mountain first.
+    pub fn peaks(&self) -> Vec<String> {
+        self.peaks.iter().map(hex::encode).collect()
+    }
+
+    /// The root, or `None` while the MMR is empty.
+    pub fn root(&self) -> Option<String> {
+        bag_root(self.leaf_count, &self.peaks)
+    }
+}
+
+/// A node of an MMR at a level that is a multiple of `CHECKPOINT_STRIDE`.
+/// Node `index` of `level` covers leaves `index << level` up to
+/// `(index + 1) << level`, and never changes once formed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MmrCheckpoint {
+    pub level: u32,
+    pub index: u64,
+    pub node: String,
+}
+
+/// Shows that a leaf is in the MMR of `leaf_count` leaves whose root is
+/// `bag_root(leaf_count, peaks)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MmrInclusionProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    /// Sibling hashes from the leaf up to the peak of its mountain.
+    pub path: Vec<String>,
+    /// Every peak of the MMR, largest mountain first.
+    pub peaks: Vec<String>,
+}
+
+/// Shows that the MMR of `old_leaf_count` leaves is a prefix of the MMR of
+/// `new_leaf_count` leaves: each old peak is a node of a new mountain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MmrConsistencyProof {
+    pub old_leaf_count: u64,
+    pub new_leaf_count: u64,
+    pub old_peaks: Vec<String>,
+    pub new_peaks: Vec<String>,
+    /// For each old peak, the sibling hashes from it up to the new peak whose
+    /// mountain contains it.
+    pub paths: Vec<Vec<String>>,
+}
+
+/// Computes the root of the MMR over `leaves`, which must not be empty.
+pub fn mmr_root(leaves: &[String]) -> Result<String> {
+    build(leaves)?
+        .root()
+        .context("An MMR needs at least one leaf")
+}
+
+/// Generates the proof that the leaf at `leaf_index` is in the MMR over
+/// `leaves`.
+pub fn generate_mmr_inclusion_proof(
+    leaves: &[String],
+    leaf_index: u64,
+) -> Result<MmrInclusionProof> {
+    let leaf_count = leaves.len() as u64;
+    let (start, height) = mountain_containing(leaf_index, leaf_count)?;
+    Ok(MmrInclusionProof {
+        leaf_index,
+        leaf_count,
+        path: mountain_path(leaf_range(leaves, start, height)?, 0, leaf_index - start),
+        peaks: build(leaves)?.peaks(),
+    })
+}
+
+/// Verifies an inclusion proof against `root`. Malformed proofs do not
+/// verify.
+pub fn verify_mmr_inclusion_proof(leaf: &str, proof: &MmrInclusionProof, root: &str) -> bool {
+    let mountains = mmr_mountains(proof.leaf_count);
+    let Some(position) = mountain_of(&mountains, proof.leaf_index) else {
+        return false;
+    };
+    let (start, height) = mountains[position];
+    let (Ok(peaks), Ok(leaf)) = (
+        decode_peaks(proof.leaf_count, &proof.peaks),
+        leaf_node(leaf),
+    ) else {
+        return false;
+    };
+    proof.path.len() == height as usize
+        && climb(leaf, proof.leaf_index - start, &proof.path) == Some(peaks[position])
+        && root_matches(proof.leaf_count, &peaks, root)
+}
+
+/// Generates the proof that the MMR over the first `old_leaf_count` of
+/// `leaves` is a prefix of the MMR over all of them.
+pub fn generate_mmr_consistency_proof(
+    leaves: &[String],
+    old_leaf_count: u64,
+) -> Result<MmrConsistencyProof> {
+    let new_leaf_count = leaves.len() as u64;
+    anyhow::ensure!(
+        old_leaf_count >= 1 && old_leaf_count <= new_leaf_count,
+        "An MMR of {} leaves has no prefix of {} leaves",
+        new_leaf_count,
+        old_leaf_count
+    );
+    let new_mountains = mmr_mountains(new_leaf_count);
+    let mut paths = Vec::new();
+    for (start, height) in mmr_mountains(old_leaf_count) {
+        let position =
+            mountain_of(&new_mountains, start).expect("every old leaf is in the new MMR");
+        let (new_start, new_height) = new_mountains[position];
+        paths.push(mountain_path(
+            leaf_range(leaves, new_start, new_height)?,
+            height,
+            (start - new_start) >> height,
+        ));
+    }
+    Ok(MmrConsistencyProof {
+        old_leaf_count,
+        new_leaf_count,
+        old_peaks: build(&leaves[..old_leaf_count as usize])?.peaks(),
+        new_peaks: build(leaves)?.peaks(),
+        paths,
+    })
+}
+
+/// Verifies a consistency proof between `old_root` and `new_root`. Malformed
+/// proofs do not verify.
+pub fn verify_mmr_consistency_proof(
+    old_root: &str,
+    new_root: &str,
+    proof: &MmrConsistencyProof,
+) -> bool {
+    if proof.old_leaf_count == 0 || proof.old_leaf_count > proof.new_leaf_count {
+        return false;
+    }
+    let (Ok(old_peaks), Ok(new_peaks)) = (
+        decode_peaks(proof.old_leaf_count, &proof.old_peaks),
+        decode_peaks(proof.new_leaf_count, &proof.new_peaks),
+    ) else {
+        return false;
+    };
+    if !root_matches(proof.old_leaf_count, &old_peaks, old_root)
+        || !root_matches(proof.new_leaf_count, &new_peaks, new_root)
+    {
+        return false;
+    }
+
+    let old_mountains = mmr_mountains(proof.old_leaf_count);
+    let new_mountains = mmr_mountains(proof.new_leaf_count);
+    proof.paths.len() == old_mountains.len()
+        && old_mountains
+            .iter()
+            .zip(old_peaks.iter().zip(&proof.paths))
+            .all(|(&(start, height), (peak, path))| {
+                let Some(position) = mountain_of(&new_mountains, start) else {
+                    return false;
+                };
+                let (new_start, new_height) = new_mountains[position];
+                path.len() == (new_height - height) as usize
+                    && climb(*peak, (start - new_start) >> height, path)
+                        == Some(new_peaks[position])
+            })
+}
+
+/// The spans the path of the leaf at `leaf_index` up to the peak of its
+/// mountain, in an MMR of `leaf_count` leaves, is rebuilt from; see
+/// `checkpoint_spans`. Spans index whole MMR levels: leaves for level 0 and
+/// `MmrCheckpoint`s above it.
+pub fn mmr_checkpoint_spans(leaf_index: u64, leaf_count: u64) -> Result<Vec<CheckpointSpan>> {
+    let (start, height) = mountain_containing(leaf_index, leaf_count)?;
+    Ok(
+        checkpoint_spans((leaf_index - start) as usize, 1 << height)?
+            .into_iter()
+            .map(|span| {
+                let offset = (start >> span.level) as usize;
+                CheckpointSpan {
+                    level: span.level,
+                    start: span.start + offset,
+                    end: span.end + offset,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Rebuilds the `path` of the inclusion proof for the leaf at `leaf_index`
+/// from the nodes of each of its `mmr_checkpoint_spans`, in order: the raw
+/// leaves for the first span and checkpoint nodes for the rest.
+pub fn mmr_path_from_checkpoints(
+    leaf_index: u64,
+    leaf_count: u64,
+    spans: &[Vec<[u8; 32]>],
+) -> Result<Vec<String>> {
+    let (start, height) = mountain_containing(leaf_index, leaf_count)?;
+    proof_from_checkpoints((leaf_index - start) as usize, 1 << height, spans)
+}
+
+/// Assembles the consistency proof from `old_leaf_count` to `new_leaf_count`
+/// leaves from the peaks of both and, for each old mountain, the inclusion
+/// path of its first leaf in the new MMR; see `mmr_mountains`. The part of a
+/// path above the old peak is the path of the peak itself.
+pub fn mmr_consistency_proof_from_paths(
+    old_leaf_count: u64,
+    new_leaf_count: u64,
+    old_peaks: Vec<String>,
+    new_peaks: Vec<String>,
+    leaf_paths: Vec<Vec<String>>,
+) -> Result<MmrConsistencyProof> {
+    anyhow::ensure!(
+        old_leaf_count >= 1 && old_leaf_count <= new_leaf_count,
+        "An MMR of {} leaves has no prefix of {} leaves",
+        new_leaf_count,
+        old_leaf_count
+    );
+    let old_mountains = mmr_mountains(old_leaf_count);
+    anyhow::ensure!(
+        leaf_paths.len() == old_mountains.len(),
+        "An MMR of {} leaves needs {} leaf paths, got {}",
+        old_leaf_count,
+        old_mountains.len(),
+        leaf_paths.len()
+    );
+    let mut paths = Vec::with_capacity(leaf_paths.len());
+    for (mut path, (start, height)) in leaf_paths.into_iter().zip(old_mountains) {
+        anyhow::ensure!(
+            path.len() >= height as usize,
+            "The path of leaf {} is shorter than its old mountain",
+            start
+        );
+        path.drain(..height as usize);
+        paths.push(path);
+    }
+    Ok(MmrConsistencyProof {
+        old_leaf_count,
+        new_leaf_count,
+        old_peaks,
+        new_peaks,
+        paths,
+    })
+}
+
+fn mountain_containing(leaf_index: u64, leaf_count: u64) -> Result<(u64, u32)> {
+    let mountains = mmr_mountains(leaf_count);
+    let position = mountain_of(&mountains, leaf_index).with_context(|| {
+        format!(
+            "Leaf {} is not in an MMR of {} leaves",
+            leaf_index, leaf_count
+        )
+    })?;
+    Ok(mountains[position])
+}
+
+fn build(leaves: &[String]) -> Result<MerkleMountainRange> {
+    let mut mmr = MerkleMountainRange::new();
+    for leaf in leaves {
+        mmr.append(leaf)?;
+    }
+    Ok(mmr)
+}
+
+fn decode_peaks(leaf_count: u64, peaks: &[String]) -> Result<Vec<[u8; 32]>> {
+    anyhow::ensure!(
+        peaks.len() == leaf_count.count_ones() as usize,
+        "An MMR of {} leaves has {} peaks, not {}",
+        leaf_count,
+        leaf_count.count_ones(),
+        peaks.len()
+    );
+    peaks.iter().map(|peak| digest(peak)).collect()
+}
+
+fn bag_root(leaf_count: u64, peaks: &[[u8; 32]]) -> Option<String> {
+    let (last, rest) = peaks.split_last()?;
+    let bag = rest
+        .iter()
+        .rev()
+        .fold(*last, |bag, peak| node_hash(peak, &bag));
+    let mut hasher = Sha256::new();
+    hasher.update([ROOT_PREFIX]);
+    hasher.update(leaf_count.to_be_bytes());
+    hasher.update(bag);
+    Some(hex::encode(hasher.finalize()))
+}
+
+fn root_matches(leaf_count: u64, peaks: &[[u8; 32]], root: &str) -> bool {
+    bag_root(leaf_count, peaks)
+        .is_some_and(|bagged| bagged == root.trim_start_matches("0x").to_lowercase())
+}
+
+/// The first leaf and the height of each mountain of an MMR of `leaf_count`
+/// leaves, left to right.
+pub fn mmr_mountains(leaf_count: u64) -> Vec<(u64, u32)> {
+    let mut start = 0;
+    (0..u64::BITS)
+        .rev()
+        .filter(|height| leaf_count >> height & 1 == 1)
+        .map(|height| {
+            let mountain = (start, height);
+            start += 1 << height;
+            mountain
+        })
+        .collect()
+}
+
+fn mountain_of(mountains: &[(u64, u32)], leaf_index: u64) -> Option<usize> {
+    mountains
+        .iter()
+        .position(|&(start, height)| leaf_index >= start && leaf_index - start < 1 << height)
+}
+
+/// The leaf nodes of the mountain of `height` starting at leaf `start`.
+fn leaf_range(leaves: &[String], start: u64, height: u32) -> Result<Vec<[u8; 32]>> {
+    leaves[start as usize..(start + (1 << height)) as usize]
+        .iter()
+        .map(|leaf| leaf_node(leaf))
+        .collect()
+}
+
+/// Sibling hashes from the node at `index` on level `height` of the mountain
+/// over `level` up to its peak.
+fn mountain_path(mut level: Vec<[u8; 32]>, height: u32, mut index: u64) -> Vec<String> {
+    for _ in 0..height {
+        level = parent_level(&level);
+    }
+    let mut path = Vec::new();
+    while level.len() > 1 {
+        path.push(hex::encode(level[(index ^ 1) as usize]));
+        level = parent_level(&level);
+        index >>= 1;
+    }
+    path
+}
+
+/// Hashes `node`, at `index` on its level, up through the siblings in `path`.
+fn climb(mut node: [u8; 32], mut index: u64, path: &[String]) -> Option<[u8; 32]> {
+    for sibling in path {
+        let sibling = digest(sibling).ok()?;
+        node = if index & 1 == 0 {
+            node_hash(&node, &sibling)
+        } else {
+            node_hash(&sibling, &node)
+        };
+        index >>= 1;
+    }
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{compute_versioned_root, hash_content, MERKLE_TREE_VERSION};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n)
+            .map(|i| hash_content(format!("leaf{}", i).as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_single_leaf_root() {
+        let leaf = hash_content(b"leaf");
+        let mut hasher = Sha256::new();
+        hasher.update([0x00]);
+        hasher.update(hex::decode(&leaf).unwrap());
+        let peak: [u8; 32] = hasher.finalize().into();
+
+        let mut hasher = Sha256::new();
+        hasher.update([0x02]);
+        hasher.update(1u64.to_be_bytes());
+        hasher.update(peak);
+        let expected = hex::encode(hasher.finalize());
+
+        assert_eq!(mmr_root(&[leaf]).unwrap(), expected);
+        assert!(MerkleMountainRange::new().root().is_none());
+        assert!(mmr_root(&[]).is_err());
+    }
+
+    #[test]
+    fn test_mountains_are_rfc6962_trees() {
+        // Eight leaves are one mountain, whose peak is the version 2 root.
+        let leaves = leaves(8);
+        let mmr = build(&leaves).unwrap();
+        assert_eq!(
+            mmr.peaks(),
+            vec![compute_versioned_root(&leaves, MERKLE_TREE_VERSION).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_incremental_matches_restored_accumulator() {
+        let leaves = leaves(20);
+        let mut mmr = MerkleMountainRange::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(mmr.append(leaf).unwrap(), i as u64);
+            assert_eq!(mmr.peaks().len(), (i + 1).count_ones() as usize);
+            assert_eq!(mmr.root().unwrap(), mmr_root(&leaves[..=i]).unwrap());
+
+            // The stored form of the accumulator resumes where it left off.
+            let mut restored =
+                MerkleMountainRange::from_peaks(mmr.leaf_count(), &mmr.peaks()).unwrap();
+            assert_eq!(restored, mmr);
+            if let Some(next) = leaves.get(i + 1) {
+                restored.append(next).unwrap();
+                assert_eq!(
+                    restored.root().unwrap(),
+                    mmr_root(&leaves[..i + 2]).unwrap()
+                );
+            }
+        }
+        assert!(MerkleMountainRange::from_peaks(3, &mmr.peaks()[..1]).is_err());
+    }
+
+    #[test]
+    fn test_inclusion_proofs() {
+        let all = leaves(12);
+        for n in 1..=all.len() {
+            let leaves = &all[..n];
+            let root = mmr_root(leaves).unwrap();
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = generate_mmr_inclusion_proof(leaves, i as u64).unwrap();
+                assert!(
+                    verify_mmr_inclusion_proof(leaf, &proof, &root),
+                    "{} of {}",
+                    i,
+                    n
+                );
+
+                let other = &leaves[(i + 1) % n];
+                if other != leaf {
+                    assert!(!verify_mmr_inclusion_proof(other, &proof, &root));
+                }
+                let mut wrong_count = proof.clone();
+                wrong_count.leaf_count += 1;
+                assert!(!verify_mmr_inclusion_proof(leaf, &wrong_count, &root));
+            }
+            assert!(generate_mmr_inclusion_proof(leaves, n as u64).is_err());
+        }
+
+        // A root committed earlier stays provable after more appends.
+        let proof = generate_mmr_inclusion_proof(&all[..5], 4).unwrap();
+        assert!(verify_mmr_inclusion_proof(
+            &all[4],
+            &proof,
+            &mmr_root(&all[..5]).unwrap()
+        ));
+        assert!(!verify_mmr_inclusion_proof(
+            &all[4],
+            &proof,
+            &mmr_root(&all).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_proofs_from_checkpoints_match_rebuilt_proofs() {
+        let all = leaves(300);
+        let mut mmr = MerkleMountainRange::new();
+        let mut checkpoints = Vec::new();
+        for leaf in &all {
+            mmr.append_with_checkpoints(leaf, &mut checkpoints).unwrap();
+        }
+        assert!(checkpoints.iter().any(|c| c.level == 8));
+        let stored: std::collections::HashMap<(u32, u64), [u8; 32]> = checkpoints
+            .iter()
+            .map(|c| ((c.level, c.index), digest(&c.node).unwrap()))
+            .collect();
+        let path = |leaf_index: u64, leaf_count: u64| {
+            let nodes: Vec<Vec<[u8; 32]>> = mmr_checkpoint_spans(leaf_index, leaf_count)
+                .unwrap()
+                .iter()
+                .map(|span| {
+                    (span.start..span.end)
+                        .map(|i| {
+                            if span.level == 0 {
+                                digest(&all[i]).unwrap()
+                            } else {
+                                stored[&(span.level, i as u64)]
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            mmr_path_from_checkpoints(leaf_index, leaf_count, &nodes).unwrap()
+        };
+
+        for n in [1, 2, 3, 15, 16, 17, 31, 33, 255, 256, 257, 300] {
+            let leaves = &all[..n];
+            for i in (0..n).step_by(7).chain([n - 1]) {
+                let expected = generate_mmr_inclusion_proof(leaves, i as u64).unwrap();
+                assert_eq!(path(i as u64, n as u64), expected.path, "{} of {}", i, n);
+            }
+            for m in [1, n / 3 + 1, n - 1, n].into_iter().filter(|&m| m >= 1) {
+                let paths = mmr_mountains(m as u64)
+                    .iter()
+                    .map(|&(start, _)| path(start, n as u64))
+                    .collect();
+                let proof = mmr_consistency_proof_from_paths(
+                    m as u64,
+                    n as u64,
+                    build(&all[..m]).unwrap().peaks(),
+                    build(leaves).unwrap().peaks(),
+                    paths,
+                )
+                .unwrap();
+                assert_eq!(
+                    proof,
+                    generate_mmr_consistency_proof(leaves, m as u64).unwrap(),
+                    "{} to {}",
+                    m,
+                    n
+                );
+            }
+        }
+        assert!(mmr_checkpoint_spans(3, 3).is_err());
+        assert!(
+            mmr_consistency_proof_from_paths(2, 3, Vec::new(), Vec::new(), Vec::new()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_consistency_proofs() {
+        let all = leaves(12);
+        for n in 1..=all.len() {
+            let new_root = mmr_root(&all[..n]).unwrap();
+            for m in 1..=n {
+                let old_root = mmr_root(&all[..m]).unwrap();
+                let proof = generate_mmr_consistency_proof(&all[..n], m as u64).unwrap();
+                assert!(
+                    verify_mmr_consistency_proof(&old_root, &new_root, &proof),
+                    "{} to {}",
+                    m,
+                    n
+                );
+                assert!(!verify_mmr_consistency_proof(&new_root, &old_root, &proof) || m == n);
+            }
+            assert!(generate_mmr_consistency_proof(&all[..n], 0).is_err());
+            assert!(generate_mmr_consistency_proof(&all[..n], n as u64 + 1).is_err());
+        }
+
+        // An MMR whose history was rewritten is not consistent with the old root.
+        let mut rewritten = all.clone();
+        rewritten[2] = hash_content(b"rewritten");
+        let old_root = mmr_root(&all[..5]).unwrap();
+        let new_root = mmr_root(&rewritten).unwrap();
+        let proof = generate_mmr_consistency_proof(&rewritten, 5).unwrap();
+        assert!(!verify_mmr_consistency_proof(&old_root, &new_root, &proof));
+
+        let mut tampered = generate_mmr_consistency_proof(&all, 5).unwrap();
+        tampered.paths[0].pop();
+        assert!(!verify_mmr_consistency_proof(
+            &old_root,
+            &mmr_root(&all).unwrap(),
+            &tampered
+        ));
+    }
+}
@@ -0,0 +1,621 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, H256, U256, U64};
+use ethers::utils::keccak256;
+use futures::channel::mpsc;
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::backfill::ScanCursor;
+use crate::blockchain::{decode_log, BlockchainEvent, EventFilter, ParsedFilter};
+use crate::merkle::hash_content;
+use crate::provider::{ChainProvider, ContentStore, CreditSpender, HashCommitter};
+
+/// Chain ID reported for the registry of a `MockChain`.
+pub const MOCK_CHAIN_ID: u64 = 31337;
+/// Address reported for the registry of a `MockChain`.
+pub const MOCK_REGISTRY_ADDRESS: Address = ethers::types::H160([0x7e; 20]);
+/// Timestamp of block 0 on a `MockChain`.
+const MOCK_GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+/// Seconds between consecutive blocks on a `MockChain`.
+const MOCK_BLOCK_TIME: u64 = 12;
+/// Gas used by every transaction on a `MockChain`.
+const MOCK_GAS_USED: u64 = 21_000;
+/// Effective gas price of every transaction on a `MockChain`, in wei.
+const MOCK_GAS_PRICE: u64 = 1_000_000_000;
+
+/// A log to be included in a block mined on a `MockChain`.
+#[derive(Debug, Clone)]
+pub struct MockLog {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+}
+
+impl MockLog {
+    /// A log whose first topic is the hash of `signature`
+    /// (e.g. `"Transfer(address,address,uint256)"`), followed by `indexed`.
+    pub fn event(address: Address, signature: &str, indexed: &[H256], data: Vec<u8>) -> Self {
+        let mut topics = vec![H256::from(keccak256(signature.as_bytes()))];
+        topics.extend_from_slice(indexed);
+        Self {
+            address,
+            topics,
+            data,
+        }
+    }
+}
+
+/// A transaction to be included in a block mined on a `MockChain`.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransaction {
+    pub from: Address,
+    /// `None` for a contract creation.
+    pub to: Option<Address>,
+    pub value: U256,
+    pub input: Vec<u8>,
+    /// Whether the transaction reverted.
+    pub reverted: bool,
+}
+
+impl MockTransaction {
+    /// A successful call from `from` to `to` with calldata `input`.
+    pub fn call(from: Address, to: Address, input: Vec<u8>) -> Self {
+        Self {
+            from,
+            to: Some(to),
+            input,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockBlock {
+    hash: H256,
+    timestamp: u64,
+    logs: Vec<Log>,
+    transactions: Vec<Transaction>,
+    receipts: Vec<TransactionReceipt>,
+}
+
+/// A scripted `eth_call` result, returned from `first_block` onward.
+struct CallResult {
+    first_block: u64,
+    return_data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct MockChainState {
+    /// Canonical blocks; index is the block number.
+    blocks: Vec<MockBlock>,
+    /// Incremented on every reorg so replacement blocks get new hashes.
+    fork: u64,
+    commits: Vec<String>,
+    commit_failures: u32,
+    log_failures: u32,
+    /// Blocks whose next `get_events_chunked` call fails.
+    failing_log_blocks: Vec<u64>,
+    log_fetches: Vec<(u64, u64)>,
+    head_failures: u32,
+    traces_unsupported: bool,
+    /// Scripted `eth_call` results per `(contract, calldata)`.
+    call_results: HashMap<(Address, Vec<u8>), Vec<CallResult>>,
+    credit_spends: Vec<(Address, U256, String)>,
+    head_subscribers: Vec<mpsc::UnboundedSender<u64>>,
+}
+
+/// A deterministic in-memory chain for exercising the indexer without a
+/// network. Blocks are mined explicitly with scripted logs, reorgs replace the
+/// newest blocks, and hash commits and credit spends are recorded for
+/// inspection.
+///
+/// Block hashes, transaction hashes and timestamps are derived from the block
+/// number and fork count, so identical scripts produce identical chains.
+/// Clones share the same chain.
+#[derive(Clone)]
+pub struct MockChain {
+    confirmations: u64,
+    state: Arc<Mutex<MockChainState>>,
+}
+
+impl MockChain {
+    /// Creates a chain containing only the genesis block.
+    pub fn new(confirmations: u64) -> Self {
+        let chain = Self {
+            confirmations,
+            state: Arc::new(Mutex::new(MockChainState::default())),
+        };
+        chain.mine(Vec::new());
+        chain
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockChainState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Mines a block containing `logs`, in order, and returns its number.
+    pub fn mine(&self, logs: Vec<MockLog>) -> u64 {
+        self.mine_block(logs, Vec::new())
+    }
+
+    /// Mines a block containing `transactions`, in order, and returns its
+    /// number. Each gets a receipt using `MOCK_GAS_USED` gas.
+    pub fn mine_transactions(&self, transactions: Vec<MockTransaction>) -> u64 {
+        self.mine_block(Vec::new(), transactions)
+    }
+
+    fn mine_block(&self, logs: Vec<MockLog>, transactions: Vec<MockTransaction>) -> u64 {
+        let mut state = self.state();
+        let number = state.blocks.len() as u64;
+        let hash = H256::from(keccak256(
+            [number.to_be_bytes(), state.fork.to_be_bytes()].concat(),
+        ));
+        let tx_hash = |i: usize| {
+            H256::from(keccak256(
+                [hash.as_bytes(), &(i as u64).to_be_bytes()].concat(),
+            ))
+        };
+        let logs = logs
+            .into_iter()
+            .enumerate()
+            .map(|(i, log)| Log {
+                address: log.address,
+                topics: log.topics,
+                data: Bytes::from(log.data),
+                block_hash: Some(hash),
+                block_number: Some(U64::from(number)),
+                transaction_hash: Some(tx_hash(i)),
+                transaction_index: Some(U64::from(i)),
+                log_index: Some(U256::from(i)),
+                removed: Some(false),
+                ..Default::default()
+            })
+            .collect();
+        let (transactions, receipts) = transactions
+            .into_iter()
+            .enumerate()
+            .map(|(i, tx)| {
+                let transaction = Transaction {
+                    hash: tx_hash(i),
+                    nonce: U256::from(i),
+                    block_hash: Some(hash),
+                    block_number: Some(U64::from(number)),
+                    transaction_index: Some(U64::from(i)),
+                    from: tx.from,
+                    to: tx.to,
+                    value: tx.value,
+                    gas: U256::from(MOCK_GAS_USED * 2),
+                    input: Bytes::from(tx.input),
+                    ..Default::default()
+                };
+                let receipt = TransactionReceipt {
+                    transaction_hash: transaction.hash,
+                    transaction_index: U64::from(i),
+                    block_hash: Some(hash),
+                    block_number: Some(U64::from(number)),
+                    from: tx.from,
+                    to: tx.to,
+                    gas_used: Some(U256::from(MOCK_GAS_USED)),
+                    effective_gas_price: Some(U256::from(MOCK_GAS_PRICE)),
+                    contract_address: tx
+                        .to
+                        .is_none()
+                        .then(|| ethers::utils::get_contract_address(tx.from, i)),
+                    status: Some(U64::from(u64::from(!tx.reverted))),
+                    ..Default::default()
+                };
+                (transaction, receipt)
+            })
+            .unzip();
+        state.blocks.push(MockBlock {
+            hash,
+            timestamp: MOCK_GENESIS_TIMESTAMP + number * MOCK_BLOCK_TIME,
+            logs,
+            transactions,
+            receipts,
+        });
+        state
+            .head_subscribers
+            .retain(|subscriber| subscriber.unbounded_send(number).is_ok());
+        number
+    }
+
+    /// Mines `count` empty blocks and returns the new head.
+    pub fn mine_empty(&self, count: u64) -> u64 {
+        let mut head = self.head();
+        for _ in 0..count {
+            head = self.mine(Vec::new());
+        }
+        head
+    }
+
+    /// Drops the newest `depth` blocks. Blocks mined afterwards replace them
+    /// with different hashes, as after a reorg.
+    pub fn reorg(&self, depth: u64) {
+        let mut state = self.state();
+        let keep = state.blocks.len().saturating_sub(depth as usize).max(1);
+        state.blocks.truncate(keep);
+        state.fork += 1;
+    }
+
+    /// The current head block number.
+    pub fn head(&self) -> u64 {
+        self.state().blocks.len() as u64 - 1
+    }
+
+    /// Makes the next `count` calls to `commit_hash` fail.
+    pub fn fail_next_commits(&self, count: u32) {
+        self.state().commit_failures = count;
+    }
+
+    /// Makes the next `count` calls to `get_events_chunked` leave their whole
+    /// range unfetched.
+    pub fn fail_next_log_fetches(&self, count: u32) {
+        self.state().log_failures = count;
+    }
+
+    /// Makes the next call to `get_events_chunked` whose range contains
+    /// `block` leave its whole range unfetched.
+    pub fn fail_next_log_fetch_of(&self, block: u64) {
+        self.state().failing_log_blocks.push(block);
+    }
+
+    /// The ranges `get_events_chunked` was called with, in call order.
+    pub fn log_fetches(&self) -> Vec<(u64, u64)> {
+        self.state().log_fetches.clone()
+    }
+
+    /// Makes the next `count` calls to `get_latest_block` fail, as when the
+    /// RPC is unreachable.
+    pub fn fail_next_head_fetches(&self, count: u32) {
+        self.state().head_failures = count;
+    }
+
+    /// Makes `trace_transaction` fail, as on a node without the debug API.
+    pub fn disable_traces(&self) {
+        self.state().traces_unsupported = true;
+    }
+
+    /// Makes `call_contract(to, data, ..)` return `result` at the current head
+    /// and every later block, as if a transaction had just changed state.
+    /// Calls without a scripted result revert.
+    pub fn set_call_result(&self, to: Address, data: Vec<u8>, result: Vec<u8>) {
+        let head = self.head();
+        self.state()
+            .call_results
+            .entry((to, data))
+            .or_default()
+            .push(CallResult {
+                first_block: head,
+                return_data: result,
+            });
+    }
+
+    /// Hashes committed so far, in commit order.
+    pub fn commits(&self) -> Vec<String> {
+        self.state().commits.clone()
+    }
+
+    /// Credit spends so far as `(user, amount, job_type)`.
+    pub fn credit_spends(&self) -> Vec<(Address, U256, String)> {
+        self.state().credit_spends.clone()
+    }
+}
+
+#[async_trait]
+impl ChainProvider for MockChain {
+    fn confirmations(&self) -> u64 {
+        self.confirmations
+    }
+
+    async fn get_latest_block(&self) -> Result<u64> {
+        let mut state = self.state();
+        if state.head_failures > 0 {
+            state.head_failures -= 1;
+            anyhow::bail!("Mock head fetch failure");
+        }
+        Ok(state.blocks.len() as u64 - 1)
+    }
+
+    async fn get_block_hash(&self, block_number: u64) -> Result<Option<String>> {
+        Ok(self
+            .state()
+            .blocks
+            .get(block_number as usize)
+            .map(|block| format!("{:?}", block.hash)))
+    }
+
+    async fn get_events_chunked(
+        &self,
+        filter: &EventFilter,
+    ) -> Result<(Vec<BlockchainEvent>, ScanCursor)> {
+        let parsed = ParsedFilter::new(filter)?;
+        let mut cursor = ScanCursor::new(filter.from_block, filter.to_block);
+        cursor.next_block = filter.to_block.saturating_add(1);
+        let mut state = self.state();
+        state.log_fetches.push((filter.from_block, filter.to_block));
+        let failing = state
+            .failing_log_blocks
+            .iter()
+            .position(|b| (filter.from_block..=filter.to_block).contains(b));
+        if let Some(position) = failing {
+            state.failing_log_blocks.remove(position);
+            cursor
+                .failed_ranges
+                .push((filter.from_block, filter.to_block));
+            return Ok((Vec::new(), cursor));
+        }
+        if state.log_failures > 0 {
+            state.log_failures -= 1;
+            cursor
+                .failed_ranges
+                .push((filter.from_block, filter.to_block));
+            return Ok((Vec::new(), cursor));
+        }
+
+        let mut events = Vec::new();
+        let from = filter.from_block as usize;
+        let to = (filter.to_block as usize).min(state.blocks.len().saturating_sub(1));
+        for block in state.blocks.iter().take(to + 1).skip(from) {
+            for log in block.logs.iter().filter(|log| parsed.matches(log)) {
+                if let Some(decoder) = parsed.decoder(log) {
+                    events.push(decode_log(&filter.chain, decoder, log, block.timestamp)?);
+                }
+            }
+        }
+        Ok((events, cursor))
+    }
+
+    async fn subscribe_new_heads(&self) -> Result<BoxStream<'static, u64>> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.state().head_subscribers.push(sender);
+        Ok(receiver.boxed())
+    }
+
+    async fn get_block_with_transactions(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<Block<Transaction>>> {
+        Ok(self
+            .state()
+            .blocks
+            .get(block_number as usize)
+            .map(|block| Block {
+                hash: Some(block.hash),
+                number: Some(U64::from(block_number)),
+                timestamp: U256::from(block.timestamp),
+                transactions: block.transactions.clone(),
+                ..Default::default()
+            }))
+    }
+
+    async fn get_transaction_receipt(&self, hash: H256) -> Result<Option<TransactionReceipt>> {
+        Ok(self
+            .state()
+            .blocks
+            .iter()
+            .flat_map(|block| &block.receipts)
+            .find(|receipt| receipt.transaction_hash == hash)
+            .cloned())
+    }
+
+    async fn trace_transaction(&self, hash: H256) -> Result<serde_json::Value> {
+        let state = self.state();
+        if state.traces_unsupported {
+            anyhow::bail!("the method debug_traceTransaction does not exist/is not available");
+        }
+        let tx = state
+            .blocks
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .find(|tx| tx.hash == hash)
+            .with_context(|| format!("Transaction {:?} not found", hash))?;
+        Ok(serde_json::json!({
+            "type": if tx.to.is_some() { "CALL" } else { "CREATE" },
+            "from": format!("{:?}", tx.from),
+            "to": tx.to.map(|to| format!("{:?}", to)),
+            "value": format!("{:#x}", tx.value),
+            "gasUsed": format!("{:#x}", MOCK_GAS_USED),
+            "input": format!("{}", tx.input),
+            "calls": [],
+        }))
+    }
+
+    async fn call_contract(&self, to: Address, data: Bytes, block_hash: H256) -> Result<Bytes> {
+        let state = self.state();
+        let number = state
+            .blocks
+            .iter()
+            .position(|block| block.hash == block_hash)
+            .with_context(|| format!("Unknown block {:?}", block_hash))?
+            as u64;
+        state
+            .call_results
+            .get(&(to, data.to_vec()))
+            .and_then(|results| {
+                results
+                    .iter()
+                    .rev()
+                    .find(|result| result.first_block <= number)
+            })
+            .map(|result| Bytes::from(result.return_data.clone()))
+            .context("execution reverted")
+    }
+}
+
+#[async_trait]
+impl HashCommitter for MockChain {
+    async fn commit_hash(&self, hash: &str) -> Result<(H256, u64)> {
+        let mut state = self.state();
+        if state.commit_failures > 0 {
+            state.commit_failures -= 1;
+            anyhow::bail!("Mock commit failure");
+        }
+        state.commits.push(hash.to_string());
+        let tx_hash = H256::from(keccak256(hash.as_bytes()));
+        Ok((tx_hash, state.blocks.len() as u64 - 1))
+    }
+
+    fn registry(&self) -> (u64, Address) {
+        (MOCK_CHAIN_ID, MOCK_REGISTRY_ADDRESS)
+    }
+}
+
+#[async_trait]
+impl CreditSpender for MockChain {
+    async fn spend_credits(&self, user: Address, amount: U256, job_type: String) -> Result<H256> {
+        let mut state = self.state();
+        let tx_hash = H256::from(keccak256(
+            [
+                user.as_bytes(),
+                &(state.credit_spends.len() as u64).to_be_bytes(),
+            ]
+            .concat(),
+        ));
+        state.credit_spends.push((user, amount, job_type));
+        Ok(tx_hash)
+    }
+}
+
+/// An in-memory `ContentStore`. Content identifiers are content hashes, so
+/// storing the same bytes twice yields the same identifier.
+#[derive(Default)]
+pub struct MemoryContentStore {
+    content: Mutex<HashMap<String, Vec<u8>>>,
+    pinned: Mutex<HashSet<String>>,
+}
+
+impl MemoryContentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns stored content by identifier.
+    pub fn get(&self, cid: &str) -> Option<Vec<u8>> {
+        self.content
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(cid)
+            .cloned()
+    }
+
+    /// Whether `cid` has been pinned.
+    pub fn is_pinned(&self, cid: &str) -> bool {
+        self.pinned
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(cid)
+    }
+}
+
+#[async_trait(?Send)]
+impl ContentStore for MemoryContentStore {
+    async fn store_content(&self, data: &[u8]) -> Result<String> {
+        let cid = hash_content(data);
+        self.content
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cid.clone(), data.to_vec());
+        Ok(cid)
+    }
+
+    async fn pin_content(&self, cid: &str) -> Result<()> {
+        if self.get(cid).is_none() {
+            anyhow::bail!("Content {} not found", cid);
+        }
+        self.pinned
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cid.to_string());
+        Ok(())
+    }
+
+    async fn retrieve_content(&self, cid: &str) -> Result<Vec<u8>> {
+        self.get(cid)
+            .with_context(|| format!("Content {} not found", cid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSFER: &str = "Transfer(address,address,uint256)";
+
+    fn transfer(token: Address, amount: u64) -> MockLog {
+        let from = H256::from(Address::repeat_byte(1));
+        let to = H256::from(Address::repeat_byte(2));
+        let mut data = [0u8; 32];
+        data[24..].copy_from_slice(&amount.to_be_bytes());
+        MockLog::event(token, TRANSFER, &[from, to], data.to_vec())
+    }
+
+    fn filter(token: Address, from_block: u64, to_block: u64) -> EventFilter {
+        EventFilter::new(
+            "mock",
+            vec![token],
+            vec![TRANSFER.to_string()],
+            from_block,
+            to_block,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_scripted_logs_are_filtered_and_decoded() {
+        let token = Address::repeat_byte(0xaa);
+        let chain = MockChain::new(2);
+        chain.mine(vec![
+            transfer(token, 5),
+            transfer(Address::repeat_byte(0xbb), 6),
+        ]);
+        chain.mine_empty(1);
+        chain.mine(vec![transfer(token, 7)]);
+
+        assert_eq!(chain.get_latest_block().await.unwrap(), 3);
+        assert_eq!(chain.get_finalized_block().await.unwrap(), 1);
+
+        let (events, cursor) = chain
+            .get_events_chunked(&filter(token, 0, 3))
+            .await
+            .unwrap();
+        assert!(cursor.is_complete());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].block_number, 1);
+        assert_eq!(events[0].event_name, "Transfer");
+        assert_eq!(events[1].block_number, 3);
+        assert_eq!(events[1].log_index, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reorg_replaces_block_hashes() {
+        let token = Address::repeat_byte(0xaa);
+        let chain = MockChain::new(0);
+        chain.mine(vec![transfer(token, 5)]);
+        let before = chain.get_block_hash(1).await.unwrap();
+
+        chain.reorg(1);
+        assert_eq!(chain.head(), 0);
+        assert_eq!(chain.get_block_hash(1).await.unwrap(), None);
+
+        chain.mine_empty(1);
+        let after = chain.get_block_hash(1).await.unwrap();
+        assert!(after.is_some());
+        assert_ne!(before, after);
+        let (events, _) = chain
+            .get_events_chunked(&filter(token, 0, 1))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scripted_commit_failures() {
+        let chain = MockChain::new(0);
+        chain.fail_next_commits(1);
+        assert!(chain.commit_hash("ab").await.is_err());
+        assert!(chain.commit_hash("ab").await.is_ok());
+        assert_eq!(chain.commits(), vec!["ab".to_string()]);
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Bytes, Transaction, TransactionReceipt, H256, U256};
+use futures::stream::BoxStream;
+
+use crate::backfill::ScanCursor;
+use crate::blockchain::{BlockchainClient, BlockchainEvent, EventFilter};
+use crate::credits::CreditManager;
+use crate::ipfs::IpfsStorage;
+use crate::timestamp::TimestampClient;
+
+/// Read access to one chain: heads, block hashes and event logs. Implemented by
+/// `BlockchainClient` and, for tests, by `mock::MockChain`.
+#[async_trait]
+pub trait ChainProvider: Send + Sync {
+    /// How many blocks behind the head a block must be to count as final.
+    fn confirmations(&self) -> u64;
+
+    /// The latest block number.
+    async fn get_latest_block(&self) -> Result<u64>;
+
+    /// The newest block at least `confirmations()` blocks behind the head.
+    async fn get_finalized_block(&self) -> Result<u64> {
+        let latest = self.get_latest_block().await?;
+        Ok(latest.saturating_sub(self.confirmations()))
+    }
+
+    /// The hash of the canonical block at `block_number`, or `None` if the
+    /// chain has not reached it.
+    async fn get_block_hash(&self, block_number: u64) -> Result<Option<String>>;
+
+    /// Fetches and decodes the events matching `filter`, together with a cursor
+    /// recording which parts of the range could not be fetched.
+    async fn get_events_chunked(
+        &self,
+        filter: &EventFilter,
+    ) -> Result<(Vec<BlockchainEvent>, ScanCursor)>;
+
+    /// Yields the block number of each new head.
+    async fn subscribe_new_heads(&self) -> Result<BoxStream<'static, u64>>;
+
+    /// The canonical block at `block_number` with its full transactions, or
+    /// `None` if the chain has not reached it.
+    async fn get_block_with_transactions(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<Block<Transaction>>>;
+
+    /// The receipt of a mined transaction.
+    async fn get_transaction_receipt(&self, hash: H256) -> Result<Option<TransactionReceipt>>;
+
+    /// The `callTracer` call trace of a transaction. Fails on nodes that do
+    /// not support `debug_traceTransaction`.
+    async fn trace_transaction(&self, hash: H256) -> Result<serde_json::Value>;
+
+    /// Runs `eth_call` against the state at the block with hash `block_hash`
+    /// and returns the raw return data. Fails if the call reverts.
+    async fn call_contract(&self, to: Address, data: Bytes, block_hash: H256) -> Result<Bytes>;
+}
+
+/// Commits content hashes on-chain.
+#[async_trait]
+pub trait HashCommitter: Send + Sync {
+    /// Commits `hash` and returns `(transaction_hash, block_number)`.
+    async fn commit_hash(&self, hash: &str) -> Result<(H256, u64)>;
+
+    /// The chain ID and contract address of the registry commitments go to.
+    fn registry(&self) -> (u64, Address);
+}
+
+/// Spends users' on-chain credits.
+#[async_trait]
+pub trait CreditSpender: Send + Sync {
+    /// Spends `amount` credits from `user` for a job of `job_type` and returns
+    /// the transaction hash.
+    async fn spend_credits(&self, user: Address, amount: U256, job_type: String) -> Result<H256>;
+}
+
+/// Content-addressed storage for indexed event payloads. Its futures are not
+/// `Send` because the IPFS client's are not.
+#[async_trait(?Send)]
+pub trait ContentStore {
+    /// Stores `data` and returns its content identifier.
+    async fn store_content(&self, data: &[u8]) -> Result<String>;
+
+    /// Pins previously stored content so it is retained.
+    async fn pin_content(&self, cid: &str) -> Result<()>;
+
+    /// Reads content by identifier, optionally followed by a path inside it.
+    async fn retrieve_content(&self, cid: &str) -> Result<Vec<u8>>;
+}
+
+#[async_trait]
+impl ChainProvider for BlockchainClient {
+    fn confirmations(&self) -> u64 {
+        BlockchainClient::confirmations(self)
+    }
+
+    async fn get_latest_block(&self) -> Result<u64> {
+        BlockchainClient::get_latest_block(self).await
+    }
+
+    async fn get_block_hash(&self, block_number: u64) -> Result<Option<String>> {
+        BlockchainClient::get_block_hash(self, block_number).await
+    }
+
+    async fn get_events_chunked(
+        &self,
+        filter: &EventFilter,
+    ) -> Result<(Vec<BlockchainEvent>, ScanCursor)> {
+        BlockchainClient::get_events_chunked(self, filter).await
+    }
+
+    async fn subscribe_new_heads(&self) -> Result<BoxStream<'static, u64>> {
+        BlockchainClient::subscribe_new_heads(self).await
+    }
+
+    async fn get_block_with_transactions(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<Block<Transaction>>> {
+        BlockchainClient::get_block_with_transactions(self, block_number).await
+    }
+
+    async fn get_transaction_receipt(&self, hash: H256) -> Result<Option<TransactionReceipt>> {
+        BlockchainClient::get_transaction_receipt(self, hash).await
+    }
+
+    async fn trace_transaction(&self, hash: H256) -> Result<serde_json::Value> {
+        BlockchainClient::trace_transaction(self, hash).await
+    }
+
+    async fn call_contract(&self, to: Address, data: Bytes, block_hash: H256) -> Result<Bytes> {
+        BlockchainClient::call_contract(self, to, data, block_hash).await
+    }
+}
+
+#[async_trait]
+impl HashCommitter for TimestampClient {
+    async fn commit_hash(&self, hash: &str) -> Result<(H256, u64)> {
+        TimestampClient::commit_hash(self, hash).await
+    }
+
+    fn registry(&self) -> (u64, Address) {
+        (self.chain_id(), self.address())
+    }
+}
+
+#[async_trait]
+impl CreditSpender for CreditManager {
+    async fn spend_credits(&self, user: Address, amount: U256, job_type: String) -> Result<H256> {
+        CreditManager::spend_credits(self, user, amount, job_type).await
+    }
+}
+
+#[async_trait(?Send)]
+impl ContentStore for IpfsStorage {
+    async fn store_content(&self, data: &[u8]) -> Result<String> {
+        IpfsStorage::store_content(self, data).await
+    }
+
+    async fn pin_content(&self, cid: &str) -> Result<()> {
+        IpfsStorage::pin_content(self, cid).await
+    }
+
+    async fn retrieve_content(&self, cid: &str) -> Result<Vec<u8>> {
+        IpfsStorage::retrieve_content(self, cid).await
+    }
+}
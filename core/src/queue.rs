@@ -1,5 +1,6 @@
 use crate::job::{Job, JobStatus};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
@@ -34,7 +35,7 @@ impl JobQueue {
             "UPDATE jobs SET status = 'processing', started_at = NOW() 
              WHERE id = (
                  SELECT id FROM jobs 
-                 WHERE status = 'queued' 
+                 WHERE status = 'queued' AND (scheduled_at IS NULL OR scheduled_at <= NOW())
                  ORDER BY priority DESC, created_at ASC 
                  LIMIT 1 FOR UPDATE SKIP LOCKED
              ) RETURNING *",
@@ -84,6 +85,19 @@ impl JobQueue {
         Ok(())
     }
 
+    /// Puts a job back in the queue, not to be picked up before `at`.
+    pub async fn reschedule(&self, job_id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'queued', scheduled_at = $1, started_at = NULL WHERE id = $2",
+        )
+        .bind(at)
+        .bind(job_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_job(&self, job_id: Uuid) -> Result<Option<Job>> {
         let row = sqlx::query("SELECT * FROM jobs WHERE id = $1")
             .bind(job_id)
@@ -0,0 +1,528 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpClientError, JsonRpcClient, JsonRpcError, ProviderError, RpcError, Ws, WsClientError,
+};
+use ethers::types::{U256, U64};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::backfill::is_rate_limit_error;
+
+/// Cooldown after the first consecutive failure; doubles per further failure.
+const BASE_COOLDOWN: Duration = Duration::from_secs(1);
+/// Upper bound on an endpoint's cooldown.
+const MAX_COOLDOWN: Duration = Duration::from_secs(60);
+/// Minimum cooldown after the endpoint reports a rate limit.
+const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(10);
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+/// Error returned by [`FailoverTransport`]: the last endpoint's error once
+/// every endpoint has been tried, or the first non-retryable JSON-RPC error.
+#[derive(Debug, thiserror::Error)]
+pub enum FailoverError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error(transparent)]
+    Ws(#[from] WsClientError),
+    #[error("Failed to serialize request params: {0}")]
+    Params(#[from] serde_json::Error),
+    #[error("No RPC endpoints configured")]
+    NoEndpoints,
+    #[cfg(test)]
+    #[error(transparent)]
+    Mock(#[from] ethers::providers::MockError),
+}
+
+impl RpcError for FailoverError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            Self::Http(e) => e.as_error_response(),
+            Self::Ws(e) => e.as_error_response(),
+            #[cfg(test)]
+            Self::Mock(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            Self::Http(e) => e.as_serde_error(),
+            Self::Ws(e) => e.as_serde_error(),
+            Self::Params(e) => Some(e),
+            #[cfg(test)]
+            Self::Mock(e) => e.as_serde_error(),
+            _ => None,
+        }
+    }
+}
+
+impl From<FailoverError> for ProviderError {
+    fn from(e: FailoverError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+impl FailoverError {
+    /// Whether another endpoint might succeed where this one failed.
+    ///
+    /// Transport failures, rate limits and signs of an unhealthy or lagging
+    /// node are retried elsewhere; ordinary JSON-RPC errors (reverts, invalid
+    /// params, log-range limits) would fail the same way on every endpoint.
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Params(_) | Self::NoEndpoints => false,
+            _ => match self.as_error_response() {
+                None => true,
+                Some(rpc) => {
+                    let msg = rpc.message.to_lowercase();
+                    rpc.code == 429
+                        || is_rate_limit_error(&msg)
+                        || [
+                            "header not found",
+                            "missing trie node",
+                            "unknown block",
+                            "service unavailable",
+                            "bad gateway",
+                            "no backends",
+                        ]
+                        .iter()
+                        .any(|needle| msg.contains(needle))
+                }
+            },
+        }
+    }
+
+    fn is_rate_limit(&self) -> bool {
+        self.as_error_response()
+            .is_some_and(|rpc| rpc.code == 429 || is_rate_limit_error(&rpc.message.to_lowercase()))
+    }
+}
+
+/// Rolling health statistics for one endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointHealth {
+    /// Exponentially weighted moving average of request latency.
+    pub latency_ms: f64,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    /// The endpoint is skipped until this instant unless every endpoint is cooling down.
+    pub cooldown_until: Option<Instant>,
+    /// Set by [`FailoverTransport::cross_check_heads`] when the endpoint's head
+    /// trails the best head by more than the allowed lag.
+    pub lagging: bool,
+}
+
+impl EndpointHealth {
+    /// Ranking score; lower is better.
+    fn score(&self, now: Instant) -> f64 {
+        let mut score = self.latency_ms.max(1.0) * f64::from(1 + self.consecutive_failures);
+        if self.lagging {
+            score += 10_000.0;
+        }
+        if self.cooldown_until.is_some_and(|until| until > now) {
+            score += 1_000_000.0;
+        }
+        score
+    }
+
+    fn record_success(&mut self, latency: Duration) {
+        let sample = latency.as_secs_f64() * 1_000.0;
+        self.latency_ms = if self.successes == 0 {
+            sample
+        } else {
+            LATENCY_EWMA_ALPHA * sample + (1.0 - LATENCY_EWMA_ALPHA) * self.latency_ms
+        };
+        self.successes += 1;
+        self.consecutive_failures = 0;
+        self.cooldown_until = None;
+    }
+
+    fn record_failure(&mut self, rate_limited: bool) {
+        self.failures += 1;
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let mut cooldown = BASE_COOLDOWN
+            .saturating_mul(2u32.saturating_pow(self.consecutive_failures - 1))
+            .min(MAX_COOLDOWN);
+        if rate_limited {
+            cooldown = cooldown.max(RATE_LIMIT_COOLDOWN);
+        }
+        self.cooldown_until = Some(Instant::now() + cooldown);
+    }
+}
+
+#[derive(Debug)]
+enum Connection {
+    Http(Http),
+    /// Connected lazily and dropped after a transport error so the next
+    /// request reconnects.
+    Ws(tokio::sync::RwLock<Option<Ws>>),
+    #[cfg(test)]
+    Mock(ethers::providers::MockProvider),
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    connection: Connection,
+    health: Mutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    async fn request<R: DeserializeOwned + Send>(
+        &self,
+        method: &str,
+        params: &serde_json::Value,
+    ) -> Result<R, FailoverError> {
+        match &self.connection {
+            Connection::Http(http) => Ok(http.request(method, params).await?),
+            Connection::Ws(slot) => {
+                let ws = self.ws_connection(slot).await?;
+                let result = ws.request(method, params).await;
+                if let Err(e) = &result {
+                    if e.as_error_response().is_none() {
+                        // Transport-level failure: reconnect on next use.
+                        *slot.write().await = None;
+                    }
+                }
+                Ok(result?)
+            }
+            #[cfg(test)]
+            Connection::Mock(mock) => Ok(mock.request(method, params).await?),
+        }
+    }
+
+    async fn ws_connection(
+        &self,
+        slot: &tokio::sync::RwLock<Option<Ws>>,
+    ) -> Result<Ws, FailoverError> {
+        if let Some(ws) = slot.read().await.as_ref() {
+            return Ok(ws.clone());
+        }
+        let mut guard = slot.write().await;
+        if let Some(ws) = guard.as_ref() {
+            return Ok(ws.clone());
+        }
+        tracing::info!("Connecting to RPC endpoint {}", redact(&self.url));
+        let ws = Ws::connect(self.url.as_str()).await?;
+        *guard = Some(ws.clone());
+        Ok(ws)
+    }
+
+    fn health(&self) -> std::sync::MutexGuard<'_, EndpointHealth> {
+        // Health data is advisory; recover it even if a holder panicked.
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// JSON-RPC transport over several HTTP and WebSocket endpoints for one chain.
+///
+/// Each request goes to the healthiest endpoint (ranked by recent failures,
+/// cooldowns, head lag and latency). Transport errors, rate limits and
+/// unhealthy-node responses fail over to the next endpoint; WebSocket
+/// endpoints reconnect automatically after a dropped connection.
+#[derive(Debug)]
+pub struct FailoverTransport {
+    endpoints: Vec<Endpoint>,
+    max_head_lag: u64,
+}
+
+impl FailoverTransport {
+    /// Creates a transport over `urls` (`http(s)://` or `ws(s)://`).
+    ///
+    /// WebSocket endpoints are connected eagerly where possible; one that is
+    /// unreachable now is retried on first use, as long as at least one
+    /// endpoint is usable.
+    pub async fn connect(urls: &[String], max_head_lag: u64) -> anyhow::Result<Self> {
+        if urls.is_empty() {
+            anyhow::bail!("No RPC endpoints configured");
+        }
+        let mut endpoints = Vec::with_capacity(urls.len());
+        let mut connected = 0;
+        for url in urls {
+            let connection = if url.starts_with("ws://") || url.starts_with("wss://") {
+                let ws = match Ws::connect(url.as_str()).await {
+                    Ok(ws) => {
+                        connected += 1;
+                        Some(ws)
+                    }
+                    Err(e) => {
+                        tracing::warn!("RPC endpoint {} unreachable: {}", redact(url), e);
+                        None
+                    }
+                };
+                Connection::Ws(tokio::sync::RwLock::new(ws))
+            } else if url.starts_with("http://") || url.starts_with("https://") {
+                connected += 1;
+                Connection::Http(
+                    Http::from_str(url)
+                        .with_context(|| format!("Invalid RPC URL {}", redact(url)))?,
+                )
+            } else {
+                anyhow::bail!("Unsupported RPC URL scheme: {}", redact(url));
+            };
+            endpoints.push(Endpoint {
+                url: url.clone(),
+                connection,
+                health: Mutex::new(EndpointHealth::default()),
+            });
+        }
+        if connected == 0 {
+            anyhow::bail!("No RPC endpoint reachable");
+        }
+        Ok(Self {
+            endpoints,
+            max_head_lag,
+        })
+    }
+
+    /// Endpoint indices from healthiest to least healthy.
+    fn ranked(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let mut scored: Vec<(f64, usize)> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.health().score(now), i))
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    async fn request_endpoint<R: DeserializeOwned + Send>(
+        &self,
+        index: usize,
+        method: &str,
+        params: &serde_json::Value,
+    ) -> Result<R, FailoverError> {
+        let endpoint = &self.endpoints[index];
+        let started = Instant::now();
+        let result = endpoint.request(method, params).await;
+        match &result {
+            Ok(_) => endpoint.health().record_success(started.elapsed()),
+            Err(e) if e.is_retryable() => endpoint.health().record_failure(e.is_rate_limit()),
+            // The node answered; the request itself was bad.
+            Err(_) => endpoint.health().record_success(started.elapsed()),
+        }
+        result
+    }
+
+    /// Checks every endpoint's chain ID. Unreachable endpoints are logged and
+    /// skipped; a mismatch, or no reachable endpoint at all, is an error.
+    pub async fn verify_chain_id(&self, expected: u64) -> anyhow::Result<()> {
+        let mut reachable = 0;
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            match self
+                .request_endpoint::<U256>(i, "eth_chainId", &serde_json::Value::Null)
+                .await
+            {
+                Ok(chain_id) if chain_id.as_u64() == expected => reachable += 1,
+                Ok(chain_id) => anyhow::bail!(
+                    "RPC endpoint {} reports chain ID {} but {} is configured",
+                    redact(&endpoint.url),
+                    chain_id,
+                    expected
+                ),
+                Err(e) => {
+                    tracing::warn!(
+                        "Could not verify chain ID of {}: {}",
+                        redact(&endpoint.url),
+                        e
+                    );
+                }
+            }
+        }
+        if reachable == 0 {
+            anyhow::bail!("No RPC endpoint is reachable");
+        }
+        Ok(())
+    }
+
+    /// Queries `eth_blockNumber` on every endpoint, flags endpoints trailing
+    /// the best head by more than the allowed lag, and returns the best head.
+    pub async fn cross_check_heads(&self) -> Result<u64, FailoverError> {
+        let heads = futures::future::join_all((0..self.endpoints.len()).map(|i| async move {
+            self.request_endpoint::<U64>(i, "eth_blockNumber", &serde_json::Value::Null)
+                .await
+                .map(|n| n.as_u64())
+        }))
+        .await;
+
+        let best = heads.iter().filter_map(|h| h.as_ref().ok()).copied().max();
+        let Some(best) = best else {
+            return Err(heads
+                .into_iter()
+                .find_map(Result::err)
+                .unwrap_or(FailoverError::NoEndpoints));
+        };
+
+        for (endpoint, head) in self.endpoints.iter().zip(&heads) {
+            if let Ok(head) = head {
+                let lagging = head + self.max_head_lag < best;
+                let mut health = endpoint.health();
+                if lagging && !health.lagging {
+                    tracing::warn!(
+                        "RPC endpoint {} is lagging: head {} vs best {}",
+                        redact(&endpoint.url),
+                        head,
+                        best
+                    );
+                }
+                health.lagging = lagging;
+            }
+        }
+        Ok(best)
+    }
+
+    /// WebSocket endpoint URLs, healthiest first. Used for subscriptions.
+    pub fn ws_urls(&self) -> Vec<String> {
+        self.ranked()
+            .into_iter()
+            .map(|i| &self.endpoints[i])
+            .filter(|e| matches!(e.connection, Connection::Ws(_)))
+            .map(|e| e.url.clone())
+            .collect()
+    }
+
+    /// Snapshot of each endpoint's health, keyed by redacted URL.
+    pub fn endpoint_health(&self) -> Vec<(String, EndpointHealth)> {
+        self.endpoints
+            .iter()
+            .map(|e| (redact(&e.url), e.health().clone()))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverTransport {
+    type Error = FailoverError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params)?;
+        let mut last_error = None;
+        for index in self.ranked() {
+            match self.request_endpoint(index, method, &params).await {
+                Ok(result) => return Ok(result),
+                Err(e) if e.is_retryable() => {
+                    tracing::debug!(
+                        "{} failed on {}, failing over: {}",
+                        method,
+                        redact(&self.endpoints[index].url),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or(FailoverError::NoEndpoints))
+    }
+}
+
+/// Strips the path and query from an RPC URL for logging, since providers
+/// commonly embed API keys there.
+fn redact(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => format!(
+            "{}://{}",
+            parsed.scheme(),
+            parsed.host_str().unwrap_or_default()
+        ),
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockProvider, MockResponse};
+
+    fn mock_transport(mocks: Vec<MockProvider>) -> FailoverTransport {
+        FailoverTransport {
+            endpoints: mocks
+                .into_iter()
+                .enumerate()
+                .map(|(i, mock)| Endpoint {
+                    url: format!("https://node{}.example/key", i),
+                    connection: Connection::Mock(mock),
+                    health: Mutex::new(EndpointHealth::default()),
+                })
+                .collect(),
+            max_head_lag: 5,
+        }
+    }
+
+    fn rpc_error(code: i64, message: &str) -> MockResponse {
+        MockResponse::Error(JsonRpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_on_rate_limit() {
+        let primary = MockProvider::new();
+        primary.push_response(rpc_error(429, "Too Many Requests"));
+        let secondary = MockProvider::new();
+        secondary.push(U64::from(100)).expect("Mock response");
+        let transport = mock_transport(vec![primary, secondary]);
+
+        let head: U64 = transport
+            .request("eth_blockNumber", ())
+            .await
+            .expect("Secondary endpoint answers");
+        assert_eq!(head.as_u64(), 100);
+
+        let health = transport.endpoint_health();
+        assert_eq!(health[0].0, "https://node0.example");
+        assert_eq!(health[0].1.consecutive_failures, 1);
+        assert!(health[0].1.cooldown_until.is_some());
+        assert_eq!(transport.ranked(), vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_fail_over_on_request_errors() {
+        let primary = MockProvider::new();
+        primary.push_response(rpc_error(3, "execution reverted"));
+        let secondary = MockProvider::new();
+        secondary.push(U64::from(100)).expect("Mock response");
+        let transport = mock_transport(vec![primary, secondary]);
+
+        let err = transport
+            .request::<_, U64>("eth_call", ())
+            .await
+            .expect_err("Revert is returned as-is");
+        assert_eq!(
+            err.as_error_response().map(|e| e.message.as_str()),
+            Some("execution reverted")
+        );
+        assert_eq!(transport.endpoint_health()[0].1.consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cross_check_flags_lagging_endpoint() {
+        let fresh = MockProvider::new();
+        fresh.push(U64::from(1_000)).expect("Mock response");
+        let stale = MockProvider::new();
+        stale.push(U64::from(990)).expect("Mock response");
+        let transport = mock_transport(vec![stale, fresh]);
+
+        let best = transport.cross_check_heads().await.expect("Heads resolve");
+        assert_eq!(best, 1_000);
+        let health = transport.endpoint_health();
+        assert!(health[0].1.lagging);
+        assert!(!health[1].1.lagging);
+        assert_eq!(transport.ranked()[0], 1);
+    }
+}
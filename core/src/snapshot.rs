@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use ethers::abi::token::{LenientTokenizer, Tokenizer};
+use ethers::abi::Function;
+use ethers::types::{Address, Bytes, H256};
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::decoder::{params_to_json, parse_named_function, Components, NamedFunction};
+use crate::job::ViewCall;
+use crate::merkle::hash_content;
+use crate::provider::ChainProvider;
+
+/// Concurrent `eth_call` requests per snapshot block.
+const CALL_CONCURRENCY: usize = 8;
+
+/// A `ViewCall` with its function parsed and calldata encoded.
+#[derive(Debug, Clone)]
+pub struct PreparedCall {
+    contract: Address,
+    function: Function,
+    /// The component names of the function's outputs.
+    outputs: Vec<Components>,
+    args: Vec<String>,
+    calldata: Bytes,
+}
+
+impl PreparedCall {
+    /// Parses the call's contract, function and arguments.
+    pub fn new(call: &ViewCall) -> Result<Self> {
+        let contract = call
+            .contract
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid contract address '{}'", call.contract))?;
+        let NamedFunction {
+            function, outputs, ..
+        } = parse_named_function(&call.function)?;
+        if function.inputs.len() != call.args.len() {
+            anyhow::bail!(
+                "{} takes {} arguments, {} given",
+                function.name,
+                function.inputs.len(),
+                call.args.len()
+            );
+        }
+        let tokens = function
+            .inputs
+            .iter()
+            .zip(&call.args)
+            .map(|(param, arg)| {
+                LenientTokenizer::tokenize(&param.kind, arg).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Invalid {} argument '{}' for {}: {}",
+                        param.kind,
+                        arg,
+                        function.name,
+                        e
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let calldata = function
+            .encode_input(&tokens)
+            .with_context(|| format!("Failed to encode call to {}", function.name))?;
+        Ok(Self {
+            contract,
+            function,
+            outputs,
+            args: call.args.clone(),
+            calldata: Bytes::from(calldata),
+        })
+    }
+
+    /// The canonical signature, e.g. `balanceOf(address)`.
+    pub fn signature(&self) -> String {
+        let types: Vec<String> = self
+            .function
+            .inputs
+            .iter()
+            .map(|p| p.kind.to_string())
+            .collect();
+        format!("{}({})", self.function.name, types.join(","))
+    }
+
+    /// The encoded calldata sent with `eth_call`.
+    pub fn calldata(&self) -> &Bytes {
+        &self.calldata
+    }
+}
+
+/// Prepares a snapshot job's calls, failing on the first invalid one.
+pub fn prepare_calls(calls: &[ViewCall]) -> Result<Vec<PreparedCall>> {
+    calls
+        .iter()
+        .enumerate()
+        .map(|(i, call)| PreparedCall::new(call).with_context(|| format!("Invalid call {}", i)))
+        .collect()
+}
+
+/// The result of one view call at one block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotValue {
+    pub chain: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    /// Position of the call in the job's `calls`.
+    pub call_index: u64,
+    pub contract_address: String,
+    /// The canonical signature of the called function.
+    pub function: String,
+    pub args: Vec<String>,
+    /// `0x`-prefixed calldata sent with `eth_call`.
+    pub calldata: String,
+    /// `0x`-prefixed raw return data.
+    pub return_data: String,
+    /// Return values keyed by output name (or `argN`); `None` when the function
+    /// definition declares no return types or they fail to decode.
+    pub result: Option<Value>,
+    /// Why the return data could not be decoded, if it could not.
+    pub decode_error: Option<String>,
+    /// Hash of every other field of the record.
+    pub content_hash: String,
+}
+
+/// Runs every call against the state at canonical block `block_number`.
+///
+/// The block's hash is resolved first and each call is pinned to that hash,
+/// so all values in the result come from the same block. Fails if the block
+/// does not exist yet or any call reverts.
+pub async fn take_snapshot(
+    client: &dyn ChainProvider,
+    chain: &str,
+    calls: &[PreparedCall],
+    block_number: u64,
+) -> Result<Vec<SnapshotValue>> {
+    let block_hash: H256 = client
+        .get_block_hash(block_number)
+        .await?
+        .with_context(|| format!("Block {} not found", block_number))?
+        .parse()
+        .context("Invalid block hash")?;
+
+    futures::stream::iter(calls.iter().enumerate())
+        .map(|(index, call)| async move {
+            let return_data = client
+                .call_contract(call.contract, call.calldata.clone(), block_hash)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Call {} to {:?} at block {} failed",
+                        call.signature(),
+                        call.contract,
+                        block_number
+                    )
+                })?;
+            let (result, decode_error) = if call.function.outputs.is_empty() {
+                (None, None)
+            } else {
+                match call.function.decode_output(&return_data) {
+                    Ok(tokens) => (
+                        Some(Value::Object(params_to_json(
+                            &call.function.outputs,
+                            &call.outputs,
+                            &tokens,
+                        ))),
+                        None,
+                    ),
+                    Err(e) => (None, Some(e.to_string())),
+                }
+            };
+            let mut value = SnapshotValue {
+                chain: chain.to_string(),
+                block_number,
+                block_hash: format!("{:?}", block_hash),
+                call_index: index as u64,
+                contract_address: format!("{:?}", call.contract),
+                function: call.signature(),
+                args: call.args.clone(),
+                calldata: format!("0x{}", hex::encode(&call.calldata)),
+                return_data: format!("0x{}", hex::encode(&return_data)),
+                result,
+                decode_error,
+                content_hash: String::new(),
+            };
+            // Hashed while `content_hash` is still empty.
+            value.content_hash = hash_content(&serde_json::to_vec(&value)?);
+            Ok::<_, anyhow::Error>(value)
+        })
+        .buffered(CALL_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockChain;
+    use ethers::abi::Token;
+    use ethers::types::U256;
+
+    fn balance_of(token: Address, holder: Address) -> ViewCall {
+        ViewCall {
+            contract: format!("{:?}", token),
+            function: "function balanceOf(address owner) view returns (uint256 balance)"
+                .to_string(),
+            args: vec![format!("{:?}", holder)],
+        }
+    }
+
+    #[test]
+    fn test_prepare_checks_arguments() {
+        let token = Address::repeat_byte(0xaa);
+        let call = PreparedCall::new(&balance_of(token, Address::repeat_byte(1))).unwrap();
+        assert_eq!(call.signature(), "balanceOf(address)");
+        assert_eq!(&call.calldata[..4], &[0x70, 0xa0, 0x82, 0x31]);
+
+        let mut missing = balance_of(token, Address::repeat_byte(1));
+        missing.args.clear();
+        assert!(PreparedCall::new(&missing).is_err());
+
+        let mut invalid = balance_of(token, Address::repeat_byte(1));
+        invalid.args = vec!["not-an-address".to_string()];
+        assert!(PreparedCall::new(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_reads_state_at_each_block() {
+        let token = Address::repeat_byte(0xaa);
+        let holder = Address::repeat_byte(1);
+        let call = PreparedCall::new(&balance_of(token, holder)).unwrap();
+        let encode = |n: u64| ethers::abi::encode(&[Token::Uint(U256::from(n))]);
+
+        let chain = MockChain::new(0);
+        chain.set_call_result(token, call.calldata.to_vec(), encode(5));
+        chain.mine_empty(2);
+        chain.set_call_result(token, call.calldata.to_vec(), encode(8));
+
+        let before = take_snapshot(&chain, "mock", std::slice::from_ref(&call), 1)
+            .await
+            .unwrap();
+        let after = take_snapshot(&chain, "mock", std::slice::from_ref(&call), 2)
+            .await
+            .unwrap();
+        assert_eq!(before[0].result.as_ref().unwrap()["balance"], "5");
+        assert_eq!(after[0].result.as_ref().unwrap()["balance"], "8");
+        assert_eq!(after[0].block_number, 2);
+        assert_ne!(before[0].content_hash, after[0].content_hash);
+
+        let unknown = PreparedCall::new(&balance_of(token, Address::repeat_byte(2))).unwrap();
+        assert!(take_snapshot(&chain, "mock", &[unknown], 2).await.is_err());
+        assert!(take_snapshot(&chain, "mock", &[call], 3).await.is_err());
+    }
+}
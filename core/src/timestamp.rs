@@ -12,6 +12,7 @@ abigen!(
 /// Client for interacting with the on-chain TimestampRegistry smart contract.
 pub struct TimestampClient {
     contract: TimestampRegistry<SignerMiddleware<Provider<Ws>, LocalWallet>>,
+    chain_id: u64,
 }
 
 impl TimestampClient {
@@ -40,7 +41,17 @@ impl TimestampClient {
         let client = SignerMiddleware::new(provider, wallet);
         let contract = TimestampRegistry::new(contract_addr, Arc::new(client));
 
-        Ok(Self { contract })
+        Ok(Self { contract, chain_id })
+    }
+
+    /// The ID of the chain the registry is deployed on.
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// The address of the registry contract.
+    pub fn address(&self) -> Address {
+        self.contract.address()
     }
 
     /// Commits a content hash to the blockchain.
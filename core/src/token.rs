@@ -0,0 +1,490 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use ethers::abi::{ParamType, Token};
+use ethers::types::{Address, H256, U256};
+use ethers::utils::{id, keccak256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::blockchain::BlockchainEvent;
+use crate::provider::{ChainProvider, ContentStore};
+
+/// ERC-165 interface IDs of ERC-721 and ERC-1155.
+const ERC721_INTERFACE: [u8; 4] = [0x80, 0xac, 0x58, 0xcd];
+const ERC1155_INTERFACE: [u8; 4] = [0xd9, 0xb6, 0x7a, 0x26];
+
+/// Largest NFT metadata document fetched from a token URI.
+const MAX_METADATA_BYTES: usize = 1 << 20;
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A token standard recognised by the enrichment stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStandard {
+    Erc20,
+    Erc721,
+    Erc1155,
+}
+
+impl TokenStandard {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenStandard::Erc20 => "erc20",
+            TokenStandard::Erc721 => "erc721",
+            TokenStandard::Erc1155 => "erc1155",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "erc20" => Some(TokenStandard::Erc20),
+            "erc721" => Some(TokenStandard::Erc721),
+            "erc1155" => Some(TokenStandard::Erc1155),
+            _ => None,
+        }
+    }
+}
+
+/// A token contract's standard and descriptive fields. Name and symbol are
+/// optional in every standard; decimals only exist for ERC-20.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub standard: TokenStandard,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+impl TokenMetadata {
+    /// Detects whether `contract` is a token, reading its state at the block
+    /// with hash `block_hash`. ERC-721 and ERC-1155 are recognised through
+    /// ERC-165 `supportsInterface`, ERC-20 through a working `decimals()`.
+    ///
+    /// Returns `None` for other contracts. A failing RPC call is treated like
+    /// a revert, so a `None` should not be cached permanently.
+    pub async fn fetch(
+        client: &dyn ChainProvider,
+        contract: Address,
+        block_hash: H256,
+    ) -> Option<Self> {
+        let call = ContractCall {
+            client,
+            contract,
+            block_hash,
+        };
+        let standard = if call.supports_interface(ERC721_INTERFACE).await {
+            TokenStandard::Erc721
+        } else if call.supports_interface(ERC1155_INTERFACE).await {
+            TokenStandard::Erc1155
+        } else {
+            TokenStandard::Erc20
+        };
+        let decimals = match call.call("decimals()", &[], ParamType::Uint(8)).await {
+            Some(Token::Uint(d)) if d <= U256::from(u8::MAX) => Some(d.as_u32() as u8),
+            _ => None,
+        };
+        if standard == TokenStandard::Erc20 && decimals.is_none() {
+            return None;
+        }
+        Some(Self {
+            standard,
+            name: call.text("name()").await,
+            symbol: call.text("symbol()").await,
+            decimals: decimals.filter(|_| standard == TokenStandard::Erc20),
+        })
+    }
+}
+
+/// The token movement described by a transfer event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenTransfer {
+    pub standard: TokenStandard,
+    /// Amount in the token's base units, as a decimal string. `None` for
+    /// ERC-721 and batch transfers.
+    pub amount_raw: Option<String>,
+    /// `amount_raw` scaled by the token's decimals; ERC-20 only.
+    pub amount: Option<String>,
+    /// The NFT moved, for ERC-721 and single ERC-1155 transfers.
+    pub token_id: Option<U256>,
+}
+
+/// Whether `event` is an ERC-20/721 `Transfer` or an ERC-1155 `TransferSingle`
+/// or `TransferBatch`, judged by its signature topic.
+pub fn is_transfer_event(event: &BlockchainEvent) -> bool {
+    let Some(topic0) = event.topics.first() else {
+        return false;
+    };
+    [
+        "Transfer(address,address,uint256)",
+        "TransferSingle(address,address,address,uint256,uint256)",
+        "TransferBatch(address,address,address,uint256[],uint256[])",
+    ]
+    .iter()
+    .any(|signature| *topic0 == format!("{:?}", H256::from(keccak256(signature))))
+}
+
+/// Reads the amount and token ID of a transfer event from its raw log, using
+/// the emitting contract's metadata. Returns `None` for events that are not
+/// transfers of the contract's standard.
+pub fn token_transfer(event: &BlockchainEvent, metadata: &TokenMetadata) -> Option<TokenTransfer> {
+    if !is_transfer_event(event) {
+        return None;
+    }
+    let topics: Vec<H256> = event
+        .topics
+        .iter()
+        .map(|t| t.parse().ok())
+        .collect::<Option<_>>()?;
+    let data = hex::decode(
+        event.event_data["raw"]["data"]
+            .as_str()?
+            .trim_start_matches("0x"),
+    )
+    .ok()?;
+    let word = |bytes: &[u8]| U256::from_big_endian(bytes);
+    let transfer = |amount_raw: Option<U256>, token_id: Option<U256>| TokenTransfer {
+        standard: metadata.standard,
+        amount_raw: amount_raw.map(|a| a.to_string()),
+        amount: amount_raw
+            .zip(metadata.decimals)
+            .map(|(a, d)| scale_amount(a, d)),
+        token_id,
+    };
+
+    let is_single = topics[0]
+        == H256::from(keccak256(
+            "TransferSingle(address,address,address,uint256,uint256)",
+        ));
+    let is_transfer = topics[0] == H256::from(keccak256("Transfer(address,address,uint256)"));
+    match metadata.standard {
+        TokenStandard::Erc20 if is_transfer && topics.len() == 3 && data.len() == 32 => {
+            Some(transfer(Some(word(&data)), None))
+        }
+        TokenStandard::Erc721 if is_transfer && topics.len() == 4 => {
+            Some(transfer(None, Some(word(topics[3].as_bytes()))))
+        }
+        TokenStandard::Erc1155 if is_single && data.len() == 64 => {
+            Some(transfer(Some(word(&data[32..])), Some(word(&data[..32]))))
+        }
+        TokenStandard::Erc1155 if !is_transfer => Some(transfer(None, None)),
+        _ => None,
+    }
+}
+
+/// Formats `amount` base units with `decimals` decimal places, without
+/// trailing zeros (e.g. `1500000` with 6 decimals is `1.5`).
+pub fn scale_amount(amount: U256, decimals: u8) -> String {
+    let digits = amount.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Reads an NFT's metadata URI: `tokenURI` for ERC-721, `uri` for ERC-1155
+/// with the `{id}` placeholder substituted.
+pub async fn fetch_token_uri(
+    client: &dyn ChainProvider,
+    contract: Address,
+    standard: TokenStandard,
+    token_id: U256,
+    block_hash: H256,
+) -> Option<String> {
+    let call = ContractCall {
+        client,
+        contract,
+        block_hash,
+    };
+    let id_arg = [Token::Uint(token_id)];
+    match standard {
+        TokenStandard::Erc20 => None,
+        TokenStandard::Erc721 => call
+            .call("tokenURI(uint256)", &id_arg, ParamType::String)
+            .await?
+            .into_string(),
+        TokenStandard::Erc1155 => {
+            let uri = call
+                .call("uri(uint256)", &id_arg, ParamType::String)
+                .await?
+                .into_string()?;
+            let mut id_hex = [0u8; 32];
+            token_id.to_big_endian(&mut id_hex);
+            Some(uri.replace("{id}", &hex::encode(id_hex)))
+        }
+    }
+    .map(|uri| clean_text(&uri))
+}
+
+/// Fetches the JSON metadata document a token URI points to. `ipfs://` URIs
+/// are read through `store`; `data:` URIs are decoded inline and `http(s)`
+/// URIs are fetched directly.
+pub async fn resolve_token_uri(store: &dyn ContentStore, uri: &str) -> Result<Value> {
+    let body = if let Some(path) = uri.strip_prefix("ipfs://") {
+        store
+            .retrieve_content(path.trim_start_matches("ipfs/"))
+            .await?
+    } else if let Some(data) = uri.strip_prefix("data:") {
+        let (header, payload) = data.split_once(',').context("Malformed data URI")?;
+        if header.ends_with(";base64") {
+            base64::engine::general_purpose::STANDARD
+                .decode(payload)
+                .context("Invalid base64 in data URI")?
+        } else {
+            payload.as_bytes().to_vec()
+        }
+    } else if uri.starts_with("https://") || uri.starts_with("http://") {
+        let response = reqwest::Client::builder()
+            .timeout(METADATA_TIMEOUT)
+            .build()?
+            .get(uri)
+            .send()
+            .await?
+            .error_for_status()?;
+        if response
+            .content_length()
+            .is_some_and(|len| len > MAX_METADATA_BYTES as u64)
+        {
+            anyhow::bail!("Metadata at {} is too large", uri);
+        }
+        response.bytes().await?.to_vec()
+    } else {
+        anyhow::bail!("Unsupported token URI scheme: {}", uri);
+    };
+    if body.len() > MAX_METADATA_BYTES {
+        anyhow::bail!("Metadata at {} is too large", uri);
+    }
+    serde_json::from_slice(&body).with_context(|| format!("Metadata at {} is not JSON", uri))
+}
+
+/// Drops NUL and surrounding whitespace, which some contracts pad strings
+/// with and Postgres rejects in text.
+fn clean_text(s: &str) -> String {
+    s.replace('\0', "").trim().to_string()
+}
+
+/// Read-only calls to one contract at one block.
+struct ContractCall<'a> {
+    client: &'a dyn ChainProvider,
+    contract: Address,
+    block_hash: H256,
+}
+
+impl ContractCall<'_> {
+    /// Calls `signature` and decodes its single return value; `None` if the
+    /// call fails or returns something else.
+    async fn call(&self, signature: &str, args: &[Token], output: ParamType) -> Option<Token> {
+        let mut data = id(signature).to_vec();
+        data.extend(ethers::abi::encode(args));
+        let result = self
+            .client
+            .call_contract(self.contract, data.into(), self.block_hash)
+            .await
+            .ok()?;
+        ethers::abi::decode(&[output], &result).ok()?.pop()
+    }
+
+    async fn supports_interface(&self, interface: [u8; 4]) -> bool {
+        matches!(
+            self.call(
+                "supportsInterface(bytes4)",
+                &[Token::FixedBytes(interface.to_vec())],
+                ParamType::Bool,
+            )
+            .await,
+            Some(Token::Bool(true))
+        )
+    }
+
+    /// Reads a `string` getter, falling back to `bytes32` as returned by some
+    /// early tokens.
+    async fn text(&self, signature: &str) -> Option<String> {
+        let text = match self.call(signature, &[], ParamType::String).await {
+            Some(Token::String(s)) => s,
+            _ => match self.call(signature, &[], ParamType::FixedBytes(32)).await? {
+                Token::FixedBytes(bytes) => String::from_utf8(bytes).ok()?,
+                _ => return None,
+            },
+        };
+        Some(clean_text(&text)).filter(|s| !s.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MemoryContentStore, MockChain};
+
+    fn returns(tokens: &[Token]) -> Vec<u8> {
+        ethers::abi::encode(tokens)
+    }
+
+    fn selector(signature: &str) -> Vec<u8> {
+        id(signature).to_vec()
+    }
+
+    fn supports(interface: [u8; 4]) -> Vec<u8> {
+        let mut data = selector("supportsInterface(bytes4)");
+        data.extend(returns(&[Token::FixedBytes(interface.to_vec())]));
+        data
+    }
+
+    fn event(contract: Address, topics: Vec<H256>, data: &[u8]) -> BlockchainEvent {
+        BlockchainEvent {
+            chain: "mock".to_string(),
+            contract_address: format!("{:?}", contract),
+            event_name: "Transfer".to_string(),
+            block_number: 1,
+            transaction_hash: format!("{:?}", H256::repeat_byte(1)),
+            log_index: 0,
+            topics: topics.iter().map(|t| format!("{:?}", t)).collect(),
+            block_hash: format!("{:?}", H256::repeat_byte(2)),
+            block_timestamp: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+            removed: false,
+            event_data: serde_json::json!({ "raw": { "data": format!("0x{}", hex::encode(data)) } }),
+            content_hash: String::new(),
+            hash_version: crate::blockchain::EVENT_HASH_VERSION,
+        }
+    }
+
+    fn transfer_topic() -> H256 {
+        H256::from(keccak256("Transfer(address,address,uint256)"))
+    }
+
+    #[tokio::test]
+    async fn test_detects_erc20_and_scales_amounts() {
+        let chain = MockChain::new(0);
+        let token = Address::repeat_byte(0xaa);
+        chain.set_call_result(
+            token,
+            selector("decimals()"),
+            returns(&[Token::Uint(6.into())]),
+        );
+        chain.set_call_result(
+            token,
+            selector("name()"),
+            returns(&[Token::String("USD Coin".to_string())]),
+        );
+        let mut symbol = b"USDC".to_vec();
+        symbol.resize(32, 0);
+        chain.set_call_result(token, selector("symbol()"), symbol);
+        let block_hash: H256 = chain
+            .get_block_hash(0)
+            .await
+            .unwrap()
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let metadata = TokenMetadata::fetch(&chain, token, block_hash)
+            .await
+            .unwrap();
+        assert_eq!(metadata.standard, TokenStandard::Erc20);
+        assert_eq!(metadata.name.as_deref(), Some("USD Coin"));
+        assert_eq!(metadata.symbol.as_deref(), Some("USDC"));
+        assert_eq!(metadata.decimals, Some(6));
+
+        let transfer = event(
+            token,
+            vec![transfer_topic(), H256::zero(), H256::zero()],
+            &returns(&[Token::Uint(1_500_000.into())]),
+        );
+        let enriched = token_transfer(&transfer, &metadata).unwrap();
+        assert_eq!(enriched.amount_raw.as_deref(), Some("1500000"));
+        assert_eq!(enriched.amount.as_deref(), Some("1.5"));
+
+        assert!(
+            TokenMetadata::fetch(&chain, Address::repeat_byte(0xbb), block_hash)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detects_erc721_and_resolves_ipfs_metadata() {
+        let chain = MockChain::new(0);
+        let nft = Address::repeat_byte(0xcc);
+        chain.set_call_result(
+            nft,
+            supports(ERC721_INTERFACE),
+            returns(&[Token::Bool(true)]),
+        );
+        chain.set_call_result(
+            nft,
+            selector("symbol()"),
+            returns(&[Token::String("PUNK".to_string())]),
+        );
+        let block_hash: H256 = chain
+            .get_block_hash(0)
+            .await
+            .unwrap()
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let metadata = TokenMetadata::fetch(&chain, nft, block_hash).await.unwrap();
+        assert_eq!(metadata.standard, TokenStandard::Erc721);
+        assert_eq!(metadata.decimals, None);
+
+        let transfer = event(
+            nft,
+            vec![
+                transfer_topic(),
+                H256::zero(),
+                H256::zero(),
+                H256::from_low_u64_be(42),
+            ],
+            &[],
+        );
+        let enriched = token_transfer(&transfer, &metadata).unwrap();
+        assert_eq!(enriched.token_id, Some(U256::from(42)));
+        assert_eq!(enriched.amount_raw, None);
+
+        let store = MemoryContentStore::new();
+        let cid = store
+            .store_content(br#"{"name":"Punk #42"}"#)
+            .await
+            .unwrap();
+        let mut call = selector("tokenURI(uint256)");
+        call.extend(returns(&[Token::Uint(42.into())]));
+        chain.set_call_result(
+            nft,
+            call,
+            returns(&[Token::String(format!("ipfs://{}", cid))]),
+        );
+
+        let uri = fetch_token_uri(&chain, nft, metadata.standard, 42.into(), block_hash)
+            .await
+            .unwrap();
+        let document = resolve_token_uri(&store, &uri).await.unwrap();
+        assert_eq!(document["name"], "Punk #42");
+    }
+
+    #[tokio::test]
+    async fn test_resolves_data_uris() {
+        let store = MemoryContentStore::new();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(br#"{"name":"On-chain"}"#);
+        let document =
+            resolve_token_uri(&store, &format!("data:application/json;base64,{}", encoded))
+                .await
+                .unwrap();
+        assert_eq!(document["name"], "On-chain");
+        assert!(resolve_token_uri(&store, "ar://abc").await.is_err());
+    }
+
+    #[test]
+    fn test_scale_amount() {
+        assert_eq!(scale_amount(U256::from(1_500_000u64), 6), "1.5");
+        assert_eq!(scale_amount(U256::from(5u64), 18), "0.000000000000000005");
+        assert_eq!(scale_amount(U256::from(2_000_000u64), 6), "2");
+        assert_eq!(scale_amount(U256::from(7u64), 0), "7");
+    }
+}
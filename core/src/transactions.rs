@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use ethers::types::{Address, Block, Transaction, TransactionReceipt, H256};
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::decoder::FunctionDecoder;
+use crate::job::TransactionIndexParams;
+use crate::merkle::hash_content;
+use crate::provider::ChainProvider;
+
+/// Concurrent blocks fetched while scanning for transactions.
+const BLOCK_SCAN_CONCURRENCY: usize = 8;
+
+/// A transaction indexed with its receipt, decoded calldata and, optionally,
+/// its call trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedTransaction {
+    pub chain: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub block_timestamp: DateTime<Utc>,
+    pub transaction_hash: String,
+    pub transaction_index: u64,
+    pub from_address: String,
+    /// `None` for contract creations.
+    pub to_address: Option<String>,
+    /// Value transferred in wei, as a decimal string.
+    pub value: String,
+    pub nonce: u64,
+    /// `0x`-prefixed calldata.
+    pub input: String,
+    /// Calldata decoded by `FunctionDecoder::decode_call`; `None` for calls
+    /// to functions missing from the job's ABI.
+    pub call: Option<Value>,
+    /// Whether the transaction succeeded; `None` for pre-Byzantium receipts.
+    pub status: Option<bool>,
+    pub gas_used: Option<u64>,
+    /// Price paid per unit of gas in wei, as a decimal string.
+    pub effective_gas_price: Option<String>,
+    /// The contract created by the transaction, if any.
+    pub contract_address: Option<String>,
+    /// `callTracer` output, including internal calls.
+    pub trace: Option<Value>,
+    /// Hash of every other field of the record.
+    pub content_hash: String,
+}
+
+impl IndexedTransaction {
+    fn new(
+        chain: &str,
+        block: &Block<Transaction>,
+        tx: &Transaction,
+        receipt: &TransactionReceipt,
+        call: Option<Value>,
+        trace: Option<Value>,
+    ) -> Result<Self> {
+        let block_hash = block.hash.context("Missing block hash")?;
+        if receipt.block_hash != Some(block_hash) {
+            anyhow::bail!(
+                "Receipt for {:?} is from block {:?}, not {:?}",
+                tx.hash,
+                receipt.block_hash,
+                block_hash
+            );
+        }
+        let mut record = Self {
+            chain: chain.to_string(),
+            block_number: block.number.context("Missing block number")?.as_u64(),
+            block_hash: format!("{:?}", block_hash),
+            block_timestamp: DateTime::from_timestamp(block.timestamp.as_u64() as i64, 0)
+                .context("Invalid block timestamp")?,
+            transaction_hash: format!("{:?}", tx.hash),
+            transaction_index: receipt.transaction_index.as_u64(),
+            from_address: format!("{:?}", tx.from),
+            to_address: tx.to.map(|to| format!("{:?}", to)),
+            value: tx.value.to_string(),
+            nonce: tx.nonce.as_u64(),
+            input: format!("0x{}", hex::encode(&tx.input)),
+            call,
+            status: receipt.status.map(|s| s.as_u64() == 1),
+            gas_used: receipt.gas_used.map(|g| g.as_u64()),
+            effective_gas_price: receipt.effective_gas_price.map(|p| p.to_string()),
+            contract_address: receipt.contract_address.map(|a| format!("{:?}", a)),
+            trace,
+            content_hash: String::new(),
+        };
+        // Hashed while `content_hash` is still empty.
+        record.content_hash = hash_content(&serde_json::to_vec(&record)?);
+        Ok(record)
+    }
+}
+
+/// Fetches the transactions sent from or to `params.addresses` in blocks
+/// `[from_block, to_block]`, in block order, with their receipts and decoded
+/// calldata.
+///
+/// With `include_traces`, a call trace is attached to each transaction. If the
+/// node cannot trace, a warning is logged and the remaining transactions of
+/// the scan are indexed without traces.
+pub async fn scan_transactions(
+    client: &dyn ChainProvider,
+    params: &TransactionIndexParams,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<IndexedTransaction>> {
+    let scan = BlockScan {
+        client,
+        chain: &params.chain,
+        addresses: params.addresses()?.into_iter().collect(),
+        decoder: FunctionDecoder::parse(&params.abi)?,
+        traces: AtomicBool::new(params.include_traces),
+    };
+    let blocks: Vec<Vec<IndexedTransaction>> = futures::stream::iter(from_block..=to_block)
+        .map(|number| scan.block(number))
+        .buffered(BLOCK_SCAN_CONCURRENCY)
+        .try_collect()
+        .await?;
+    Ok(blocks.into_iter().flatten().collect())
+}
+
+struct BlockScan<'a> {
+    client: &'a dyn ChainProvider,
+    chain: &'a str,
+    addresses: HashSet<Address>,
+    decoder: FunctionDecoder,
+    /// Cleared after the first failed trace.
+    traces: AtomicBool,
+}
+
+impl BlockScan<'_> {
+    fn matches(&self, tx: &Transaction) -> bool {
+        self.addresses.contains(&tx.from) || tx.to.is_some_and(|to| self.addresses.contains(&to))
+    }
+
+    async fn block(&self, number: u64) -> Result<Vec<IndexedTransaction>> {
+        let block = self
+            .client
+            .get_block_with_transactions(number)
+            .await?
+            .with_context(|| format!("Block {} not found", number))?;
+        let mut records = Vec::new();
+        for tx in block.transactions.iter().filter(|tx| self.matches(tx)) {
+            let receipt = self
+                .client
+                .get_transaction_receipt(tx.hash)
+                .await?
+                .with_context(|| format!("Receipt for {:?} not found", tx.hash))?;
+            let trace = self.trace(tx.hash).await;
+            records.push(IndexedTransaction::new(
+                self.chain,
+                &block,
+                tx,
+                &receipt,
+                self.decoder.decode_call(&tx.input),
+                trace,
+            )?);
+        }
+        Ok(records)
+    }
+
+    async fn trace(&self, hash: H256) -> Option<Value> {
+        if !self.traces.load(Ordering::Relaxed) {
+            return None;
+        }
+        match self.client.trace_transaction(hash).await {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                if self.traces.swap(false, Ordering::Relaxed) {
+                    tracing::warn!(
+                        "Tracing {:?} on {} failed; indexing without traces: {:?}",
+                        hash,
+                        self.chain,
+                        e
+                    );
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{JobConfig, JobParams, JobType};
+    use crate::mock::{MockChain, MockTransaction};
+
+    fn params(addresses: &[Address], include_traces: bool) -> TransactionIndexParams {
+        TransactionIndexParams {
+            chain: "mock".to_string(),
+            addresses: addresses.iter().map(|a| format!("{:?}", a)).collect(),
+            abi: vec!["function transfer(address to, uint256 amount)".to_string()],
+            from_block: 0,
+            to_block: 2,
+            include_traces,
+            merkle_mode: Default::default(),
+        }
+    }
+
+    fn transfer_call(to: Address) -> Vec<u8> {
+        let mut input = hex::decode("a9059cbb").unwrap();
+        input.extend(ethers::abi::encode(&[
+            ethers::abi::Token::Address(to),
+            ethers::abi::Token::Uint(7.into()),
+        ]));
+        input
+    }
+
+    #[tokio::test]
+    async fn test_scan_matches_sender_and_recipient() {
+        let token = Address::repeat_byte(0xaa);
+        let user = Address::repeat_byte(0x01);
+        let chain = MockChain::new(0);
+        chain.mine_transactions(vec![
+            MockTransaction::call(user, token, transfer_call(Address::repeat_byte(2))),
+            MockTransaction::call(Address::repeat_byte(3), Address::repeat_byte(4), Vec::new()),
+        ]);
+        chain.mine_transactions(vec![MockTransaction {
+            from: Address::repeat_byte(5),
+            to: Some(token),
+            input: vec![0xde, 0xad, 0xbe, 0xef],
+            reverted: true,
+            ..Default::default()
+        }]);
+
+        let txs = scan_transactions(&chain, &params(&[token], true), 0, 2)
+            .await
+            .unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].block_number, 1);
+        assert_eq!(txs[0].call.as_ref().unwrap()["function"], "transfer");
+        assert_eq!(txs[0].status, Some(true));
+        assert!(txs[0].trace.is_some());
+        assert_eq!(txs[1].status, Some(false));
+        assert!(txs[1].call.is_none());
+        assert_ne!(txs[0].content_hash, txs[1].content_hash);
+
+        let again = scan_transactions(&chain, &params(&[token], true), 0, 2)
+            .await
+            .unwrap();
+        assert_eq!(txs[0].content_hash, again[0].content_hash);
+    }
+
+    #[tokio::test]
+    async fn test_scan_without_trace_support() {
+        let user = Address::repeat_byte(0x01);
+        let chain = MockChain::new(0);
+        chain.mine_transactions(vec![MockTransaction::call(
+            user,
+            Address::repeat_byte(0xaa),
+            Vec::new(),
+        )]);
+        chain.disable_traces();
+
+        let txs = scan_transactions(&chain, &params(&[user], true), 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(txs.len(), 1);
+        assert!(txs[0].trace.is_none());
+    }
+
+    #[test]
+    fn test_transaction_params_round_trip() {
+        let config = JobConfig {
+            job_type: JobType::TransactionIndex,
+            params: JobParams::TransactionIndex(params(&[Address::repeat_byte(1)], false)),
+        };
+        let parsed: JobConfig =
+            serde_json::from_value(serde_json::to_value(&config).unwrap()).unwrap();
+        assert!(parsed.params.as_transaction_index().is_some());
+    }
+}
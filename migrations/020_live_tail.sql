@@ -0,0 +1,9 @@
+-- Live-tail indexing jobs: after the initial backfill the job stays in status
+-- 'live' and follows the chain head. last_processed_block is the cursor the
+-- worker resumes from (including after a pause or restart).
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS last_processed_block BIGINT;
+
+-- AI tokens consumed so far, so the per-job budget spans every live batch.
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS ai_tokens_used INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_jobs_live ON jobs(created_at) WHERE status = 'live';
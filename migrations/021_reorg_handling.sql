@@ -0,0 +1,34 @@
+-- Chain reorganization handling. Each event records the hash of its block so
+-- the worker can compare it with the canonical chain. Events whose block was
+-- replaced are marked orphaned (kept for audit, hidden from queries) and any
+-- committed Merkle root covering them is flagged as reorg-affected.
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS block_hash TEXT;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS orphaned BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS orphaned_at TIMESTAMPTZ;
+
+ALTER TABLE timestamp_commits ADD COLUMN IF NOT EXISTS reorg_affected BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS idx_blockchain_events_chain_block
+    ON blockchain_events(chain, block_number) WHERE NOT orphaned;
+
+-- Include block_hash in the real-time notification payload.
+CREATE OR REPLACE FUNCTION notify_blockchain_event()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify(
+        'blockchain_event',
+        json_build_object(
+            'id',                  NEW.id,
+            'contract_address',    NEW.contract_address,
+            'event_name',          NEW.event_name,
+            'block_number',        NEW.block_number,
+            'block_hash',          NEW.block_hash,
+            'transaction_hash',    NEW.transaction_hash,
+            'event_data',          NEW.event_data,
+            'content_hash',        NEW.content_hash,
+            'ipfs_cid',            NEW.ipfs_cid
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,37 @@
+-- Natural key and raw log fields for indexed events.
+-- log_index distinguishes multiple logs emitted by the same transaction, and
+-- (chain, transaction_hash, log_index) makes re-indexing idempotent.
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS log_index BIGINT;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS topics TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS block_timestamp TIMESTAMPTZ;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS removed BOOLEAN NOT NULL DEFAULT false;
+
+-- Rows indexed before this migration have no log_index; NULLs never conflict.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_blockchain_events_natural_key
+    ON blockchain_events(chain, transaction_hash, log_index);
+
+CREATE INDEX IF NOT EXISTS idx_blockchain_events_block_timestamp
+    ON blockchain_events(block_timestamp);
+
+CREATE OR REPLACE FUNCTION notify_blockchain_event()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify(
+        'blockchain_event',
+        json_build_object(
+            'id',                  NEW.id,
+            'contract_address',    NEW.contract_address,
+            'event_name',          NEW.event_name,
+            'block_number',        NEW.block_number,
+            'block_hash',          NEW.block_hash,
+            'block_timestamp',     NEW.block_timestamp,
+            'transaction_hash',    NEW.transaction_hash,
+            'log_index',           NEW.log_index,
+            'event_data',          NEW.event_data,
+            'content_hash',        NEW.content_hash,
+            'ipfs_cid',            NEW.ipfs_cid
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
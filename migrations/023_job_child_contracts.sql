@@ -0,0 +1,21 @@
+-- Contracts discovered from a factory's creation events, per job. Each row is
+-- a leaf of the job's Merkle commitment alongside the job's events.
+CREATE TABLE IF NOT EXISTS job_child_contracts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    chain TEXT NOT NULL,
+    factory_address TEXT NOT NULL,
+    child_address TEXT NOT NULL,
+    creation_block BIGINT NOT NULL,
+    creation_block_hash TEXT NOT NULL,
+    creation_transaction_hash TEXT NOT NULL,
+    creation_log_index BIGINT NOT NULL,
+    content_hash TEXT NOT NULL,
+    merkle_root TEXT,
+    orphaned BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (job_id, child_address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_child_contracts_job ON job_child_contracts(job_id, creation_block);
+CREATE INDEX IF NOT EXISTS idx_job_child_contracts_creation ON job_child_contracts(chain, creation_block);
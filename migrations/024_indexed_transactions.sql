@@ -0,0 +1,37 @@
+-- Transactions indexed by transaction_index jobs, with receipt fields,
+-- decoded calldata and optional call traces. Each row is a leaf of its job's
+-- Merkle commitment. Keyed per job because the decoded call depends on the
+-- job's ABI.
+CREATE TABLE IF NOT EXISTS indexed_transactions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    chain TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_timestamp TIMESTAMPTZ NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    transaction_index BIGINT NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT,
+    value NUMERIC(78, 0) NOT NULL,
+    input TEXT NOT NULL,
+    function_name TEXT,
+    decoded_call JSONB,
+    status BOOLEAN,
+    gas_used BIGINT,
+    effective_gas_price NUMERIC(78, 0),
+    contract_address TEXT,
+    trace JSONB,
+    content_hash TEXT NOT NULL,
+    ipfs_cid TEXT,
+    merkle_root TEXT,
+    orphaned BOOLEAN NOT NULL DEFAULT false,
+    orphaned_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (job_id, transaction_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_indexed_transactions_job ON indexed_transactions(job_id, block_number, transaction_index);
+CREATE INDEX IF NOT EXISTS idx_indexed_transactions_block ON indexed_transactions(chain, block_number);
+CREATE INDEX IF NOT EXISTS idx_indexed_transactions_from ON indexed_transactions(from_address);
+CREATE INDEX IF NOT EXISTS idx_indexed_transactions_to ON indexed_transactions(to_address);
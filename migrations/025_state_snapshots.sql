@@ -0,0 +1,27 @@
+-- Results of view calls run by state_snapshot jobs, one row per call per
+-- snapshot block. Each row is a leaf of its job's Merkle commitment.
+CREATE TABLE IF NOT EXISTS state_snapshots (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    chain TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    call_index INTEGER NOT NULL,
+    contract_address TEXT NOT NULL,
+    function_signature TEXT NOT NULL,
+    args JSONB NOT NULL,
+    calldata TEXT NOT NULL,
+    return_data TEXT NOT NULL,
+    result JSONB,
+    decode_error TEXT,
+    content_hash TEXT NOT NULL,
+    ipfs_cid TEXT,
+    merkle_root TEXT,
+    orphaned BOOLEAN NOT NULL DEFAULT false,
+    orphaned_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (job_id, block_number, call_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_state_snapshots_block ON state_snapshots(chain, block_number);
+CREATE INDEX IF NOT EXISTS idx_state_snapshots_contract ON state_snapshots(contract_address);
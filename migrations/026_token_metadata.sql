@@ -0,0 +1,65 @@
+-- Token enrichment. Detected ERC-20/721/1155 contracts are cached per chain
+-- and contract; transfer events carry their amount (raw and scaled by the
+-- token's decimals), token ID and, when requested, resolved NFT metadata.
+-- None of these fields are part of an event's content hash.
+CREATE TABLE IF NOT EXISTS token_metadata (
+    chain TEXT NOT NULL,
+    contract_address TEXT NOT NULL,
+    standard TEXT NOT NULL CHECK (standard IN ('erc20', 'erc721', 'erc1155')),
+    name TEXT,
+    symbol TEXT,
+    decimals SMALLINT,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain, contract_address)
+);
+
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS token_standard TEXT;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS token_amount_raw NUMERIC(78, 0);
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS token_amount NUMERIC;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS token_id NUMERIC(78, 0);
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS token_uri TEXT;
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS token_metadata JSONB;
+
+-- Include the token fields in the real-time notification payload.
+CREATE OR REPLACE FUNCTION notify_blockchain_event()
+RETURNS TRIGGER AS $$
+DECLARE
+    token JSON;
+BEGIN
+    IF NEW.token_standard IS NOT NULL THEN
+        SELECT json_build_object(
+            'standard',   NEW.token_standard,
+            'name',       m.name,
+            'symbol',     m.symbol,
+            'decimals',   m.decimals,
+            'amount_raw', NEW.token_amount_raw::TEXT,
+            'amount',     NEW.token_amount::TEXT,
+            'token_id',   NEW.token_id::TEXT,
+            'token_uri',  NEW.token_uri,
+            'metadata',   NEW.token_metadata
+        ) INTO token
+        FROM (SELECT 1) AS one
+        LEFT JOIN token_metadata m
+            ON m.chain = NEW.chain AND m.contract_address = NEW.contract_address;
+    END IF;
+
+    PERFORM pg_notify(
+        'blockchain_event',
+        json_build_object(
+            'id',                  NEW.id,
+            'contract_address',    NEW.contract_address,
+            'event_name',          NEW.event_name,
+            'block_number',        NEW.block_number,
+            'block_hash',          NEW.block_hash,
+            'block_timestamp',     NEW.block_timestamp,
+            'transaction_hash',    NEW.transaction_hash,
+            'log_index',           NEW.log_index,
+            'event_data',          NEW.event_data,
+            'content_hash',        NEW.content_hash,
+            'ipfs_cid',            NEW.ipfs_cid,
+            'token',               token
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
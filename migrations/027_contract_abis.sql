@@ -0,0 +1,20 @@
+-- User-managed ABI registry. An ABI is registered for a chain and contract
+-- address, under a reusable name, or both. Jobs created by the owner resolve
+-- event and function names against it.
+CREATE TABLE IF NOT EXISTS contract_abis (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT,
+    chain TEXT,
+    contract_address TEXT,
+    abi JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((chain IS NULL) = (contract_address IS NULL)),
+    CHECK (name IS NOT NULL OR contract_address IS NOT NULL)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_contract_abis_name
+    ON contract_abis(user_id, name) WHERE name IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_contract_abis_contract
+    ON contract_abis(user_id, chain, contract_address) WHERE contract_address IS NOT NULL;
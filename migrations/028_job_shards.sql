@@ -0,0 +1,16 @@
+-- Block-range shards of a blockchain backfill. Shards are fetched concurrently;
+-- their events are merged in block order before the job's Merkle root is
+-- computed, so these rows only track progress.
+CREATE TABLE IF NOT EXISTS job_shards (
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    shard_index INTEGER NOT NULL,
+    from_block BIGINT NOT NULL,
+    to_block BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'completed', 'failed', 'cancelled')),
+    events_found BIGINT,
+    error TEXT,
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ,
+    PRIMARY KEY (job_id, shard_index)
+);
@@ -0,0 +1,111 @@
+-- Token projections: aggregates derived from a job's ERC-20 and ERC-721
+-- transfer events, maintained by a trigger as events are stored. Orphaning an
+-- event reverses its contribution, and re-including it applies it again.
+-- A job opts in through the `projections` list of its params.
+
+-- Current balance per holder: base units for ERC-20, tokens held for ERC-721.
+CREATE TABLE IF NOT EXISTS token_balances (
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    contract_address TEXT NOT NULL,
+    holder TEXT NOT NULL,
+    balance NUMERIC(78, 0) NOT NULL,
+    PRIMARY KEY (job_id, contract_address, holder)
+);
+
+CREATE INDEX IF NOT EXISTS idx_token_balances_top
+    ON token_balances(job_id, contract_address, balance DESC);
+
+-- Tokens minted (sent from the zero address) and burned (sent to it) per block.
+CREATE TABLE IF NOT EXISTS token_supply_changes (
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    contract_address TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    minted NUMERIC(78, 0) NOT NULL,
+    burned NUMERIC(78, 0) NOT NULL,
+    PRIMARY KEY (job_id, contract_address, block_number)
+);
+
+-- Adds (sign = 1) or removes (sign = -1) one event's contribution to the
+-- projections its job maintains.
+CREATE OR REPLACE FUNCTION apply_token_projections(ev blockchain_events, sign INTEGER)
+RETURNS VOID AS $$
+DECLARE
+    zero CONSTANT TEXT := '0x0000000000000000000000000000000000000000';
+    projections JSONB;
+    amount NUMERIC(78, 0);
+    sender TEXT;
+    recipient TEXT;
+BEGIN
+    -- Amounts and token IDs are only set on well-formed transfers, whose
+    -- second and third topics are the sender and recipient.
+    IF ev.orphaned THEN
+        RETURN;
+    ELSIF ev.token_standard = 'erc20' AND ev.token_amount_raw IS NOT NULL THEN
+        amount := ev.token_amount_raw;
+    ELSIF ev.token_standard = 'erc721' AND ev.token_id IS NOT NULL THEN
+        amount := 1;
+    ELSE
+        RETURN;
+    END IF;
+
+    SELECT config->'params'->'projections' INTO projections FROM jobs WHERE id = ev.job_id;
+    IF projections IS NULL OR jsonb_typeof(projections) <> 'array' THEN
+        RETURN;
+    END IF;
+
+    amount := amount * sign;
+    sender := '0x' || right(lower(ev.topics[2]), 40);
+    recipient := '0x' || right(lower(ev.topics[3]), 40);
+
+    IF projections ? 'balances' AND sender <> recipient THEN
+        INSERT INTO token_balances (job_id, contract_address, holder, balance)
+        SELECT ev.job_id, ev.contract_address, h.holder, h.delta
+        FROM (VALUES (sender, -amount), (recipient, amount)) AS h(holder, delta)
+        WHERE h.holder <> zero
+        ON CONFLICT (job_id, contract_address, holder)
+            DO UPDATE SET balance = token_balances.balance + EXCLUDED.balance;
+        DELETE FROM token_balances
+        WHERE job_id = ev.job_id AND contract_address = ev.contract_address
+          AND holder IN (sender, recipient) AND balance = 0;
+    END IF;
+
+    IF projections ? 'supply' AND (sender = zero OR recipient = zero) THEN
+        INSERT INTO token_supply_changes (job_id, contract_address, block_number, minted, burned)
+        VALUES (
+            ev.job_id, ev.contract_address, ev.block_number,
+            CASE WHEN sender = zero THEN amount ELSE 0 END,
+            CASE WHEN recipient = zero THEN amount ELSE 0 END
+        )
+        ON CONFLICT (job_id, contract_address, block_number) DO UPDATE SET
+            minted = token_supply_changes.minted + EXCLUDED.minted,
+            burned = token_supply_changes.burned + EXCLUDED.burned;
+        DELETE FROM token_supply_changes
+        WHERE job_id = ev.job_id AND contract_address = ev.contract_address
+          AND block_number = ev.block_number AND minted = 0 AND burned = 0;
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION update_token_projections()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+       AND (OLD.orphaned, OLD.block_number, OLD.token_standard, OLD.token_amount_raw, OLD.token_id)
+           IS NOT DISTINCT FROM
+           (NEW.orphaned, NEW.block_number, NEW.token_standard, NEW.token_amount_raw, NEW.token_id) THEN
+        RETURN NULL;
+    END IF;
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM apply_token_projections(OLD, -1);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        PERFORM apply_token_projections(NEW, 1);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS blockchain_events_token_projections ON blockchain_events;
+CREATE TRIGGER blockchain_events_token_projections
+    AFTER INSERT OR UPDATE OR DELETE ON blockchain_events
+    FOR EACH ROW EXECUTE FUNCTION update_token_projections();
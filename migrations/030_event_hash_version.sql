@@ -0,0 +1,50 @@
+-- Versioned content hashes. Version 1 hashed Rust's Debug rendering of
+-- event_data; version 2 hashes the RFC 8785 canonical JSON of the event's
+-- chain, contract address, transaction hash, log index and data. Existing
+-- rows keep version 1 so their hashes remain verifiable.
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS hash_version SMALLINT NOT NULL DEFAULT 1;
+
+-- Include the hash version in the real-time notification payload.
+CREATE OR REPLACE FUNCTION notify_blockchain_event()
+RETURNS TRIGGER AS $$
+DECLARE
+    token JSON;
+BEGIN
+    IF NEW.token_standard IS NOT NULL THEN
+        SELECT json_build_object(
+            'standard',   NEW.token_standard,
+            'name',       m.name,
+            'symbol',     m.symbol,
+            'decimals',   m.decimals,
+            'amount_raw', NEW.token_amount_raw::TEXT,
+            'amount',     NEW.token_amount::TEXT,
+            'token_id',   NEW.token_id::TEXT,
+            'token_uri',  NEW.token_uri,
+            'metadata',   NEW.token_metadata
+        ) INTO token
+        FROM (SELECT 1) AS one
+        LEFT JOIN token_metadata m
+            ON m.chain = NEW.chain AND m.contract_address = NEW.contract_address;
+    END IF;
+
+    PERFORM pg_notify(
+        'blockchain_event',
+        json_build_object(
+            'id',                  NEW.id,
+            'contract_address',    NEW.contract_address,
+            'event_name',          NEW.event_name,
+            'block_number',        NEW.block_number,
+            'block_hash',          NEW.block_hash,
+            'block_timestamp',     NEW.block_timestamp,
+            'transaction_hash',    NEW.transaction_hash,
+            'log_index',           NEW.log_index,
+            'event_data',          NEW.event_data,
+            'content_hash',        NEW.content_hash,
+            'hash_version',        NEW.hash_version,
+            'ipfs_cid',            NEW.ipfs_cid,
+            'token',               token
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
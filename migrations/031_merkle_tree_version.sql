@@ -0,0 +1,6 @@
+-- Versioned Merkle tree formats. Version 1 hashed concatenated hex strings and
+-- duplicated the last node of odd levels; version 2 is the RFC 6962 tree with
+-- domain-separated leaf and node hashes over raw digests. Existing and queued
+-- commitments keep version 1 so they can still be verified.
+ALTER TABLE timestamp_commits ADD COLUMN IF NOT EXISTS tree_version SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE pending_merkle_commits ADD COLUMN IF NOT EXISTS tree_version SMALLINT NOT NULL DEFAULT 1;
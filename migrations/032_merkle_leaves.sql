@@ -0,0 +1,21 @@
+-- The leaves of every Merkle tree a job commits, in tree order, so inclusion
+-- proofs can be regenerated for any leaf. Trees are keyed by root: the same
+-- leaves in the same order always give the same root.
+CREATE TABLE IF NOT EXISTS merkle_trees (
+    merkle_root TEXT PRIMARY KEY,
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    tree_version SMALLINT NOT NULL,
+    leaf_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_merkle_trees_job_id ON merkle_trees(job_id);
+
+CREATE TABLE IF NOT EXISTS merkle_leaves (
+    merkle_root TEXT NOT NULL REFERENCES merkle_trees(merkle_root) ON DELETE CASCADE,
+    leaf_index INTEGER NOT NULL,
+    content_hash TEXT NOT NULL,
+    PRIMARY KEY (merkle_root, leaf_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_merkle_leaves_content_hash ON merkle_leaves(content_hash);
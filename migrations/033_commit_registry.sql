@@ -0,0 +1,5 @@
+-- The registry each root was committed to, so proof bundles can name the
+-- chain and contract to check without trusting the API. Commitments made
+-- before this migration leave them NULL.
+ALTER TABLE timestamp_commits ADD COLUMN IF NOT EXISTS registry_chain_id BIGINT;
+ALTER TABLE timestamp_commits ADD COLUMN IF NOT EXISTS registry_address TEXT;
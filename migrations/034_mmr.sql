@@ -0,0 +1,35 @@
+-- Live jobs append every content hash they index to a Merkle Mountain Range
+-- and commit its root periodically instead of a tree per batch. The
+-- accumulator keeps the peaks; the leaves are kept in order so proofs can be
+-- generated against any committed root.
+CREATE TABLE IF NOT EXISTS mmr_accumulators (
+    job_id UUID PRIMARY KEY REFERENCES jobs(id) ON DELETE CASCADE,
+    leaf_count BIGINT NOT NULL DEFAULT 0,
+    peaks TEXT[] NOT NULL DEFAULT '{}',
+    committed_leaf_count BIGINT NOT NULL DEFAULT 0,
+    -- The job's last indexed block when its root was last committed.
+    committed_through_block BIGINT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS mmr_leaves (
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    leaf_index BIGINT NOT NULL,
+    content_hash TEXT NOT NULL,
+    PRIMARY KEY (job_id, leaf_index),
+    UNIQUE (job_id, content_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mmr_leaves_content_hash ON mmr_leaves(content_hash);
+
+-- Every committed root, with the leaf count it covers. The commitment itself
+-- is in timestamp_commits under the root.
+CREATE TABLE IF NOT EXISTS mmr_roots (
+    root TEXT PRIMARY KEY,
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    leaf_count BIGINT NOT NULL,
+    indexed_through_block BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_mmr_roots_job_id ON mmr_roots(job_id, leaf_count);
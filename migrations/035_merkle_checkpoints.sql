@@ -0,0 +1,13 @@
+-- Every node at every fourth level of a tree version 2 tree, so an inclusion
+-- proof is rebuilt from O(log n) stored nodes instead of every leaf. Trees
+-- recorded before checkpoints were kept have none and are proven from their
+-- leaves.
+ALTER TABLE merkle_trees ADD COLUMN IF NOT EXISTS checkpointed BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS merkle_checkpoints (
+    merkle_root TEXT NOT NULL REFERENCES merkle_trees(merkle_root) ON DELETE CASCADE,
+    level SMALLINT NOT NULL,
+    node_index INTEGER NOT NULL,
+    node TEXT NOT NULL,
+    PRIMARY KEY (merkle_root, level, node_index)
+);
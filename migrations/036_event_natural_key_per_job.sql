@@ -0,0 +1,6 @@
+-- Jobs index independently: two jobs that cover the same log each keep their
+-- own row, with their own Merkle root, so the natural key includes the job.
+DROP INDEX IF EXISTS idx_blockchain_events_natural_key;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_blockchain_events_job_natural_key
+    ON blockchain_events(job_id, chain, transaction_hash, log_index);
@@ -0,0 +1,8 @@
+-- A crawled page is committed as a Merkle leaf: the hash of its canonical
+-- document (url, status_code, body_hash, fetched_at), so it can be proven and
+-- exported like an indexed event.
+ALTER TABLE crawl_results ADD COLUMN IF NOT EXISTS body_hash TEXT;
+ALTER TABLE crawl_results ADD COLUMN IF NOT EXISTS hash_version SMALLINT;
+ALTER TABLE crawl_results ADD COLUMN IF NOT EXISTS merkle_root TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_crawl_results_merkle_root ON crawl_results(merkle_root);
@@ -0,0 +1,18 @@
+-- Jobs that index the same data build the same tree, so a tree can belong to
+-- several jobs. Every job that records a tree gets a row here, and proofs are
+-- scoped through it instead of through the job that recorded the tree first.
+CREATE TABLE IF NOT EXISTS merkle_tree_jobs (
+    merkle_root TEXT NOT NULL REFERENCES merkle_trees(merkle_root) ON DELETE CASCADE,
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (merkle_root, job_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_merkle_tree_jobs_job_id ON merkle_tree_jobs(job_id);
+
+INSERT INTO merkle_tree_jobs (merkle_root, job_id, created_at)
+SELECT merkle_root, job_id, created_at FROM merkle_trees
+ON CONFLICT DO NOTHING;
+
+-- The tree's owners are the rows above; a single owning job is misleading.
+ALTER TABLE merkle_trees DROP COLUMN IF EXISTS job_id;
@@ -0,0 +1,4 @@
+-- A crawl job stores one crawl_results row per link found on its page, and
+-- each row is committed as a leaf of the job's tree. A row's canonical
+-- document names the page the link was found on, so it is stored with the row.
+ALTER TABLE crawl_results ADD COLUMN IF NOT EXISTS page_url TEXT;
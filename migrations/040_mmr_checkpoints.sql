@@ -0,0 +1,15 @@
+-- Every node at every fourth level of a job's MMR, recorded as it is formed,
+-- and the peaks of each committed root, so an MMR proof is rebuilt from
+-- O(log n) stored nodes instead of every leaf. MMRs started before checkpoints
+-- were kept, and roots committed before their peaks were, are proven from
+-- their leaves.
+ALTER TABLE mmr_accumulators ADD COLUMN IF NOT EXISTS checkpointed BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE mmr_roots ADD COLUMN IF NOT EXISTS peaks TEXT[];
+
+CREATE TABLE IF NOT EXISTS mmr_checkpoints (
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    level SMALLINT NOT NULL,
+    node_index BIGINT NOT NULL,
+    node TEXT NOT NULL,
+    PRIMARY KEY (job_id, level, node_index)
+);
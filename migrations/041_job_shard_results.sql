@@ -0,0 +1,5 @@
+-- The events and factory child hashes a completed shard fetched, kept until
+-- its job has stored them, so a re-run job resumes from its completed shards
+-- and fetches only the ranges that are missing or did not complete.
+ALTER TABLE job_shards ADD COLUMN IF NOT EXISTS events JSONB;
+ALTER TABLE job_shards ADD COLUMN IF NOT EXISTS child_hashes TEXT[];